@@ -292,8 +292,10 @@ impl CanvasRenderingContext2D {
         let mut outline = path.into_outline();
         outline.transform(&self.current_state.transform);
 
+        // Successive clips intersect with the current one.
         let mut clip_path = ClipPath::new(outline);
         clip_path.set_fill_rule(fill_rule);
+        clip_path.set_clip_path(self.current_state.clip_path);
         let clip_path_id = self.canvas.scene.push_clip_path(clip_path);
 
         self.current_state.clip_path = Some(clip_path_id);
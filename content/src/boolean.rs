@@ -0,0 +1,677 @@
+// pathfinder/content/src/boolean.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Boolean operations that combine the areas filled by two outlines.
+
+use crate::fill::FillRule;
+use crate::outline::{self, Contour, ContourIterFlags, Outline};
+use crate::segment::{Segment, SegmentFlags};
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::util;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::collections::HashMap;
+use std::iter;
+
+// The distances below are fractions of the larger side of the inputs' combined bounds, so that
+// the result doesn't depend on the units the inputs are in.
+
+// The maximum distance between a curve and the lines it's flattened to when finding
+// intersections.
+const INTERSECTION_TOLERANCE: f32 = 0.00005;
+const MAX_INTERSECTION_SUBDIVISIONS: u32 = 24;
+
+// Points closer together than this are treated as the same vertex.
+const SNAP_DISTANCE: f32 = 0.0001;
+
+// How far to each side of an edge the inputs are sampled to decide whether the edge bounds the
+// result, and the tolerance that winding numbers are computed to there.
+const SAMPLE_DISTANCE: f32 = 0.0005;
+const WINDING_NUMBER_TOLERANCE: f32 = 0.0001;
+
+const EPSILON: f32 = 0.0001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BooleanOp {
+    /// The area inside either outline.
+    Union,
+    /// The area inside both outlines.
+    Intersection,
+    /// The area inside the first outline but not the second.
+    Difference,
+    /// The area inside exactly one of the outlines.
+    Xor,
+}
+
+/// Combines the areas filled by two outlines into a new outline.
+///
+/// Each input is filled with its own fill rule, and open contours are treated as though they
+/// were closed. Curves are kept as curves, split where they cross. The result never overlaps
+/// itself, so it fills the same under either fill rule.
+pub struct OutlineBooleanOp<'a> {
+    inputs: [(&'a Outline, FillRule); 2],
+    op: BooleanOp,
+    tolerances: Tolerances,
+    output: Outline,
+}
+
+// The distances above, scaled to the inputs.
+#[derive(Clone, Copy)]
+struct Tolerances {
+    intersection: f32,
+    snap: f32,
+    sample: f32,
+    winding_number: f32,
+}
+
+impl<'a> OutlineBooleanOp<'a> {
+    #[inline]
+    pub fn new(a: &'a Outline,
+               a_fill_rule: FillRule,
+               b: &'a Outline,
+               b_fill_rule: FillRule,
+               op: BooleanOp)
+               -> OutlineBooleanOp<'a> {
+        let inputs = [(a, a_fill_rule), (b, b_fill_rule)];
+        let tolerances = Tolerances::new(a, b);
+        OutlineBooleanOp { inputs, op, tolerances, output: Outline::new() }
+    }
+
+    pub fn compute(&mut self) {
+        let mut edges = vec![];
+        for (source, &(input, _)) in self.inputs.iter().enumerate() {
+            for contour in input.contours() {
+                push_contour_edges(contour, source, &mut edges);
+            }
+        }
+
+        // Split every edge wherever it meets another, including edges of the same outline, so
+        // that no edge crosses from inside the result to outside it. Looping cubics have already
+        // been split where they cross themselves.
+        //
+        // The edges are swept from left to right, so that each is only compared with the edges
+        // whose hulls overlap it horizontally.
+        let hulls: Vec<_> = edges.iter().map(|edge| hull(&edge.segment)).collect();
+        let mut sweep_order: Vec<_> = (0..edges.len()).collect();
+        sweep_order.sort_by(|&a, &b| hulls[a].min_x().total_cmp(&hulls[b].min_x()));
+        let mut splits = vec![vec![]; edges.len()];
+        for (sweep_index, &edge_index) in sweep_order.iter().enumerate() {
+            let max_x = hulls[edge_index].max_x() + self.tolerances.snap;
+            for &other_edge_index in &sweep_order[(sweep_index + 1)..] {
+                if hulls[other_edge_index].min_x() > max_x {
+                    break;
+                }
+                let (edge_index_a, edge_index_b) = (edge_index.min(other_edge_index),
+                                                    edge_index.max(other_edge_index));
+                let (edge_a, edge_b) = (&edges[edge_index_a], &edges[edge_index_b]);
+                let mut intersections = vec![];
+                find_intersections(&edge_a.segment,
+                                   (0.0, 1.0),
+                                   &edge_b.segment,
+                                   (0.0, 1.0),
+                                   0,
+                                   &self.tolerances,
+                                   &mut intersections);
+                for intersection in intersections {
+                    let point = edge_a.segment.sample(intersection.t_a);
+                    splits[edge_index_a].push((intersection.t_a, point));
+                    splits[edge_index_b].push((intersection.t_b, point));
+                }
+            }
+        }
+
+        let mut graph = EdgeGraph::new(self.tolerances.snap);
+        for (edge, splits) in edges.iter().zip(splits) {
+            for piece in split_edge(&edge.segment, splits, self.tolerances.snap) {
+                self.classify_and_add_piece(&piece, &edges, &mut graph);
+            }
+        }
+
+        for contour in graph.link() {
+            self.output.push_contour(contour);
+        }
+    }
+
+    #[inline]
+    pub fn into_outline(self) -> Outline {
+        self.output
+    }
+
+    // Keeps the piece if the result is filled on exactly one side of it, oriented so that the
+    // filled side is on its left.
+    fn classify_and_add_piece(&self, piece: &Segment, edges: &[Edge], graph: &mut EdgeGraph) {
+        let mut tangent = if piece.is_line() {
+            piece.baseline.vector()
+        } else {
+            (piece.baseline.to() + piece.ctrl.to() - piece.ctrl.from() - piece.baseline.from()) *
+                0.75
+        };
+        if tangent.square_length() < EPSILON * EPSILON {
+            tangent = piece.baseline.vector();
+        }
+        if tangent.square_length() < EPSILON * EPSILON {
+            return;
+        }
+
+        let midpoint = piece.sample(0.5);
+        let normal = vec2f(-tangent.y(), tangent.x()).normalize() * self.tolerances.sample;
+        let filled_on_left = self.is_filled(midpoint + normal, edges);
+        let filled_on_right = self.is_filled(midpoint - normal, edges);
+        match (filled_on_left, filled_on_right) {
+            (true, false) => graph.add_edge(*piece),
+            (false, true) => graph.add_edge(piece.reversed()),
+            _ => {}
+        }
+    }
+
+    fn is_filled(&self, point: Vector2F, edges: &[Edge]) -> bool {
+        let mut inside = [false; 2];
+        for (source, &(_, fill_rule)) in self.inputs.iter().enumerate() {
+            let tolerance = self.tolerances.winding_number;
+            let winding_number: i32 = edges.iter().filter(|edge| edge.source == source).map(|edge| {
+                outline::segment_winding_number(&edge.segment, point, tolerance, 0)
+            }).sum();
+            inside[source] = match fill_rule {
+                FillRule::Winding => winding_number != 0,
+                FillRule::EvenOdd => winding_number % 2 != 0,
+            };
+        }
+
+        match self.op {
+            BooleanOp::Union => inside[0] || inside[1],
+            BooleanOp::Intersection => inside[0] && inside[1],
+            BooleanOp::Difference => inside[0] && !inside[1],
+            BooleanOp::Xor => inside[0] != inside[1],
+        }
+    }
+}
+
+impl Tolerances {
+    fn new(a: &Outline, b: &Outline) -> Tolerances {
+        // Empty outlines have empty bounds at the origin, which mustn't count.
+        let bounds = [a, b].iter()
+                           .filter(|outline| !outline.contours().is_empty())
+                           .map(|outline| outline.bounds())
+                           .fold(None, |bounds: Option<RectF>, outline_bounds| {
+            Some(bounds.map_or(outline_bounds, |bounds| bounds.union_rect(outline_bounds)))
+        });
+        let scale = bounds.map_or(0.0, |bounds| bounds.size().x().max(bounds.size().y()));
+        Tolerances {
+            intersection: INTERSECTION_TOLERANCE * scale,
+            snap: SNAP_DISTANCE * scale,
+            sample: SAMPLE_DISTANCE * scale,
+            winding_number: WINDING_NUMBER_TOLERANCE * scale,
+        }
+    }
+}
+
+struct Edge {
+    segment: Segment,
+    source: usize,
+}
+
+// Adds the segments of a contour, including the segment that closes it, as lines and cubics.
+// Looping cubics are split where they cross themselves, so that the loop is an edge of its own.
+fn push_contour_edges(contour: &Contour, source: usize, edges: &mut Vec<Edge>) {
+    let (first_position, last_position) = match contour.last_position() {
+        None => return,
+        Some(last_position) => (contour.position_of(0), last_position),
+    };
+    let close_segment = Segment::line(LineSegment2F::new(last_position, first_position));
+    for segment in contour.iter(ContourIterFlags::IGNORE_CLOSE_SEGMENT)
+                          .chain(iter::once(close_segment)) {
+        if segment.is_none() || hull(&segment).size().is_zero() {
+            continue;
+        }
+        let mut segment = if segment.is_line() { segment } else { segment.to_cubic() };
+        segment.flags = SegmentFlags::empty();
+        let (t0, t1) = match find_self_intersection(&segment) {
+            None => {
+                edges.push(Edge { segment, source });
+                continue;
+            }
+            Some(self_intersection) => self_intersection,
+        };
+
+        // Make the pieces meet exactly at the crossing.
+        let (mut before, rest) = segment.split(t0);
+        let (mut loop_segment, mut after) = rest.split((t1 - t0) / (1.0 - t0));
+        let point = before.baseline.to().lerp(loop_segment.baseline.to(), 0.5);
+        before.baseline.set_to(point);
+        loop_segment.baseline = LineSegment2F::new(point, point);
+        after.baseline.set_from(point);
+        for segment in [before, loop_segment, after].iter() {
+            edges.push(Edge { segment: *segment, source });
+        }
+    }
+}
+
+// Returns the parameters of the two points where a looping cubic crosses itself.
+//
+// Writing the curve as `a t³ + b t² + c t + d`, two different parameters `s` and `t` give the
+// same point where `a (s² + s t + t²) + b (s + t) + c = 0`. Taking the cross product with `a`
+// gives `s + t`, the dot product then gives `s t`, and `s` and `t` are the roots of the
+// quadratic with that sum and product.
+fn find_self_intersection(segment: &Segment) -> Option<(f32, f32)> {
+    if !segment.is_cubic() {
+        return None;
+    }
+    let (p0, p1) = (segment.baseline.from(), segment.ctrl.from());
+    let (p2, p3) = (segment.ctrl.to(), segment.baseline.to());
+    let a = p3 - p0 + (p1 - p2) * 3.0;
+    let b = (p0 - p1 * 2.0 + p2) * 3.0;
+    let c = (p1 - p0) * 3.0;
+    let det = a.det(b);
+    if det.abs() <= EPSILON * a.length() * b.length() {
+        return None;
+    }
+
+    let sum = -a.det(c) / det;
+    let product = sum * sum + a.dot(b * sum + c) / a.square_length();
+    let discriminant = sum * sum - 4.0 * product;
+    if discriminant <= 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (s, t) = ((sum - root) * 0.5, (sum + root) * 0.5);
+    if s > 0.0 && t < 1.0 {
+        Some((s, t))
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Intersection {
+    t_a: f32,
+    t_b: f32,
+}
+
+// Finds where two segments meet by subdividing them until they're flat enough to intersect as
+// lines. `range_a` and `range_b` are the spans of the original segments that `a` and `b` cover.
+fn find_intersections(a: &Segment,
+                      range_a: (f32, f32),
+                      b: &Segment,
+                      range_b: (f32, f32),
+                      depth: u32,
+                      tolerances: &Tolerances,
+                      intersections: &mut Vec<Intersection>) {
+    // Segments that miss each other by less than the snap distance still meet.
+    let (hull_a, hull_b) = (hull(a), hull(b));
+    let slop = tolerances.snap;
+    if hull_a.max_x() + slop < hull_b.min_x() || hull_b.max_x() + slop < hull_a.min_x() ||
+            hull_a.max_y() + slop < hull_b.min_y() || hull_b.max_y() + slop < hull_a.min_y() {
+        return;
+    }
+
+    let at_max_depth = depth == MAX_INTERSECTION_SUBDIVISIONS;
+    let a_is_flat = a.is_line() || at_max_depth ||
+        a.as_cubic_segment().is_flat(tolerances.intersection);
+    let b_is_flat = b.is_line() || at_max_depth ||
+        b.as_cubic_segment().is_flat(tolerances.intersection);
+
+    if a_is_flat && b_is_flat {
+        // Non-finite input can produce non-finite parameters, which can't be ordered along edges.
+        for (t_a, t_b) in intersect_lines(a.baseline, b.baseline, tolerances.snap) {
+            let (t_a, t_b) = (util::lerp(range_a.0, range_a.1, t_a),
+                              util::lerp(range_b.0, range_b.1, t_b));
+            if t_a.is_finite() && t_b.is_finite() {
+                intersections.push(Intersection { t_a, t_b });
+            }
+        }
+        return;
+    }
+
+    // Subdivide whichever curve is larger.
+    let split_a = !a_is_flat && (b_is_flat || hull_a.size().x().max(hull_a.size().y()) >=
+                                 hull_b.size().x().max(hull_b.size().y()));
+    if split_a {
+        let (a0, a1) = a.split(0.5);
+        let mid = (range_a.0 + range_a.1) * 0.5;
+        find_intersections(&a0, (range_a.0, mid), b, range_b, depth + 1, tolerances, intersections);
+        find_intersections(&a1, (mid, range_a.1), b, range_b, depth + 1, tolerances, intersections);
+    } else {
+        let (b0, b1) = b.split(0.5);
+        let mid = (range_b.0 + range_b.1) * 0.5;
+        find_intersections(a, range_a, &b0, (range_b.0, mid), depth + 1, tolerances, intersections);
+        find_intersections(a, range_a, &b1, (mid, range_b.1), depth + 1, tolerances, intersections);
+    }
+}
+
+// Returns the parameters along each line of the points where they meet. Where collinear lines
+// overlap, the ends of the overlap are returned.
+fn intersect_lines(a: LineSegment2F, b: LineSegment2F, snap_distance: f32) -> Vec<(f32, f32)> {
+    let (vector_a, vector_b) = (a.vector(), b.vector());
+    let (length_a, length_b) = (vector_a.length(), vector_b.length());
+    if length_a < EPSILON || length_b < EPSILON {
+        return vec![];
+    }
+
+    let det = vector_a.det(vector_b);
+    let offset = b.from() - a.from();
+    if det.abs() > EPSILON * length_a * length_b {
+        let (t_a, t_b) = (offset.det(vector_b) / det, offset.det(vector_a) / det);
+        let (slop_a, slop_b) = (snap_distance / length_a, snap_distance / length_b);
+        if t_a < -slop_a || t_a > 1.0 + slop_a || t_b < -slop_b || t_b > 1.0 + slop_b {
+            return vec![];
+        }
+        return vec![(util::clamp(t_a, 0.0, 1.0), util::clamp(t_b, 0.0, 1.0))];
+    }
+
+    if offset.det(vector_a).abs() > snap_distance * length_a {
+        return vec![];
+    }
+    let mut overlaps = vec![];
+    for &(point, t_b) in &[(b.from(), 0.0), (b.to(), 1.0)] {
+        let t_a = (point - a.from()).dot(vector_a) / (length_a * length_a);
+        if (0.0..=1.0).contains(&t_a) {
+            overlaps.push((t_a, t_b));
+        }
+    }
+    for &(point, t_a) in &[(a.from(), 0.0), (a.to(), 1.0)] {
+        let t_b = (point - b.from()).dot(vector_b) / (length_b * length_b);
+        if (0.0..=1.0).contains(&t_b) {
+            overlaps.push((t_a, t_b));
+        }
+    }
+    overlaps
+}
+
+// Splits a segment at the given parameters, making the pieces meet exactly at the given points.
+fn split_edge(segment: &Segment, mut splits: Vec<(f32, Vector2F)>, snap_distance: f32)
+              -> Vec<Segment> {
+    splits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut pieces = vec![];
+    let (mut rest, mut rest_start_t) = (*segment, 0.0);
+    for (t, point) in splits {
+        if t <= rest_start_t ||
+                (point - rest.baseline.from()).length() < snap_distance ||
+                (point - segment.baseline.to()).length() < snap_distance {
+            continue;
+        }
+        let (mut piece, mut next_rest) = rest.split((t - rest_start_t) / (1.0 - rest_start_t));
+        piece.baseline.set_to(point);
+        next_rest.baseline.set_from(point);
+        pieces.push(piece);
+        rest = next_rest;
+        rest_start_t = t;
+    }
+    pieces.push(rest);
+    pieces
+}
+
+fn hull(segment: &Segment) -> RectF {
+    let mut hull = RectF::from_points(segment.baseline.from(), segment.baseline.from());
+    hull = hull.union_point(segment.baseline.to());
+    if !segment.is_line() {
+        hull = hull.union_point(segment.ctrl.from()).union_point(segment.ctrl.to());
+    }
+    hull
+}
+
+// The edges of the result, joined at shared vertices.
+//
+// Vertices are found through a grid of cells as large as the snap distance, so that a point only
+// has to be compared with the vertices in the cells around it.
+struct EdgeGraph {
+    vertices: Vec<Vector2F>,
+    grid: HashMap<(i32, i32), Vec<usize>>,
+    edges: Vec<GraphEdge>,
+    outgoing: Vec<Vec<usize>>,
+    snap_distance: f32,
+}
+
+struct GraphEdge {
+    segment: Segment,
+    from: usize,
+    to: usize,
+}
+
+impl EdgeGraph {
+    fn new(snap_distance: f32) -> EdgeGraph {
+        EdgeGraph {
+            vertices: vec![],
+            grid: HashMap::new(),
+            edges: vec![],
+            outgoing: vec![],
+            snap_distance,
+        }
+    }
+
+    fn add_edge(&mut self, mut segment: Segment) {
+        let (from, to) = (self.vertex(segment.baseline.from()), self.vertex(segment.baseline.to()));
+        if from == to && segment.is_line() {
+            return;
+        }
+        segment.baseline = LineSegment2F::new(self.vertices[from], self.vertices[to]);
+
+        // Where the inputs share a boundary, both copies of it are kept; drop one of them.
+        let midpoint = segment.sample(0.5);
+        let edges = &self.edges;
+        if self.outgoing[from].iter().any(|&edge_index| {
+            let edge = &edges[edge_index];
+            edge.to == to && (edge.segment.sample(0.5) - midpoint).length() < self.snap_distance
+        }) {
+            return;
+        }
+
+        self.outgoing[from].push(self.edges.len());
+        self.edges.push(GraphEdge { segment, from, to });
+    }
+
+    fn vertex(&mut self, point: Vector2F) -> usize {
+        // A vertex within the snap distance is in this cell or one of its neighbors.
+        let cell = self.cell(point);
+        for y in cell.1.saturating_sub(1)..=cell.1.saturating_add(1) {
+            for x in cell.0.saturating_sub(1)..=cell.0.saturating_add(1) {
+                let vertex_indices = match self.grid.get(&(x, y)) {
+                    None => continue,
+                    Some(vertex_indices) => vertex_indices,
+                };
+                for &vertex_index in vertex_indices {
+                    if (self.vertices[vertex_index] - point).length() < self.snap_distance {
+                        return vertex_index;
+                    }
+                }
+            }
+        }
+
+        let vertex_index = self.vertices.len();
+        self.vertices.push(point);
+        self.outgoing.push(vec![]);
+        self.grid.entry(cell).or_default().push(vertex_index);
+        vertex_index
+    }
+
+    fn cell(&self, point: Vector2F) -> (i32, i32) {
+        if self.snap_distance <= 0.0 {
+            return (0, 0);
+        }
+        let cell = (point / self.snap_distance).floor();
+        (cell.x() as i32, cell.y() as i32)
+    }
+
+    // Follows edges head to tail until each chain returns to where it started. Every vertex has
+    // as many edges leaving it as entering it, so every edge ends up in some contour.
+    fn link(self) -> Vec<Contour> {
+        // Edges are taken from the back, so reverse them to follow them in the order they came.
+        let mut outgoing = self.outgoing;
+        outgoing.iter_mut().for_each(|edge_indices| edge_indices.reverse());
+
+        let mut contours = vec![];
+        for start_edge_index in 0..self.edges.len() {
+            let start_vertex = self.edges[start_edge_index].from;
+            let mut segments = vec![];
+            let mut next_edge_index = match outgoing[start_vertex].pop() {
+                None => continue,
+                Some(edge_index) => edge_index,
+            };
+            loop {
+                let edge = &self.edges[next_edge_index];
+                push_merged_segment(&mut segments, edge.segment);
+                if edge.to == start_vertex {
+                    break;
+                }
+                next_edge_index = match outgoing[edge.to].pop() {
+                    None => break,
+                    Some(edge_index) => edge_index,
+                };
+            }
+            if let Some(contour) = contour_from_segments(segments) {
+                contours.push(contour);
+            }
+        }
+        contours
+    }
+}
+
+// Appends a segment, extending the last one instead if both are lines running the same way.
+fn push_merged_segment(segments: &mut Vec<Segment>, segment: Segment) {
+    if let Some(last_segment) = segments.last_mut() {
+        if lines_are_continuous(last_segment, &segment) {
+            last_segment.baseline.set_to(segment.baseline.to());
+            return;
+        }
+    }
+    segments.push(segment);
+}
+
+fn lines_are_continuous(a: &Segment, b: &Segment) -> bool {
+    if !a.is_line() || !b.is_line() {
+        return false;
+    }
+    let (vector_a, vector_b) = (a.baseline.vector(), b.baseline.vector());
+    vector_a.dot(vector_b) > 0.0 &&
+        vector_a.det(vector_b).abs() <= EPSILON * vector_a.length() * vector_b.length()
+}
+
+fn contour_from_segments(mut segments: Vec<Segment>) -> Option<Contour> {
+    // The contour may have started partway along a straight run.
+    if segments.len() > 1 && lines_are_continuous(&segments[segments.len() - 1], &segments[0]) {
+        let last_segment = segments.pop().unwrap();
+        segments[0].baseline.set_from(last_segment.baseline.from());
+    }
+    if segments.len() < 2 && segments.iter().all(|segment| segment.is_line()) {
+        return None;
+    }
+
+    let mut contour = Contour::new();
+    contour.push_endpoint(segments[0].baseline.from());
+    for (segment_index, segment) in segments.iter().enumerate() {
+        if segment.is_line() {
+            // The closing line is implied.
+            if segment_index + 1 < segments.len() {
+                contour.push_endpoint(segment.baseline.to());
+            }
+        } else {
+            contour.push_cubic(segment.ctrl.from(), segment.ctrl.to(), segment.baseline.to());
+        }
+    }
+    contour.close();
+    Some(contour)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::boolean::{BooleanOp, EdgeGraph, OutlineBooleanOp};
+    use crate::fill::FillRule;
+    use crate::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_boolean_ops() {
+        let a = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(20.0, 20.0)));
+        let b = Outline::from_rect(RectF::new(vec2f(10.0, 10.0), vec2f(20.0, 20.0)));
+        let combine = |a: &Outline, b: &Outline, op: BooleanOp| {
+            let mut boolean_op = OutlineBooleanOp::new(a, FillRule::Winding,
+                                                       b, FillRule::Winding,
+                                                       op);
+            boolean_op.compute();
+            boolean_op.into_outline()
+        };
+        let points = [vec2f(5.0, 5.0), vec2f(25.0, 25.0), vec2f(15.0, 15.0), vec2f(25.0, 5.0)];
+
+        // The union of two overlapping squares is a single octagon-shaped contour.
+        let union = combine(&a, &b, BooleanOp::Union);
+        assert_eq!(union.contours().len(), 1);
+        assert_eq!(union.contours()[0].len(), 8);
+        for &(op, expected) in &[
+            (BooleanOp::Union, [true, true, true, false]),
+            (BooleanOp::Intersection, [false, false, true, false]),
+            (BooleanOp::Difference, [true, false, false, false]),
+            (BooleanOp::Xor, [true, true, false, false]),
+        ] {
+            // Points inside only the first square, only the second, both, and neither.
+            let result = combine(&a, &b, op);
+            for &fill_rule in &[FillRule::Winding, FillRule::EvenOdd] {
+                let filled: Vec<_> =
+                    points.iter().map(|&point| result.contains_point(point, fill_rule)).collect();
+                assert_eq!(filled, expected, "{:?} {:?}: {:?}", op, fill_rule, result);
+            }
+        }
+
+        // Cutting a circle out of a square keeps the circle's curves.
+        let mut circle = Contour::new();
+        circle.push_ellipse(&Transform2F::from_scale(8.0).translate(vec2f(20.0, 20.0)));
+        let mut circle_outline = Outline::new();
+        circle_outline.push_contour(circle);
+        let difference = combine(&a, &circle_outline, BooleanOp::Difference);
+        let contour = &difference.contours()[0];
+        assert!((0..contour.len()).any(|point_index| !contour.point_is_endpoint(point_index)));
+        assert!(difference.contains_point(vec2f(11.0, 11.0), FillRule::Winding));
+        assert!(!difference.contains_point(vec2f(15.0, 15.0), FillRule::Winding));
+        assert!(!difference.contains_point(vec2f(19.0, 19.0), FillRule::Winding));
+
+        // The result doesn't depend on the units the inputs are in.
+        for &scale in &[0.001, 1000.0] {
+            let transform = Transform2F::from_scale(scale);
+            let (mut scaled_a, mut scaled_b) = (a.clone(), b.clone());
+            scaled_a.transform(&transform);
+            scaled_b.transform(&transform);
+            let union = combine(&scaled_a, &scaled_b, BooleanOp::Union);
+            assert_eq!(union.contours().len(), 1, "{}: {:?}", scale, union);
+            assert_eq!(union.contours()[0].len(), 8, "{}: {:?}", scale, union);
+            let filled: Vec<_> = points.iter().map(|&point| {
+                union.contains_point(point * scale, FillRule::Winding)
+            }).collect();
+            assert_eq!(filled, [true, true, true, false], "{}: {:?}", scale, union);
+        }
+
+        // A cubic that loops over itself is split where it crosses, so the loop becomes a
+        // contour of its own.
+        let mut looping = Contour::new();
+        looping.push_endpoint(vec2f(0.0, 0.0));
+        looping.push_cubic(vec2f(30.0, 30.0), vec2f(-10.0, 30.0), vec2f(20.0, 0.0));
+        looping.close();
+        let mut looping_outline = Outline::new();
+        looping_outline.push_contour(looping);
+        let union = combine(&looping_outline, &Outline::new(), BooleanOp::Union);
+        assert_eq!(union.contours().len(), 2, "{:?}", union);
+        for &fill_rule in &[FillRule::Winding, FillRule::EvenOdd] {
+            assert!(union.contains_point(vec2f(10.0, 5.0), fill_rule), "{:?}", union);
+            assert!(union.contains_point(vec2f(10.0, 18.0), fill_rule), "{:?}", union);
+            assert!(!union.contains_point(vec2f(2.0, 20.0), fill_rule), "{:?}", union);
+        }
+    }
+
+    #[test]
+    fn test_snap_vertices_across_grid_cells() {
+        let mut graph = EdgeGraph::new(0.5);
+        let vertex = graph.vertex(vec2f(0.99, 0.99));
+        assert_eq!(graph.vertex(vec2f(1.01, 1.01)), vertex);
+        assert_eq!(graph.vertex(vec2f(0.6, 0.99)), vertex);
+        assert_ne!(graph.vertex(vec2f(1.6, 0.99)), vertex);
+        assert_ne!(graph.vertex(vec2f(-0.99, -0.99)), vertex);
+        assert_eq!(graph.vertices.len(), 3);
+    }
+}
@@ -10,6 +10,7 @@
 
 //! Special effects that can be applied to layers.
 
+use crate::gradient::GradientSpread;
 use pathfinder_color::ColorF;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::vector::Vector2F;
@@ -37,6 +38,12 @@ pub enum Filter {
     /// No special filter.
     None,
 
+    /// Applies a spread mode other than padding to a linear gradient.
+    LinearGradient {
+        /// What the gradient does past its ends.
+        spread: GradientSpread,
+    },
+
     /// Converts a linear gradient to a radial one.
     RadialGradient {
         /// The line that the circles lie along.
@@ -45,6 +52,18 @@ pub enum Filter {
         radii: F32x2,
         /// The origin of the linearized gradient in the texture.
         uv_origin: Vector2F,
+        /// What the gradient does past its ends.
+        spread: GradientSpread,
+    },
+
+    /// Converts a linear gradient to a conic one.
+    ConicGradient {
+        /// The point that the gradient sweeps around.
+        center: Vector2F,
+        /// The angle, in radians clockwise from the positive X axis, at which the sweep starts.
+        angle: f32,
+        /// The origin of the linearized gradient in the texture.
+        uv_origin: Vector2F,
     },
 
     PatternFilter(PatternFilter),
@@ -75,6 +94,36 @@ pub enum PatternFilter {
         direction: BlurDirection,
         sigma: f32,
     },
+
+    /// Replaces the alpha channel with the luminance of the color, and the color with black.
+    ///
+    /// This turns a pattern into a luminance mask when composited with `BlendMode::DestIn`.
+    LuminanceToAlpha,
+
+    /// Divides the color by the alpha, so that a pattern with premultiplied alpha, such as a
+    /// render target, composites like any other.
+    Unpremultiply,
+
+    /// Transforms the unpremultiplied color with a matrix, as SVG's `feColorMatrix` does.
+    ///
+    /// This and the filters below expect the pattern to have premultiplied alpha, as render
+    /// targets do.
+    ColorMatrix(ColorMatrix),
+
+    /// Remaps the red, green, blue, and alpha channels of the unpremultiplied color, in that
+    /// order, as SVG's `feComponentTransfer` does.
+    ComponentTransfer([TransferFunction; 4]),
+
+    /// Replaces each color with the per-channel minimum or maximum of the colors around it in one
+    /// direction, as SVG's `feMorphology` does.
+    ///
+    /// To cover a rectangle, perform two successive operations, one in each direction.
+    Morphology {
+        operator: MorphologyOperator,
+        direction: BlurDirection,
+        /// How many texels on either side of the center are covered.
+        radius: f32,
+    },
 }
 
 /// Blend modes that can be applied to individual paths.
@@ -121,6 +170,43 @@ pub enum BlurDirection {
     Y,
 }
 
+/// A 4×5 matrix that transforms colors, in row-major order.
+///
+/// The rows compute red, green, blue, and alpha in turn from the four channels of the input,
+/// plus the constant in the last column. Channels range from 0 to 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorMatrix(pub [f32; 20]);
+
+/// The most values that a table-based transfer function can have.
+pub const MAX_TRANSFER_TABLE_LENGTH: usize = 4;
+
+/// A function that remaps one color channel, as SVG's `feFuncR` and its siblings do.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransferFunction {
+    Identity,
+    /// Interpolates linearly between evenly spaced values.
+    Table(TransferTable),
+    /// Steps between evenly spaced values.
+    Discrete(TransferTable),
+    Linear { slope: f32, intercept: f32 },
+    Gamma { amplitude: f32, exponent: f32, offset: f32 },
+}
+
+/// The values of a table-based transfer function.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TransferTable {
+    values: [f32; MAX_TRANSFER_TABLE_LENGTH],
+    len: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MorphologyOperator {
+    /// Takes the minimum, thinning the pattern.
+    Erode,
+    /// Takes the maximum, thickening the pattern.
+    Dilate,
+}
+
 impl Default for BlendMode {
     #[inline]
     fn default() -> BlendMode {
@@ -135,6 +221,137 @@ impl Default for Filter {
     }
 }
 
+impl ColorMatrix {
+    #[inline]
+    pub fn identity() -> ColorMatrix {
+        ColorMatrix([
+            1.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Scales the saturation, as SVG's `saturate` type does. Zero makes the colors gray.
+    pub fn saturate(amount: f32) -> ColorMatrix {
+        let s = amount;
+        ColorMatrix([
+            0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s, 0.0, 0.0,
+            0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s, 0.0, 0.0,
+            0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s, 0.0, 0.0,
+            0.0,               0.0,               0.0,               1.0, 0.0,
+        ])
+    }
+
+    /// Rotates the hue by the given angle in radians, as SVG's `hueRotate` type does.
+    pub fn hue_rotate(angle: f32) -> ColorMatrix {
+        let (sin, cos) = angle.sin_cos();
+        ColorMatrix([
+            0.213 + 0.787 * cos - 0.213 * sin,
+            0.715 - 0.715 * cos - 0.715 * sin,
+            0.072 - 0.072 * cos + 0.928 * sin,
+            0.0, 0.0,
+            0.213 - 0.213 * cos + 0.143 * sin,
+            0.715 + 0.285 * cos + 0.140 * sin,
+            0.072 - 0.072 * cos - 0.283 * sin,
+            0.0, 0.0,
+            0.213 - 0.213 * cos - 0.787 * sin,
+            0.715 - 0.715 * cos + 0.715 * sin,
+            0.072 + 0.928 * cos + 0.072 * sin,
+            0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Replaces the alpha with the luminance of the color, and the color with black, as SVG's
+    /// `luminanceToAlpha` type does.
+    ///
+    /// Unlike `PatternFilter::LuminanceToAlpha`, the luminance isn't multiplied by the alpha.
+    pub fn luminance_to_alpha() -> ColorMatrix {
+        ColorMatrix([
+            0.0,    0.0,    0.0,    0.0, 0.0,
+            0.0,    0.0,    0.0,    0.0, 0.0,
+            0.0,    0.0,    0.0,    0.0, 0.0,
+            0.2125, 0.7154, 0.0721, 0.0, 0.0,
+        ])
+    }
+
+    /// Replaces the color with the given one, multiplying its alpha by the alpha of the input.
+    ///
+    /// With black, this extracts the alpha channel, like SVG's `SourceAlpha` input.
+    pub fn colorize(color: ColorF) -> ColorMatrix {
+        ColorMatrix([
+            0.0, 0.0, 0.0, 0.0,       color.r(),
+            0.0, 0.0, 0.0, 0.0,       color.g(),
+            0.0, 0.0, 0.0, 0.0,       color.b(),
+            0.0, 0.0, 0.0, color.a(), 0.0,
+        ])
+    }
+
+    /// Applies this matrix to an unpremultiplied color, clamping the result.
+    pub fn transform(&self, color: ColorF) -> ColorF {
+        let m = &self.0;
+        let mut result = [0.0; 4];
+        for (channel, result) in result.iter_mut().enumerate() {
+            let row = &m[(channel * 5)..(channel * 5 + 5)];
+            let value = row[0] * color.r() + row[1] * color.g() + row[2] * color.b() +
+                row[3] * color.a() + row[4];
+            *result = value.clamp(0.0, 1.0);
+        }
+        ColorF::new(result[0], result[1], result[2], result[3])
+    }
+}
+
+impl TransferFunction {
+    /// Remaps a channel value between 0 and 1, clamping the result.
+    pub fn apply(self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        let result = match self {
+            TransferFunction::Identity => value,
+            TransferFunction::Table(table) => {
+                let values = table.values();
+                let last = values.len() - 1;
+                if last == 0 {
+                    values[0]
+                } else {
+                    let position = value * last as f32;
+                    let index = (position as usize).min(last - 1);
+                    let t = position - index as f32;
+                    values[index] + (values[index + 1] - values[index]) * t
+                }
+            }
+            TransferFunction::Discrete(table) => {
+                let values = table.values();
+                let index = (value * values.len() as f32) as usize;
+                values[index.min(values.len() - 1)]
+            }
+            TransferFunction::Linear { slope, intercept } => slope * value + intercept,
+            TransferFunction::Gamma { amplitude, exponent, offset } => {
+                amplitude * value.powf(exponent) + offset
+            }
+        };
+        result.clamp(0.0, 1.0)
+    }
+}
+
+impl TransferTable {
+    /// Returns `None` if there are no values or more than `MAX_TRANSFER_TABLE_LENGTH`.
+    pub fn new(values: &[f32]) -> Option<TransferTable> {
+        if values.is_empty() || values.len() > MAX_TRANSFER_TABLE_LENGTH {
+            return None;
+        }
+        let mut table = TransferTable { values: [0.0; MAX_TRANSFER_TABLE_LENGTH], len: 0 };
+        table.values[0..values.len()].copy_from_slice(values);
+        table.len = values.len() as u8;
+        Some(table)
+    }
+
+    #[inline]
+    pub fn values(&self) -> &[f32] {
+        &self.values[0..(self.len as usize)]
+    }
+}
+
 impl BlendMode {
     /// Whether the backdrop is irrelevant when applying this blend mode (i.e. destination blend
     /// factor is zero when source alpha is one).
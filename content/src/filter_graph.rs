@@ -0,0 +1,135 @@
+// pathfinder/content/src/filter_graph.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Graphs of image processing operations, as in SVG's `<filter>` element.
+
+use crate::effects::{BlendMode, ColorMatrix, MorphologyOperator, TransferFunction};
+use pathfinder_color::ColorU;
+use pathfinder_geometry::vector::Vector2F;
+use std::slice;
+
+/// A graph of filter primitives applied to a layer.
+///
+/// Each primitive reads the layer or the results of earlier primitives, and the result of the
+/// last primitive replaces the layer. A graph without primitives makes the layer transparent, as
+/// an empty SVG `<filter>` does.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct FilterGraph {
+    primitives: Vec<FilterPrimitive>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FilterPrimitiveId(pub u32);
+
+/// What a filter primitive reads.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FilterInput {
+    /// The layer that the graph applies to.
+    SourceGraphic,
+    /// The alpha channel of the layer, colored black.
+    SourceAlpha,
+    /// The result of an earlier primitive.
+    Primitive(FilterPrimitiveId),
+}
+
+/// One operation in a filter graph.
+///
+/// Distances are in scene units.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FilterPrimitive {
+    /// Transforms colors with a matrix, as `feColorMatrix` does.
+    ColorMatrix { input: FilterInput, matrix: ColorMatrix },
+    /// Remaps the red, green, blue, and alpha channels, as `feComponentTransfer` does.
+    ComponentTransfer { input: FilterInput, functions: [TransferFunction; 4] },
+    /// Moves the input, as `feOffset` does.
+    Offset { input: FilterInput, offset: Vector2F },
+    /// Fills everything with one color, as `feFlood` does.
+    Flood { color: ColorU },
+    /// Blurs with the given standard deviation along each axis, as `feGaussianBlur` does.
+    Blur { input: FilterInput, sigma: Vector2F },
+    /// Composites the first input onto the second, as `feComposite` does.
+    Composite { inputs: [FilterInput; 2], operator: CompositeOperator },
+    /// Blends the first input onto the second, as `feBlend` does.
+    Blend { inputs: [FilterInput; 2], mode: BlendMode },
+    /// Draws the inputs over one another in order, as `feMerge` does.
+    Merge { inputs: Vec<FilterInput> },
+    /// Thins or thickens the input by the given radius along each axis, as `feMorphology` does.
+    Morphology { input: FilterInput, operator: MorphologyOperator, radius: Vector2F },
+    /// Draws the input over a blurred and offset copy of its alpha in the given color, as
+    /// `feDropShadow` does.
+    DropShadow { input: FilterInput, offset: Vector2F, sigma: Vector2F, color: ColorU },
+}
+
+/// The Porter-Duff operators that `FilterPrimitive::Composite` supports.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompositeOperator {
+    Over,
+    In,
+    Out,
+    Atop,
+    Xor,
+    Lighter,
+}
+
+impl FilterGraph {
+    #[inline]
+    pub fn new() -> FilterGraph {
+        FilterGraph::default()
+    }
+
+    /// Adds a primitive, which may only read the results of primitives added before it.
+    pub fn push_primitive(&mut self, primitive: FilterPrimitive) -> FilterPrimitiveId {
+        let primitive_id = FilterPrimitiveId(self.primitives.len() as u32);
+        for input in primitive.inputs() {
+            if let FilterInput::Primitive(input_id) = *input {
+                assert!(input_id.0 < primitive_id.0,
+                        "Filter primitives can only read earlier primitives!");
+            }
+        }
+        self.primitives.push(primitive);
+        primitive_id
+    }
+
+    #[inline]
+    pub fn primitives(&self) -> &[FilterPrimitive] {
+        &self.primitives
+    }
+}
+
+impl FilterPrimitive {
+    pub fn inputs(&self) -> &[FilterInput] {
+        match *self {
+            FilterPrimitive::Flood { .. } => &[],
+            FilterPrimitive::ColorMatrix { ref input, .. } |
+            FilterPrimitive::ComponentTransfer { ref input, .. } |
+            FilterPrimitive::Offset { ref input, .. } |
+            FilterPrimitive::Blur { ref input, .. } |
+            FilterPrimitive::Morphology { ref input, .. } |
+            FilterPrimitive::DropShadow { ref input, .. } => slice::from_ref(input),
+            FilterPrimitive::Composite { ref inputs, .. } |
+            FilterPrimitive::Blend { ref inputs, .. } => inputs,
+            FilterPrimitive::Merge { ref inputs } => inputs,
+        }
+    }
+}
+
+impl CompositeOperator {
+    /// The blend mode that draws the first input onto the second.
+    pub fn to_blend_mode(self) -> BlendMode {
+        match self {
+            CompositeOperator::Over => BlendMode::SrcOver,
+            CompositeOperator::In => BlendMode::SrcIn,
+            CompositeOperator::Out => BlendMode::SrcOut,
+            CompositeOperator::Atop => BlendMode::SrcAtop,
+            CompositeOperator::Xor => BlendMode::Xor,
+            CompositeOperator::Lighter => BlendMode::Lighter,
+        }
+    }
+}
@@ -10,12 +10,12 @@
 
 use crate::sorted_vector::SortedVector;
 use crate::util;
-use pathfinder_color::ColorU;
+use pathfinder_color::{ColorF, ColorU};
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::Vector2F;
 use pathfinder_geometry::util as geometry_util;
-use pathfinder_simd::default::F32x2;
+use pathfinder_simd::default::{F32x2, F32x4};
 use std::cmp::{Ordering, PartialOrd};
 use std::convert;
 use std::hash::{Hash, Hasher};
@@ -25,6 +25,9 @@ use std::mem;
 pub struct Gradient {
     pub geometry: GradientGeometry,
     stops: SortedVector<ColorStop>,
+    spread: GradientSpread,
+    color_space: GradientColorSpace,
+    premultiplied: bool,
 }
 
 #[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
@@ -47,7 +50,45 @@ pub enum GradientGeometry {
         /// Like `gradientTransform` in SVG. Note that this is the inverse of Cairo's gradient
         /// transform.
         transform: Transform2F,
-    }
+    },
+    /// A sweep around a center point, like `createConicGradient()` in the HTML canvas API.
+    ///
+    /// Every angle falls within a single sweep, so the spread mode has no effect.
+    Conic {
+        /// The point that the gradient sweeps around.
+        center: Vector2F,
+        /// The angle, in radians clockwise from the positive X axis, at which the gradient
+        /// starts.
+        angle: f32,
+        /// Transform from conic gradient space into screen space.
+        transform: Transform2F,
+    },
+}
+
+/// What a gradient does outside the range between its first and last color stops.
+///
+/// These match the `spreadMethod` values in SVG.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GradientSpread {
+    /// The colors of the first and last stops extend outward.
+    Pad,
+    /// The gradient repeats.
+    Repeat,
+    /// The gradient repeats, reversing direction every other time.
+    Reflect,
+}
+
+/// The color space in which a gradient interpolates between its color stops.
+///
+/// These match the color spaces that CSS Color 4 gradients may interpolate in.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GradientColorSpace {
+    /// The gamma-encoded sRGB components are interpolated, as most 2D graphics APIs do.
+    Srgb,
+    /// The linear light intensities are interpolated, which avoids darkened midpoints.
+    LinearSrgb,
+    /// The perceptually uniform OKLab components are interpolated.
+    Oklab,
 }
 
 impl Eq for Gradient {}
@@ -71,8 +112,23 @@ impl Hash for Gradient {
                 util::hash_f32(transform.m22(), state);
                 util::hash_f32(transform.m23(), state);
             }
+            GradientGeometry::Conic { center, angle, transform } => {
+                (2).hash(state);
+                util::hash_f32(center.x(), state);
+                util::hash_f32(center.y(), state);
+                util::hash_f32(angle, state);
+                util::hash_f32(transform.m11(), state);
+                util::hash_f32(transform.m12(), state);
+                util::hash_f32(transform.m13(), state);
+                util::hash_f32(transform.m21(), state);
+                util::hash_f32(transform.m22(), state);
+                util::hash_f32(transform.m23(), state);
+            }
         }
         self.stops.hash(state);
+        self.spread.hash(state);
+        self.color_space.hash(state);
+        self.premultiplied.hash(state);
     }
 }
 
@@ -91,7 +147,13 @@ impl Hash for ColorStop {
 impl Gradient {
     #[inline]
     pub fn linear(line: LineSegment2F) -> Gradient {
-        Gradient { geometry: GradientGeometry::Linear(line), stops: SortedVector::new() }
+        Gradient {
+            geometry: GradientGeometry::Linear(line),
+            stops: SortedVector::new(),
+            spread: GradientSpread::Pad,
+            color_space: GradientColorSpace::Srgb,
+            premultiplied: false,
+        }
     }
 
     #[inline]
@@ -105,6 +167,22 @@ impl Gradient {
         Gradient {
             geometry: GradientGeometry::Radial { line: line.to_line(), radii, transform },
             stops: SortedVector::new(),
+            spread: GradientSpread::Pad,
+            color_space: GradientColorSpace::Srgb,
+            premultiplied: false,
+        }
+    }
+
+    /// Creates a gradient that sweeps clockwise around `center`, starting at `angle` radians.
+    #[inline]
+    pub fn conic(center: Vector2F, angle: f32) -> Gradient {
+        let transform = Transform2F::default();
+        Gradient {
+            geometry: GradientGeometry::Conic { center, angle, transform },
+            stops: SortedVector::new(),
+            spread: GradientSpread::Pad,
+            color_space: GradientColorSpace::Srgb,
+            premultiplied: false,
         }
     }
 
@@ -129,12 +207,45 @@ impl Gradient {
         &mut self.stops.array
     }
 
+    #[inline]
+    pub fn spread(&self) -> GradientSpread {
+        self.spread
+    }
+
+    #[inline]
+    pub fn set_spread(&mut self, new_spread: GradientSpread) {
+        self.spread = new_spread
+    }
+
+    #[inline]
+    pub fn color_space(&self) -> GradientColorSpace {
+        self.color_space
+    }
+
+    #[inline]
+    pub fn set_color_space(&mut self, new_color_space: GradientColorSpace) {
+        self.color_space = new_color_space
+    }
+
+    /// Whether the color stops are multiplied by their alpha before being interpolated.
+    ///
+    /// This keeps the colors of transparent stops from bleeding into their neighbors, as in CSS.
+    #[inline]
+    pub fn premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+
+    #[inline]
+    pub fn set_premultiplied(&mut self, premultiplied: bool) {
+        self.premultiplied = premultiplied
+    }
+
     pub fn sample(&self, mut t: f32) -> ColorU {
         if self.stops.is_empty() {
             return ColorU::transparent_black();
         }
 
-        t = geometry_util::clamp(t, 0.0, 1.0);
+        t = self.spread.apply(t);
         let last_index = self.stops.len() - 1;
         let upper_index = self.stops.binary_search_by(|stop| {
             stop.offset.partial_cmp(&t).unwrap_or(Ordering::Less)
@@ -149,10 +260,23 @@ impl Gradient {
             return lower_stop.color;
         }
 
-        lower_stop.color
-                  .to_f32()
-                  .lerp(upper_stop.color.to_f32(), (t - lower_stop.offset) / denom)
-                  .to_u8()
+        let t = (t - lower_stop.offset) / denom;
+        if self.color_space == GradientColorSpace::Srgb && !self.premultiplied {
+            return lower_stop.color.to_f32().lerp(upper_stop.color.to_f32(), t).to_u8();
+        }
+
+        let mut lower_color = self.color_space.convert_from_srgb(lower_stop.color.to_f32());
+        let mut upper_color = self.color_space.convert_from_srgb(upper_stop.color.to_f32());
+        if self.premultiplied {
+            lower_color = premultiply(lower_color);
+            upper_color = premultiply(upper_color);
+        }
+        let mut color = lower_color.lerp(upper_color, t);
+        if self.premultiplied {
+            color = unpremultiply(color);
+        }
+        let color = self.color_space.convert_to_srgb(color);
+        ColorF(color.0.clamp(F32x4::default(), F32x4::splat(1.0))).to_u8()
     }
 
     #[inline]
@@ -172,13 +296,59 @@ impl Gradient {
 
         match self.geometry {
             GradientGeometry::Linear(ref mut line) => *line = new_transform * *line,
-            GradientGeometry::Radial { ref mut transform, .. } => {
+            GradientGeometry::Radial { ref mut transform, .. } |
+            GradientGeometry::Conic { ref mut transform, .. } => {
                 *transform = new_transform * *transform
             }
         }
     }
 }
 
+impl GradientColorSpace {
+    // Converts a straight-alpha sRGB color to this color space. OKLab's L, a, and b components
+    // are stored in the red, green, and blue channels respectively.
+    fn convert_from_srgb(self, color: ColorF) -> ColorF {
+        match self {
+            GradientColorSpace::Srgb => color,
+            GradientColorSpace::LinearSrgb => srgb_to_linear(color),
+            GradientColorSpace::Oklab => linear_srgb_to_oklab(srgb_to_linear(color)),
+        }
+    }
+
+    fn convert_to_srgb(self, color: ColorF) -> ColorF {
+        match self {
+            GradientColorSpace::Srgb => color,
+            GradientColorSpace::LinearSrgb => linear_to_srgb(color),
+            GradientColorSpace::Oklab => linear_to_srgb(oklab_to_linear_srgb(color)),
+        }
+    }
+}
+
+impl Default for GradientColorSpace {
+    #[inline]
+    fn default() -> GradientColorSpace {
+        GradientColorSpace::Srgb
+    }
+}
+
+impl GradientSpread {
+    /// Maps a position along the gradient onto the range [0, 1] that the color stops cover.
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            GradientSpread::Pad => geometry_util::clamp(t, 0.0, 1.0),
+            GradientSpread::Repeat => t - t.floor(),
+            GradientSpread::Reflect => 1.0 - (t - 2.0 * (t * 0.5).floor() - 1.0).abs(),
+        }
+    }
+}
+
+impl Default for GradientSpread {
+    #[inline]
+    fn default() -> GradientSpread {
+        GradientSpread::Pad
+    }
+}
+
 impl ColorStop {
     #[inline]
     pub fn new(color: ColorU, offset: f32) -> ColorStop {
@@ -203,3 +373,102 @@ impl RadialGradientLine for Vector2F {
         LineSegment2F::new(self, self)
     }
 }
+
+fn premultiply(color: ColorF) -> ColorF {
+    ColorF(color.0 * F32x4::new(color.a(), color.a(), color.a(), 1.0))
+}
+
+fn unpremultiply(color: ColorF) -> ColorF {
+    if color.a() == 0.0 {
+        return ColorF::transparent_black();
+    }
+    let recip_alpha = 1.0 / color.a();
+    ColorF(color.0 * F32x4::new(recip_alpha, recip_alpha, recip_alpha, 1.0))
+}
+
+// https://www.w3.org/TR/css-color-4/#color-conversion-code
+fn srgb_to_linear(color: ColorF) -> ColorF {
+    let convert = |value: f32| {
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            f32::powf((value + 0.055) / 1.055, 2.4)
+        }
+    };
+    ColorF::new(convert(color.r()), convert(color.g()), convert(color.b()), color.a())
+}
+
+fn linear_to_srgb(color: ColorF) -> ColorF {
+    let convert = |value: f32| {
+        if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            1.055 * f32::powf(value, 1.0 / 2.4) - 0.055
+        }
+    };
+    ColorF::new(convert(color.r()), convert(color.g()), convert(color.b()), color.a())
+}
+
+// https://bottosson.github.io/posts/oklab/
+fn linear_srgb_to_oklab(color: ColorF) -> ColorF {
+    let (r, g, b) = (color.r(), color.g(), color.b());
+    let l = f32::cbrt(0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b);
+    let m = f32::cbrt(0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b);
+    let s = f32::cbrt(0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b);
+    ColorF::new(0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+                1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+                0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+                color.a())
+}
+
+fn oklab_to_linear_srgb(color: ColorF) -> ColorF {
+    let (l, a, b) = (color.r(), color.g(), color.b());
+    let l_ = l + 0.396_337_78 * a + 0.215_803_76 * b;
+    let m_ = l - 0.105_561_346 * a - 0.063_854_17 * b;
+    let s_ = l - 0.089_484_18 * a - 1.291_485_5 * b;
+    let (l, m, s) = (l_ * l_ * l_, m_ * m_ * m_, s_ * s_ * s_);
+    ColorF::new( 4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+                -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+                -0.0041960863 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+                color.a())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gradient::{Gradient, GradientColorSpace};
+    use pathfinder_color::ColorU;
+    use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+    #[test]
+    fn test_interpolation_color_spaces() {
+        let sample_midpoint = |from: ColorU,
+                               to: ColorU,
+                               color_space: GradientColorSpace,
+                               premultiplied: bool| {
+            let mut gradient = Gradient::linear_from_points(Vector2F::zero(), vec2f(1.0, 0.0));
+            gradient.add_color_stop(from, 0.0);
+            gradient.add_color_stop(to, 1.0);
+            gradient.set_color_space(color_space);
+            gradient.set_premultiplied(premultiplied);
+            assert_eq!(gradient.sample(0.0), from);
+            assert_eq!(gradient.sample(1.0), to);
+            gradient.sample(0.5)
+        };
+
+        let (black, white) = (ColorU::black(), ColorU::white());
+        assert_eq!(sample_midpoint(black, white, GradientColorSpace::Srgb, false).r, 128);
+        assert_eq!(sample_midpoint(black, white, GradientColorSpace::LinearSrgb, false).r, 188);
+        assert_eq!(sample_midpoint(black, white, GradientColorSpace::Oklab, false).r, 99);
+
+        // Premultiplying keeps the transparent stop's color from darkening the midpoint.
+        let (red, transparent) = (ColorU::new(255, 0, 0, 255), ColorU::transparent_black());
+        let straight = sample_midpoint(red, transparent, GradientColorSpace::Srgb, false);
+        assert_eq!(straight, ColorU::new(128, 0, 0, 128));
+        for &color_space in &[GradientColorSpace::Srgb,
+                              GradientColorSpace::LinearSrgb,
+                              GradientColorSpace::Oklab] {
+            let midpoint = sample_midpoint(red, transparent, color_space, true);
+            assert_eq!((midpoint.r, midpoint.g, midpoint.b, midpoint.a), (255, 0, 0, 128));
+        }
+    }
+}
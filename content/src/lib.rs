@@ -17,11 +17,14 @@ extern crate bitflags;
 #[macro_use]
 extern crate log;
 
+pub mod boolean;
 pub mod clip;
 pub mod dash;
 pub mod effects;
 pub mod fill;
+pub mod filter_graph;
 pub mod gradient;
+pub mod mesh;
 pub mod orientation;
 pub mod outline;
 pub mod pattern;
@@ -0,0 +1,234 @@
+// pathfinder/content/src/mesh.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Mesh gradients made of Coons patches, as in PDF and PostScript type 6 shadings.
+
+use crate::pattern::{Image, Pattern};
+use crate::util;
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
+use pathfinder_simd::default::F32x4;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+// The number of texels along each side of a patch that each cell of its tessellation spans.
+const TEXELS_PER_CELL: f32 = 4.0;
+const MAX_CELLS_PER_SIDE: i32 = 64;
+
+/// A gradient made of Coons patches, each of which blends the colors at its four corners across
+/// a surface bounded by four cubic Bézier curves.
+///
+/// Patches are painted in order, so later patches cover earlier ones where they overlap. Points
+/// that no patch covers are transparent.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MeshGradient {
+    patches: Vec<CoonsPatch>,
+    transform: Transform2F,
+}
+
+/// A patch bounded by four cubic Bézier curves.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CoonsPatch {
+    /// The control points of the boundary, starting at a corner and running around the patch.
+    ///
+    /// The corners are at indices 0, 3, 6, and 9. This is the order in which PDF type 6 shadings
+    /// store the points of a patch.
+    pub points: [Vector2F; 12],
+    /// The colors at the corners, in the same order as the corners in `points`.
+    pub colors: [ColorU; 4],
+}
+
+impl MeshGradient {
+    #[inline]
+    pub fn new() -> MeshGradient {
+        MeshGradient { patches: vec![], transform: Transform2F::default() }
+    }
+
+    #[inline]
+    pub fn push_patch(&mut self, patch: CoonsPatch) {
+        self.patches.push(patch);
+    }
+
+    #[inline]
+    pub fn patches(&self) -> &[CoonsPatch] {
+        &self.patches
+    }
+
+    /// The transform from mesh space into scene space.
+    #[inline]
+    pub fn transform(&self) -> Transform2F {
+        self.transform
+    }
+
+    #[inline]
+    pub fn apply_transform(&mut self, transform: Transform2F) {
+        self.transform = transform * self.transform;
+    }
+
+    /// Returns the bounds of the control points of all patches, in mesh space.
+    pub fn bounds(&self) -> Option<RectF> {
+        let mut points = self.patches.iter().flat_map(|patch| patch.points.iter());
+        let first_point = *points.next()?;
+        Some(points.fold(RectF::new(first_point, Vector2F::zero()), |bounds, &point| {
+            bounds.union_point(point)
+        }))
+    }
+
+    #[inline]
+    pub fn is_fully_transparent(&self) -> bool {
+        self.patches.iter().all(|patch| {
+            patch.colors.iter().all(|color| color.is_fully_transparent())
+        })
+    }
+
+    /// Rasterizes this mesh into an image pattern that covers its bounds.
+    ///
+    /// The image has one texel per unit of scene space, up to `max_size` texels along each axis.
+    pub fn to_pattern(&self, max_size: i32) -> Pattern {
+        let bounds = match self.bounds() {
+            Some(bounds) => bounds,
+            None => {
+                let image = Image::new(vec2i(1, 1), Arc::new(vec![ColorU::transparent_black()]));
+                return Pattern::from_image(image);
+            }
+        };
+
+        let size = (bounds.size() * self.transform.extract_scale()).ceil().to_i32();
+        let size = size.max(Vector2I::splat(1)).min(Vector2I::splat(max_size));
+        let texel_scale = size.to_f32() / bounds.size().max(Vector2F::splat(f32::EPSILON));
+        let to_texels = Transform2F::from_translation(-bounds.origin()).scale(texel_scale);
+
+        let mut texels = vec![ColorF::transparent_black(); size.x() as usize * size.y() as usize];
+        for patch in &self.patches {
+            patch.rasterize(&to_texels, size, &mut texels);
+        }
+        let texels = texels.into_iter().map(|texel| {
+            ColorF(texel.0.clamp(F32x4::default(), F32x4::splat(1.0))).to_u8()
+        }).collect();
+
+        let mut pattern = Pattern::from_image(Image::new(size, Arc::new(texels)));
+        pattern.apply_transform(self.transform * to_texels.inverse());
+        pattern
+    }
+}
+
+impl Default for MeshGradient {
+    #[inline]
+    fn default() -> MeshGradient {
+        MeshGradient::new()
+    }
+}
+
+impl Eq for MeshGradient {}
+
+impl Hash for MeshGradient {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        for patch in &self.patches {
+            for point in &patch.points {
+                util::hash_f32x2(point.0, state);
+            }
+            patch.colors.hash(state);
+        }
+        util::hash_transform2f(self.transform, state);
+    }
+}
+
+impl CoonsPatch {
+    /// Returns the point on the patch at the given parametric coordinates.
+    ///
+    /// `u` runs from the first corner to the fourth and `v` from the first corner to the second.
+    pub fn sample_point(&self, u: f32, v: f32) -> Vector2F {
+        let p = &self.points;
+        let d1 = cubic(p[0], p[1], p[2], p[3], v);
+        let d2 = cubic(p[9], p[8], p[7], p[6], v);
+        let c1 = cubic(p[0], p[11], p[10], p[9], u);
+        let c2 = cubic(p[3], p[4], p[5], p[6], u);
+        let corners = (p[0] * (1.0 - u) + p[9] * u) * (1.0 - v) +
+            (p[3] * (1.0 - u) + p[6] * u) * v;
+        c1 * (1.0 - v) + c2 * v + d1 * (1.0 - u) + d2 * u - corners
+    }
+
+    /// Returns the color of the patch at the given parametric coordinates.
+    pub fn sample_color(&self, u: f32, v: f32) -> ColorF {
+        let c = &self.colors;
+        let (c00, c03, c33, c30) = (c[0].to_f32(), c[1].to_f32(), c[2].to_f32(), c[3].to_f32());
+        c00.lerp(c30, u).lerp(c03.lerp(c33, u), v)
+    }
+
+    // Tessellates the patch into a grid of cells and draws each as two Gouraud-shaded triangles.
+    fn rasterize(&self, to_texels: &Transform2F, size: Vector2I, texels: &mut [ColorF]) {
+        let mut bounds = RectF::new(*to_texels * self.points[0], Vector2F::zero());
+        for &point in &self.points[1..] {
+            bounds = bounds.union_point(*to_texels * point);
+        }
+        let cells = f32::ceil(bounds.size().x().max(bounds.size().y()) / TEXELS_PER_CELL) as i32;
+        let cells = cells.clamp(1, MAX_CELLS_PER_SIDE);
+
+        let mut vertices = Vec::with_capacity(((cells + 1) * (cells + 1)) as usize);
+        for row in 0..=cells {
+            let v = row as f32 / cells as f32;
+            for column in 0..=cells {
+                let u = column as f32 / cells as f32;
+                vertices.push((*to_texels * self.sample_point(u, v), self.sample_color(u, v)));
+            }
+        }
+
+        let stride = (cells + 1) as usize;
+        for row in 0..(cells as usize) {
+            for column in 0..(cells as usize) {
+                let index = row * stride + column;
+                let (a, b) = (vertices[index], vertices[index + 1]);
+                let (c, d) = (vertices[index + stride], vertices[index + stride + 1]);
+                rasterize_triangle(a, b, d, size, texels);
+                rasterize_triangle(a, d, c, size, texels);
+            }
+        }
+    }
+}
+
+fn cubic(p0: Vector2F, p1: Vector2F, p2: Vector2F, p3: Vector2F, t: f32) -> Vector2F {
+    let s = 1.0 - t;
+    p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+}
+
+// Fills the texels whose centers lie inside the triangle, interpolating the vertex colors.
+fn rasterize_triangle(a: (Vector2F, ColorF),
+                      b: (Vector2F, ColorF),
+                      c: (Vector2F, ColorF),
+                      size: Vector2I,
+                      texels: &mut [ColorF]) {
+    let area = (b.0 - a.0).det(c.0 - a.0);
+    if area == 0.0 {
+        return;
+    }
+
+    let lower = a.0.min(b.0).min(c.0) - vec2f(0.5, 0.5);
+    let upper = a.0.max(b.0).max(c.0) - vec2f(0.5, 0.5);
+    let lower = lower.ceil().to_i32().max(Vector2I::zero());
+    let upper = upper.floor().to_i32().min(size - Vector2I::splat(1));
+
+    for y in lower.y()..=upper.y() {
+        for x in lower.x()..=upper.x() {
+            let point = vec2f(x as f32, y as f32) + vec2f(0.5, 0.5);
+            let wa = (b.0 - point).det(c.0 - point) / area;
+            let wb = (c.0 - point).det(a.0 - point) / area;
+            let wc = 1.0 - wa - wb;
+            // Allow a little slop so that texels on shared edges aren't left unpainted.
+            if wa < -0.0001 || wb < -0.0001 || wc < -0.0001 {
+                continue;
+            }
+            let color = a.1.0 * F32x4::splat(wa) + b.1.0 * F32x4::splat(wb) +
+                c.1.0 * F32x4::splat(wc);
+            texels[(y * size.x() + x) as usize] = ColorF(color);
+        }
+    }
+}
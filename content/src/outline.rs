@@ -12,6 +12,7 @@
 
 use crate::clip::{self, ContourPolygonClipper, ContourRectClipper};
 use crate::dilation::ContourDilator;
+use crate::fill::FillRule;
 use crate::orientation::Orientation;
 use crate::segment::{Segment, SegmentFlags, SegmentKind};
 use pathfinder_geometry::line_segment::LineSegment2F;
@@ -22,6 +23,7 @@ use pathfinder_geometry::unit_vector::UnitVector;
 use pathfinder_geometry::vector::{Vector2F, vec2f};
 use std::f32::consts::PI;
 use std::fmt::{self, Debug, Formatter};
+use std::iter;
 use std::mem;
 
 #[derive(Clone)]
@@ -238,6 +240,30 @@ impl Outline {
     pub fn close_all_contours(&mut self) {
         self.contours.iter_mut().for_each(|contour| contour.close());
     }
+
+    /// Returns true if the given point lies inside this outline when it's filled with the given
+    /// fill rule.
+    ///
+    /// As when filling, open contours are treated as though they were closed.
+    pub fn contains_point(&self, point: Vector2F, fill_rule: FillRule) -> bool {
+        if !self.bounds.contains_point(point) {
+            return false;
+        }
+        let winding_number: i32 =
+            self.contours.iter().map(|contour| contour.winding_number(point)).sum();
+        match fill_rule {
+            FillRule::Winding => winding_number != 0,
+            FillRule::EvenOdd => winding_number % 2 != 0,
+        }
+    }
+
+    /// Approximates each contour with a polyline that strays no farther than `tolerance` from
+    /// the curves it replaces.
+    ///
+    /// Closed contours end with their first point repeated.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Vec<Vector2F>> {
+        self.contours.iter().map(|contour| contour.flatten(tolerance)).collect()
+    }
 }
 
 impl Debug for Outline {
@@ -621,6 +647,35 @@ impl Contour {
         self.bounds = self.bounds.dilate(amount);
     }
 
+    /// Returns the number of times this contour winds around the given point, treating the
+    /// contour as closed.
+    pub fn winding_number(&self, point: Vector2F) -> i32 {
+        let (first_position, last_position) = match self.last_position() {
+            None => return 0,
+            Some(last_position) => (self.position_of(0), last_position),
+        };
+        let close_segment = Segment::line(LineSegment2F::new(last_position, first_position));
+        self.iter(ContourIterFlags::IGNORE_CLOSE_SEGMENT)
+            .chain(iter::once(close_segment))
+            .map(|segment| segment_winding_number(&segment, point, WINDING_NUMBER_TOLERANCE, 0))
+            .sum()
+    }
+
+    /// Approximates this contour with a polyline that strays no farther than `tolerance` from
+    /// the curves it replaces.
+    ///
+    /// If the contour is closed, the polyline ends with its first point repeated.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Vector2F> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let mut points = vec![self.position_of(0)];
+        for segment in self.iter(ContourIterFlags::empty()) {
+            flatten_segment(&segment, tolerance, 0, &mut points);
+        }
+        points
+    }
+
     fn prepare_for_tiling(&mut self, view_box: RectF) {
         // Snap points to the view box bounds. This mops up floating point error from the clipping
         // process.
@@ -774,6 +829,77 @@ impl Contour {
     }
 }
 
+// The maximum distance between a curve and the lines it's flattened to when computing winding
+// numbers.
+const WINDING_NUMBER_TOLERANCE: f32 = 0.01;
+const MAX_WINDING_NUMBER_SUBDIVISIONS: u32 = 16;
+
+// Counts the signed crossings of `segment` with a ray cast from `point` in the +x direction,
+// flattening curves to within `tolerance`.
+pub(crate) fn segment_winding_number(segment: &Segment, point: Vector2F, tolerance: f32, depth: u32)
+                                     -> i32 {
+    match segment.kind {
+        SegmentKind::None => return 0,
+        SegmentKind::Line => return line_winding_number(segment.baseline, point),
+        SegmentKind::Quadratic | SegmentKind::Cubic => {}
+    }
+
+    // Curves lie within the hull of their control points, so if the ray misses the hull, or
+    // passes entirely through it, the curve crosses the ray exactly as its baseline does.
+    let segment = segment.to_cubic();
+    let mut hull = RectF::from_points(segment.baseline.from(), segment.baseline.from());
+    for &hull_point in &[segment.baseline.to(), segment.ctrl.from(), segment.ctrl.to()] {
+        hull = hull.union_point(hull_point);
+    }
+    if point.y() < hull.min_y() || point.y() >= hull.max_y() || point.x() > hull.max_x() {
+        return 0;
+    }
+    if point.x() < hull.min_x() || depth == MAX_WINDING_NUMBER_SUBDIVISIONS ||
+            segment.as_cubic_segment().is_flat(tolerance) {
+        return line_winding_number(segment.baseline, point);
+    }
+
+    let (prev, next) = segment.split(0.5);
+    segment_winding_number(&prev, point, tolerance, depth + 1) +
+        segment_winding_number(&next, point, tolerance, depth + 1)
+}
+
+const MAX_FLATTENING_SUBDIVISIONS: u32 = 16;
+
+// Appends the points after the first that approximate `segment` to within `tolerance`.
+fn flatten_segment(segment: &Segment, tolerance: f32, depth: u32, points: &mut Vec<Vector2F>) {
+    match segment.kind {
+        SegmentKind::None => return,
+        SegmentKind::Line => {
+            points.push(segment.baseline.to());
+            return;
+        }
+        SegmentKind::Quadratic | SegmentKind::Cubic => {}
+    }
+
+    let segment = segment.to_cubic();
+    if depth == MAX_FLATTENING_SUBDIVISIONS || segment.as_cubic_segment().is_flat(tolerance) {
+        points.push(segment.baseline.to());
+        return;
+    }
+
+    let (prev, next) = segment.split(0.5);
+    flatten_segment(&prev, tolerance, depth + 1, points);
+    flatten_segment(&next, tolerance, depth + 1, points);
+}
+
+fn line_winding_number(line: LineSegment2F, point: Vector2F) -> i32 {
+    let (from, to) = (line.from(), line.to());
+    let side = (to - from).det(point - from);
+    if from.y() <= point.y() && to.y() > point.y() && side > 0.0 {
+        1
+    } else if to.y() <= point.y() && from.y() > point.y() && side < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
 impl Debug for Contour {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         for (segment_index, segment) in self.iter(ContourIterFlags::IGNORE_CLOSE_SEGMENT)
@@ -925,3 +1051,46 @@ pub(crate) fn union_rect(bounds: &mut RectF, new_point: Vector2F, first: bool) {
         *bounds = bounds.union_point(new_point)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::outline::{Contour, Outline};
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_flatten() {
+        let mut outline = Outline::new();
+        let mut circle = Contour::new();
+        circle.push_ellipse(&Transform2F::from_scale(10.0).translate(vec2f(20.0, 20.0)));
+        outline.push_contour(circle);
+        let mut polyline = Contour::new();
+        polyline.push_endpoint(vec2f(0.0, 0.0));
+        polyline.push_endpoint(vec2f(5.0, 0.0));
+        polyline.push_endpoint(vec2f(5.0, 5.0));
+        outline.push_contour(polyline);
+
+        // Tighter tolerances take more lines.
+        let coarse = outline.flatten(1.0);
+        let fine = outline.flatten(0.01);
+        assert_eq!(fine.len(), 2);
+        assert!(fine[0].len() > coarse[0].len());
+
+        // The circle stays closed, and every line stays within the tolerance of it, give or take
+        // the error of the cubics that approximate the circle.
+        let circle = &fine[0];
+        assert_eq!(circle.first(), circle.last());
+        for line in circle.windows(2) {
+            for &point in &[line[0], (line[0] + line[1]) * 0.5] {
+                let radius = (point - vec2f(20.0, 20.0)).length();
+                assert!((radius - 10.0).abs() <= 0.015,
+                        "{:?} is {} from the center",
+                        point,
+                        radius);
+            }
+        }
+
+        // Lines are kept as they are, and open contours aren't closed.
+        assert_eq!(fine[1], vec![vec2f(0.0, 0.0), vec2f(5.0, 0.0), vec2f(5.0, 5.0)]);
+    }
+}
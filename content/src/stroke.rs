@@ -25,6 +25,7 @@ pub struct OutlineStrokeToFill<'a> {
     input: &'a Outline,
     output: Outline,
     style: StrokeStyle,
+    width_profile: Option<WidthProfile>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -48,19 +49,61 @@ pub enum LineJoin {
     Round,
 }
 
+/// How the width of a stroke varies along each contour, replacing `StrokeStyle::line_width`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WidthProfile {
+    /// Widths at fractions of the arc length of each contour, in increasing order of `t`.
+    ///
+    /// The width is interpolated linearly between stops and held constant before the first stop
+    /// and after the last.
+    Stops(Vec<WidthStop>),
+    /// One width per point of the outline, in the order of its contours and their points, as
+    /// from pen pressure samples.
+    ///
+    /// The width is interpolated linearly along each segment between its endpoints; the widths
+    /// of control points are ignored. Points without a width use `StrokeStyle::line_width`.
+    Points(Vec<f32>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WidthStop {
+    /// The fraction of the arc length of the contour, from 0 to 1.
+    pub t: f32,
+    pub width: f32,
+}
+
+// A segment of the input, along with the stroke radius at each of its ends.
+#[derive(Clone, Copy, Debug)]
+struct StrokeSegment {
+    segment: Segment,
+    from_radius: f32,
+    to_radius: f32,
+}
+
 impl<'a> OutlineStrokeToFill<'a> {
     #[inline]
     pub fn new(input: &Outline, style: StrokeStyle) -> OutlineStrokeToFill {
-        OutlineStrokeToFill { input, output: Outline::new(), style }
+        OutlineStrokeToFill { input, output: Outline::new(), style, width_profile: None }
+    }
+
+    /// Makes the width vary along the stroke. Joins and caps take the width at the point where
+    /// they are drawn.
+    #[inline]
+    pub fn set_width_profile(&mut self, width_profile: Option<WidthProfile>) {
+        self.width_profile = width_profile;
     }
 
     pub fn offset(&mut self) {
         let mut new_contours = vec![];
+        let mut first_point_index = 0;
         for input in &self.input.contours {
             let closed = input.closed;
+            let segments = self.stroke_segments(input, first_point_index);
+            first_point_index += input.len() as usize;
+
             let mut stroker = ContourStrokeToFill::new(input,
                                                        Contour::new(),
-                                                       self.style.line_width * 0.5,
+                                                       &segments,
                                                        self.style.line_join);
 
             stroker.offset_forward();
@@ -68,15 +111,17 @@ impl<'a> OutlineStrokeToFill<'a> {
                 self.push_stroked_contour(&mut new_contours, stroker, true);
                 stroker = ContourStrokeToFill::new(input,
                                                    Contour::new(),
-                                                   self.style.line_width * 0.5,
+                                                   &segments,
                                                    self.style.line_join);
             } else {
-                self.add_cap(&mut stroker.output);
+                let end_radius = segments.last().map_or(0.0, |segment| segment.to_radius);
+                self.add_cap(&mut stroker.output, end_radius * 2.0);
             }
 
             stroker.offset_backward();
             if !closed {
-                self.add_cap(&mut stroker.output);
+                let start_radius = segments.first().map_or(0.0, |segment| segment.from_radius);
+                self.add_cap(&mut stroker.output, start_radius * 2.0);
             }
 
             self.push_stroked_contour(&mut new_contours, stroker, closed);
@@ -94,6 +139,83 @@ impl<'a> OutlineStrokeToFill<'a> {
         self.output
     }
 
+    // Splits the contour into segments with the radius of the stroke at each end.
+    fn stroke_segments(&self, input: &Contour, first_point_index: usize) -> Vec<StrokeSegment> {
+        let default_radius = self.style.line_width * 0.5;
+        let segments = input.iter(ContourIterFlags::empty());
+        match self.width_profile {
+            None => {
+                segments.map(|segment| {
+                    StrokeSegment {
+                        segment,
+                        from_radius: default_radius,
+                        to_radius: default_radius,
+                    }
+                }).collect()
+            }
+            Some(WidthProfile::Points(ref widths)) => {
+                let radius_at = |point_index: usize| {
+                    widths.get(first_point_index + point_index)
+                          .map_or(default_radius, |&width| width * 0.5)
+                };
+                let mut point_index = 0;
+                segments.map(|segment| {
+                    let point_count = if segment.is_cubic() {
+                        3
+                    } else if segment.is_quadratic() {
+                        2
+                    } else {
+                        1
+                    };
+                    let from_radius = radius_at(point_index);
+                    point_index += point_count;
+                    // The closing segment ends at the first point.
+                    let to_radius = radius_at(point_index % input.len() as usize);
+                    StrokeSegment { segment, from_radius, to_radius }
+                }).collect()
+            }
+            Some(WidthProfile::Stops(ref stops)) => {
+                let segments: Vec<Segment> = segments.collect();
+                let total_length: f32 = segments.iter().map(|segment| segment.arc_length()).sum();
+                let radius_at = |distance: f32| {
+                    let t = if total_length > 0.0 { distance / total_length } else { 0.0 };
+                    width_at(stops, t).unwrap_or(self.style.line_width) * 0.5
+                };
+
+                // Split segments wherever a stop falls inside them, so that the radius varies
+                // linearly along each piece.
+                let mut stroke_segments = vec![];
+                let mut start_distance = 0.0;
+                for &segment in &segments {
+                    let end_distance = start_distance + segment.arc_length();
+                    let (mut segment, mut piece_start_distance) = (segment, start_distance);
+                    for stop in stops {
+                        let stop_distance = stop.t * total_length;
+                        if stop_distance <= piece_start_distance || stop_distance >= end_distance {
+                            continue;
+                        }
+                        let t = segment.time_for_distance(stop_distance - piece_start_distance);
+                        let (before, after) = segment.split(t);
+                        stroke_segments.push(StrokeSegment {
+                            segment: before,
+                            from_radius: radius_at(piece_start_distance),
+                            to_radius: radius_at(stop_distance),
+                        });
+                        segment = after;
+                        piece_start_distance = stop_distance;
+                    }
+                    stroke_segments.push(StrokeSegment {
+                        segment,
+                        from_radius: radius_at(piece_start_distance),
+                        to_radius: radius_at(end_distance),
+                    });
+                    start_distance = end_distance;
+                }
+                stroke_segments
+            }
+        }
+    }
+
     fn push_stroked_contour(&mut self,
                             new_contours: &mut Vec<Contour>,
                             mut stroker: ContourStrokeToFill,
@@ -102,7 +224,7 @@ impl<'a> OutlineStrokeToFill<'a> {
         if closed && stroker.output.might_need_join(self.style.line_join) {
             let (p1, p0) = (stroker.output.position_of(1), stroker.output.position_of(0));
             let final_segment = LineSegment2F::new(p1, p0);
-            stroker.output.add_join(self.style.line_width * 0.5,
+            stroker.output.add_join(stroker.start_radius,
                                     self.style.line_join,
                                     stroker.input.position_of(0),
                                     final_segment);
@@ -112,12 +234,11 @@ impl<'a> OutlineStrokeToFill<'a> {
         new_contours.push(stroker.output);
     }
 
-    fn add_cap(&mut self, contour: &mut Contour) {
+    fn add_cap(&mut self, contour: &mut Contour, width: f32) {
         if self.style.line_cap == LineCap::Butt || contour.len() < 2 {
             return
         }
 
-        let width = self.style.line_width;
         let p1 = contour.position_of_last(1);
 
         // Determine the ending gradient.
@@ -165,63 +286,70 @@ impl<'a> OutlineStrokeToFill<'a> {
 struct ContourStrokeToFill<'a> {
     input: &'a Contour,
     output: Contour,
-    radius: f32,
+    segments: &'a [StrokeSegment],
     join: LineJoin,
+    // The radius where the output starts.
+    start_radius: f32,
 }
 
 impl<'a> ContourStrokeToFill<'a> {
     #[inline]
-    fn new(input: &Contour, output: Contour, radius: f32, join: LineJoin) -> ContourStrokeToFill {
-        ContourStrokeToFill { input, output, radius, join }
+    fn new(input: &'a Contour, output: Contour, segments: &'a [StrokeSegment], join: LineJoin)
+           -> ContourStrokeToFill<'a> {
+        ContourStrokeToFill { input, output, segments, join, start_radius: 0.0 }
     }
 
     fn offset_forward(&mut self) {
-        for (segment_index, segment) in self.input.iter(ContourIterFlags::empty()).enumerate() {
+        self.start_radius = self.segments.first().map_or(0.0, |segment| segment.from_radius);
+        for (segment_index, segment) in self.segments.iter().enumerate() {
             // FIXME(pcwalton): We negate the radius here so that round end caps can be drawn
             // clockwise. Of course, we should just implement anticlockwise arcs to begin with...
             let join = if segment_index == 0 { LineJoin::Bevel } else { self.join };
-            segment.offset(-self.radius, join, &mut self.output);
+            segment.segment.offset(-segment.from_radius,
+                                   -segment.to_radius,
+                                   join,
+                                   &mut self.output);
         }
     }
 
     fn offset_backward(&mut self) {
-        let mut segments: Vec<_> = self
-            .input
-            .iter(ContourIterFlags::empty())
-            .map(|segment| segment.reversed())
-            .collect();
-        segments.reverse();
-        for (segment_index, segment) in segments.iter().enumerate() {
+        self.start_radius = self.segments.last().map_or(0.0, |segment| segment.to_radius);
+        for (segment_index, segment) in self.segments.iter().rev().enumerate() {
             // FIXME(pcwalton): We negate the radius here so that round end caps can be drawn
             // clockwise. Of course, we should just implement anticlockwise arcs to begin with...
             let join = if segment_index == 0 { LineJoin::Bevel } else { self.join };
-            segment.offset(-self.radius, join, &mut self.output);
+            segment.segment.reversed().offset(-segment.to_radius,
+                                              -segment.from_radius,
+                                              join,
+                                              &mut self.output);
         }
     }
 }
 
+// Offsets take a distance at each end of the segment and vary linearly between them.
 trait Offset {
-    fn offset(&self, distance: f32, join: LineJoin, contour: &mut Contour);
+    fn offset(&self, from_distance: f32, to_distance: f32, join: LineJoin, contour: &mut Contour);
     fn add_to_contour(&self,
                       distance: f32,
                       join: LineJoin,
                       join_point: Vector2F,
                       contour: &mut Contour);
-    fn offset_once(&self, distance: f32) -> Self;
-    fn error_is_within_tolerance(&self, other: &Segment, distance: f32) -> bool;
+    fn offset_once(&self, from_distance: f32, to_distance: f32) -> Self;
+    fn error_is_within_tolerance(&self, other: &Segment, from_distance: f32, to_distance: f32)
+                                 -> bool;
 }
 
 impl Offset for Segment {
-    fn offset(&self, distance: f32, join: LineJoin, contour: &mut Contour) {
+    fn offset(&self, from_distance: f32, to_distance: f32, join: LineJoin, contour: &mut Contour) {
         let join_point = self.baseline.from();
         if self.baseline.square_length() < TOLERANCE * TOLERANCE {
-            self.add_to_contour(distance, join, join_point, contour);
+            self.add_to_contour(from_distance, join, join_point, contour);
             return;
         }
 
-        let candidate = self.offset_once(distance);
-        if self.error_is_within_tolerance(&candidate, distance) {
-            candidate.add_to_contour(distance, join, join_point, contour);
+        let candidate = self.offset_once(from_distance, to_distance);
+        if self.error_is_within_tolerance(&candidate, from_distance, to_distance) {
+            candidate.add_to_contour(from_distance, join, join_point, contour);
             return;
         }
 
@@ -229,8 +357,9 @@ impl Offset for Segment {
         debug!("... PRE-SPLIT: {:?}", self);
         let (before, after) = self.split(0.5);
         debug!("... AFTER-SPLIT: {:?} {:?}", before, after);
-        before.offset(distance, join, contour);
-        after.offset(distance, join, contour);
+        let mid_distance = lerp(from_distance, to_distance, 0.5);
+        before.offset(from_distance, mid_distance, join, contour);
+        after.offset(mid_distance, to_distance, join, contour);
     }
 
     fn add_to_contour(&self,
@@ -257,16 +386,19 @@ impl Offset for Segment {
         contour.push_segment(self, flags);
     }
 
-    fn offset_once(&self, distance: f32) -> Segment {
+    fn offset_once(&self, from_distance: f32, to_distance: f32) -> Segment {
+        // Control points take the distance at their share of the way along the segment.
+        let distance_at = |t: f32| lerp(from_distance, to_distance, t);
+
         if self.is_line() {
-            return Segment::line(self.baseline.offset(distance));
+            return Segment::line(offset_line(self.baseline, from_distance, to_distance));
         }
 
         if self.is_quadratic() {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
             let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.baseline.to());
-            segment_0 = segment_0.offset(distance);
-            segment_1 = segment_1.offset(distance);
+            segment_0 = offset_line(segment_0, from_distance, distance_at(0.5));
+            segment_1 = offset_line(segment_1, distance_at(0.5), to_distance);
             let ctrl = match segment_0.intersection_t(segment_1) {
                 Some(t) => segment_0.sample(t),
                 None => segment_0.to().lerp(segment_1.from(), 0.5),
@@ -280,8 +412,8 @@ impl Offset for Segment {
         if self.baseline.from() == self.ctrl.from() {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.to());
             let mut segment_1 = LineSegment2F::new(self.ctrl.to(), self.baseline.to());
-            segment_0 = segment_0.offset(distance);
-            segment_1 = segment_1.offset(distance);
+            segment_0 = offset_line(segment_0, from_distance, distance_at(2.0 / 3.0));
+            segment_1 = offset_line(segment_1, distance_at(2.0 / 3.0), to_distance);
             let ctrl = match segment_0.intersection_t(segment_1) {
                 Some(t) => segment_0.sample(t),
                 None => segment_0.to().lerp(segment_1.from(), 0.5),
@@ -294,8 +426,8 @@ impl Offset for Segment {
         if self.ctrl.to() == self.baseline.to() {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
             let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.baseline.to());
-            segment_0 = segment_0.offset(distance);
-            segment_1 = segment_1.offset(distance);
+            segment_0 = offset_line(segment_0, from_distance, distance_at(1.0 / 3.0));
+            segment_1 = offset_line(segment_1, distance_at(1.0 / 3.0), to_distance);
             let ctrl = match segment_0.intersection_t(segment_1) {
                 Some(t) => segment_0.sample(t),
                 None => segment_0.to().lerp(segment_1.from(), 0.5),
@@ -308,9 +440,9 @@ impl Offset for Segment {
         let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
         let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.ctrl.to());
         let mut segment_2 = LineSegment2F::new(self.ctrl.to(), self.baseline.to());
-        segment_0 = segment_0.offset(distance);
-        segment_1 = segment_1.offset(distance);
-        segment_2 = segment_2.offset(distance);
+        segment_0 = offset_line(segment_0, from_distance, distance_at(1.0 / 3.0));
+        segment_1 = offset_line(segment_1, distance_at(1.0 / 3.0), distance_at(2.0 / 3.0));
+        segment_2 = offset_line(segment_2, distance_at(2.0 / 3.0), to_distance);
         let (ctrl_0, ctrl_1) = match (
             segment_0.intersection_t(segment_1),
             segment_1.intersection_t(segment_2),
@@ -326,16 +458,18 @@ impl Offset for Segment {
         Segment::cubic(baseline, ctrl)
     }
 
-    fn error_is_within_tolerance(&self, other: &Segment, distance: f32) -> bool {
-        let (mut min, mut max) = (
-            f32::abs(distance) - TOLERANCE,
-            f32::abs(distance) + TOLERANCE,
-        );
-        min = if min <= 0.0 { 0.0 } else { min * min };
-        max = if max <= 0.0 { 0.0 } else { max * max };
-
+    fn error_is_within_tolerance(&self, other: &Segment, from_distance: f32, to_distance: f32)
+                                 -> bool {
         for t_num in 0..(SAMPLE_COUNT + 1) {
             let t = t_num as f32 / SAMPLE_COUNT as f32;
+            let distance = lerp(from_distance, to_distance, t);
+            let (mut min, mut max) = (
+                f32::abs(distance) - TOLERANCE,
+                f32::abs(distance) + TOLERANCE,
+            );
+            min = if min <= 0.0 { 0.0 } else { min * min };
+            max = if max <= 0.0 { 0.0 } else { max * max };
+
             // FIXME(pcwalton): Use signed distance!
             let (this_p, other_p) = (self.sample(t), other.sample(t));
             let vector = this_p - other_p;
@@ -406,6 +540,37 @@ impl Contour {
     }
 }
 
+// Offsets a line by distances that vary linearly from one end to the other.
+fn offset_line(line: LineSegment2F, from_distance: f32, to_distance: f32) -> LineSegment2F {
+    if line.is_zero_length() {
+        return line;
+    }
+    let normal = line.vector().yx().normalize() * vec2f(-1.0, 1.0);
+    LineSegment2F::new(line.from() + normal * from_distance, line.to() + normal * to_distance)
+}
+
+// Returns the width of the profile at the given fraction of the arc length, or `None` if there
+// are no stops.
+fn width_at(stops: &[WidthStop], t: f32) -> Option<f32> {
+    let (first, last) = (stops.first()?, stops.last()?);
+    if t <= first.t {
+        return Some(first.width);
+    }
+    for pair in stops.windows(2) {
+        if t <= pair[1].t {
+            let span = pair[1].t - pair[0].t;
+            let fraction = if span > 0.0 { (t - pair[0].t) / span } else { 1.0 };
+            return Some(lerp(pair[0].width, pair[1].width, fraction));
+        }
+    }
+    Some(last.width)
+}
+
+#[inline]
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
 impl Default for StrokeStyle {
     #[inline]
     fn default() -> StrokeStyle {
@@ -426,3 +591,47 @@ impl Default for LineJoin {
     #[inline]
     fn default() -> LineJoin { LineJoin::Miter(10.0) }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::outline::{Contour, Outline};
+    use crate::stroke::{LineCap, OutlineStrokeToFill, StrokeStyle, WidthProfile, WidthStop};
+    use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+    #[test]
+    fn test_width_profiles() {
+        let stroke = |points: &[Vector2F], width_profile: WidthProfile| {
+            let mut contour = Contour::new();
+            for &point in points {
+                contour.push_endpoint(point);
+            }
+            let mut outline = Outline::new();
+            outline.push_contour(contour);
+            let style = StrokeStyle { line_cap: LineCap::Butt, ..StrokeStyle::default() };
+            let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, style);
+            stroke_to_fill.set_width_profile(Some(width_profile));
+            stroke_to_fill.offset();
+            let outline = stroke_to_fill.into_outline();
+            let contour = &outline.contours()[0];
+            (0..contour.len()).map(|index| contour.position_of(index)).collect::<Vec<_>>()
+        };
+        let contains = |points: &[Vector2F], expected: Vector2F| {
+            points.iter().any(|&point| (point - expected).length() < 0.001)
+        };
+
+        // A line that widens from 2 to 10 halfway along and then stays wide.
+        let stops = vec![WidthStop { t: 0.0, width: 2.0 }, WidthStop { t: 0.5, width: 10.0 }];
+        let points = stroke(&[vec2f(0.0, 0.0), vec2f(100.0, 0.0)], WidthProfile::Stops(stops));
+        for &expected in &[vec2f(0.0, -1.0), vec2f(50.0, -5.0), vec2f(100.0, -5.0),
+                           vec2f(0.0, 1.0), vec2f(50.0, 5.0), vec2f(100.0, 5.0)] {
+            assert!(contains(&points, expected), "{:?} not in {:?}", expected, points);
+        }
+
+        // Per-point widths, as from pen pressure.
+        let line = [vec2f(0.0, 0.0), vec2f(50.0, 0.0), vec2f(100.0, 0.0)];
+        let points = stroke(&line, WidthProfile::Points(vec![2.0, 10.0, 4.0]));
+        for &expected in &[vec2f(0.0, 1.0), vec2f(50.0, 5.0), vec2f(100.0, 2.0)] {
+            assert!(contains(&points, expected), "{:?} not in {:?}", expected, points);
+        }
+    }
+}
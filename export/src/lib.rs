@@ -53,7 +53,7 @@ fn export_svg<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
         view_box.size().x(),
         view_box.size().y()
     )?;
-    for (paint, outline, name) in scene.paths() {
+    for (paint, outline, name) in scene.transformed_paths() {
         write!(writer, "    <path")?;
         if !name.is_empty() {
             write!(writer, " id=\"{}\"", name)?;
@@ -75,12 +75,27 @@ fn export_pdf<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
         vec2f(r.x(), height - r.y())
     };
 
-    for (paint, outline, _) in scene.paths() {
+    for (paint, outline, _) in scene.transformed_paths() {
         // TODO(pcwalton): Gradients and patterns.
         if paint.is_color() {
             pdf.set_fill_color(paint.base_color());
         }
 
+        // Mesh gradients become shadings, painted through the outline as a clip.
+        let shading = paint.mesh_gradient().map(|mesh| {
+            let patches: Vec<_> = mesh.patches().iter().map(|patch| {
+                let mut points = patch.points;
+                for point in &mut points {
+                    *point = tr(mesh.transform() * *point);
+                }
+                (points, patch.colors)
+            }).collect();
+            pdf.add_coons_patch_shading(&patches)
+        });
+        if shading.is_some() {
+            pdf.save_state();
+        }
+
         for contour in outline.contours() {
             for (segment_index, segment) in contour.iter(ContourIterFlags::empty()).enumerate() {
                 if segment_index == 0 {
@@ -111,8 +126,15 @@ fn export_pdf<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
             }
         }
 
-        // closes implicitly
-        pdf.fill();
+        match shading {
+            Some(shading) => {
+                pdf.clip();
+                pdf.shade(shading);
+                pdf.restore_state();
+            }
+            // closes implicitly
+            None => pdf.fill(),
+        }
     }
     pdf.write_to(writer)
 }
@@ -138,7 +160,7 @@ fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
     writeln!(writer, "0 {} translate", view_box.size().y())?;
     writeln!(writer, "1 -1 scale")?;
 
-    for (paint, outline, name) in scene.paths() {
+    for (paint, outline, name) in scene.transformed_paths() {
         if !name.is_empty() {
             writeln!(writer, "newpath % {}", name)?;
         } else {
@@ -65,6 +65,7 @@ pub struct Pdf {
     page_buffer: Vec<u8>,
     objects: Vec<PdfObject>,
     page_size: Option<Vector2F>,
+    page_shadings: Vec<usize>,
     compression: Option<Compression>,
 }
 
@@ -95,6 +96,7 @@ impl Pdf {
                 },
             ],
             page_size: None,
+            page_shadings: Vec::new(),
             compression: Some(Compression::Fast)
         }
     }
@@ -152,6 +154,74 @@ impl Pdf {
     pub fn close(&mut self) {
         writeln!(self.page_buffer, "h").unwrap();
     }
+
+    pub fn save_state(&mut self) {
+        writeln!(self.page_buffer, "q").unwrap();
+    }
+
+    pub fn restore_state(&mut self) {
+        writeln!(self.page_buffer, "Q").unwrap();
+    }
+
+    /// Intersects the clip with the current path, without painting it.
+    pub fn clip(&mut self) {
+        writeln!(self.page_buffer, "W n").unwrap();
+    }
+
+    /// Paints a shading returned by `add_coons_patch_shading` over the current clip.
+    pub fn shade(&mut self, shading: usize) {
+        writeln!(self.page_buffer, "/Sh{} sh", shading).unwrap();
+    }
+
+    /// Adds a type 6 (Coons patch mesh) shading to the current page and returns its index.
+    ///
+    /// Each patch consists of its 12 control points, in the order that PDF expects them, and the
+    /// colors of its four corners. PDF shadings have no alpha, so the alpha of the colors is
+    /// ignored.
+    pub fn add_coons_patch_shading(&mut self, patches: &[([Vector2F; 12], [ColorU; 4])])
+                                   -> usize {
+        let mut points = patches.iter().flat_map(|(points, _)| points.iter());
+        let (mut min, mut max) = match points.next() {
+            Some(&point) => (point, point),
+            None => (Vector2F::zero(), Vector2F::zero()),
+        };
+        for &point in points {
+            min = min.min(point);
+            max = max.max(point);
+        }
+        let max = max.max(min + Vector2F::splat(1.0));
+
+        // Coordinates are scaled to 32-bit integers spanning the range given by `/Decode`.
+        let encode = |value: f32, min: f32, max: f32| {
+            let value = (value - min) as f64 / (max - min) as f64;
+            (value * u32::MAX as f64).round() as u32
+        };
+        let mut data = Vec::new();
+        for (points, colors) in patches {
+            data.push(0);
+            for point in points.iter() {
+                data.extend_from_slice(&encode(point.x(), min.x(), max.x()).to_be_bytes());
+                data.extend_from_slice(&encode(point.y(), min.y(), max.y()).to_be_bytes());
+            }
+            for color in colors.iter() {
+                data.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+
+        let mut shading = format!(
+            "<< /ShadingType 6 /ColorSpace /DeviceRGB\n \
+               /BitsPerCoordinate 32 /BitsPerComponent 8 /BitsPerFlag 8\n \
+               /Decode [{} {} {} {} 0 1 0 1 0 1]\n \
+               /Length {} >>\nstream\n",
+            min.x(), max.x(), min.y(), max.y(), data.len()
+        ).into_bytes();
+        shading.extend_from_slice(&data);
+        shading.extend(b"\nendstream\n");
+
+        let object_id = self.add_object(shading, false, false);
+        self.page_shadings.push(object_id);
+        self.page_shadings.len() - 1
+    }
     /// Dump a page out to disk
     fn end_page(&mut self) {
         let size = match self.page_size.take() {
@@ -189,6 +259,14 @@ impl Pdf {
             write!(page_object, "/XObject {} 0 R ", idx+1).unwrap();
         }
 
+        if !self.page_shadings.is_empty() {
+            write!(page_object, "/Shading << ").unwrap();
+            for (index, object_id) in self.page_shadings.drain(..).enumerate() {
+                write!(page_object, "/Sh{} {} 0 R ", index, object_id).unwrap();
+            }
+            write!(page_object, ">> ").unwrap();
+        }
+
         write!(page_object,
             " >>\n \
                 /MediaBox [0 0 {} {}]\n \
@@ -18,7 +18,8 @@ const ATLAS_TEXTURE_LENGTH: u32 = 1024;
 
 #[derive(Clone, Debug)]
 pub struct TextureAllocator {
-    pages: Vec<TexturePage>,
+    // Freed pages are `None` and are reused by later allocations.
+    pages: Vec<Option<TexturePage>>,
 }
 
 #[derive(Clone, Debug)]
@@ -71,39 +72,75 @@ impl TextureAllocator {
 
         // Try to add to each atlas.
         for (page_index, page) in self.pages.iter_mut().enumerate() {
-            match page.allocator {
-                TexturePageAllocator::Image { .. } => {}
-                TexturePageAllocator::Atlas(ref mut allocator) => {
+            match *page {
+                Some(TexturePage {
+                    allocator: TexturePageAllocator::Atlas(ref mut allocator),
+                    ..
+                }) => {
                     if let Some(rect) = allocator.allocate(requested_size) {
                         return TextureLocation { page: TexturePageId(page_index as u32), rect };
                     }
                 }
+                Some(TexturePage { allocator: TexturePageAllocator::Image { .. }, .. }) |
+                None => {}
             }
         }
 
         // Add a new atlas.
-        let page = TexturePageId(self.pages.len() as u32);
         let mut allocator = TextureAtlasAllocator::new();
         let rect = allocator.allocate(requested_size).expect("Allocation failed!");
-        self.pages.push(TexturePage {
-            is_new: true,
-            allocator: TexturePageAllocator::Atlas(allocator),
-        });
+        let page = self.push_page(TexturePageAllocator::Atlas(allocator));
         TextureLocation { page, rect }
     }
 
     pub fn allocate_image(&mut self, requested_size: Vector2I) -> TextureLocation {
-        let page = TexturePageId(self.pages.len() as u32);
         let rect = RectI::new(Vector2I::default(), requested_size);
-        self.pages.push(TexturePage {
-            is_new: true,
-            allocator: TexturePageAllocator::Image { size: rect.size() },
-        });
+        let page = self.push_page(TexturePageAllocator::Image { size: rect.size() });
         TextureLocation { page, rect }
     }
 
+    // Puts a new page in the first free slot.
+    fn push_page(&mut self, allocator: TexturePageAllocator) -> TexturePageId {
+        let page = Some(TexturePage { allocator, is_new: true });
+        match self.pages.iter().position(Option::is_none) {
+            Some(page_index) => {
+                self.pages[page_index] = page;
+                TexturePageId(page_index as u32)
+            }
+            None => {
+                self.pages.push(page);
+                TexturePageId(self.pages.len() as u32 - 1)
+            }
+        }
+    }
+
+    /// Frees a location returned by `allocate()` or `allocate_image()`.
+    ///
+    /// Returns true if this freed the entire page, in which case the page ID may be reused by
+    /// subsequent allocations.
+    pub fn free(&mut self, location: TextureLocation) -> bool {
+        let page_index = location.page.0 as usize;
+        let page_is_empty = match self.pages[page_index] {
+            Some(TexturePage { allocator: TexturePageAllocator::Atlas(ref mut allocator), .. }) => {
+                allocator.free(location.rect);
+                allocator.is_empty()
+            }
+            Some(TexturePage { allocator: TexturePageAllocator::Image { .. }, .. }) => true,
+            None => panic!("Texture page was already freed!"),
+        };
+        if page_is_empty {
+            self.pages[page_index] = None;
+        }
+        page_is_empty
+    }
+
+    #[inline]
+    fn page(&self, page_id: TexturePageId) -> &TexturePage {
+        self.pages[page_id.0 as usize].as_ref().expect("Texture page was freed!")
+    }
+
     pub fn page_size(&self, page_id: TexturePageId) -> Vector2I {
-        match self.pages[page_id.0 as usize].allocator {
+        match self.page(page_id).allocator {
             TexturePageAllocator::Atlas(ref atlas) => Vector2I::splat(atlas.size as i32),
             TexturePageAllocator::Image { size, .. } => size,
         }
@@ -114,16 +151,27 @@ impl TextureAllocator {
     }
 
     pub fn page_is_new(&self, page_id: TexturePageId) -> bool {
-        self.pages[page_id.0 as usize].is_new
+        self.page(page_id).is_new
     }
 
     pub fn mark_page_as_allocated(&mut self, page_id: TexturePageId) {
-        self.pages[page_id.0 as usize].is_new = false;
+        if let Some(ref mut page) = self.pages[page_id.0 as usize] {
+            page.is_new = false;
+        }
     }
 
-    #[inline]
-    pub fn page_count(&self) -> u32 {
-        self.pages.len() as u32
+    /// Marks every page as new, so that they're all allocated again by the next build.
+    pub fn mark_all_pages_as_new(&mut self) {
+        for page in self.pages.iter_mut().flatten() {
+            page.is_new = true;
+        }
+    }
+
+    /// Returns the IDs of all pages that are currently allocated.
+    pub fn page_ids(&self) -> impl Iterator<Item = TexturePageId> + '_ {
+        self.pages.iter().enumerate().filter_map(|(page_index, page)| {
+            page.as_ref().map(|_| TexturePageId(page_index as u32))
+        })
     }
 }
 
@@ -140,20 +188,18 @@ impl TextureAtlasAllocator {
 
     #[inline]
     fn allocate(&mut self, requested_size: Vector2I) -> Option<RectI> {
-        let requested_length =
-            (requested_size.x().max(requested_size.y()) as u32).next_power_of_two();
-        self.root.allocate(Vector2I::default(), self.size, requested_length)
+        let requested_length = atlas_length(requested_size);
+        let origin = self.root.allocate(Vector2I::default(), self.size, requested_length)?;
+        Some(RectI::new(origin, requested_size))
     }
 
     #[inline]
-    #[allow(dead_code)]
     fn free(&mut self, rect: RectI) {
-        let requested_length = rect.width() as u32;
+        let requested_length = atlas_length(rect.size());
         self.root.free(Vector2I::default(), self.size, rect.origin(), requested_length)
     }
 
     #[inline]
-    #[allow(dead_code)]
     fn is_empty(&self) -> bool {
         match self.root {
             TreeNode::EmptyLeaf => true,
@@ -162,10 +208,16 @@ impl TextureAtlasAllocator {
     }
 }
 
+// The length of the square quadtree node that an allocation of the given size occupies.
+#[inline]
+fn atlas_length(requested_size: Vector2I) -> u32 {
+    (requested_size.x().max(requested_size.y()) as u32).next_power_of_two()
+}
+
 impl TreeNode {
     // Invariant: `requested_size` must be a power of two.
     fn allocate(&mut self, this_origin: Vector2I, this_size: u32, requested_size: u32)
-                -> Option<RectI> {
+                -> Option<Vector2I> {
         if let TreeNode::FullLeaf = *self {
             // No room here.
             return None;
@@ -180,7 +232,7 @@ impl TreeNode {
             // Do we have a perfect fit?
             if this_size == requested_size {
                 *self = TreeNode::FullLeaf;
-                return Some(RectI::new(this_origin, Vector2I::splat(this_size as i32)));
+                return Some(this_origin);
             }
 
             // Split.
@@ -222,7 +274,6 @@ impl TreeNode {
         }
     }
 
-    #[allow(dead_code)]
     fn free(&mut self,
             this_origin: Vector2I,
             this_size: u32,
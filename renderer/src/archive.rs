@@ -0,0 +1,885 @@
+// pathfinder/renderer/src/archive.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A compact binary format for saving and loading scenes.
+//!
+//! Unlike the exporters in `pathfinder_export`, this is lossless: paths, clip paths, scene nodes,
+//! render targets, paints, and the view box all survive a round trip exactly. Like captures,
+//! archives are little-endian and versioned.
+
+use crate::capture::{self, invalid_data, preallocation_size};
+use crate::paint::{Paint, PaintCompositeOp, PaintContents};
+use crate::scene::{ClipPath, ClipPathId, DisplayItem, DrawPath, RenderTarget, Scene};
+use crate::scene::{SceneNode, SceneNodeId};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use pathfinder_color::ColorU;
+use pathfinder_content::effects::Filter;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::gradient::{ColorStop, Gradient, GradientColorSpace, GradientGeometry};
+use pathfinder_content::mesh::{CoonsPatch, MeshGradient};
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_content::pattern::{Image, Pattern, PatternSource};
+use pathfinder_content::render_target::RenderTargetId;
+use pathfinder_content::stroke::{LineCap, LineJoin, StrokeStyle};
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_simd::default::F32x2;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+/// The version of the scene archive format that this module reads and writes.
+pub const ARCHIVE_VERSION: u32 = 9;
+
+const ARCHIVE_MAGIC: [u8; 4] = *b"PFSC";
+
+const DISPLAY_ITEM_DRAW_PATHS:         u8 = 0;
+const DISPLAY_ITEM_PUSH_RENDER_TARGET: u8 = 1;
+const DISPLAY_ITEM_POP_RENDER_TARGET:  u8 = 2;
+
+const PAINT_CONTENTS_GRADIENT: u8 = 0;
+const PAINT_CONTENTS_PATTERN:  u8 = 1;
+const PAINT_CONTENTS_MESH:     u8 = 2;
+
+const GRADIENT_GEOMETRY_LINEAR: u8 = 0;
+const GRADIENT_GEOMETRY_RADIAL: u8 = 1;
+const GRADIENT_GEOMETRY_CONIC:  u8 = 2;
+
+const PATTERN_SOURCE_IMAGE:         u8 = 0;
+const PATTERN_SOURCE_RENDER_TARGET: u8 = 1;
+
+const PATTERN_FLAG_REPEAT_X:     u8 = 0x01;
+const PATTERN_FLAG_REPEAT_Y:     u8 = 0x02;
+const PATTERN_FLAG_NO_SMOOTHING: u8 = 0x04;
+
+const POINT_ENDPOINT:      u8 = 0;
+const POINT_CONTROL_POINT: u8 = 1;
+
+const LINE_CAP_BUTT:   u8 = 0;
+const LINE_CAP_SQUARE: u8 = 1;
+const LINE_CAP_ROUND:  u8 = 2;
+
+const LINE_JOIN_MITER: u8 = 0;
+const LINE_JOIN_BEVEL: u8 = 1;
+const LINE_JOIN_ROUND: u8 = 2;
+
+/// Writes a scene to an archive.
+pub fn save_scene<W>(scene: &Scene, mut writer: W) -> io::Result<()> where W: Write {
+    writer.write_all(&ARCHIVE_MAGIC)?;
+    writer.write_u32::<LittleEndian>(ARCHIVE_VERSION)?;
+
+    capture::write_f32x4(&mut writer, scene.view_box().0)?;
+
+    writer.write_u32::<LittleEndian>(scene.palette.paints.len() as u32)?;
+    for paint in &scene.palette.paints {
+        write_paint(&mut writer, paint)?;
+    }
+
+    let render_targets: Vec<_> = scene.palette.render_targets().collect();
+    writer.write_u32::<LittleEndian>(render_targets.len() as u32)?;
+    for render_target in render_targets {
+        capture::write_vector2i(&mut writer, render_target.size())?;
+        write_string(&mut writer, render_target.name())?;
+    }
+
+    writer.write_u32::<LittleEndian>(scene.clip_paths.len() as u32)?;
+    for clip_path in &scene.clip_paths {
+        write_outline(&mut writer, clip_path.outline())?;
+        write_clip_path_id(&mut writer, clip_path.clip_path())?;
+        write_node_id(&mut writer, clip_path.node())?;
+        write_fill_rule(&mut writer, clip_path.fill_rule())?;
+        write_string(&mut writer, clip_path.name())?;
+    }
+
+    writer.write_u32::<LittleEndian>(scene.nodes.len() as u32)?;
+    for node in &scene.nodes {
+        capture::write_transform(&mut writer, &node.transform())?;
+        write_node_id(&mut writer, node.parent())?;
+        write_clip_path_id(&mut writer, node.clip_path())?;
+        write_string(&mut writer, node.name())?;
+    }
+
+    // Outlines shared between draw paths are written once.
+    let mut outline_indices = HashMap::new();
+    let mut outlines = vec![];
+    for draw_path in &scene.paths {
+        let outline = draw_path.shared_outline();
+        outline_indices.entry(Arc::as_ptr(outline)).or_insert_with(|| {
+            outlines.push(outline);
+            outlines.len() as u32 - 1
+        });
+    }
+    writer.write_u32::<LittleEndian>(outlines.len() as u32)?;
+    for outline in outlines {
+        write_outline(&mut writer, outline)?;
+    }
+
+    writer.write_u32::<LittleEndian>(scene.paths.len() as u32)?;
+    for draw_path in &scene.paths {
+        let outline_index = outline_indices[&Arc::as_ptr(draw_path.shared_outline())];
+        writer.write_u32::<LittleEndian>(outline_index)?;
+        capture::write_transform(&mut writer, &draw_path.transform())?;
+        writer.write_u16::<LittleEndian>(draw_path.paint().0)?;
+        write_clip_path_id(&mut writer, draw_path.clip_path())?;
+        write_node_id(&mut writer, draw_path.node())?;
+        write_fill_rule(&mut writer, draw_path.fill_rule())?;
+        writer.write_u8(capture::blend_mode_to_u8(draw_path.blend_mode()))?;
+        write_stroke_style(&mut writer, draw_path.non_scaling_stroke())?;
+        write_render_target_id(&mut writer, draw_path.composite_source())?;
+        write_string(&mut writer, draw_path.name())?;
+    }
+
+    writer.write_u32::<LittleEndian>(scene.display_list.len() as u32)?;
+    for display_item in &scene.display_list {
+        match *display_item {
+            DisplayItem::DrawPaths { start_index, end_index } => {
+                writer.write_u8(DISPLAY_ITEM_DRAW_PATHS)?;
+                writer.write_u32::<LittleEndian>(start_index)?;
+                writer.write_u32::<LittleEndian>(end_index)?;
+            }
+            DisplayItem::PushRenderTarget(render_target_id) => {
+                writer.write_u8(DISPLAY_ITEM_PUSH_RENDER_TARGET)?;
+                writer.write_u32::<LittleEndian>(render_target_id.render_target)?;
+            }
+            DisplayItem::PopRenderTarget => writer.write_u8(DISPLAY_ITEM_POP_RENDER_TARGET)?,
+        }
+    }
+
+    writer.flush()
+}
+
+/// Reads a scene back from an archive.
+///
+/// The loaded scene is a new scene with its own ID, so render target IDs in its paints are
+/// rewritten to refer to it.
+pub fn load_scene<R>(mut reader: R) -> io::Result<Scene> where R: Read {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != ARCHIVE_MAGIC {
+        return Err(invalid_data("Not a scene archive!"));
+    }
+    if reader.read_u32::<LittleEndian>()? != ARCHIVE_VERSION {
+        return Err(invalid_data("Unsupported scene archive version!"));
+    }
+
+    let mut scene = Scene::new();
+    scene.set_view_box(RectF(capture::read_f32x4(&mut reader)?));
+
+    // The palette deduplicates paints, so IDs may not be preserved exactly.
+    let paint_count = reader.read_u32::<LittleEndian>()?;
+    let mut paint_mapping = Vec::with_capacity(preallocation_size(paint_count as usize));
+    for _ in 0..paint_count {
+        let paint = read_paint(&mut reader, &scene)?;
+        paint_mapping.push(scene.push_paint(&paint));
+    }
+
+    let render_target_count = reader.read_u32::<LittleEndian>()?;
+    let mut render_targets =
+        Vec::with_capacity(preallocation_size(render_target_count as usize));
+    for _ in 0..render_target_count {
+        let size = capture::read_vector2i(&mut reader)?;
+        let name = read_string(&mut reader)?;
+        render_targets.push(RenderTarget::new(size, name));
+    }
+
+    // Clip paths can be attached to nodes and nodes can be clipped by clip paths, so clip paths
+    // are only pushed once the nodes have been read.
+    let clip_path_count = reader.read_u32::<LittleEndian>()?;
+    let mut clip_paths = Vec::with_capacity(preallocation_size(clip_path_count as usize));
+    for clip_path_index in 0..clip_path_count {
+        let mut clip_path = ClipPath::new(read_outline(&mut reader)?);
+        // Parent clip paths always precede their children.
+        clip_path.set_clip_path(read_clip_path_id(&mut reader, clip_path_index)?);
+        clip_path.set_node(read_node_id(&mut reader, u32::MAX)?);
+        clip_path.set_fill_rule(read_fill_rule(&mut reader)?);
+        clip_path.set_name(read_string(&mut reader)?);
+        clip_paths.push(clip_path);
+    }
+
+    let node_count = reader.read_u32::<LittleEndian>()?;
+    for node_index in 0..node_count {
+        let mut node = SceneNode::new(capture::read_transform(&mut reader)?);
+        // Parent nodes always precede their children.
+        node.set_parent(read_node_id(&mut reader, node_index)?);
+        node.set_clip_path(read_clip_path_id(&mut reader, clip_path_count)?);
+        node.set_name(read_string(&mut reader)?);
+        scene.push_node(node);
+    }
+
+    for clip_path in clip_paths {
+        match clip_path.node() {
+            Some(node_id) if node_id.0 >= node_count => {
+                return Err(invalid_data("Invalid node ID!"));
+            }
+            _ => {}
+        }
+        scene.push_clip_path(clip_path);
+    }
+
+    let outline_count = reader.read_u32::<LittleEndian>()?;
+    let mut outlines = Vec::with_capacity(preallocation_size(outline_count as usize));
+    for _ in 0..outline_count {
+        outlines.push(Arc::new(read_outline(&mut reader)?));
+    }
+
+    let path_count = reader.read_u32::<LittleEndian>()?;
+    let mut paths = Vec::with_capacity(preallocation_size(path_count as usize));
+    for _ in 0..path_count {
+        let outline = match outlines.get(reader.read_u32::<LittleEndian>()? as usize) {
+            Some(outline) => (*outline).clone(),
+            None => return Err(invalid_data("Invalid outline index!")),
+        };
+        let transform = capture::read_transform(&mut reader)?;
+        let paint_id = match paint_mapping.get(reader.read_u16::<LittleEndian>()? as usize) {
+            Some(&paint_id) => paint_id,
+            None => return Err(invalid_data("Invalid paint ID!")),
+        };
+        let mut draw_path = DrawPath::from_shared_outline(outline, paint_id);
+        draw_path.set_transform(transform);
+        draw_path.set_clip_path(read_clip_path_id(&mut reader, clip_path_count)?);
+        draw_path.set_node(read_node_id(&mut reader, node_count)?);
+        draw_path.set_fill_rule(read_fill_rule(&mut reader)?);
+        draw_path.set_blend_mode(capture::blend_mode_from_u8(reader.read_u8()?)?);
+        draw_path.set_non_scaling_stroke(read_stroke_style(&mut reader)?);
+        draw_path.set_composite_source(read_render_target_id(&mut reader,
+                                                             &scene,
+                                                             render_target_count)?);
+        draw_path.set_name(read_string(&mut reader)?);
+        paths.push(Some(draw_path));
+    }
+
+    // Replay the display list. Render targets are pushed in ID order, so their IDs are preserved.
+    let display_item_count = reader.read_u32::<LittleEndian>()?;
+    let mut render_targets = render_targets.into_iter();
+    let mut next_render_target_index = 0;
+    let mut render_target_depth = 0;
+    for _ in 0..display_item_count {
+        match reader.read_u8()? {
+            DISPLAY_ITEM_DRAW_PATHS => {
+                let start_index = reader.read_u32::<LittleEndian>()?;
+                let end_index = reader.read_u32::<LittleEndian>()?;
+                for path_index in start_index..end_index {
+                    match paths.get_mut(path_index as usize).and_then(|path| path.take()) {
+                        Some(draw_path) => {
+                            scene.push_path(draw_path);
+                        }
+                        None => return Err(invalid_data("Invalid path index!")),
+                    }
+                }
+            }
+            DISPLAY_ITEM_PUSH_RENDER_TARGET => {
+                let render_target_index = reader.read_u32::<LittleEndian>()?;
+                if render_target_index != next_render_target_index {
+                    return Err(invalid_data("Render targets are out of order!"));
+                }
+                match render_targets.next() {
+                    Some(render_target) => scene.push_render_target(render_target),
+                    None => return Err(invalid_data("Invalid render target ID!")),
+                };
+                next_render_target_index += 1;
+                render_target_depth += 1;
+            }
+            DISPLAY_ITEM_POP_RENDER_TARGET => {
+                if render_target_depth == 0 {
+                    return Err(invalid_data("Unbalanced render targets!"));
+                }
+                scene.pop_render_target();
+                render_target_depth -= 1;
+            }
+            _ => return Err(invalid_data("Unknown display item!")),
+        }
+    }
+    if render_target_depth != 0 {
+        return Err(invalid_data("Unbalanced render targets!"));
+    }
+
+    Ok(scene)
+}
+
+// Paints
+
+fn write_paint<W>(writer: &mut W, paint: &Paint) -> io::Result<()> where W: Write {
+    capture::write_color_u(writer, paint.base_color())?;
+    writer.write_u8(paint.device_space() as u8)?;
+    let overlay = match *paint.overlay() {
+        None => return writer.write_u8(0),
+        Some(ref overlay) => overlay,
+    };
+    writer.write_u8(1)?;
+    writer.write_u8(match overlay.composite_op() {
+        PaintCompositeOp::SrcIn => 0,
+        PaintCompositeOp::DestIn => 1,
+    })?;
+
+    match *overlay.contents() {
+        PaintContents::Gradient(ref gradient) => {
+            writer.write_u8(PAINT_CONTENTS_GRADIENT)?;
+            match gradient.geometry {
+                GradientGeometry::Linear(line) => {
+                    writer.write_u8(GRADIENT_GEOMETRY_LINEAR)?;
+                    capture::write_f32x4(writer, line.0)?;
+                }
+                GradientGeometry::Radial { line, radii, ref transform } => {
+                    writer.write_u8(GRADIENT_GEOMETRY_RADIAL)?;
+                    capture::write_f32x4(writer, line.0)?;
+                    writer.write_f32::<LittleEndian>(radii.x())?;
+                    writer.write_f32::<LittleEndian>(radii.y())?;
+                    capture::write_transform(writer, transform)?;
+                }
+                GradientGeometry::Conic { center, angle, ref transform } => {
+                    writer.write_u8(GRADIENT_GEOMETRY_CONIC)?;
+                    capture::write_vector2f(writer, center)?;
+                    writer.write_f32::<LittleEndian>(angle)?;
+                    capture::write_transform(writer, transform)?;
+                }
+            }
+            writer.write_u32::<LittleEndian>(gradient.stops().len() as u32)?;
+            for stop in gradient.stops() {
+                writer.write_f32::<LittleEndian>(stop.offset)?;
+                capture::write_color_u(writer, stop.color)?;
+            }
+            writer.write_u8(capture::gradient_spread_to_u8(gradient.spread()))?;
+            writer.write_u8(match gradient.color_space() {
+                GradientColorSpace::Srgb => 0,
+                GradientColorSpace::LinearSrgb => 1,
+                GradientColorSpace::Oklab => 2,
+            })?;
+            writer.write_u8(gradient.premultiplied() as u8)?;
+        }
+        PaintContents::Pattern(ref pattern) => {
+            writer.write_u8(PAINT_CONTENTS_PATTERN)?;
+            match *pattern.source() {
+                PatternSource::Image(ref image) => {
+                    writer.write_u8(PATTERN_SOURCE_IMAGE)?;
+                    capture::write_vector2i(writer, image.size())?;
+                    for &pixel in image.pixels().iter() {
+                        capture::write_color_u(writer, pixel)?;
+                    }
+                }
+                PatternSource::RenderTarget { id, size } => {
+                    writer.write_u8(PATTERN_SOURCE_RENDER_TARGET)?;
+                    writer.write_u32::<LittleEndian>(id.render_target)?;
+                    capture::write_vector2i(writer, size)?;
+                }
+            }
+            capture::write_transform(writer, &pattern.transform())?;
+            let filter = match pattern.filter() {
+                None => Filter::None,
+                Some(pattern_filter) => Filter::PatternFilter(pattern_filter),
+            };
+            capture::write_filter(writer, &filter)?;
+
+            let mut flags = 0;
+            if pattern.repeat_x() {
+                flags |= PATTERN_FLAG_REPEAT_X;
+            }
+            if pattern.repeat_y() {
+                flags |= PATTERN_FLAG_REPEAT_Y;
+            }
+            if !pattern.smoothing_enabled() {
+                flags |= PATTERN_FLAG_NO_SMOOTHING;
+            }
+            writer.write_u8(flags)?;
+        }
+        PaintContents::Mesh(ref mesh) => {
+            writer.write_u8(PAINT_CONTENTS_MESH)?;
+            writer.write_u32::<LittleEndian>(mesh.patches().len() as u32)?;
+            for patch in mesh.patches() {
+                for &point in &patch.points {
+                    capture::write_vector2f(writer, point)?;
+                }
+                for &color in &patch.colors {
+                    capture::write_color_u(writer, color)?;
+                }
+            }
+            capture::write_transform(writer, &mesh.transform())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_paint<R>(reader: &mut R, scene: &Scene) -> io::Result<Paint> where R: Read {
+    let base_color = capture::read_color_u(reader)?;
+    let device_space = capture::read_bool(reader)?;
+    if !capture::read_bool(reader)? {
+        let mut paint = Paint::from_color(base_color);
+        paint.set_device_space(device_space);
+        return Ok(paint);
+    }
+    let composite_op = match reader.read_u8()? {
+        0 => PaintCompositeOp::SrcIn,
+        1 => PaintCompositeOp::DestIn,
+        _ => return Err(invalid_data("Invalid paint composite op!")),
+    };
+
+    let mut paint = match reader.read_u8()? {
+        PAINT_CONTENTS_GRADIENT => {
+            let mut gradient = match reader.read_u8()? {
+                GRADIENT_GEOMETRY_LINEAR => {
+                    Gradient::linear(LineSegment2F(capture::read_f32x4(reader)?))
+                }
+                GRADIENT_GEOMETRY_RADIAL => {
+                    let line = LineSegment2F(capture::read_f32x4(reader)?);
+                    let radii_x = reader.read_f32::<LittleEndian>()?;
+                    let radii_y = reader.read_f32::<LittleEndian>()?;
+                    let mut gradient = Gradient::radial(line, F32x2::new(radii_x, radii_y));
+                    if let GradientGeometry::Radial { ref mut transform, .. } = gradient.geometry {
+                        *transform = capture::read_transform(reader)?;
+                    }
+                    gradient
+                }
+                GRADIENT_GEOMETRY_CONIC => {
+                    let center = capture::read_vector2f(reader)?;
+                    let angle = reader.read_f32::<LittleEndian>()?;
+                    let mut gradient = Gradient::conic(center, angle);
+                    if let GradientGeometry::Conic { ref mut transform, .. } = gradient.geometry {
+                        *transform = capture::read_transform(reader)?;
+                    }
+                    gradient
+                }
+                _ => return Err(invalid_data("Unknown gradient geometry!")),
+            };
+            let stop_count = reader.read_u32::<LittleEndian>()?;
+            for _ in 0..stop_count {
+                let offset = reader.read_f32::<LittleEndian>()?;
+                let color = capture::read_color_u(reader)?;
+                gradient.add(ColorStop { offset, color });
+            }
+            gradient.set_spread(capture::gradient_spread_from_u8(reader.read_u8()?)?);
+            gradient.set_color_space(match reader.read_u8()? {
+                0 => GradientColorSpace::Srgb,
+                1 => GradientColorSpace::LinearSrgb,
+                2 => GradientColorSpace::Oklab,
+                _ => return Err(invalid_data("Unknown gradient color space!")),
+            });
+            gradient.set_premultiplied(capture::read_bool(reader)?);
+            Paint::from_gradient(gradient)
+        }
+        PAINT_CONTENTS_PATTERN => {
+            let mut pattern = match reader.read_u8()? {
+                PATTERN_SOURCE_IMAGE => {
+                    let size = capture::read_vector2i(reader)?;
+                    if size.x() < 0 || size.y() < 0 {
+                        return Err(invalid_data("Invalid image size!"));
+                    }
+                    let pixel_count = size.x() as usize * size.y() as usize;
+                    let mut pixels = Vec::with_capacity(preallocation_size(pixel_count));
+                    for _ in 0..pixel_count {
+                        pixels.push(capture::read_color_u(reader)?);
+                    }
+                    Pattern::from_image(Image::new(size, Arc::new(pixels)))
+                }
+                PATTERN_SOURCE_RENDER_TARGET => {
+                    let render_target = reader.read_u32::<LittleEndian>()?;
+                    let size = capture::read_vector2i(reader)?;
+                    let id = RenderTargetId { scene: scene.id.0, render_target };
+                    Pattern::from_render_target(id, size)
+                }
+                _ => return Err(invalid_data("Unknown pattern source!")),
+            };
+            pattern.apply_transform(capture::read_transform(reader)?);
+            match capture::read_filter(reader)? {
+                Filter::None => {}
+                Filter::PatternFilter(pattern_filter) => pattern.set_filter(Some(pattern_filter)),
+                Filter::LinearGradient { .. } |
+                Filter::RadialGradient { .. } |
+                Filter::ConicGradient { .. } => {
+                    return Err(invalid_data("Patterns can't have gradient filters!"))
+                }
+            }
+            let flags = reader.read_u8()?;
+            pattern.set_repeat_x((flags & PATTERN_FLAG_REPEAT_X) != 0);
+            pattern.set_repeat_y((flags & PATTERN_FLAG_REPEAT_Y) != 0);
+            pattern.set_smoothing_enabled((flags & PATTERN_FLAG_NO_SMOOTHING) == 0);
+            Paint::from_pattern(pattern)
+        }
+        PAINT_CONTENTS_MESH => {
+            let mut mesh = MeshGradient::new();
+            let patch_count = reader.read_u32::<LittleEndian>()?;
+            for _ in 0..patch_count {
+                let mut patch = CoonsPatch {
+                    points: [Vector2F::zero(); 12],
+                    colors: [ColorU::transparent_black(); 4],
+                };
+                for point in &mut patch.points {
+                    *point = capture::read_vector2f(reader)?;
+                }
+                for color in &mut patch.colors {
+                    *color = capture::read_color_u(reader)?;
+                }
+                mesh.push_patch(patch);
+            }
+            mesh.apply_transform(capture::read_transform(reader)?);
+            Paint::from_mesh_gradient(mesh)
+        }
+        _ => return Err(invalid_data("Unknown paint contents!")),
+    };
+
+    paint.set_base_color(base_color);
+    paint.set_device_space(device_space);
+    if let Some(ref mut overlay) = *paint.overlay_mut() {
+        overlay.set_composite_op(composite_op);
+    }
+    Ok(paint)
+}
+
+// Outlines
+
+fn write_outline<W>(writer: &mut W, outline: &Outline) -> io::Result<()> where W: Write {
+    writer.write_u32::<LittleEndian>(outline.contours().len() as u32)?;
+    for contour in outline.contours() {
+        writer.write_u32::<LittleEndian>(contour.len())?;
+        writer.write_u8(contour.is_closed() as u8)?;
+        for point_index in 0..contour.len() {
+            capture::write_vector2f(writer, contour.position_of(point_index))?;
+            writer.write_u8(if contour.point_is_endpoint(point_index) {
+                POINT_ENDPOINT
+            } else {
+                POINT_CONTROL_POINT
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn read_outline<R>(reader: &mut R) -> io::Result<Outline> where R: Read {
+    let mut outline = Outline::new();
+    let contour_count = reader.read_u32::<LittleEndian>()?;
+    for _ in 0..contour_count {
+        let point_count = reader.read_u32::<LittleEndian>()?;
+        let closed = capture::read_bool(reader)?;
+        let mut contour = Contour::with_capacity(preallocation_size(point_count as usize));
+        let mut control_points: Vec<Vector2F> = vec![];
+        for _ in 0..point_count {
+            let position = capture::read_vector2f(reader)?;
+            match reader.read_u8()? {
+                POINT_CONTROL_POINT => control_points.push(position),
+                POINT_ENDPOINT => {
+                    match control_points[..] {
+                        [] => contour.push_endpoint(position),
+                        [ctrl] => contour.push_quadratic(ctrl, position),
+                        [ctrl0, ctrl1] => contour.push_cubic(ctrl0, ctrl1, position),
+                        _ => return Err(invalid_data("Too many control points in a segment!")),
+                    }
+                    control_points.clear();
+                }
+                _ => return Err(invalid_data("Unknown point kind!")),
+            }
+        }
+        if !control_points.is_empty() {
+            return Err(invalid_data("Contour ends with a control point!"));
+        }
+        if closed {
+            contour.close();
+        }
+        outline.push_contour(contour);
+    }
+    Ok(outline)
+}
+
+// Utilities
+
+fn write_fill_rule<W>(writer: &mut W, fill_rule: FillRule) -> io::Result<()> where W: Write {
+    writer.write_u8(match fill_rule {
+        FillRule::Winding => 0,
+        FillRule::EvenOdd => 1,
+    })
+}
+
+fn read_fill_rule<R>(reader: &mut R) -> io::Result<FillRule> where R: Read {
+    match reader.read_u8()? {
+        0 => Ok(FillRule::Winding),
+        1 => Ok(FillRule::EvenOdd),
+        _ => Err(invalid_data("Unknown fill rule!")),
+    }
+}
+
+fn write_stroke_style<W>(writer: &mut W, stroke_style: Option<StrokeStyle>) -> io::Result<()>
+                         where W: Write {
+    let stroke_style = match stroke_style {
+        None => return writer.write_u8(0),
+        Some(stroke_style) => stroke_style,
+    };
+    writer.write_u8(1)?;
+    writer.write_f32::<LittleEndian>(stroke_style.line_width)?;
+    writer.write_u8(match stroke_style.line_cap {
+        LineCap::Butt => LINE_CAP_BUTT,
+        LineCap::Square => LINE_CAP_SQUARE,
+        LineCap::Round => LINE_CAP_ROUND,
+    })?;
+    match stroke_style.line_join {
+        LineJoin::Miter(miter_limit) => {
+            writer.write_u8(LINE_JOIN_MITER)?;
+            writer.write_f32::<LittleEndian>(miter_limit)
+        }
+        LineJoin::Bevel => writer.write_u8(LINE_JOIN_BEVEL),
+        LineJoin::Round => writer.write_u8(LINE_JOIN_ROUND),
+    }
+}
+
+fn read_stroke_style<R>(reader: &mut R) -> io::Result<Option<StrokeStyle>> where R: Read {
+    if !capture::read_bool(reader)? {
+        return Ok(None);
+    }
+    let line_width = reader.read_f32::<LittleEndian>()?;
+    let line_cap = match reader.read_u8()? {
+        LINE_CAP_BUTT => LineCap::Butt,
+        LINE_CAP_SQUARE => LineCap::Square,
+        LINE_CAP_ROUND => LineCap::Round,
+        _ => return Err(invalid_data("Unknown line cap!")),
+    };
+    let line_join = match reader.read_u8()? {
+        LINE_JOIN_MITER => LineJoin::Miter(reader.read_f32::<LittleEndian>()?),
+        LINE_JOIN_BEVEL => LineJoin::Bevel,
+        LINE_JOIN_ROUND => LineJoin::Round,
+        _ => return Err(invalid_data("Unknown line join!")),
+    };
+    Ok(Some(StrokeStyle { line_width, line_cap, line_join }))
+}
+
+fn write_render_target_id<W>(writer: &mut W, render_target_id: Option<RenderTargetId>)
+                             -> io::Result<()> where W: Write {
+    match render_target_id {
+        None => writer.write_u8(0),
+        Some(render_target_id) => {
+            writer.write_u8(1)?;
+            writer.write_u32::<LittleEndian>(render_target_id.render_target)
+        }
+    }
+}
+
+// Only render target IDs less than `render_target_count` are valid.
+fn read_render_target_id<R>(reader: &mut R, scene: &Scene, render_target_count: u32)
+                            -> io::Result<Option<RenderTargetId>> where R: Read {
+    if !capture::read_bool(reader)? {
+        return Ok(None);
+    }
+    let render_target = reader.read_u32::<LittleEndian>()?;
+    if render_target >= render_target_count {
+        return Err(invalid_data("Invalid render target ID!"));
+    }
+    Ok(Some(RenderTargetId { scene: scene.id.0, render_target }))
+}
+
+fn write_clip_path_id<W>(writer: &mut W, clip_path_id: Option<ClipPathId>) -> io::Result<()>
+                         where W: Write {
+    match clip_path_id {
+        None => writer.write_u8(0),
+        Some(clip_path_id) => {
+            writer.write_u8(1)?;
+            writer.write_u32::<LittleEndian>(clip_path_id.0)
+        }
+    }
+}
+
+// Only clip path IDs less than `clip_path_count` are valid.
+fn read_clip_path_id<R>(reader: &mut R, clip_path_count: u32) -> io::Result<Option<ClipPathId>>
+                        where R: Read {
+    if !capture::read_bool(reader)? {
+        return Ok(None);
+    }
+    let clip_path_id = reader.read_u32::<LittleEndian>()?;
+    if clip_path_id >= clip_path_count {
+        return Err(invalid_data("Invalid clip path ID!"));
+    }
+    Ok(Some(ClipPathId(clip_path_id)))
+}
+
+fn write_node_id<W>(writer: &mut W, node_id: Option<SceneNodeId>) -> io::Result<()>
+                    where W: Write {
+    match node_id {
+        None => writer.write_u8(0),
+        Some(node_id) => {
+            writer.write_u8(1)?;
+            writer.write_u32::<LittleEndian>(node_id.0)
+        }
+    }
+}
+
+// Only node IDs less than `node_count` are valid.
+fn read_node_id<R>(reader: &mut R, node_count: u32) -> io::Result<Option<SceneNodeId>>
+                   where R: Read {
+    if !capture::read_bool(reader)? {
+        return Ok(None);
+    }
+    let node_id = reader.read_u32::<LittleEndian>()?;
+    if node_id >= node_count {
+        return Err(invalid_data("Invalid node ID!"));
+    }
+    Ok(Some(SceneNodeId(node_id)))
+}
+
+fn write_string<W>(writer: &mut W, string: &str) -> io::Result<()> where W: Write {
+    writer.write_u32::<LittleEndian>(string.len() as u32)?;
+    writer.write_all(string.as_bytes())
+}
+
+fn read_string<R>(reader: &mut R) -> io::Result<String> where R: Read {
+    let length = reader.read_u32::<LittleEndian>()?;
+    let mut bytes = vec![];
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length as usize {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "String was truncated!"));
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("String is not valid UTF-8!"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::paint::Paint;
+    use crate::scene::{ClipPath, DrawPath, RenderTarget, Scene, SceneNode};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::BlendMode;
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_content::gradient::{Gradient, GradientColorSpace, GradientSpread};
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_content::pattern::Pattern;
+    use pathfinder_content::stroke::{LineCap, LineJoin, StrokeStyle};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{vec2f, vec2i};
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use std::sync::Arc;
+
+    use super::{load_scene, save_scene, ARCHIVE_MAGIC, ARCHIVE_VERSION};
+
+    #[test]
+    fn test_round_trip() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(100.0, 50.0)));
+
+        let render_target_id =
+            scene.push_render_target(RenderTarget::new(vec2i(32, 32), "layer".to_owned()));
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_quadratic(vec2f(10.0, 0.0), vec2f(10.0, 10.0));
+        contour.push_cubic(vec2f(5.0, 15.0), vec2f(0.0, 15.0), vec2f(0.0, 10.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        scene.push_path(DrawPath::new(outline, red));
+        scene.pop_render_target();
+
+        let mut gradient = Gradient::linear_from_points(vec2f(0.0, 0.0), vec2f(50.0, 0.0));
+        gradient.add_color_stop(ColorU::black(), 0.0);
+        gradient.add_color_stop(ColorU::white(), 1.0);
+        gradient.set_spread(GradientSpread::Reflect);
+        gradient.set_color_space(GradientColorSpace::Oklab);
+        gradient.set_premultiplied(true);
+        let gradient = scene.push_paint(&Paint::from_gradient(gradient));
+        let mut pattern = Pattern::from_render_target(render_target_id, vec2i(32, 32));
+        pattern.set_repeat_x(true);
+        let pattern = scene.push_paint(&Paint::from_pattern(pattern));
+
+        let mut clip_path = ClipPath::new(Outline::from_rect(RectF::new(vec2f(5.0, 5.0),
+                                                                        vec2f(40.0, 40.0))));
+        clip_path.set_fill_rule(FillRule::EvenOdd);
+        let clip_path_id = scene.push_clip_path(clip_path);
+        let mut clip_path = ClipPath::new(Outline::from_rect(RectF::new(vec2f(20.0, 0.0),
+                                                                        vec2f(30.0, 50.0))));
+        clip_path.set_clip_path(Some(clip_path_id));
+        let clip_path_id = scene.push_clip_path(clip_path);
+
+        let mut path = DrawPath::new(Outline::from_rect(RectF::new(vec2f(0.0, 0.0),
+                                                                   vec2f(50.0, 50.0))),
+                                     gradient);
+        let mut node = SceneNode::new(Transform2F::from_translation(vec2f(2.0, 3.0)));
+        node.set_clip_path(Some(clip_path_id));
+        node.set_name("node".to_owned());
+        let node_id = scene.push_node(node);
+        path.set_clip_path(Some(clip_path_id));
+        path.set_node(Some(node_id));
+        path.set_blend_mode(BlendMode::Multiply);
+        path.set_non_scaling_stroke(Some(StrokeStyle {
+            line_width: 2.0,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Miter(4.0),
+        }));
+        path.set_name("gradient".to_owned());
+        scene.push_path(path);
+        let square = Arc::new(Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(25.0, 25.0))));
+        for &origin in &[vec2f(50.0, 0.0), vec2f(75.0, 25.0)] {
+            let mut path = DrawPath::from_shared_outline(square.clone(), pattern);
+            path.set_transform(Transform2F::from_translation(origin));
+            scene.push_path(path);
+        }
+        scene.push_group(0.5, BlendMode::SrcOver, None);
+        scene.push_path(DrawPath::new(Outline::from_rect(RectF::new(vec2f(0.0, 0.0),
+                                                                     vec2f(10.0, 10.0))),
+                                      red));
+        scene.pop_group();
+
+        let mut archive = vec![];
+        save_scene(&scene, &mut archive).unwrap();
+        let loaded = load_scene(&archive[..]).unwrap();
+
+        assert_eq!(loaded.view_box(), scene.view_box());
+        assert_eq!(loaded.bounds(), scene.bounds());
+        assert_eq!(loaded.path_count(), scene.path_count());
+        assert_eq!(loaded.paths[1].name(), "gradient");
+        assert_eq!(loaded.palette.paints[1].gradient(), scene.palette.paints[1].gradient());
+        assert!(loaded.palette.paints[2].pattern().unwrap().repeat_x());
+        assert_eq!(loaded.clip_paths[1].clip_path().map(|clip_path_id| clip_path_id.0), Some(0));
+        assert_eq!(loaded.paths[1].node(), Some(node_id));
+        assert_eq!(loaded.paths[1].non_scaling_stroke(), scene.paths[1].non_scaling_stroke());
+        assert_eq!(loaded.nodes[0].transform(), scene.nodes[0].transform());
+        assert!(Arc::ptr_eq(loaded.paths[2].shared_outline(), loaded.paths[3].shared_outline()));
+        assert_eq!(loaded.paths[3].transform(), scene.paths[3].transform());
+        let group_path = scene.path_count() - 1;
+        assert!(scene.paths[group_path].composite_source().is_some());
+        assert_eq!(loaded.paths[group_path].composite_source().map(|id| id.render_target),
+                   scene.paths[group_path].composite_source().map(|id| id.render_target));
+        let group_paint_id = loaded.paths[group_path].paint();
+        assert!(loaded.palette.paints[group_paint_id.0 as usize].device_space());
+
+        // Saving the loaded scene must produce exactly the same archive.
+        let mut resaved = vec![];
+        save_scene(&loaded, &mut resaved).unwrap();
+        assert_eq!(resaved, archive);
+    }
+
+    #[test]
+    fn test_load_corrupt_archive() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(100.0, 50.0)));
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        scene.push_path(DrawPath::new(Outline::from_rect(RectF::new(vec2f(0.0, 0.0),
+                                                                     vec2f(10.0, 10.0))),
+                                      red));
+        let mut archive = vec![];
+        save_scene(&scene, &mut archive).unwrap();
+        for length in 0..archive.len() {
+            assert!(load_scene(&archive[..length]).is_err());
+        }
+
+        // A huge element count must fail on the missing data, not on the allocation.
+        let mut archive = ARCHIVE_MAGIC.to_vec();
+        archive.write_u32::<LittleEndian>(ARCHIVE_VERSION).unwrap();
+        for _ in 0..4 {
+            archive.write_f32::<LittleEndian>(0.0).unwrap();
+        }
+        archive.write_u32::<LittleEndian>(!0).unwrap();
+        assert!(load_scene(&archive[..]).is_err());
+
+        // Render targets that are popped without being pushed, or never popped, are rejected.
+        let mut unbalanced_scene = scene.clone();
+        unbalanced_scene.pop_render_target();
+        let mut archive = vec![];
+        save_scene(&unbalanced_scene, &mut archive).unwrap();
+        assert!(load_scene(&archive[..]).is_err());
+        let mut unbalanced_scene = scene;
+        unbalanced_scene.push_render_target(RenderTarget::new(vec2i(8, 8), String::new()));
+        let mut archive = vec![];
+        save_scene(&unbalanced_scene, &mut archive).unwrap();
+        assert!(load_scene(&archive[..]).is_err());
+    }
+}
@@ -12,17 +12,20 @@
 
 use crate::concurrent::executor::Executor;
 use crate::gpu::renderer::{BlendModeExt, MASK_TILES_ACROSS, MASK_TILES_DOWN};
-use crate::gpu_data::{AlphaTileId, Clip, ClipBatch, ClipBatchKey, ClipBatchKind, Fill};
+use crate::gpu_data::{AlphaTileId, BuildStats, Clip, ClipBatch, ClipBatchKey, ClipBatchKind};
+use crate::gpu_data::Fill;
 use crate::gpu_data::{FillBatchEntry, RenderCommand, TILE_CTRL_MASK_0_SHIFT};
 use crate::gpu_data::{TILE_CTRL_MASK_EVEN_ODD, TILE_CTRL_MASK_WINDING, Tile, TileBatch};
 use crate::gpu_data::{TileBatchTexture, TileObjectPrimitive};
 use crate::options::{PreparedBuildOptions, PreparedRenderTransform, RenderCommandListener};
+use crate::options::TileSize;
 use crate::paint::{PaintInfo, PaintMetadata};
-use crate::scene::{DisplayItem, Scene};
+use crate::scene::{DisplayItem, DrawPathId, Scene};
 use crate::tile_map::DenseTileMap;
-use crate::tiles::{self, DrawTilingPathInfo, PackedTile, TILE_HEIGHT, TILE_WIDTH};
+use crate::tiles::{self, DrawTilingPathInfo, PackedTile};
 use crate::tiles::{Tiler, TilingPathInfo};
 use crate::z_buffer::{DepthMetadata, ZBuffer};
+use fxhash::FxHashMap;
 use pathfinder_content::effects::{BlendMode, Filter};
 use pathfinder_content::fill::FillRule;
 use pathfinder_content::render_target::RenderTargetId;
@@ -33,7 +36,7 @@ use pathfinder_geometry::util;
 use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
 use pathfinder_gpu::TextureSamplingFlags;
 use pathfinder_simd::default::{F32x4, I32x4};
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use instant::Instant;
 use std::u32;
 
@@ -45,6 +48,10 @@ pub(crate) struct SceneBuilder<'a, 'b> {
     built_options: &'b PreparedBuildOptions,
     next_alpha_tile_indices: [AtomicUsize; ALPHA_TILE_LEVEL_COUNT],
     pub(crate) listener: Box<dyn RenderCommandListener>,
+    // Updated by paths as they're built in parallel.
+    cached_path_count: AtomicUsize,
+    fill_count: AtomicUsize,
+    max_path_fill_count: AtomicUsize,
 }
 
 #[derive(Debug)]
@@ -52,6 +59,7 @@ pub(crate) struct ObjectBuilder {
     pub built_path: BuiltPath,
     pub fills: Vec<FillBatchEntry>,
     pub bounds: RectF,
+    pub tile_size: TileSize,
 }
 
 #[derive(Debug)]
@@ -64,7 +72,7 @@ struct BuiltDrawPath {
     mask_0_fill_rule: FillRule,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct BuiltPath {
     pub solid_tiles: SolidTiles,
     pub empty_tiles: Vec<BuiltTile>,
@@ -97,6 +105,33 @@ pub(crate) struct Occluder {
     pub(crate) coords: Vector2I,
 }
 
+/// Tiling results from previous builds, reused for draw paths that haven't changed since.
+///
+/// Cached paths refer to the alpha tiles that were allocated when they were built. Since alpha
+/// tiles are allocated afresh every build, those are remapped to new tiles whenever a cached path
+/// is reused.
+#[derive(Clone)]
+pub(crate) struct BuildCache {
+    key: Option<BuildCacheKey>,
+    draw_paths: Vec<Option<CachedDrawPath>>,
+}
+
+// Everything besides the paths themselves that affects tiling.
+#[derive(Clone, Copy, PartialEq)]
+struct BuildCacheKey {
+    transform: Transform2F,
+    dilation: Vector2F,
+    subpixel_aa_enabled: bool,
+    tile_size: TileSize,
+    view_box: RectF,
+}
+
+#[derive(Clone)]
+pub(crate) struct CachedDrawPath {
+    built_path: BuiltPath,
+    fills: Vec<FillBatchEntry>,
+}
+
 impl<'a, 'b> SceneBuilder<'a, 'b> {
     pub(crate) fn new(
         scene: &'a mut Scene,
@@ -108,6 +143,9 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
             built_options,
             next_alpha_tile_indices: [AtomicUsize::new(0), AtomicUsize::new(0)],
             listener,
+            cached_path_count: AtomicUsize::new(0),
+            fill_count: AtomicUsize::new(0),
+            max_path_fill_count: AtomicUsize::new(0),
         }
     }
 
@@ -127,6 +165,7 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
             bounding_quad,
             path_count: total_path_count,
             needs_readable_framebuffer,
+            tile_size: self.built_options.tile_size,
         });
 
         let render_transform = match self.built_options.transform {
@@ -134,32 +173,51 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
             _ => Transform2F::default()
         };
 
+        let mut build_stats = BuildStats::default();
+
         // Build paint data.
+        let paint_start_time = Instant::now();
         let PaintInfo {
             render_commands,
             paint_metadata,
             render_target_metadata: _,
         } = self.scene.build_paint_info(render_transform);
         for render_command in render_commands {
+            if let RenderCommand::AllocateTexturePage { .. } = render_command {
+                build_stats.new_texture_page_count += 1;
+            }
             self.listener.send(render_command);
         }
+        build_stats.resident_texture_page_count = self.scene.palette.texture_page_count();
+        build_stats.paint_time = Instant::now() - paint_start_time;
 
         let effective_view_box = self.scene.effective_view_box(self.built_options);
 
+        // Throw away cached tiles if anything that affects tiling has changed.
+        let cache_key = BuildCacheKey::new(self.built_options, effective_view_box);
+        self.scene.build_cache.prepare(cache_key, draw_path_count);
+
+        let node_transforms = self.scene.node_transforms();
+
+        let clip_path_tiling_start_time = Instant::now();
         let built_clip_paths = executor.build_vector(clip_path_count, |path_index| {
             self.build_clip_path(PathBuildParams {
                 path_index,
                 view_box: effective_view_box,
+                node_transforms: &node_transforms,
                 built_options: &self.built_options,
                 scene: &self.scene,
             })
         });
+        build_stats.clip_path_tiling_time = Instant::now() - clip_path_tiling_start_time;
 
+        let draw_path_tiling_start_time = Instant::now();
         let built_draw_paths = executor.build_vector(draw_path_count, |path_index| {
             self.build_draw_path(DrawPathBuildParams {
                 path_build_params: PathBuildParams {
                     path_index,
                     view_box: effective_view_box,
+                    node_transforms: &node_transforms,
                     built_options: &self.built_options,
                     scene: &self.scene,
                 },
@@ -167,17 +225,39 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
                 built_clip_paths: &built_clip_paths,
             })
         });
+        let (built_draw_paths, cached_draw_paths): (Vec<_>, Vec<_>) =
+            built_draw_paths.into_iter().unzip();
+        self.scene.build_cache.update(cached_draw_paths);
+        build_stats.draw_path_tiling_time = Instant::now() - draw_path_tiling_start_time;
+        build_stats.cached_path_count = self.cached_path_count.load(Ordering::Relaxed);
+        build_stats.fill_count = self.fill_count.load(Ordering::Relaxed);
+        build_stats.max_path_fill_count = self.max_path_fill_count.load(Ordering::Relaxed);
 
-        self.finish_building(&paint_metadata, built_draw_paths);
+        self.finish_building(&paint_metadata, built_draw_paths, &mut build_stats);
 
         let cpu_build_time = Instant::now() - start_time;
-        self.listener.send(RenderCommand::Finish { cpu_build_time });
+        self.listener.send(RenderCommand::Finish { cpu_build_time, build_stats });
     }
 
     fn build_clip_path(&self, params: PathBuildParams) -> BuiltPath {
-        let PathBuildParams { path_index, view_box, built_options, scene } = params;
+        let PathBuildParams {
+            path_index,
+            view_box,
+            node_transforms,
+            built_options,
+            scene,
+        } = params;
         let path_object = &scene.clip_paths[path_index];
-        let outline = scene.apply_render_options(path_object.outline(), built_options);
+        let transform = match path_object.node() {
+            None => Transform2F::default(),
+            Some(node_id) => node_transforms[node_id.0 as usize],
+        };
+        let outline =
+            scene.apply_render_options(path_object.outline(),
+                                       transform,
+                                       None,
+                                       false,
+                                       built_options);
 
         let mut tiler = Tiler::new(self,
                                    &outline,
@@ -190,21 +270,61 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
         tiler.object_builder.built_path
     }
 
-    fn build_draw_path(&self, params: DrawPathBuildParams) -> BuiltDrawPath {
+    // Returns the built path, along with a new cache entry for it if it was tiled from scratch
+    // and can be cached.
+    fn build_draw_path(&self, params: DrawPathBuildParams)
+                       -> (BuiltDrawPath, Option<CachedDrawPath>) {
         let DrawPathBuildParams {
-            path_build_params: PathBuildParams { path_index, view_box, built_options, scene },
+            path_build_params: PathBuildParams {
+                path_index,
+                view_box,
+                node_transforms,
+                built_options,
+                scene,
+            },
             paint_metadata,
             built_clip_paths,
         } = params;
 
         let path_object = &scene.paths[path_index];
-        let outline = scene.apply_render_options(path_object.outline(), built_options);
-
         let paint_id = path_object.paint();
         let paint_metadata = &paint_metadata[paint_id.0 as usize];
-        let built_clip_path = path_object.clip_path().map(|clip_path_id| {
-            &built_clip_paths[clip_path_id.0 as usize]
-        });
+
+        // Clip tiles refer to the alpha tiles of the clip path, so clipped paths aren't cached.
+        let clip_path_ids = scene.draw_path_clip_paths(path_object);
+        let cacheable = scene.build_cache.is_enabled() && clip_path_ids.is_empty();
+        if cacheable {
+            let instance = scene.build_cache
+                                .get(path_index)
+                                .and_then(|cached_draw_path| cached_draw_path.instantiate(self));
+            if let Some((path, fills)) = instance {
+                self.cached_path_count.fetch_add(1, Ordering::Relaxed);
+                self.send_fills(fills);
+                return (BuiltDrawPath {
+                    path,
+                    blend_mode: path_object.blend_mode(),
+                    filter: paint_metadata.filter(),
+                    color_texture: paint_metadata.tile_batch_texture(),
+                    sampling_flags_1: TextureSamplingFlags::empty(),
+                    mask_0_fill_rule: path_object.fill_rule(),
+                }, None);
+            }
+        }
+
+        let transform = match path_object.node() {
+            None => path_object.transform(),
+            Some(node_id) => node_transforms[node_id.0 as usize] * path_object.transform(),
+        };
+        let outline = scene.apply_render_options(path_object.outline(),
+                                                 transform,
+                                                 path_object.non_scaling_stroke(),
+                                                 scene.palette.paints[paint_id.0 as usize]
+                                                      .device_space(),
+                                                 built_options);
+        let built_clip_paths: Vec<_> =
+            clip_path_ids.into_iter()
+                         .map(|clip_path_id| &built_clip_paths[clip_path_id.0 as usize])
+                         .collect();
 
         let mut tiler = Tiler::new(self,
                                    &outline,
@@ -214,23 +334,32 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
             paint_id,
             paint_metadata,
             blend_mode: path_object.blend_mode(),
-            built_clip_path,
+            built_clip_paths: &built_clip_paths,
             fill_rule: path_object.fill_rule(),
         }));
 
         tiler.generate_tiles();
-        self.send_fills(tiler.object_builder.fills);
-        BuiltDrawPath {
-            path: tiler.object_builder.built_path,
+        let ObjectBuilder { built_path, fills, .. } = tiler.object_builder;
+        let cached_draw_path = if cacheable {
+            Some(CachedDrawPath { built_path: built_path.clone(), fills: fills.clone() })
+        } else {
+            None
+        };
+        self.send_fills(fills);
+
+        (BuiltDrawPath {
+            path: built_path,
             blend_mode: path_object.blend_mode(),
             filter: paint_metadata.filter(),
             color_texture: paint_metadata.tile_batch_texture(),
             sampling_flags_1: TextureSamplingFlags::empty(),
             mask_0_fill_rule: path_object.fill_rule(),
-        }
+        }, cached_draw_path)
     }
 
     fn send_fills(&self, fills: Vec<FillBatchEntry>) {
+        self.fill_count.fetch_add(fills.len(), Ordering::Relaxed);
+        self.max_path_fill_count.fetch_max(fills.len(), Ordering::Relaxed);
         if !fills.is_empty() {
             self.listener.send(RenderCommand::AddFills(fills));
         }
@@ -259,12 +388,17 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
         }
     }
 
-    fn cull_tiles(&self, paint_metadata: &[PaintMetadata], built_draw_paths: Vec<BuiltDrawPath>)
+    fn cull_tiles(&self,
+                  paint_metadata: &[PaintMetadata],
+                  built_draw_paths: Vec<BuiltDrawPath>,
+                  build_stats: &mut BuildStats)
                   -> CulledTiles {
-        let mut culled_tiles = CulledTiles { display_list: vec![] };
+        let mut culled_tiles = CulledTiles { display_list: vec![], occluded_tile_count: 0 };
 
+        let solid_tile_start_time = Instant::now();
         let mut remaining_layer_z_buffers = self.build_solid_tiles(&built_draw_paths);
         remaining_layer_z_buffers.reverse();
+        build_stats.solid_tile_time = Instant::now() - solid_tile_start_time;
 
         // Process first Z-buffer.
         let first_z_buffer = remaining_layer_z_buffers.pop().unwrap();
@@ -346,7 +480,7 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
 
     fn build_solid_tiles(&self, built_draw_paths: &[BuiltDrawPath]) -> Vec<ZBuffer> {
         let effective_view_box = self.scene.effective_view_box(self.built_options);
-        let mut z_buffers = vec![ZBuffer::new(effective_view_box)];
+        let mut z_buffers = vec![ZBuffer::new(effective_view_box, self.tile_size())];
         let mut z_buffer_index_stack = vec![0];
         let mut current_depth = 1;
 
@@ -355,7 +489,7 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
             match *display_item {
                 DisplayItem::PushRenderTarget { .. } => {
                     z_buffer_index_stack.push(z_buffers.len());
-                    z_buffers.push(ZBuffer::new(effective_view_box));
+                    z_buffers.push(ZBuffer::new(effective_view_box, self.tile_size()));
                 }
                 DisplayItem::PopRenderTarget => {
                     z_buffer_index_stack.pop();
@@ -399,6 +533,7 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
             // Early cull if possible.
             let alpha_tile_coords = built_alpha_tile.tile.tile_position();
             if !layer_z_buffer.test(alpha_tile_coords, current_depth) {
+                culled_tiles.occluded_tile_count += 1;
                 continue;
             }
 
@@ -509,11 +644,27 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
 
     fn finish_building(&mut self,
                        paint_metadata: &[PaintMetadata],
-                       built_draw_paths: Vec<BuiltDrawPath>) {
+                       built_draw_paths: Vec<BuiltDrawPath>,
+                       build_stats: &mut BuildStats) {
         self.listener.send(RenderCommand::FlushFills);
+
+        let clip_start_time = Instant::now();
         self.build_clips(&built_draw_paths);
-        let culled_tiles = self.cull_tiles(paint_metadata, built_draw_paths);
+        build_stats.clip_time = Instant::now() - clip_start_time;
+
+        let cull_start_time = Instant::now();
+        let culled_tiles = self.cull_tiles(paint_metadata, built_draw_paths, build_stats);
+        build_stats.cull_time = Instant::now() - cull_start_time - build_stats.solid_tile_time;
+        build_stats.occluded_tile_count = culled_tiles.occluded_tile_count;
+
+        let pack_start_time = Instant::now();
         self.pack_tiles(culled_tiles);
+        build_stats.pack_time = Instant::now() - pack_start_time;
+    }
+
+    #[inline]
+    pub(crate) fn tile_size(&self) -> TileSize {
+        self.built_options.tile_size
     }
 
     fn needs_readable_framebuffer(&self) -> bool {
@@ -542,6 +693,7 @@ impl<'a, 'b> SceneBuilder<'a, 'b> {
 struct PathBuildParams<'a> {
     path_index: usize,
     view_box: RectF,
+    node_transforms: &'a [Transform2F],
     built_options: &'a PreparedBuildOptions,
     scene: &'a Scene,
 }
@@ -556,6 +708,7 @@ impl BuiltPath {
     fn new(path_bounds: RectF,
            view_box_bounds: RectF,
            fill_rule: FillRule,
+           tile_size: TileSize,
            tiling_path_info: &TilingPathInfo)
            -> BuiltPath {
         let occludes = match *tiling_path_info {
@@ -581,7 +734,8 @@ impl BuiltPath {
             } else {
                 SolidTiles::Regular(vec![])
             },
-            tiles: DenseTileMap::new(tiles::round_rect_out_to_tile_bounds(tile_map_bounds)),
+            tiles: DenseTileMap::new(tiles::round_rect_out_to_tile_bounds(tile_map_bounds,
+                                                                          tile_size)),
             fill_rule,
         }
     }
@@ -594,8 +748,130 @@ impl Occluder {
     }
 }
 
+impl BuildCache {
+    #[inline]
+    pub(crate) fn new() -> BuildCache {
+        BuildCache { key: None, draw_paths: vec![] }
+    }
+
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.draw_paths.clear();
+    }
+
+    #[inline]
+    pub(crate) fn invalidate_path(&mut self, path_id: DrawPathId) {
+        if let Some(cached_draw_path) = self.draw_paths.get_mut(path_id.0 as usize) {
+            *cached_draw_path = None;
+        }
+    }
+
+    fn prepare(&mut self, key: Option<BuildCacheKey>, draw_path_count: usize) {
+        if key.is_none() || key != self.key {
+            self.draw_paths.clear();
+        }
+        self.key = key;
+        self.draw_paths.resize(draw_path_count, None);
+    }
+
+    #[inline]
+    fn is_enabled(&self) -> bool {
+        self.key.is_some()
+    }
+
+    #[inline]
+    fn get(&self, path_index: usize) -> Option<&CachedDrawPath> {
+        self.draw_paths[path_index].as_ref()
+    }
+
+    fn update(&mut self, new_draw_paths: Vec<Option<CachedDrawPath>>) {
+        for (cached_draw_path, new_draw_path) in self.draw_paths.iter_mut().zip(new_draw_paths) {
+            if new_draw_path.is_some() {
+                *cached_draw_path = new_draw_path;
+            }
+        }
+    }
+}
+
+impl BuildCacheKey {
+    // Returns `None` if the results of this build can't be cached.
+    fn new(built_options: &PreparedBuildOptions, view_box: RectF) -> Option<BuildCacheKey> {
+        let transform = match built_options.transform {
+            PreparedRenderTransform::None => Transform2F::default(),
+            PreparedRenderTransform::Transform2D(transform) => transform,
+            PreparedRenderTransform::Perspective { .. } => return None,
+        };
+        Some(BuildCacheKey {
+            transform,
+            dilation: built_options.dilation,
+            subpixel_aa_enabled: built_options.subpixel_aa_enabled,
+            tile_size: built_options.tile_size,
+            view_box,
+        })
+    }
+}
+
+impl CachedDrawPath {
+    // Makes a copy of this path that refers to newly-allocated alpha tiles.
+    //
+    // Returns `None` if a cached fill refers to a tile without an alpha tile, in which case the
+    // path has to be tiled from scratch. That should never happen; the alpha tiles allocated
+    // before finding out are merely left empty.
+    fn instantiate(&self, scene_builder: &SceneBuilder)
+                   -> Option<(BuiltPath, Vec<FillBatchEntry>)> {
+        let mut built_path = self.built_path.clone();
+        let mut alpha_tile_mapping = FxHashMap::default();
+        for tile in &mut built_path.tiles.data {
+            if tile.alpha_tile_id.is_valid() {
+                let new_alpha_tile_id =
+                    AlphaTileId::new(&scene_builder.next_alpha_tile_indices, 0);
+                alpha_tile_mapping.insert(tile.alpha_tile_id.0, new_alpha_tile_id);
+                tile.alpha_tile_id = new_alpha_tile_id;
+            }
+        }
+
+        let fills = self.fills.iter().map(|fill_batch_entry| {
+            let old_alpha_tile_id = AlphaTileId((fill_batch_entry.page as u32) << 16 |
+                                                fill_batch_entry.fill.alpha_tile_index as u32);
+            let new_alpha_tile_id = alpha_tile_mapping.get(&old_alpha_tile_id.0);
+            debug_assert!(new_alpha_tile_id.is_some(), "Cached fill has no alpha tile!");
+            let new_alpha_tile_id = new_alpha_tile_id?;
+            Some(FillBatchEntry {
+                fill: Fill {
+                    alpha_tile_index: new_alpha_tile_id.tile(),
+                    ..fill_batch_entry.fill
+                },
+                page: new_alpha_tile_id.page(),
+            })
+        }).collect::<Option<Vec<_>>>()?;
+
+        // Solid and empty tiles have no masks, so they won't be found in the mapping.
+        let remap_tiles = |built_tiles: &mut Vec<BuiltTile>| {
+            for built_tile in built_tiles {
+                let old_tile_index = built_tile.tile.mask_0_v as u32 * MASK_TILES_ACROSS +
+                    built_tile.tile.mask_0_u as u32;
+                let old_alpha_tile_id = (built_tile.page as u32) << 16 | old_tile_index;
+                if let Some(&new_alpha_tile_id) = alpha_tile_mapping.get(&old_alpha_tile_id) {
+                    let mask_0_uv = calculate_mask_uv(new_alpha_tile_id.tile());
+                    built_tile.page = new_alpha_tile_id.page();
+                    built_tile.tile.mask_0_u = mask_0_uv.x() as u8;
+                    built_tile.tile.mask_0_v = mask_0_uv.y() as u8;
+                }
+            }
+        };
+        remap_tiles(&mut built_path.single_mask_tiles);
+        remap_tiles(&mut built_path.empty_tiles);
+        if let SolidTiles::Regular(ref mut solid_tiles) = built_path.solid_tiles {
+            remap_tiles(solid_tiles);
+        }
+
+        Some((built_path, fills))
+    }
+}
+
 struct CulledTiles {
     display_list: Vec<CulledDisplayItem>,
+    occluded_tile_count: usize,
 }
 
 enum CulledDisplayItem {
@@ -616,12 +892,18 @@ impl ObjectBuilder {
     pub(crate) fn new(path_bounds: RectF,
                       view_box_bounds: RectF,
                       fill_rule: FillRule,
+                      tile_size: TileSize,
                       tiling_path_info: &TilingPathInfo)
                       -> ObjectBuilder {
         ObjectBuilder {
-            built_path: BuiltPath::new(path_bounds, view_box_bounds, fill_rule, tiling_path_info),
+            built_path: BuiltPath::new(path_bounds,
+                                       view_box_bounds,
+                                       fill_rule,
+                                       tile_size,
+                                       tiling_path_info),
             bounds: path_bounds,
             fills: vec![],
+            tile_size,
         }
     }
 
@@ -643,15 +925,14 @@ impl ObjectBuilder {
             return;
         }
 
-        debug_assert_eq!(TILE_WIDTH, TILE_HEIGHT);
-
         // Compute the upper left corner of the tile.
-        let tile_size = F32x4::splat(TILE_WIDTH as f32);
-        let tile_upper_left = tile_coords.to_f32().0.to_f32x4().xyxy() * tile_size;
+        let tile_size = self.tile_size.get() as f32;
+        let tile_upper_left = tile_coords.to_f32().0.to_f32x4().xyxy() * F32x4::splat(tile_size);
 
-        // Convert to 4.8 fixed point.
-        let segment = (segment.0 - tile_upper_left) * F32x4::splat(256.0);
-        let (min, max) = (F32x4::default(), F32x4::splat((TILE_WIDTH * 256 - 1) as f32));
+        // Convert to 4.8 fixed point, in units of 1/16 of the tile size. For 16x16 tiles, this
+        // makes the integer part whole pixels.
+        let segment = (segment.0 - tile_upper_left) * F32x4::splat(4096.0 / tile_size);
+        let (min, max) = (F32x4::default(), F32x4::splat(4095.0));
         let segment = segment.clamp(min, max).to_i32x4();
         let (from_x, from_y, to_x, to_y) = (segment[0], segment[1], segment[2], segment[3]);
 
@@ -709,7 +990,7 @@ impl ObjectBuilder {
         mut winding: i32,
         tile_coords: Vector2I,
     ) {
-        let tile_origin_y = (tile_coords.y() * TILE_HEIGHT as i32) as f32;
+        let tile_origin_y = (tile_coords.y() * self.tile_size.get() as i32) as f32;
         let left = vec2f(left, tile_origin_y);
         let right = vec2f(right, tile_origin_y);
 
@@ -743,12 +1024,13 @@ impl ObjectBuilder {
         mut segment: LineSegment2F,
         tile_y: i32,
     ) {
+        let tile_size = self.tile_size.get() as i32;
         debug!(
             "... generate_fill_primitives_for_line(): segment={:?} tile_y={} ({}-{})",
             segment,
             tile_y,
-            tile_y as f32 * TILE_HEIGHT as f32,
-            (tile_y + 1) as f32 * TILE_HEIGHT as f32
+            tile_y * tile_size,
+            (tile_y + 1) * tile_size
         );
 
         let winding = segment.from_x() > segment.to_x();
@@ -758,10 +1040,10 @@ impl ObjectBuilder {
             (segment.to_x(), segment.from_x())
         };
 
-        let mut subsegment_x = (segment_left as i32 & !(TILE_WIDTH as i32 - 1)) as f32;
+        let mut subsegment_x = (segment_left as i32 & !(tile_size - 1)) as f32;
         while subsegment_x < segment_right {
             let (mut fill_from, mut fill_to) = (segment.from(), segment.to());
-            let subsegment_x_next = subsegment_x + TILE_WIDTH as f32;
+            let subsegment_x_next = subsegment_x + tile_size as f32;
             if subsegment_x_next < segment_right {
                 let x = subsegment_x_next;
                 let point = Vector2F::new(x, segment.solve_y_for_x(x));
@@ -775,7 +1057,7 @@ impl ObjectBuilder {
             }
 
             let fill_segment = LineSegment2F::new(fill_from, fill_to);
-            let fill_tile_coords = vec2i(subsegment_x as i32 / TILE_WIDTH as i32, tile_y);
+            let fill_tile_coords = vec2i(subsegment_x as i32 / tile_size, tile_y);
             self.add_fill(scene_builder, fill_segment, fill_tile_coords);
 
             subsegment_x = subsegment_x_next;
@@ -803,50 +1085,46 @@ impl<'a> PackedTile<'a> {
         let draw_tile_index = self.draw_tile.alpha_tile_id.tile() as u16;
         let draw_tile_backdrop = self.draw_tile.backdrop as i8;
 
-        match self.clip_tile {
-            None => {
-                tiles.push(BuiltTile {
-                    page: draw_tile_page,
-                    tile: Tile::new_alpha(self.tile_coords,
-                                          draw_tile_index,
-                                          draw_tile_backdrop,
-                                          draw_tiling_path_info),
-                });
-            }
-            Some(clip_tile) => {
-                let clip_tile_page = clip_tile.alpha_tile_id.page() as u16;
-                let clip_tile_index = clip_tile.alpha_tile_id.tile() as u16;
-                let clip_tile_backdrop = clip_tile.backdrop;
-
-                let dest_tile_id = AlphaTileId::new(&scene_builder.next_alpha_tile_indices, 1);
-                let dest_tile_page = dest_tile_id.page() as u16;
-                let dest_tile_index = dest_tile_id.tile() as u16;
-
-                clips.push(BuiltClip {
-                    clip: Clip::new(dest_tile_index, draw_tile_index, draw_tile_backdrop),
-                    key: ClipBatchKey {
-                        src_page: draw_tile_page,
-                        dest_page: dest_tile_page,
-                        kind: ClipBatchKind::Draw,
-                    },
-                });
-                clips.push(BuiltClip {
-                    clip: Clip::new(dest_tile_index, clip_tile_index, clip_tile_backdrop),
-                    key: ClipBatchKey {
-                        src_page: clip_tile_page,
-                        dest_page: dest_tile_page,
-                        kind: ClipBatchKind::Clip,
-                    },
-                });
-                tiles.push(BuiltTile {
-                    page: dest_tile_page,
-                    tile: Tile::new_alpha(self.tile_coords,
-                                          dest_tile_index,
-                                          0,
-                                          draw_tiling_path_info),
-                });
-            }
+        if self.clip_tiles.is_empty() {
+            tiles.push(BuiltTile {
+                page: draw_tile_page,
+                tile: Tile::new_alpha(self.tile_coords,
+                                      draw_tile_index,
+                                      draw_tile_backdrop,
+                                      draw_tiling_path_info),
+            });
+            return;
+        }
+
+        let dest_tile_id = AlphaTileId::new(&scene_builder.next_alpha_tile_indices, 1);
+        let dest_tile_page = dest_tile_id.page() as u16;
+        let dest_tile_index = dest_tile_id.tile() as u16;
+
+        // Copy the draw tile, then take the minimum coverage of it and each clip tile.
+        clips.push(BuiltClip {
+            clip: Clip::new(dest_tile_index, draw_tile_index, draw_tile_backdrop),
+            key: ClipBatchKey {
+                dest_page: dest_tile_page,
+                kind: ClipBatchKind::Draw,
+                src_page: draw_tile_page,
+            },
+        });
+        for clip_tile in &self.clip_tiles {
+            let clip_tile_page = clip_tile.alpha_tile_id.page() as u16;
+            let clip_tile_index = clip_tile.alpha_tile_id.tile() as u16;
+            clips.push(BuiltClip {
+                clip: Clip::new(dest_tile_index, clip_tile_index, clip_tile.backdrop),
+                key: ClipBatchKey {
+                    dest_page: dest_tile_page,
+                    kind: ClipBatchKind::Clip,
+                    src_page: clip_tile_page,
+                },
+            });
         }
+        tiles.push(BuiltTile {
+            page: dest_tile_page,
+            tile: Tile::new_alpha(self.tile_coords, dest_tile_index, 0, draw_tiling_path_info),
+        });
     }
 }
 
@@ -906,3 +1184,271 @@ fn calculate_mask_uv(tile_index: u16) -> Vector2I {
     let mask_v = tile_index as i32 / MASK_TILES_ACROSS as i32;
     vec2i(mask_u, mask_v)
 }
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::cpu::rasterizer::ReferenceRasterizer;
+    use crate::gpu::options::RendererOptions;
+    use crate::gpu_data::{BuildStats, RenderCommand};
+    use crate::options::{BuildOptions, TileSize};
+    use crate::paint::Paint;
+    use crate::scene::{ClipPath, ClipPathId, DrawPath, Scene, SceneNode};
+    use pathfinder_color::{ColorF, ColorU};
+    use pathfinder_content::gradient::Gradient;
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{Vector2F, vec2f, vec2i};
+    use std::sync::{Arc, Mutex};
+
+    fn triangle(origin: Vector2F, size: f32) -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(origin);
+        contour.push_endpoint(origin + vec2f(size, 0.0));
+        contour.push_endpoint(origin + vec2f(0.0, size));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn rasterize(scene: &mut Scene) -> Vec<ColorU> {
+        rasterize_with_options(scene, BuildOptions::default())
+    }
+
+    fn rasterize_with_options(scene: &mut Scene, options: BuildOptions) -> Vec<ColorU> {
+        let rasterizer = ReferenceRasterizer::new(vec2i(64, 64), RendererOptions {
+            background_color: Some(ColorF::white()),
+        });
+        scene.build(options, Box::new(rasterizer.clone()), &SequentialExecutor);
+        rasterizer.pixels()
+    }
+
+    fn new_scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(64.0, 64.0)));
+        scene
+    }
+
+    #[test]
+    fn test_incremental_rebuild_matches_full_build() {
+        let red = Paint::from_color(ColorU::new(255, 0, 0, 255));
+        let green = Paint::from_color(ColorU::new(0, 255, 0, 255));
+        let blue = Paint::from_color(ColorU::new(0, 0, 255, 128));
+
+        let mut scene = new_scene();
+        let red_id = scene.push_paint(&red);
+        let green_id = scene.push_paint(&green);
+        scene.push_path(DrawPath::new(triangle(vec2f(1.5, 2.5), 30.0), red_id));
+        let path_id = scene.push_path(DrawPath::new(triangle(vec2f(20.5, 20.5), 20.0),
+                                                    green_id));
+        scene.push_path(DrawPath::new(triangle(vec2f(33.25, 3.75), 28.0), green_id));
+        let first_pixels = rasterize(&mut scene);
+        assert_eq!(rasterize(&mut scene), first_pixels);
+
+        // Move one path and recolor another; the first path is reused from the cache.
+        scene.set_path(path_id, DrawPath::new(triangle(vec2f(10.5, 30.5), 25.0), red_id));
+        scene.set_paint(green_id, &blue);
+        let incremental_pixels = rasterize(&mut scene);
+
+        let mut expected_scene = new_scene();
+        let red_id = expected_scene.push_paint(&red);
+        let blue_id = expected_scene.push_paint(&blue);
+        expected_scene.push_path(DrawPath::new(triangle(vec2f(1.5, 2.5), 30.0), red_id));
+        expected_scene.push_path(DrawPath::new(triangle(vec2f(10.5, 30.5), 25.0), red_id));
+        expected_scene.push_path(DrawPath::new(triangle(vec2f(33.25, 3.75), 28.0), blue_id));
+        assert_ne!(incremental_pixels, first_pixels);
+        assert_eq!(incremental_pixels, rasterize(&mut expected_scene));
+    }
+
+    #[test]
+    fn test_node_transforms_match_baked_outlines() {
+        let clip_rect = RectF::new(vec2f(0.0, 0.0), vec2f(40.0, 30.0));
+        let baked_scene = |outer: Transform2F, inner: Transform2F| {
+            let mut scene = new_scene();
+            let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+            let clip_path_id = scene.push_clip_path(ClipPath::new(Outline::from_rect(clip_rect)));
+            let mut outline = triangle(vec2f(0.0, 0.0), 20.0);
+            outline.transform(&outer);
+            scene.push_path(DrawPath::new(outline, red));
+            let mut outline = triangle(vec2f(0.0, 0.0), 30.0);
+            outline.transform(&(outer * inner));
+            let mut path = DrawPath::new(outline, red);
+            path.set_clip_path(Some(clip_path_id));
+            scene.push_path(path);
+            scene
+        };
+
+        let outer = Transform2F::from_translation(vec2f(4.5, 2.25));
+        let inner = Transform2F::from_translation(vec2f(12.0, 8.0)) *
+            Transform2F::from_scale(vec2f(1.5, 1.0));
+        let moved = Transform2F::from_translation(vec2f(10.0, 12.5));
+
+        let mut scene = new_scene();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let clip_path_id = scene.push_clip_path(ClipPath::new(Outline::from_rect(clip_rect)));
+        let outer_id = scene.push_node(SceneNode::new(outer));
+        let mut node = SceneNode::new(inner);
+        node.set_parent(Some(outer_id));
+        node.set_clip_path(Some(clip_path_id));
+        let inner_id = scene.push_node(node);
+        let mut path = DrawPath::new(triangle(vec2f(0.0, 0.0), 20.0), red);
+        path.set_node(Some(outer_id));
+        scene.push_path(path);
+        let mut path = DrawPath::new(triangle(vec2f(0.0, 0.0), 30.0), red);
+        path.set_node(Some(inner_id));
+        scene.push_path(path);
+
+        assert_eq!(rasterize(&mut scene), rasterize(&mut baked_scene(outer, inner)));
+
+        // Moving the outer node must retile the cached path attached to it.
+        scene.set_node_transform(outer_id, moved);
+        assert_eq!(rasterize(&mut scene), rasterize(&mut baked_scene(moved, inner)));
+    }
+
+    #[test]
+    fn test_instanced_outlines_match_baked_outlines() {
+        let transforms = [
+            Transform2F::from_translation(vec2f(2.5, 3.0)),
+            Transform2F::from_translation(vec2f(30.0, 10.25)) * Transform2F::from_rotation(0.5),
+            Transform2F::from_scale(vec2f(0.5, 2.0)),
+        ];
+
+        let mut scene = new_scene();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 128)));
+        let outline = Arc::new(triangle(vec2f(0.0, 0.0), 20.0));
+        for &transform in &transforms {
+            let mut path = DrawPath::from_shared_outline(outline.clone(), red);
+            path.set_transform(transform);
+            scene.push_path(path);
+        }
+
+        let mut expected_scene = new_scene();
+        let red = expected_scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 128)));
+        for transform in &transforms {
+            let mut outline = triangle(vec2f(0.0, 0.0), 20.0);
+            outline.transform(transform);
+            expected_scene.push_path(DrawPath::new(outline, red));
+        }
+
+        assert_eq!(Arc::strong_count(&outline), transforms.len() + 1);
+        assert_eq!(rasterize(&mut scene), rasterize(&mut expected_scene));
+    }
+
+    #[test]
+    fn test_tile_sizes_render_alike() {
+        let mut scene = new_scene();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        scene.push_path(DrawPath::new(triangle(vec2f(1.5, 2.5), 45.0), red));
+        scene.push_path(DrawPath::new(triangle(vec2f(33.25, 30.75), 28.0), red));
+
+        let expected = rasterize(&mut scene);
+        for &tile_size in &[8, 32] {
+            let options = BuildOptions {
+                tile_size: TileSize::new(tile_size),
+                ..BuildOptions::default()
+            };
+            let pixels = rasterize_with_options(&mut scene, options);
+            for (pixel, expected_pixel) in pixels.iter().zip(expected.iter()) {
+                assert!((pixel.g as i32 - expected_pixel.g as i32).abs() <= 2,
+                        "{:?} vs. {:?} with {}x{} tiles",
+                        pixel,
+                        expected_pixel,
+                        tile_size,
+                        tile_size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_nested_clip_paths_intersect() {
+        fn build_scene(clip_rects: &[RectF]) -> Scene {
+            let mut scene = new_scene();
+            let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+            let blue = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 255, 255)));
+
+            let mut clip_path_id: Option<ClipPathId> = None;
+            for &clip_rect in clip_rects {
+                let mut clip_path = ClipPath::new(Outline::from_rect(clip_rect));
+                clip_path.set_clip_path(clip_path_id);
+                clip_path_id = Some(scene.push_clip_path(clip_path));
+            }
+
+            // The first path has solid tiles and the second has mask tiles.
+            let mut path = DrawPath::new(Outline::from_rect(RectF::new(Vector2F::zero(),
+                                                                       vec2f(64.0, 64.0))),
+                                         red);
+            path.set_clip_path(clip_path_id);
+            scene.push_path(path);
+            let mut path = DrawPath::new(triangle(vec2f(8.5, 6.5), 48.0), blue);
+            path.set_clip_path(clip_path_id);
+            scene.push_path(path);
+            scene
+        }
+
+        // The outer clip is pixel-aligned, so intersecting coverage is exact.
+        let outer = RectF::new(vec2f(4.0, 4.0), vec2f(36.0, 36.0));
+        let inner = RectF::new(vec2f(20.25, 10.25), vec2f(40.0, 20.0));
+        let nested_pixels = rasterize(&mut build_scene(&[outer, inner]));
+        let expected_pixels = rasterize(&mut build_scene(&[outer.intersection(inner).unwrap()]));
+        assert_eq!(nested_pixels, expected_pixels);
+
+        // Only the overlap of the two clips is drawn.
+        assert_eq!(nested_pixels[20 * 64 + 30], ColorU::new(0, 0, 255, 255));
+        assert_eq!(nested_pixels[20 * 64 + 10], ColorU::white());
+        assert_eq!(nested_pixels[20 * 64 + 50], ColorU::white());
+        assert_eq!(nested_pixels[35 * 64 + 30], ColorU::white());
+    }
+
+    #[test]
+    fn test_build_stats() {
+        fn build(scene: &mut Scene) -> BuildStats {
+            let build_stats = Arc::new(Mutex::new(None));
+            let listener_build_stats = build_stats.clone();
+            let listener = move |command| {
+                if let RenderCommand::Finish { build_stats, .. } = command {
+                    *listener_build_stats.lock().unwrap() = Some(build_stats);
+                }
+            };
+            scene.build(BuildOptions::default(), Box::new(listener), &SequentialExecutor);
+            let build_stats = build_stats.lock().unwrap().take();
+            build_stats.expect("No `Finish` command was sent!")
+        }
+
+        // A triangle hidden entirely behind an opaque rect filled with a gradient.
+        let mut scene = new_scene();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let mut gradient = Gradient::linear_from_points(vec2f(0.0, 0.0), vec2f(64.0, 0.0));
+        gradient.add_color_stop(ColorU::black(), 0.0);
+        gradient.add_color_stop(ColorU::white(), 1.0);
+        let gradient = scene.push_paint(&Paint::from_gradient(gradient));
+        scene.push_path(DrawPath::new(triangle(vec2f(20.5, 20.5), 20.0), red));
+        let rect = RectF::new(vec2f(0.0, 0.0), vec2f(64.0, 64.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), gradient));
+
+        let stats = build(&mut scene);
+        assert!(stats.occluded_tile_count > 0);
+        assert!(stats.max_path_fill_count > 0);
+        assert!(stats.fill_count > stats.max_path_fill_count);
+        assert_eq!(stats.cached_path_count, 0);
+        assert_eq!(stats.new_texture_page_count, 1);
+        assert_eq!(stats.resident_texture_page_count, 1);
+
+        // The second build reuses both paths and the gradient's page.
+        let first_stats = stats;
+        let stats = build(&mut scene);
+        assert_eq!(stats.cached_path_count, 2);
+        assert_eq!(stats.fill_count, first_stats.fill_count);
+        assert_eq!(stats.new_texture_page_count, 0);
+        assert_eq!(stats.resident_texture_page_count, 1);
+
+        // Counts are summed and averaged across builds, but the per-path maximum is not.
+        let total_stats = first_stats + stats;
+        assert_eq!(total_stats.fill_count, first_stats.fill_count * 2);
+        assert_eq!(total_stats.max_path_fill_count, first_stats.max_path_fill_count);
+        let mean_stats = total_stats / 2;
+        assert_eq!(mean_stats.fill_count, first_stats.fill_count);
+        assert_eq!(mean_stats.max_path_fill_count, first_stats.max_path_fill_count);
+    }
+}
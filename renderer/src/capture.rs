@@ -0,0 +1,1096 @@
+// pathfinder/renderer/src/capture.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Recording and replaying of render command streams.
+//!
+//! A capture is a small header followed by the render commands exactly as the scene builder
+//! emitted them. Replaying a capture reproduces the frame without the original scene, which makes
+//! it possible to debug rendering problems from a file attached to a bug report.
+//!
+//! All values are stored little-endian. The format is versioned, and readers reject captures with
+//! a version they don't understand.
+
+use crate::gpu::renderer::Renderer;
+use crate::gpu_data::{BuildStats, Clip, ClipBatch, ClipBatchKey, ClipBatchKind, Fill};
+use crate::gpu_data::FillBatchEntry;
+use crate::gpu_data::{RenderCommand, TextureLocation, TextureMetadataEntry, TexturePageDescriptor};
+use crate::gpu_data::{TexturePageId, Tile, TileBatch, TileBatchTexture};
+use crate::options::{BoundingQuad, RenderCommandListener, TileSize};
+use crate::paint::PaintCompositeOp;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::effects::{BlendMode, BlurDirection, DefringingKernel, Filter};
+use pathfinder_content::effects::{ColorMatrix, MorphologyOperator, PatternFilter};
+use pathfinder_content::effects::{TransferFunction, TransferTable};
+use pathfinder_content::gradient::GradientSpread;
+use pathfinder_content::render_target::RenderTargetId;
+use pathfinder_geometry::line_segment::{LineSegment2F, LineSegmentU4, LineSegmentU8};
+use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::transform2d::{Matrix2x2F, Transform2F};
+use pathfinder_geometry::vector::{Vector2F, Vector2I, Vector4F};
+use pathfinder_gpu::{Device, TextureSamplingFlags};
+use pathfinder_simd::default::{F32x2, F32x4};
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The version of the capture format that this module reads and writes.
+///
+/// This must be bumped whenever the encoding of any render command changes.
+pub const CAPTURE_VERSION: u32 = 6;
+
+const CAPTURE_MAGIC: [u8; 4] = *b"PFRC";
+
+const MAX_PREALLOCATION: usize = 1024;
+
+const COMMAND_START:                   u8 = 0;
+const COMMAND_ALLOCATE_TEXTURE_PAGE:   u8 = 1;
+const COMMAND_UPLOAD_TEXEL_DATA:       u8 = 2;
+const COMMAND_DECLARE_RENDER_TARGET:   u8 = 3;
+const COMMAND_UPLOAD_TEXTURE_METADATA: u8 = 4;
+const COMMAND_ADD_FILLS:               u8 = 5;
+const COMMAND_FLUSH_FILLS:             u8 = 6;
+const COMMAND_CLIP_TILES:              u8 = 7;
+const COMMAND_PUSH_RENDER_TARGET:      u8 = 8;
+const COMMAND_POP_RENDER_TARGET:       u8 = 9;
+const COMMAND_BEGIN_TILE_DRAWING:      u8 = 10;
+const COMMAND_DRAW_TILES:              u8 = 11;
+const COMMAND_FINISH:                  u8 = 12;
+const COMMAND_FREE_TEXTURE_PAGE:       u8 = 13;
+
+const FILTER_NONE:               u8 = 0;
+const FILTER_RADIAL_GRADIENT:    u8 = 1;
+const FILTER_TEXT:               u8 = 2;
+const FILTER_BLUR:               u8 = 3;
+const FILTER_LUMINANCE_TO_ALPHA: u8 = 4;
+const FILTER_LINEAR_GRADIENT:    u8 = 5;
+const FILTER_CONIC_GRADIENT:     u8 = 6;
+const FILTER_COLOR_MATRIX:       u8 = 7;
+const FILTER_COMPONENT_TRANSFER: u8 = 8;
+const FILTER_MORPHOLOGY:         u8 = 9;
+const FILTER_UNPREMULTIPLY:      u8 = 10;
+
+const TRANSFER_FUNCTION_IDENTITY: u8 = 0;
+const TRANSFER_FUNCTION_TABLE:    u8 = 1;
+const TRANSFER_FUNCTION_DISCRETE: u8 = 2;
+const TRANSFER_FUNCTION_LINEAR:   u8 = 3;
+const TRANSFER_FUNCTION_GAMMA:    u8 = 4;
+
+const GRADIENT_SPREADS: [GradientSpread; 3] = [
+    GradientSpread::Pad,
+    GradientSpread::Repeat,
+    GradientSpread::Reflect,
+];
+
+// The position of each blend mode in this table is its encoding.
+const BLEND_MODES: [BlendMode; 27] = [
+    BlendMode::Clear,
+    BlendMode::Copy,
+    BlendMode::SrcIn,
+    BlendMode::SrcOut,
+    BlendMode::SrcOver,
+    BlendMode::SrcAtop,
+    BlendMode::DestIn,
+    BlendMode::DestOut,
+    BlendMode::DestOver,
+    BlendMode::DestAtop,
+    BlendMode::Xor,
+    BlendMode::Lighter,
+    BlendMode::Darken,
+    BlendMode::Lighten,
+    BlendMode::Multiply,
+    BlendMode::Screen,
+    BlendMode::HardLight,
+    BlendMode::Overlay,
+    BlendMode::ColorDodge,
+    BlendMode::ColorBurn,
+    BlendMode::SoftLight,
+    BlendMode::Difference,
+    BlendMode::Exclusion,
+    BlendMode::Hue,
+    BlendMode::Saturation,
+    BlendMode::Color,
+    BlendMode::Luminosity,
+];
+
+/// A render command listener that writes every command it receives to a capture.
+///
+/// Clones write to the same capture, so a clone can be handed to `Scene::build()` and the
+/// original finished afterward.
+///
+/// A capture only replays on its own if it contains every texture page it draws with. When
+/// recording a scene that has already been built, call `Scene::invalidate_textures()` first so
+/// that the recorded build allocates and uploads all of them.
+pub struct CaptureRecorder<W> where W: Write + Send {
+    state: Arc<Mutex<RecorderState<W>>>,
+}
+
+struct RecorderState<W> where W: Write + Send {
+    writer: W,
+    // Listeners can't report errors, so the first one is saved until `finish()`.
+    error: Option<Error>,
+}
+
+/// Reads render commands back out of a capture.
+pub struct CaptureReader<R> where R: Read {
+    reader: R,
+}
+
+impl<W> CaptureRecorder<W> where W: Write + Send {
+    /// Starts a new capture, writing the header immediately.
+    pub fn new(mut writer: W) -> io::Result<CaptureRecorder<W>> {
+        writer.write_all(&CAPTURE_MAGIC)?;
+        writer.write_u32::<LittleEndian>(CAPTURE_VERSION)?;
+        Ok(CaptureRecorder {
+            state: Arc::new(Mutex::new(RecorderState { writer, error: None })),
+        })
+    }
+
+    /// Writes a single command to the capture.
+    pub fn record(&self, command: &RenderCommand) {
+        let mut state = self.state.lock().unwrap();
+        if state.error.is_some() {
+            return;
+        }
+        if let Err(error) = write_command(&mut state.writer, command) {
+            state.error = Some(error);
+        }
+    }
+
+    /// Flushes the capture and returns the underlying writer.
+    ///
+    /// This reports the first error that occurred while recording, if any. It fails if other
+    /// clones of this recorder are still alive.
+    pub fn finish(self) -> io::Result<W> {
+        let state = match Arc::try_unwrap(self.state) {
+            Ok(state) => state.into_inner().unwrap(),
+            Err(_) => {
+                return Err(Error::new(ErrorKind::Other, "Capture recorder is still in use!"))
+            }
+        };
+        let RecorderState { mut writer, error } = state;
+        if let Some(error) = error {
+            return Err(error);
+        }
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W> Clone for CaptureRecorder<W> where W: Write + Send {
+    #[inline]
+    fn clone(&self) -> CaptureRecorder<W> {
+        CaptureRecorder { state: self.state.clone() }
+    }
+}
+
+impl<W> RenderCommandListener for CaptureRecorder<W> where W: Write + Send {
+    #[inline]
+    fn send(&self, command: RenderCommand) {
+        self.record(&command)
+    }
+}
+
+impl<R> CaptureReader<R> where R: Read {
+    /// Opens a capture, checking its header.
+    pub fn new(mut reader: R) -> io::Result<CaptureReader<R>> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != CAPTURE_MAGIC {
+            return Err(invalid_data("Not a render command capture!"));
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != CAPTURE_VERSION {
+            return Err(invalid_data("Unsupported render command capture version!"));
+        }
+        Ok(CaptureReader { reader })
+    }
+
+    /// Reads the next command, or returns `None` at the end of the capture.
+    pub fn read_command(&mut self) -> io::Result<Option<RenderCommand>> {
+        // Like `read_exact()`, retry interrupted reads, so that only a clean EOF ends the capture.
+        let mut tag = [0];
+        loop {
+            match self.reader.read(&mut tag) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        read_command(&mut self.reader, tag[0]).map(Some)
+    }
+
+    /// Feeds every command in the capture to the renderer.
+    ///
+    /// Each frame in the capture, from `Start` to `Finish`, is wrapped in a `begin_scene()` and
+    /// `end_scene()` pair, just as `SceneProxy::build_and_render()` would do.
+    pub fn replay<D>(&mut self, renderer: &mut Renderer<D>) -> io::Result<()> where D: Device {
+        while let Some(command) = self.read_command()? {
+            if let RenderCommand::Start { .. } = command {
+                renderer.begin_scene();
+            }
+            renderer.render_command(&command);
+            if let RenderCommand::Finish { .. } = command {
+                renderer.end_scene();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R> Iterator for CaptureReader<R> where R: Read {
+    type Item = io::Result<RenderCommand>;
+
+    #[inline]
+    fn next(&mut self) -> Option<io::Result<RenderCommand>> {
+        self.read_command().transpose()
+    }
+}
+
+// Writing
+
+fn write_command<W>(writer: &mut W, command: &RenderCommand) -> io::Result<()> where W: Write {
+    match *command {
+        RenderCommand::Start {
+            path_count,
+            ref bounding_quad,
+            needs_readable_framebuffer,
+            tile_size,
+        } => {
+            writer.write_u8(COMMAND_START)?;
+            writer.write_u64::<LittleEndian>(path_count as u64)?;
+            for vertex in bounding_quad {
+                write_f32x4(writer, vertex.0)?;
+            }
+            writer.write_u8(needs_readable_framebuffer as u8)?;
+            writer.write_u8(tile_size.get() as u8)?;
+        }
+        RenderCommand::AllocateTexturePage { page_id, ref descriptor } => {
+            writer.write_u8(COMMAND_ALLOCATE_TEXTURE_PAGE)?;
+            writer.write_u32::<LittleEndian>(page_id.0)?;
+            write_vector2i(writer, descriptor.size)?;
+        }
+        RenderCommand::FreeTexturePage(page_id) => {
+            writer.write_u8(COMMAND_FREE_TEXTURE_PAGE)?;
+            writer.write_u32::<LittleEndian>(page_id.0)?;
+        }
+        RenderCommand::UploadTexelData { ref texels, location } => {
+            writer.write_u8(COMMAND_UPLOAD_TEXEL_DATA)?;
+            write_texture_location(writer, location)?;
+            writer.write_u32::<LittleEndian>(texels.len() as u32)?;
+            for texel in texels.iter() {
+                write_color_u(writer, *texel)?;
+            }
+        }
+        RenderCommand::DeclareRenderTarget { id, location } => {
+            writer.write_u8(COMMAND_DECLARE_RENDER_TARGET)?;
+            write_render_target_id(writer, id)?;
+            write_texture_location(writer, location)?;
+        }
+        RenderCommand::UploadTextureMetadata(ref metadata) => {
+            writer.write_u8(COMMAND_UPLOAD_TEXTURE_METADATA)?;
+            writer.write_u32::<LittleEndian>(metadata.len() as u32)?;
+            for entry in metadata {
+                write_transform(writer, &entry.color_0_transform)?;
+                write_color_u(writer, entry.base_color)?;
+            }
+        }
+        RenderCommand::AddFills(ref fills) => {
+            writer.write_u8(COMMAND_ADD_FILLS)?;
+            writer.write_u32::<LittleEndian>(fills.len() as u32)?;
+            for fill_batch_entry in fills {
+                let fill = fill_batch_entry.fill;
+                let (px, subpx) = (fill.px, fill.subpx);
+                writer.write_all(&[px.from,
+                                   px.to,
+                                   subpx.from_x,
+                                   subpx.from_y,
+                                   subpx.to_x,
+                                   subpx.to_y])?;
+                writer.write_u16::<LittleEndian>(fill.alpha_tile_index)?;
+                writer.write_u16::<LittleEndian>(fill_batch_entry.page)?;
+            }
+        }
+        RenderCommand::FlushFills => writer.write_u8(COMMAND_FLUSH_FILLS)?,
+        RenderCommand::ClipTiles(ref batches) => {
+            writer.write_u8(COMMAND_CLIP_TILES)?;
+            writer.write_u32::<LittleEndian>(batches.len() as u32)?;
+            for batch in batches {
+                writer.write_u16::<LittleEndian>(batch.key.dest_page)?;
+                writer.write_u16::<LittleEndian>(batch.key.src_page)?;
+                writer.write_u8(match batch.key.kind {
+                    ClipBatchKind::Draw => 0,
+                    ClipBatchKind::Clip => 1,
+                })?;
+                writer.write_u32::<LittleEndian>(batch.clips.len() as u32)?;
+                for clip in &batch.clips {
+                    writer.write_all(&[clip.dest_u, clip.dest_v, clip.src_u, clip.src_v])?;
+                    writer.write_i8(clip.backdrop)?;
+                }
+            }
+        }
+        RenderCommand::PushRenderTarget(render_target_id) => {
+            writer.write_u8(COMMAND_PUSH_RENDER_TARGET)?;
+            write_render_target_id(writer, render_target_id)?;
+        }
+        RenderCommand::PopRenderTarget => writer.write_u8(COMMAND_POP_RENDER_TARGET)?,
+        RenderCommand::BeginTileDrawing => writer.write_u8(COMMAND_BEGIN_TILE_DRAWING)?,
+        RenderCommand::DrawTiles(ref batch) => {
+            writer.write_u8(COMMAND_DRAW_TILES)?;
+            writer.write_u32::<LittleEndian>(batch.tiles.len() as u32)?;
+            for tile in &batch.tiles {
+                writer.write_i16::<LittleEndian>(tile.tile_x)?;
+                writer.write_i16::<LittleEndian>(tile.tile_y)?;
+                writer.write_all(&[tile.mask_0_u, tile.mask_0_v])?;
+                writer.write_i8(tile.mask_0_backdrop)?;
+                writer.write_u16::<LittleEndian>(tile.color)?;
+                writer.write_u16::<LittleEndian>(tile.ctrl)?;
+            }
+            match batch.color_texture {
+                None => writer.write_u8(0)?,
+                Some(color_texture) => {
+                    writer.write_u8(1)?;
+                    writer.write_u32::<LittleEndian>(color_texture.page.0)?;
+                    writer.write_u8(color_texture.sampling_flags.bits())?;
+                    writer.write_u8(match color_texture.composite_op {
+                        PaintCompositeOp::SrcIn => 0,
+                        PaintCompositeOp::DestIn => 1,
+                    })?;
+                }
+            }
+            write_filter(writer, &batch.filter)?;
+            writer.write_u8(blend_mode_to_u8(batch.blend_mode))?;
+            writer.write_u16::<LittleEndian>(batch.tile_page)?;
+        }
+        RenderCommand::Finish { cpu_build_time, ref build_stats } => {
+            writer.write_u8(COMMAND_FINISH)?;
+            write_duration(writer, cpu_build_time)?;
+            write_build_stats(writer, build_stats)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn write_filter<W>(writer: &mut W, filter: &Filter) -> io::Result<()> where W: Write {
+    match *filter {
+        Filter::None => writer.write_u8(FILTER_NONE),
+        Filter::LinearGradient { spread } => {
+            writer.write_u8(FILTER_LINEAR_GRADIENT)?;
+            writer.write_u8(gradient_spread_to_u8(spread))
+        }
+        Filter::RadialGradient { line, radii, uv_origin, spread } => {
+            writer.write_u8(FILTER_RADIAL_GRADIENT)?;
+            write_f32x4(writer, line.0)?;
+            writer.write_f32::<LittleEndian>(radii.x())?;
+            writer.write_f32::<LittleEndian>(radii.y())?;
+            write_vector2f(writer, uv_origin)?;
+            writer.write_u8(gradient_spread_to_u8(spread))
+        }
+        Filter::ConicGradient { center, angle, uv_origin } => {
+            writer.write_u8(FILTER_CONIC_GRADIENT)?;
+            write_vector2f(writer, center)?;
+            writer.write_f32::<LittleEndian>(angle)?;
+            write_vector2f(writer, uv_origin)
+        }
+        Filter::PatternFilter(PatternFilter::Text {
+            fg_color,
+            bg_color,
+            defringing_kernel,
+            gamma_correction,
+        }) => {
+            writer.write_u8(FILTER_TEXT)?;
+            write_f32x4(writer, fg_color.0)?;
+            write_f32x4(writer, bg_color.0)?;
+            match defringing_kernel {
+                None => writer.write_u8(0)?,
+                Some(DefringingKernel(kernel)) => {
+                    writer.write_u8(1)?;
+                    for &value in &kernel {
+                        writer.write_f32::<LittleEndian>(value)?;
+                    }
+                }
+            }
+            writer.write_u8(gamma_correction as u8)
+        }
+        Filter::PatternFilter(PatternFilter::Blur { direction, sigma }) => {
+            writer.write_u8(FILTER_BLUR)?;
+            writer.write_u8(match direction {
+                BlurDirection::X => 0,
+                BlurDirection::Y => 1,
+            })?;
+            writer.write_f32::<LittleEndian>(sigma)
+        }
+        Filter::PatternFilter(PatternFilter::LuminanceToAlpha) => {
+            writer.write_u8(FILTER_LUMINANCE_TO_ALPHA)
+        }
+        Filter::PatternFilter(PatternFilter::Unpremultiply) => {
+            writer.write_u8(FILTER_UNPREMULTIPLY)
+        }
+        Filter::PatternFilter(PatternFilter::ColorMatrix(ColorMatrix(ref matrix))) => {
+            writer.write_u8(FILTER_COLOR_MATRIX)?;
+            for &value in matrix {
+                writer.write_f32::<LittleEndian>(value)?;
+            }
+            Ok(())
+        }
+        Filter::PatternFilter(PatternFilter::ComponentTransfer(ref functions)) => {
+            writer.write_u8(FILTER_COMPONENT_TRANSFER)?;
+            for &function in functions {
+                write_transfer_function(writer, function)?;
+            }
+            Ok(())
+        }
+        Filter::PatternFilter(PatternFilter::Morphology { operator, direction, radius }) => {
+            writer.write_u8(FILTER_MORPHOLOGY)?;
+            writer.write_u8(match operator {
+                MorphologyOperator::Erode => 0,
+                MorphologyOperator::Dilate => 1,
+            })?;
+            writer.write_u8(match direction {
+                BlurDirection::X => 0,
+                BlurDirection::Y => 1,
+            })?;
+            writer.write_f32::<LittleEndian>(radius)
+        }
+    }
+}
+
+fn write_transfer_function<W>(writer: &mut W, function: TransferFunction) -> io::Result<()>
+                              where W: Write {
+    match function {
+        TransferFunction::Identity => writer.write_u8(TRANSFER_FUNCTION_IDENTITY),
+        TransferFunction::Table(ref table) | TransferFunction::Discrete(ref table) => {
+            writer.write_u8(match function {
+                TransferFunction::Table(_) => TRANSFER_FUNCTION_TABLE,
+                _ => TRANSFER_FUNCTION_DISCRETE,
+            })?;
+            writer.write_u8(table.values().len() as u8)?;
+            for &value in table.values() {
+                writer.write_f32::<LittleEndian>(value)?;
+            }
+            Ok(())
+        }
+        TransferFunction::Linear { slope, intercept } => {
+            writer.write_u8(TRANSFER_FUNCTION_LINEAR)?;
+            writer.write_f32::<LittleEndian>(slope)?;
+            writer.write_f32::<LittleEndian>(intercept)
+        }
+        TransferFunction::Gamma { amplitude, exponent, offset } => {
+            writer.write_u8(TRANSFER_FUNCTION_GAMMA)?;
+            writer.write_f32::<LittleEndian>(amplitude)?;
+            writer.write_f32::<LittleEndian>(exponent)?;
+            writer.write_f32::<LittleEndian>(offset)
+        }
+    }
+}
+
+fn write_build_stats<W>(writer: &mut W, stats: &BuildStats) -> io::Result<()> where W: Write {
+    for &duration in &[
+        stats.paint_time,
+        stats.clip_path_tiling_time,
+        stats.draw_path_tiling_time,
+        stats.clip_time,
+        stats.solid_tile_time,
+        stats.cull_time,
+        stats.pack_time,
+    ] {
+        write_duration(writer, duration)?;
+    }
+    for &count in &[
+        stats.cached_path_count,
+        stats.fill_count,
+        stats.max_path_fill_count,
+        stats.occluded_tile_count,
+        stats.new_texture_page_count,
+        stats.resident_texture_page_count,
+    ] {
+        writer.write_u64::<LittleEndian>(count as u64)?;
+    }
+    Ok(())
+}
+
+fn write_duration<W>(writer: &mut W, duration: Duration) -> io::Result<()> where W: Write {
+    writer.write_u64::<LittleEndian>(duration.as_secs())?;
+    writer.write_u32::<LittleEndian>(duration.subsec_nanos())
+}
+
+fn write_texture_location<W>(writer: &mut W, location: TextureLocation) -> io::Result<()>
+                             where W: Write {
+    writer.write_u32::<LittleEndian>(location.page.0)?;
+    write_vector2i(writer, location.rect.origin())?;
+    write_vector2i(writer, location.rect.lower_right())
+}
+
+fn write_render_target_id<W>(writer: &mut W, id: RenderTargetId) -> io::Result<()>
+                             where W: Write {
+    writer.write_u32::<LittleEndian>(id.scene)?;
+    writer.write_u32::<LittleEndian>(id.render_target)
+}
+
+pub(crate) fn write_color_u<W>(writer: &mut W, color: ColorU) -> io::Result<()> where W: Write {
+    writer.write_all(&[color.r, color.g, color.b, color.a])
+}
+
+pub(crate) fn write_vector2i<W>(writer: &mut W, vector: Vector2I) -> io::Result<()> where W: Write {
+    writer.write_i32::<LittleEndian>(vector.x())?;
+    writer.write_i32::<LittleEndian>(vector.y())
+}
+
+pub(crate) fn write_vector2f<W>(writer: &mut W, vector: Vector2F) -> io::Result<()> where W: Write {
+    writer.write_f32::<LittleEndian>(vector.x())?;
+    writer.write_f32::<LittleEndian>(vector.y())
+}
+
+pub(crate) fn write_transform<W>(writer: &mut W, transform: &Transform2F) -> io::Result<()>
+                                 where W: Write {
+    write_f32x4(writer, transform.matrix.0)?;
+    write_vector2f(writer, transform.vector)
+}
+
+pub(crate) fn write_f32x4<W>(writer: &mut W, value: F32x4) -> io::Result<()> where W: Write {
+    for lane in 0..4 {
+        writer.write_f32::<LittleEndian>(value[lane])?;
+    }
+    Ok(())
+}
+
+// Reading
+
+fn read_command<R>(reader: &mut R, tag: u8) -> io::Result<RenderCommand> where R: Read {
+    match tag {
+        COMMAND_START => {
+            let path_count = reader.read_u64::<LittleEndian>()? as usize;
+            let mut bounding_quad: BoundingQuad = [Vector4F::default(); 4];
+            for vertex in &mut bounding_quad {
+                *vertex = Vector4F(read_f32x4(reader)?);
+            }
+            let needs_readable_framebuffer = read_bool(reader)?;
+            let tile_size = match TileSize::checked_new(reader.read_u8()? as u32) {
+                Some(tile_size) => tile_size,
+                None => return Err(invalid_data("Invalid tile size!")),
+            };
+            Ok(RenderCommand::Start {
+                path_count,
+                bounding_quad,
+                needs_readable_framebuffer,
+                tile_size,
+            })
+        }
+        COMMAND_ALLOCATE_TEXTURE_PAGE => {
+            let page_id = TexturePageId(reader.read_u32::<LittleEndian>()?);
+            let descriptor = TexturePageDescriptor { size: read_vector2i(reader)? };
+            Ok(RenderCommand::AllocateTexturePage { page_id, descriptor })
+        }
+        COMMAND_FREE_TEXTURE_PAGE => {
+            Ok(RenderCommand::FreeTexturePage(TexturePageId(reader.read_u32::<LittleEndian>()?)))
+        }
+        COMMAND_UPLOAD_TEXEL_DATA => {
+            let location = read_texture_location(reader)?;
+            let texel_count = reader.read_u32::<LittleEndian>()?;
+            let mut texels = Vec::with_capacity(preallocation_size(texel_count as usize));
+            for _ in 0..texel_count {
+                texels.push(read_color_u(reader)?);
+            }
+            Ok(RenderCommand::UploadTexelData { texels: Arc::new(texels), location })
+        }
+        COMMAND_DECLARE_RENDER_TARGET => {
+            let id = read_render_target_id(reader)?;
+            let location = read_texture_location(reader)?;
+            Ok(RenderCommand::DeclareRenderTarget { id, location })
+        }
+        COMMAND_UPLOAD_TEXTURE_METADATA => {
+            let entry_count = reader.read_u32::<LittleEndian>()?;
+            let mut metadata = Vec::with_capacity(preallocation_size(entry_count as usize));
+            for _ in 0..entry_count {
+                let color_0_transform = read_transform(reader)?;
+                let base_color = read_color_u(reader)?;
+                metadata.push(TextureMetadataEntry { color_0_transform, base_color });
+            }
+            Ok(RenderCommand::UploadTextureMetadata(metadata))
+        }
+        COMMAND_ADD_FILLS => {
+            let fill_count = reader.read_u32::<LittleEndian>()?;
+            let mut fills = Vec::with_capacity(preallocation_size(fill_count as usize));
+            for _ in 0..fill_count {
+                let mut bytes = [0; 6];
+                reader.read_exact(&mut bytes)?;
+                let alpha_tile_index = reader.read_u16::<LittleEndian>()?;
+                let page = reader.read_u16::<LittleEndian>()?;
+                fills.push(FillBatchEntry {
+                    fill: Fill {
+                        px: LineSegmentU4 { from: bytes[0], to: bytes[1] },
+                        subpx: LineSegmentU8 {
+                            from_x: bytes[2],
+                            from_y: bytes[3],
+                            to_x: bytes[4],
+                            to_y: bytes[5],
+                        },
+                        alpha_tile_index,
+                    },
+                    page,
+                });
+            }
+            Ok(RenderCommand::AddFills(fills))
+        }
+        COMMAND_FLUSH_FILLS => Ok(RenderCommand::FlushFills),
+        COMMAND_CLIP_TILES => {
+            let batch_count = reader.read_u32::<LittleEndian>()?;
+            let mut batches = Vec::with_capacity(preallocation_size(batch_count as usize));
+            for _ in 0..batch_count {
+                let dest_page = reader.read_u16::<LittleEndian>()?;
+                let src_page = reader.read_u16::<LittleEndian>()?;
+                let kind = match reader.read_u8()? {
+                    0 => ClipBatchKind::Draw,
+                    1 => ClipBatchKind::Clip,
+                    _ => return Err(invalid_data("Invalid clip batch kind!")),
+                };
+                let clip_count = reader.read_u32::<LittleEndian>()?;
+                let mut clips = Vec::with_capacity(preallocation_size(clip_count as usize));
+                for _ in 0..clip_count {
+                    let mut bytes = [0; 4];
+                    reader.read_exact(&mut bytes)?;
+                    let backdrop = reader.read_i8()?;
+                    clips.push(Clip {
+                        dest_u: bytes[0],
+                        dest_v: bytes[1],
+                        src_u: bytes[2],
+                        src_v: bytes[3],
+                        backdrop,
+                        pad_0: 0,
+                        pad_1: 0,
+                    });
+                }
+                batches.push(ClipBatch { clips, key: ClipBatchKey { dest_page, src_page, kind } });
+            }
+            Ok(RenderCommand::ClipTiles(batches))
+        }
+        COMMAND_PUSH_RENDER_TARGET => {
+            Ok(RenderCommand::PushRenderTarget(read_render_target_id(reader)?))
+        }
+        COMMAND_POP_RENDER_TARGET => Ok(RenderCommand::PopRenderTarget),
+        COMMAND_BEGIN_TILE_DRAWING => Ok(RenderCommand::BeginTileDrawing),
+        COMMAND_DRAW_TILES => {
+            let tile_count = reader.read_u32::<LittleEndian>()?;
+            let mut tiles = Vec::with_capacity(preallocation_size(tile_count as usize));
+            for _ in 0..tile_count {
+                let tile_x = reader.read_i16::<LittleEndian>()?;
+                let tile_y = reader.read_i16::<LittleEndian>()?;
+                let mask_0_u = reader.read_u8()?;
+                let mask_0_v = reader.read_u8()?;
+                let mask_0_backdrop = reader.read_i8()?;
+                let color = reader.read_u16::<LittleEndian>()?;
+                let ctrl = reader.read_u16::<LittleEndian>()?;
+                tiles.push(Tile {
+                    tile_x,
+                    tile_y,
+                    mask_0_u,
+                    mask_0_v,
+                    mask_0_backdrop,
+                    pad: 0,
+                    color,
+                    ctrl,
+                });
+            }
+            let color_texture = if read_bool(reader)? {
+                let page = TexturePageId(reader.read_u32::<LittleEndian>()?);
+                let sampling_flags = TextureSamplingFlags::from_bits(reader.read_u8()?).ok_or_else(
+                    || invalid_data("Invalid texture sampling flags!"))?;
+                let composite_op = match reader.read_u8()? {
+                    0 => PaintCompositeOp::SrcIn,
+                    1 => PaintCompositeOp::DestIn,
+                    _ => return Err(invalid_data("Invalid paint composite op!")),
+                };
+                Some(TileBatchTexture { page, sampling_flags, composite_op })
+            } else {
+                None
+            };
+            let filter = read_filter(reader)?;
+            let blend_mode = blend_mode_from_u8(reader.read_u8()?)?;
+            let tile_page = reader.read_u16::<LittleEndian>()?;
+            Ok(RenderCommand::DrawTiles(TileBatch {
+                tiles,
+                color_texture,
+                filter,
+                blend_mode,
+                tile_page,
+            }))
+        }
+        COMMAND_FINISH => {
+            let cpu_build_time = read_duration(reader)?;
+            let build_stats = read_build_stats(reader)?;
+            Ok(RenderCommand::Finish { cpu_build_time, build_stats })
+        }
+        _ => Err(invalid_data("Unknown render command!")),
+    }
+}
+
+pub(crate) fn read_filter<R>(reader: &mut R) -> io::Result<Filter> where R: Read {
+    match reader.read_u8()? {
+        FILTER_NONE => Ok(Filter::None),
+        FILTER_LINEAR_GRADIENT => {
+            let spread = gradient_spread_from_u8(reader.read_u8()?)?;
+            Ok(Filter::LinearGradient { spread })
+        }
+        FILTER_RADIAL_GRADIENT => {
+            let line = LineSegment2F(read_f32x4(reader)?);
+            let radii = F32x2::new(reader.read_f32::<LittleEndian>()?,
+                                   reader.read_f32::<LittleEndian>()?);
+            let uv_origin = read_vector2f(reader)?;
+            let spread = gradient_spread_from_u8(reader.read_u8()?)?;
+            Ok(Filter::RadialGradient { line, radii, uv_origin, spread })
+        }
+        FILTER_CONIC_GRADIENT => {
+            let center = read_vector2f(reader)?;
+            let angle = reader.read_f32::<LittleEndian>()?;
+            let uv_origin = read_vector2f(reader)?;
+            Ok(Filter::ConicGradient { center, angle, uv_origin })
+        }
+        FILTER_TEXT => {
+            let fg_color = ColorF(read_f32x4(reader)?);
+            let bg_color = ColorF(read_f32x4(reader)?);
+            let defringing_kernel = if read_bool(reader)? {
+                let mut kernel = [0.0; 4];
+                for value in &mut kernel {
+                    *value = reader.read_f32::<LittleEndian>()?;
+                }
+                Some(DefringingKernel(kernel))
+            } else {
+                None
+            };
+            let gamma_correction = read_bool(reader)?;
+            Ok(Filter::PatternFilter(PatternFilter::Text {
+                fg_color,
+                bg_color,
+                defringing_kernel,
+                gamma_correction,
+            }))
+        }
+        FILTER_BLUR => {
+            let direction = match reader.read_u8()? {
+                0 => BlurDirection::X,
+                1 => BlurDirection::Y,
+                _ => return Err(invalid_data("Invalid blur direction!")),
+            };
+            let sigma = reader.read_f32::<LittleEndian>()?;
+            Ok(Filter::PatternFilter(PatternFilter::Blur { direction, sigma }))
+        }
+        FILTER_LUMINANCE_TO_ALPHA => Ok(Filter::PatternFilter(PatternFilter::LuminanceToAlpha)),
+        FILTER_UNPREMULTIPLY => Ok(Filter::PatternFilter(PatternFilter::Unpremultiply)),
+        FILTER_COLOR_MATRIX => {
+            let mut matrix = [0.0; 20];
+            for value in &mut matrix {
+                *value = reader.read_f32::<LittleEndian>()?;
+            }
+            Ok(Filter::PatternFilter(PatternFilter::ColorMatrix(ColorMatrix(matrix))))
+        }
+        FILTER_COMPONENT_TRANSFER => {
+            let mut functions = [TransferFunction::Identity; 4];
+            for function in &mut functions {
+                *function = read_transfer_function(reader)?;
+            }
+            Ok(Filter::PatternFilter(PatternFilter::ComponentTransfer(functions)))
+        }
+        FILTER_MORPHOLOGY => {
+            let operator = match reader.read_u8()? {
+                0 => MorphologyOperator::Erode,
+                1 => MorphologyOperator::Dilate,
+                _ => return Err(invalid_data("Invalid morphology operator!")),
+            };
+            let direction = match reader.read_u8()? {
+                0 => BlurDirection::X,
+                1 => BlurDirection::Y,
+                _ => return Err(invalid_data("Invalid morphology direction!")),
+            };
+            let radius = reader.read_f32::<LittleEndian>()?;
+            Ok(Filter::PatternFilter(PatternFilter::Morphology { operator, direction, radius }))
+        }
+        _ => Err(invalid_data("Unknown filter!")),
+    }
+}
+
+fn read_transfer_function<R>(reader: &mut R) -> io::Result<TransferFunction> where R: Read {
+    match reader.read_u8()? {
+        TRANSFER_FUNCTION_IDENTITY => Ok(TransferFunction::Identity),
+        kind @ TRANSFER_FUNCTION_TABLE | kind @ TRANSFER_FUNCTION_DISCRETE => {
+            let mut values = vec![0.0; reader.read_u8()? as usize];
+            for value in &mut values {
+                *value = reader.read_f32::<LittleEndian>()?;
+            }
+            let table = TransferTable::new(&values).ok_or_else(|| {
+                invalid_data("Invalid transfer table length!")
+            })?;
+            if kind == TRANSFER_FUNCTION_TABLE {
+                Ok(TransferFunction::Table(table))
+            } else {
+                Ok(TransferFunction::Discrete(table))
+            }
+        }
+        TRANSFER_FUNCTION_LINEAR => {
+            let slope = reader.read_f32::<LittleEndian>()?;
+            let intercept = reader.read_f32::<LittleEndian>()?;
+            Ok(TransferFunction::Linear { slope, intercept })
+        }
+        TRANSFER_FUNCTION_GAMMA => {
+            let amplitude = reader.read_f32::<LittleEndian>()?;
+            let exponent = reader.read_f32::<LittleEndian>()?;
+            let offset = reader.read_f32::<LittleEndian>()?;
+            Ok(TransferFunction::Gamma { amplitude, exponent, offset })
+        }
+        _ => Err(invalid_data("Unknown transfer function!")),
+    }
+}
+
+fn read_build_stats<R>(reader: &mut R) -> io::Result<BuildStats> where R: Read {
+    Ok(BuildStats {
+        paint_time: read_duration(reader)?,
+        clip_path_tiling_time: read_duration(reader)?,
+        draw_path_tiling_time: read_duration(reader)?,
+        clip_time: read_duration(reader)?,
+        solid_tile_time: read_duration(reader)?,
+        cull_time: read_duration(reader)?,
+        pack_time: read_duration(reader)?,
+        cached_path_count: reader.read_u64::<LittleEndian>()? as usize,
+        fill_count: reader.read_u64::<LittleEndian>()? as usize,
+        max_path_fill_count: reader.read_u64::<LittleEndian>()? as usize,
+        occluded_tile_count: reader.read_u64::<LittleEndian>()? as usize,
+        new_texture_page_count: reader.read_u64::<LittleEndian>()? as usize,
+        resident_texture_page_count: reader.read_u64::<LittleEndian>()? as usize,
+    })
+}
+
+fn read_duration<R>(reader: &mut R) -> io::Result<Duration> where R: Read {
+    let seconds = reader.read_u64::<LittleEndian>()?;
+    let nanoseconds = reader.read_u32::<LittleEndian>()?;
+    Ok(Duration::new(seconds, nanoseconds))
+}
+
+fn read_texture_location<R>(reader: &mut R) -> io::Result<TextureLocation> where R: Read {
+    let page = TexturePageId(reader.read_u32::<LittleEndian>()?);
+    let origin = read_vector2i(reader)?;
+    let lower_right = read_vector2i(reader)?;
+    Ok(TextureLocation { page, rect: RectI::from_points(origin, lower_right) })
+}
+
+fn read_render_target_id<R>(reader: &mut R) -> io::Result<RenderTargetId> where R: Read {
+    let scene = reader.read_u32::<LittleEndian>()?;
+    let render_target = reader.read_u32::<LittleEndian>()?;
+    Ok(RenderTargetId { scene, render_target })
+}
+
+pub(crate) fn read_color_u<R>(reader: &mut R) -> io::Result<ColorU> where R: Read {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(ColorU::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+}
+
+pub(crate) fn read_vector2i<R>(reader: &mut R) -> io::Result<Vector2I> where R: Read {
+    let x = reader.read_i32::<LittleEndian>()?;
+    let y = reader.read_i32::<LittleEndian>()?;
+    Ok(Vector2I::new(x, y))
+}
+
+pub(crate) fn read_vector2f<R>(reader: &mut R) -> io::Result<Vector2F> where R: Read {
+    let x = reader.read_f32::<LittleEndian>()?;
+    let y = reader.read_f32::<LittleEndian>()?;
+    Ok(Vector2F::new(x, y))
+}
+
+pub(crate) fn read_transform<R>(reader: &mut R) -> io::Result<Transform2F> where R: Read {
+    let matrix = Matrix2x2F(read_f32x4(reader)?);
+    let vector = read_vector2f(reader)?;
+    Ok(Transform2F { matrix, vector })
+}
+
+pub(crate) fn read_f32x4<R>(reader: &mut R) -> io::Result<F32x4> where R: Read {
+    let mut lanes = [0.0; 4];
+    for lane in &mut lanes {
+        *lane = reader.read_f32::<LittleEndian>()?;
+    }
+    Ok(F32x4::new(lanes[0], lanes[1], lanes[2], lanes[3]))
+}
+
+pub(crate) fn read_bool<R>(reader: &mut R) -> io::Result<bool> where R: Read {
+    match reader.read_u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(invalid_data("Invalid boolean!")),
+    }
+}
+
+pub(crate) fn blend_mode_to_u8(blend_mode: BlendMode) -> u8 {
+    BLEND_MODES.iter().position(|&other| other == blend_mode).unwrap() as u8
+}
+
+pub(crate) fn blend_mode_from_u8(value: u8) -> io::Result<BlendMode> {
+    match BLEND_MODES.get(value as usize) {
+        Some(&blend_mode) => Ok(blend_mode),
+        None => Err(invalid_data("Unknown blend mode!")),
+    }
+}
+
+pub(crate) fn gradient_spread_to_u8(spread: GradientSpread) -> u8 {
+    GRADIENT_SPREADS.iter().position(|&other| other == spread).unwrap() as u8
+}
+
+pub(crate) fn gradient_spread_from_u8(value: u8) -> io::Result<GradientSpread> {
+    match GRADIENT_SPREADS.get(value as usize) {
+        Some(&spread) => Ok(spread),
+        None => Err(invalid_data("Unknown gradient spread!")),
+    }
+}
+
+pub(crate) fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Caps the number of elements preallocated for a count read from a file, so that a corrupt
+/// count fails with an I/O error partway through reading instead of exhausting memory up front.
+pub(crate) fn preallocation_size(count: usize) -> usize {
+    count.min(MAX_PREALLOCATION)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::cpu::rasterizer::ReferenceRasterizer;
+    use crate::gpu::options::RendererOptions;
+    use crate::options::BuildOptions;
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, Scene};
+    use pathfinder_color::{ColorF, ColorU};
+    use pathfinder_content::gradient::Gradient;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_content::pattern::{Image, Pattern};
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::{Vector2F, vec2f, vec2i};
+    use std::io::{self, ErrorKind, Read};
+    use std::sync::Arc;
+
+    use super::{COMMAND_ADD_FILLS, CAPTURE_VERSION, CaptureReader, CaptureRecorder};
+
+    #[test]
+    fn test_replay_matches_direct_rendering() {
+        let mut scene = Scene::new();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let rect = RectF::new(vec2f(3.5, 4.25), vec2f(20.0, 9.5));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+
+        let options = || RendererOptions { background_color: Some(ColorF::white()) };
+        let direct = ReferenceRasterizer::new(vec2i(32, 32), options());
+        scene.build(BuildOptions::default(), Box::new(direct.clone()), &SequentialExecutor);
+
+        let recorder = CaptureRecorder::new(vec![]).unwrap();
+        scene.build(BuildOptions::default(), Box::new(recorder.clone()), &SequentialExecutor);
+        let capture = recorder.finish().unwrap();
+
+        let replayed = ReferenceRasterizer::new(vec2i(32, 32), options());
+        for command in CaptureReader::new(&capture[..]).unwrap() {
+            replayed.render_command(&command.unwrap());
+        }
+        assert_eq!(direct.pixels(), replayed.pixels());
+
+        // Re-recording the replayed commands must produce an identical capture.
+        let rerecorder = CaptureRecorder::new(vec![]).unwrap();
+        for command in CaptureReader::new(&capture[..]).unwrap() {
+            rerecorder.record(&command.unwrap());
+        }
+        assert_eq!(rerecorder.finish().unwrap(), capture);
+    }
+
+    #[test]
+    fn test_replay_capture_started_after_first_build() {
+        let mut scene = Scene::new();
+        let line = LineSegment2F::new(Vector2F::zero(), vec2f(32.0, 0.0));
+        let mut gradient = Gradient::linear(line);
+        gradient.add_color_stop(ColorU::black(), 0.0);
+        gradient.add_color_stop(ColorU::white(), 1.0);
+        let gradient = scene.push_paint(&Paint::from_gradient(gradient));
+        let rect = RectF::new(Vector2F::zero(), vec2f(32.0, 16.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), gradient));
+        let image = Image::new(vec2i(4, 4), Arc::new(vec![ColorU::new(0, 0, 255, 255); 16]));
+        let image = scene.push_paint(&Paint::from_pattern(Pattern::from_image(image)));
+        let rect = RectF::new(vec2f(0.0, 16.0), vec2f(4.0, 4.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), image));
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+
+        let options = || RendererOptions { background_color: Some(ColorF::white()) };
+        let direct = ReferenceRasterizer::new(vec2i(32, 32), options());
+        scene.build(BuildOptions::default(), Box::new(direct.clone()), &SequentialExecutor);
+
+        // The texture pages were sent to the first rasterizer, so they must be sent again.
+        let recorder = CaptureRecorder::new(vec![]).unwrap();
+        scene.invalidate_textures();
+        scene.build(BuildOptions::default(), Box::new(recorder.clone()), &SequentialExecutor);
+        let capture = recorder.finish().unwrap();
+
+        let replayed = ReferenceRasterizer::new(vec2i(32, 32), options());
+        for command in CaptureReader::new(&capture[..]).unwrap() {
+            replayed.render_command(&command.unwrap());
+        }
+        assert_eq!(direct.pixels(), replayed.pixels());
+    }
+
+    #[test]
+    fn test_reject_unknown_version() {
+        let mut capture = CaptureRecorder::new(vec![]).unwrap().finish().unwrap();
+        capture[4..8].copy_from_slice(&(CAPTURE_VERSION + 1).to_le_bytes());
+        let error = CaptureReader::new(&capture[..]).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_reject_truncated_command() {
+        // A huge fill count must fail on the missing fills, not on the allocation.
+        let mut capture = CaptureRecorder::new(vec![]).unwrap().finish().unwrap();
+        capture.push(COMMAND_ADD_FILLS);
+        capture.extend_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = CaptureReader::new(&capture[..]).unwrap();
+        let error = reader.read_command().err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    // Fails every other read with `ErrorKind::Interrupted`.
+    struct InterruptingReader<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl<'a> Read for InterruptingReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::Error::new(ErrorKind::Interrupted, "interrupted"));
+            }
+            self.data.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_retry_interrupted_reads() {
+        let mut scene = Scene::new();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let rect = RectF::new(vec2f(2.0, 2.0), vec2f(8.0, 8.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(16.0, 16.0)));
+
+        let recorder = CaptureRecorder::new(vec![]).unwrap();
+        scene.build(BuildOptions::default(), Box::new(recorder.clone()), &SequentialExecutor);
+        let capture = recorder.finish().unwrap();
+
+        let expected = CaptureReader::new(&capture[..]).unwrap().count();
+        let reader = InterruptingReader { data: &capture[..], interrupt: false };
+        let commands: Vec<_> = CaptureReader::new(reader).unwrap().collect();
+        assert_eq!(commands.len(), expected);
+        assert!(commands.iter().all(|command| command.is_ok()));
+    }
+}
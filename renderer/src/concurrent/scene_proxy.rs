@@ -48,9 +48,22 @@ impl SceneProxy {
         SceneProxy { sender: main_to_worker_sender }
     }
 
+    /// Replaces the scene wholesale.
+    ///
+    /// A scene built afresh carries no tiles over from previous builds, so the next build retiles
+    /// every path. Use `update_scene()` to edit the scene in place instead.
     #[inline]
     pub fn replace_scene(&self, new_scene: Scene) {
-        self.sender.send(MainToWorkerMsg::ReplaceScene(new_scene)).unwrap();
+        self.sender.send(MainToWorkerMsg::ReplaceScene(Box::new(new_scene))).unwrap();
+    }
+
+    /// Runs the given function on the scene on the worker thread.
+    ///
+    /// Unlike `replace_scene()`, this keeps the tiles built for paths that the function leaves
+    /// untouched, so they can be reused by the next build.
+    #[inline]
+    pub fn update_scene<F>(&self, update: F) where F: FnOnce(&mut Scene) + Send + 'static {
+        self.sender.send(MainToWorkerMsg::UpdateScene(Box::new(update))).unwrap();
     }
 
     #[inline]
@@ -107,7 +120,8 @@ fn scene_thread<E>(mut scene: Scene,
                    where E: Executor {
     while let Ok(msg) = main_to_worker_receiver.recv() {
         match msg {
-            MainToWorkerMsg::ReplaceScene(new_scene) => scene = new_scene,
+            MainToWorkerMsg::ReplaceScene(new_scene) => scene = *new_scene,
+            MainToWorkerMsg::UpdateScene(update) => update(&mut scene),
             MainToWorkerMsg::CopyScene(sender) => sender.send(scene.clone()).unwrap(),
             MainToWorkerMsg::SetViewBox(new_view_box) => scene.set_view_box(new_view_box),
             MainToWorkerMsg::Build(options, listener) => scene.build(options, listener, &executor)
@@ -116,7 +130,8 @@ fn scene_thread<E>(mut scene: Scene,
 }
 
 enum MainToWorkerMsg {
-    ReplaceScene(Scene),
+    ReplaceScene(Box<Scene>),
+    UpdateScene(Box<dyn FnOnce(&mut Scene) + Send>),
     CopyScene(Sender<Scene>),
     SetViewBox(RectF),
     Build(BuildOptions, Box<dyn RenderCommandListener>),
@@ -149,3 +164,47 @@ impl Iterator for RenderCommandStream {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::gpu_data::RenderCommand;
+    use crate::options::BuildOptions;
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, Scene};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    use super::SceneProxy;
+
+    #[test]
+    fn test_update_scene_reuses_tiles() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(64.0, 64.0)));
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let rect = |origin| Outline::from_rect(RectF::new(origin, vec2f(10.5, 10.5)));
+        scene.push_path(DrawPath::new(rect(vec2f(2.0, 2.0)), red));
+        let path_id = scene.push_path(DrawPath::new(rect(vec2f(30.0, 2.0)), red));
+
+        let proxy = SceneProxy::from_scene(scene, SequentialExecutor);
+        let cached_path_count = || {
+            proxy.build_with_stream(BuildOptions::default()).filter_map(|command| {
+                match command {
+                    RenderCommand::Finish { build_stats, .. } => {
+                        Some(build_stats.cached_path_count)
+                    }
+                    _ => None,
+                }
+            }).next().unwrap()
+        };
+        assert_eq!(cached_path_count(), 0);
+        assert_eq!(cached_path_count(), 2);
+
+        proxy.update_scene(move |scene| {
+            scene.set_path(path_id, DrawPath::new(rect(vec2f(30.0, 30.0)), red));
+        });
+        assert_eq!(cached_path_count(), 1);
+    }
+}
@@ -0,0 +1,1425 @@
+// pathfinder/renderer/src/cpu/rasterizer.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A rasterizer that consumes the render command stream directly, with no `Device` involved.
+//!
+//! This processes the same `RenderCommand`s that the GPU renderer does, but fills are accumulated
+//! with exact area coverage instead of the area lookup table, and tiles are shaded and blended in
+//! plain Rust. It's slow, but it serves as a ground truth for the tiling pipeline: GPU output can
+//! be diffed against it, and it can produce golden images for tests of the scene builder.
+//!
+//! The bounding quad isn't used, so perspective rendering with the depth buffer isn't supported.
+//! Text gamma correction is also ignored, since the gamma lookup table is a GPU resource.
+
+use crate::gpu::options::RendererOptions;
+use crate::gpu::renderer::{BlendModeExt, MASK_TILES_ACROSS, ToBlendState};
+use crate::gpu_data::{Clip, ClipBatch, ClipBatchKind, Fill, FillBatchEntry, RenderCommand};
+use crate::gpu_data::{TILE_CTRL_MASK_0_SHIFT, TILE_CTRL_MASK_MASK, TILE_CTRL_MASK_WINDING};
+use crate::gpu_data::{TextureLocation, TextureMetadataEntry, TexturePageId, Tile, TileBatch};
+use crate::options::{RenderCommandListener, TileSize};
+use crate::paint::PaintCompositeOp;
+use fxhash::FxHashMap;
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::effects::{BlendMode, BlurDirection, DefringingKernel, Filter};
+use pathfinder_content::effects::{MorphologyOperator, PatternFilter};
+use pathfinder_content::render_target::RenderTargetId;
+use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
+use pathfinder_gpu::{BlendFactor, BlendOp, TextureSamplingFlags};
+use pathfinder_simd::default::F32x4;
+use std::f32::{self, consts::{FRAC_PI_3, PI}};
+use std::sync::{Arc, Mutex};
+
+// 6 / π
+const FRAC_6_PI: f32 = 1.909_859_3;
+
+// Coverage for each pixel of a tile, top row first.
+type MaskTile = Vec<f32>;
+
+/// Renders a stream of render commands into an RGBA8 framebuffer on the CPU.
+///
+/// Clones share the same framebuffer, so a clone can be handed to `Scene::build()` as the
+/// listener and the result read back from the original afterward.
+#[derive(Clone)]
+pub struct ReferenceRasterizer {
+    state: Arc<Mutex<RasterizerState>>,
+}
+
+struct RasterizerState {
+    options: RendererOptions,
+    dest_framebuffer: ColorBuffer,
+    dest_must_preserve_contents: bool,
+    texture_pages: Vec<Option<TexturePage>>,
+    render_targets: Vec<RenderTargetInfo>,
+    render_target_stack: Vec<RenderTargetId>,
+    texture_metadata: Vec<TextureMetadataEntry>,
+    tile_size: TileSize,
+    // Mask tiles that have been drawn to, keyed by page and tile index. All others are zero.
+    mask_tiles: FxHashMap<(u16, u16), MaskTile>,
+}
+
+struct TexturePage {
+    buffer: ColorBuffer,
+}
+
+struct RenderTargetInfo {
+    location: TextureLocation,
+    // Whether the render target has been drawn to this frame.
+    must_preserve_contents: bool,
+}
+
+// An RGBA8 color buffer with the origin at the lower left, like a GPU texture.
+struct ColorBuffer {
+    size: Vector2I,
+    texels: Vec<F32x4>,
+}
+
+impl ReferenceRasterizer {
+    pub fn new(framebuffer_size: Vector2I, options: RendererOptions) -> ReferenceRasterizer {
+        ReferenceRasterizer {
+            state: Arc::new(Mutex::new(RasterizerState {
+                options,
+                dest_framebuffer: ColorBuffer::new(framebuffer_size),
+                dest_must_preserve_contents: false,
+                texture_pages: vec![],
+                render_targets: vec![],
+                render_target_stack: vec![],
+                texture_metadata: vec![],
+                tile_size: TileSize::default(),
+                mask_tiles: FxHashMap::default(),
+            })),
+        }
+    }
+
+    pub fn render_command(&self, command: &RenderCommand) {
+        self.state.lock().unwrap().render_command(command)
+    }
+
+    #[inline]
+    pub fn framebuffer_size(&self) -> Vector2I {
+        self.state.lock().unwrap().dest_framebuffer.size
+    }
+
+    /// Returns the contents of the destination framebuffer, top row first, with premultiplied
+    /// alpha.
+    ///
+    /// This is the same layout that `Device::read_pixels()` produces for the default framebuffer.
+    pub fn pixels(&self) -> Vec<ColorU> {
+        let state = self.state.lock().unwrap();
+        let framebuffer = &state.dest_framebuffer;
+        let mut pixels = Vec::with_capacity(framebuffer.texels.len());
+        for y in (0..framebuffer.size.y()).rev() {
+            for x in 0..framebuffer.size.x() {
+                let texel = framebuffer.get(vec2i(x, y));
+                pixels.push(ColorU::new(unorm8(texel[0]),
+                                        unorm8(texel[1]),
+                                        unorm8(texel[2]),
+                                        unorm8(texel[3])));
+            }
+        }
+        pixels
+    }
+}
+
+impl RenderCommandListener for ReferenceRasterizer {
+    #[inline]
+    fn send(&self, command: RenderCommand) {
+        self.render_command(&command)
+    }
+}
+
+impl RasterizerState {
+    fn render_command(&mut self, command: &RenderCommand) {
+        debug!("reference render command: {:?}", command);
+        match *command {
+            RenderCommand::Start { tile_size, .. } => self.start_rendering(tile_size),
+            RenderCommand::AllocateTexturePage { page_id, ref descriptor } => {
+                self.allocate_texture_page(page_id, descriptor.size)
+            }
+            RenderCommand::FreeTexturePage(page_id) => {
+                self.texture_pages[page_id.0 as usize] = None;
+            }
+            RenderCommand::UploadTexelData { ref texels, location } => {
+                self.upload_texel_data(texels, location)
+            }
+            RenderCommand::DeclareRenderTarget { id, location } => {
+                self.declare_render_target(id, location)
+            }
+            RenderCommand::UploadTextureMetadata(ref metadata) => {
+                self.texture_metadata = metadata.clone()
+            }
+            RenderCommand::AddFills(ref fills) => self.add_fills(fills),
+            // Fills are accumulated as soon as they arrive, so there's nothing to flush.
+            RenderCommand::FlushFills => {}
+            RenderCommand::ClipTiles(ref batches) => {
+                batches.iter().for_each(|batch| self.draw_clip_batch(batch))
+            }
+            RenderCommand::PushRenderTarget(render_target_id) => {
+                self.render_target_stack.push(render_target_id)
+            }
+            RenderCommand::PopRenderTarget => {
+                self.render_target_stack.pop().expect("Render target stack underflow!");
+            }
+            RenderCommand::BeginTileDrawing => {}
+            RenderCommand::DrawTiles(ref batch) => self.draw_tiles(batch),
+            RenderCommand::Finish { .. } => {}
+        }
+    }
+
+    fn start_rendering(&mut self, tile_size: TileSize) {
+        self.tile_size = tile_size;
+        self.dest_must_preserve_contents = false;
+        self.render_targets.clear();
+        self.mask_tiles.clear();
+    }
+
+    fn allocate_texture_page(&mut self, page_id: TexturePageId, size: Vector2I) {
+        let page_index = page_id.0 as usize;
+        while self.texture_pages.len() < page_index + 1 {
+            self.texture_pages.push(None);
+        }
+        self.texture_pages[page_index] = Some(TexturePage { buffer: ColorBuffer::new(size) });
+    }
+
+    fn upload_texel_data(&mut self, texels: &[ColorU], location: TextureLocation) {
+        let texture_page = self.texture_pages[location.page.0 as usize]
+                               .as_mut()
+                               .expect("Texture page not allocated yet!");
+        let rect = location.rect;
+        assert!(texels.len() >= rect.width() as usize * rect.height() as usize);
+        for y in 0..rect.height() {
+            for x in 0..rect.width() {
+                let texel = texels[(y * rect.width() + x) as usize].to_f32().0;
+                texture_page.buffer.set(rect.origin() + vec2i(x, y), texel);
+            }
+        }
+    }
+
+    fn declare_render_target(&mut self,
+                             render_target_id: RenderTargetId,
+                             location: TextureLocation) {
+        let index = render_target_id.render_target as usize;
+        while self.render_targets.len() < index + 1 {
+            self.render_targets.push(RenderTargetInfo {
+                location: TextureLocation { page: TexturePageId(!0), rect: RectI::default() },
+                must_preserve_contents: false,
+            });
+        }
+        self.render_targets[index] = RenderTargetInfo { location, must_preserve_contents: false };
+    }
+
+    // Fills
+
+    fn add_fills(&mut self, fill_batch: &[FillBatchEntry]) {
+        let tile_size = self.tile_size.get() as i32;
+        let scale = tile_size as f32 / 16.0;
+        for fill_batch_entry in fill_batch {
+            let fill: Fill = fill_batch_entry.fill;
+            let (px, subpx) = (fill.px, fill.subpx);
+            let from = vec2f((px.from & 0xf) as f32, (px.from >> 4) as f32) +
+                vec2f(subpx.from_x as f32, subpx.from_y as f32) * (1.0 / 256.0);
+            let to = vec2f((px.to & 0xf) as f32, (px.to >> 4) as f32) +
+                vec2f(subpx.to_x as f32, subpx.to_y as f32) * (1.0 / 256.0);
+            let mask_tile = self.mask_tiles
+                                .entry((fill_batch_entry.page, fill.alpha_tile_index))
+                                .or_insert_with(|| new_mask_tile(tile_size));
+            accumulate_fill(mask_tile, tile_size, from * scale, to * scale);
+        }
+    }
+
+    // Clips
+
+    fn draw_clip_batch(&mut self, batch: &ClipBatch) {
+        for clip in &batch.clips {
+            self.draw_clip(clip, batch.key.dest_page, batch.key.src_page, batch.key.kind);
+        }
+    }
+
+    fn draw_clip(&mut self, clip: &Clip, dest_page: u16, src_page: u16, kind: ClipBatchKind) {
+        let src_tile_index = mask_tile_index(clip.src_u, clip.src_v);
+        let dest_tile_index = mask_tile_index(clip.dest_u, clip.dest_v);
+        let tile_size = self.tile_size.get() as i32;
+        let src_tile = self.mask_tiles
+                           .get(&(src_page, src_tile_index))
+                           .cloned()
+                           .unwrap_or_else(|| new_mask_tile(tile_size));
+        let dest_tile = self.mask_tiles
+                            .entry((dest_page, dest_tile_index))
+                            .or_insert_with(|| new_mask_tile(tile_size));
+        for (dest, &src) in dest_tile.iter_mut().zip(src_tile.iter()) {
+            let coverage = f32::min(f32::abs(src + clip.backdrop as f32), 1.0);
+            *dest = match kind {
+                ClipBatchKind::Draw => coverage,
+                ClipBatchKind::Clip => f32::min(*dest, coverage),
+            };
+        }
+    }
+
+    // Tiles
+
+    fn draw_tiles(&mut self, batch: &TileBatch) {
+        self.clear_draw_target_if_necessary();
+
+        let viewport = self.draw_viewport();
+        let tile_size = self.tile_size.get() as i32;
+        for tile in &batch.tiles {
+            let tile_origin = vec2i(tile.tile_x as i32, tile.tile_y as i32) * tile_size;
+            for tile_y in 0..tile_size {
+                for tile_x in 0..tile_size {
+                    let position = tile_origin + vec2i(tile_x, tile_y);
+                    if position.x() < 0 || position.x() >= viewport.width() ||
+                            position.y() < 0 || position.y() >= viewport.height() {
+                        continue;
+                    }
+
+                    // Tiles are laid out top down, but the framebuffer is bottom up.
+                    let texel_position = vec2i(viewport.min_x() + position.x(),
+                                               viewport.max_y() - 1 - position.y());
+                    let dest = self.draw_target().get(texel_position);
+                    let src = self.shade_fragment(batch, tile, vec2i(tile_x, tile_y), position, dest);
+                    let color = match batch.blend_mode.to_blend_state() {
+                        None => src,
+                        Some(blend_state) => {
+                            let src_rgb = src * blend_factor(blend_state.src_rgb_factor, src, dest);
+                            let dest_rgb =
+                                dest * blend_factor(blend_state.dest_rgb_factor, src, dest);
+                            let src_alpha = src *
+                                blend_factor(blend_state.src_alpha_factor, src, dest);
+                            let dest_alpha = dest *
+                                blend_factor(blend_state.dest_alpha_factor, src, dest);
+                            let mut color = blend_op(blend_state.op, src_rgb, dest_rgb);
+                            color[3] = blend_op(blend_state.op, src_alpha, dest_alpha)[3];
+                            color
+                        }
+                    };
+                    self.draw_target_mut().set(texel_position, color);
+                }
+            }
+        }
+
+        self.preserve_draw_target();
+    }
+
+    fn shade_fragment(&self,
+                      batch: &TileBatch,
+                      tile: &Tile,
+                      tile_pixel: Vector2I,
+                      position: Vector2I,
+                      dest: F32x4)
+                      -> F32x4 {
+        let metadata = &self.texture_metadata[tile.color as usize];
+        let mut color = metadata.base_color.to_f32().0;
+
+        if let Some(color_texture) = batch.color_texture {
+            let color_texture_page = &self.texture_page(color_texture.page).buffer;
+            let tex_coord = metadata.color_0_transform * (position.to_f32() + vec2f(0.5, 0.5));
+            let color_0 = filter_color(color_texture_page,
+                                       color_texture.sampling_flags,
+                                       tex_coord,
+                                       batch.filter);
+            color = combine_color(color, color_0, color_texture.composite_op);
+        }
+
+        color[3] *= self.sample_mask(batch.tile_page, tile, tile_pixel);
+
+        let mut color = composite(color, dest, batch.blend_mode).clamp(F32x4::default(),
+                                                                       F32x4::splat(1.0));
+        let alpha = color[3];
+        color *= F32x4::new(alpha, alpha, alpha, 1.0);
+        color
+    }
+
+    fn sample_mask(&self, page: u16, tile: &Tile, tile_pixel: Vector2I) -> f32 {
+        let mask_ctrl = (tile.ctrl as i32 >> TILE_CTRL_MASK_0_SHIFT) & TILE_CTRL_MASK_MASK;
+        if mask_ctrl == 0 {
+            return 1.0;
+        }
+
+        let tile_index = mask_tile_index(tile.mask_0_u, tile.mask_0_v);
+        let tile_size = self.tile_size.get() as i32;
+        let pixel_index = (tile_pixel.y() * tile_size + tile_pixel.x()) as usize;
+        let mask = match self.mask_tiles.get(&(page, tile_index)) {
+            Some(mask_tile) => mask_tile[pixel_index],
+            None => 0.0,
+        };
+
+        let coverage = mask + tile.mask_0_backdrop as f32;
+        let coverage = if (mask_ctrl & TILE_CTRL_MASK_WINDING) != 0 {
+            f32::abs(coverage)
+        } else {
+            1.0 - f32::abs(1.0 - glsl_mod(coverage, 2.0))
+        };
+        f32::min(coverage, 1.0)
+    }
+
+    // Render targets
+
+    fn draw_viewport(&self) -> RectI {
+        match self.render_target_stack.last() {
+            Some(&render_target_id) => self.render_target_location(render_target_id).rect,
+            None => RectI::new(Vector2I::default(), self.dest_framebuffer.size),
+        }
+    }
+
+    fn draw_target(&self) -> &ColorBuffer {
+        match self.render_target_stack.last() {
+            Some(&render_target_id) => {
+                let page = self.render_target_location(render_target_id).page;
+                &self.texture_page(page).buffer
+            }
+            None => &self.dest_framebuffer,
+        }
+    }
+
+    fn draw_target_mut(&mut self) -> &mut ColorBuffer {
+        match self.render_target_stack.last() {
+            Some(&render_target_id) => {
+                let page = self.render_target_location(render_target_id).page;
+                &mut self.texture_page_mut(page).buffer
+            }
+            None => &mut self.dest_framebuffer,
+        }
+    }
+
+    // Like the GPU renderer, this clears only the render target's rect, since other render
+    // targets and gradients may share its page.
+    fn clear_draw_target_if_necessary(&mut self) {
+        let (clear_color, clear_rect) = match self.render_target_stack.last() {
+            Some(&render_target_id) => {
+                let render_target = &self.render_targets[render_target_id.render_target as usize];
+                if render_target.must_preserve_contents {
+                    return;
+                }
+                (ColorF::default(), render_target.location.rect)
+            }
+            None => {
+                if self.dest_must_preserve_contents {
+                    return;
+                }
+                match self.options.background_color {
+                    Some(background_color) => {
+                        (background_color, RectI::new(Vector2I::default(),
+                                                      self.dest_framebuffer.size))
+                    }
+                    None => return,
+                }
+            }
+        };
+        self.draw_target_mut().clear(clear_rect, clear_color.0);
+    }
+
+    fn preserve_draw_target(&mut self) {
+        match self.render_target_stack.last() {
+            Some(&render_target_id) => {
+                self.render_targets[render_target_id.render_target as usize]
+                    .must_preserve_contents = true;
+            }
+            None => self.dest_must_preserve_contents = true,
+        }
+    }
+
+    fn render_target_location(&self, render_target_id: RenderTargetId) -> TextureLocation {
+        self.render_targets[render_target_id.render_target as usize].location
+    }
+
+    fn texture_page(&self, id: TexturePageId) -> &TexturePage {
+        self.texture_pages[id.0 as usize].as_ref().expect("Texture page not allocated!")
+    }
+
+    fn texture_page_mut(&mut self, id: TexturePageId) -> &mut TexturePage {
+        self.texture_pages[id.0 as usize].as_mut().expect("Texture page not allocated!")
+    }
+}
+
+impl ColorBuffer {
+    fn new(size: Vector2I) -> ColorBuffer {
+        let area = size.x() as usize * size.y() as usize;
+        ColorBuffer { size, texels: vec![F32x4::default(); area] }
+    }
+
+    fn clear(&mut self, rect: RectI, color: F32x4) {
+        for y in rect.min_y()..rect.max_y() {
+            for x in rect.min_x()..rect.max_x() {
+                self.set(vec2i(x, y), color);
+            }
+        }
+    }
+
+    #[inline]
+    fn get(&self, position: Vector2I) -> F32x4 {
+        self.texels[(position.y() * self.size.x() + position.x()) as usize]
+    }
+
+    #[inline]
+    fn set(&mut self, position: Vector2I, color: F32x4) {
+        self.texels[(position.y() * self.size.x() + position.x()) as usize] = quantize(color);
+    }
+
+    fn fetch(&self, position: Vector2I, flags: TextureSamplingFlags) -> F32x4 {
+        let x = wrap(position.x(), self.size.x(), flags.contains(TextureSamplingFlags::REPEAT_U));
+        let y = wrap(position.y(), self.size.y(), flags.contains(TextureSamplingFlags::REPEAT_V));
+        self.get(vec2i(x, y))
+    }
+
+    fn sample(&self, tex_coord: Vector2F, flags: TextureSamplingFlags) -> F32x4 {
+        let position = tex_coord * self.size.to_f32();
+        if flags.contains(TextureSamplingFlags::NEAREST_MAG) {
+            return self.fetch(position.floor().to_i32(), flags);
+        }
+
+        let position = position - vec2f(0.5, 0.5);
+        let origin = position.floor();
+        let fraction = position - origin;
+        let origin = origin.to_i32();
+        let lower = lerp(self.fetch(origin, flags),
+                         self.fetch(origin + vec2i(1, 0), flags),
+                         fraction.x());
+        let upper = lerp(self.fetch(origin + vec2i(0, 1), flags),
+                         self.fetch(origin + vec2i(1, 1), flags),
+                         fraction.x());
+        lerp(lower, upper, fraction.y())
+    }
+}
+
+// Accumulates the signed area between the line and the bottom of the tile into each pixel.
+//
+// As in the fill shader, lines that go from right to left add coverage and lines that go from
+// left to right subtract it. Unlike the shader, the area is computed exactly.
+fn accumulate_fill(mask_tile: &mut MaskTile, tile_size: i32, from: Vector2F, to: Vector2F) {
+    let sign = if from.x() > to.x() {
+        1.0
+    } else if from.x() < to.x() {
+        -1.0
+    } else {
+        return;
+    };
+    let (left, right) = if from.x() < to.x() { (from, to) } else { (to, from) };
+    let slope = (right.y() - left.y()) / (right.x() - left.x());
+
+    let first_column = (left.x().floor() as i32).max(0);
+    let last_column = (right.x().ceil() as i32).min(tile_size);
+    for column in first_column..last_column {
+        let x0 = f32::max(left.x(), column as f32);
+        let x1 = f32::min(right.x(), column as f32 + 1.0);
+        if x1 <= x0 {
+            continue;
+        }
+        let y0 = left.y() + (x0 - left.x()) * slope;
+        let y1 = left.y() + (x1 - left.x()) * slope;
+
+        for row in 0..tile_size {
+            let bottom = row as f32 + 1.0;
+            let area = (x1 - x0) * clamped_linear_integral(bottom - y0, bottom - y1);
+            mask_tile[(row * tile_size + column) as usize] += sign * area;
+        }
+    }
+}
+
+fn new_mask_tile(tile_size: i32) -> MaskTile {
+    vec![0.0; (tile_size * tile_size) as usize]
+}
+
+// Computes ∫₀¹ clamp(lerp(a, b, t), 0, 1) dt.
+fn clamped_linear_integral(a: f32, b: f32) -> f32 {
+    let mut breakpoints = [0.0, 1.0, 1.0, 1.0];
+    let mut breakpoint_count = 1;
+    for &level in &[0.0, 1.0] {
+        if (a - level) * (b - level) < 0.0 {
+            breakpoints[breakpoint_count] = (level - a) / (b - a);
+            breakpoint_count += 1;
+        }
+    }
+    breakpoints[breakpoint_count] = 1.0;
+    breakpoints[0..(breakpoint_count + 1)].sort_by(|t0, t1| t0.partial_cmp(t1).unwrap());
+
+    let value_at = |t: f32| (a + (b - a) * t).clamp(0.0, 1.0);
+    let mut integral = 0.0;
+    for pair in breakpoints[0..(breakpoint_count + 1)].windows(2) {
+        integral += (value_at(pair[0]) + value_at(pair[1])) * 0.5 * (pair[1] - pair[0]);
+    }
+    integral
+}
+
+#[inline]
+fn mask_tile_index(u: u8, v: u8) -> u16 {
+    v as u16 * MASK_TILES_ACROSS as u16 + u as u16
+}
+
+// Color combining and filters
+
+fn combine_color(dest_color: F32x4, src_color: F32x4, op: PaintCompositeOp) -> F32x4 {
+    let alpha = src_color[3] * dest_color[3];
+    let mut color = match op {
+        PaintCompositeOp::SrcIn => src_color,
+        PaintCompositeOp::DestIn => dest_color,
+    };
+    color[3] = alpha;
+    color
+}
+
+fn filter_color(texture: &ColorBuffer,
+                flags: TextureSamplingFlags,
+                tex_coord: Vector2F,
+                filter: Filter)
+                -> F32x4 {
+    match filter {
+        Filter::None => texture.sample(tex_coord, flags),
+        Filter::LinearGradient { spread } => {
+            let u = spread.apply(tex_coord.x());
+            texture.sample(vec2f(u, tex_coord.y()), flags)
+        }
+        Filter::RadialGradient { line, radii, uv_origin, spread } => {
+            // See `tile.fs.glsl` for the derivation.
+            let (d_p, d_c) = (tex_coord - line.from(), line.vector());
+            let d_r = radii.y() - radii.x();
+            let a = d_c.dot(d_c) - d_r * d_r;
+            let b = d_p.dot(d_c) + radii.x() * d_r;
+            let c = d_p.dot(d_p) - radii.x() * radii.x();
+            let discrim = b * b - a * c;
+            if f32::abs(discrim) < 0.00001 {
+                return F32x4::default();
+            }
+            let t0 = (f32::sqrt(discrim) + b) / a;
+            let t1 = (-f32::sqrt(discrim) + b) / a;
+            let (t0, t1) = (f32::min(t0, t1), f32::max(t0, t1));
+            let t = if t0 >= 0.0 { t0 } else { t1 };
+            texture.sample(uv_origin + vec2f(spread.apply(t), 0.0), flags)
+        }
+        Filter::ConicGradient { center, angle, uv_origin } => {
+            let d_p = tex_coord - center;
+            let t = (f32::atan2(d_p.y(), d_p.x()) - angle) / (2.0 * PI);
+            texture.sample(uv_origin + vec2f(t - f32::floor(t), 0.0), flags)
+        }
+        Filter::PatternFilter(PatternFilter::Text {
+            fg_color,
+            bg_color,
+            defringing_kernel,
+            gamma_correction: _,
+        }) => {
+            let sample = |offset: f32| {
+                texture.sample(tex_coord + vec2f(offset, 0.0), flags)[0]
+            };
+            let alpha = match defringing_kernel {
+                None => [sample(0.0); 3],
+                Some(DefringingKernel(kernel)) => {
+                    let one_pixel = 1.0 / texture.size.x() as f32;
+                    let mut taps = [0.0; 9];
+                    for (tap_index, tap) in taps.iter_mut().enumerate() {
+                        *tap = sample((tap_index as i32 - 4) as f32 * one_pixel);
+                    }
+                    let kernel = [kernel[0], kernel[1], kernel[2], kernel[3],
+                                  kernel[2], kernel[1], kernel[0]];
+                    let mut alpha = [0.0; 3];
+                    for (channel, alpha) in alpha.iter_mut().enumerate() {
+                        *alpha = kernel.iter()
+                                       .zip(taps[channel..].iter())
+                                       .map(|(weight, tap)| weight * tap)
+                                       .sum();
+                    }
+                    alpha
+                }
+            };
+            let (bg_color, fg_color) = (bg_color.0, fg_color.0);
+            F32x4::new(bg_color[0] + (fg_color[0] - bg_color[0]) * alpha[0],
+                       bg_color[1] + (fg_color[1] - bg_color[1]) * alpha[1],
+                       bg_color[2] + (fg_color[2] - bg_color[2]) * alpha[2],
+                       1.0)
+        }
+        Filter::PatternFilter(PatternFilter::Blur { direction, sigma }) => {
+            // Sum the Gaussian directly instead of incrementally as the shader does.
+            let texel_size = vec2f(1.0, 1.0) / texture.size.to_f32();
+            let offset = match direction {
+                BlurDirection::X => vec2f(texel_size.x(), 0.0),
+                BlurDirection::Y => vec2f(0.0, texel_size.y()),
+            };
+            let support = f32::ceil(3.0 * sigma) as i32;
+            let (mut color, mut weight_sum) = (F32x4::default(), 0.0);
+            for step in -support..=support {
+                let weight = f32::exp(-0.5 * (step * step) as f32 / (sigma * sigma));
+                color += texture.sample(tex_coord + offset * step as f32, flags) *
+                    F32x4::splat(weight);
+                weight_sum += weight;
+            }
+            color * F32x4::splat(1.0 / weight_sum)
+        }
+        Filter::PatternFilter(PatternFilter::LuminanceToAlpha) => {
+            let color = texture.sample(tex_coord, flags);
+            let luminance = color[0] * 0.2125 + color[1] * 0.7154 + color[2] * 0.0721;
+            F32x4::new(0.0, 0.0, 0.0, luminance)
+        }
+        Filter::PatternFilter(PatternFilter::Unpremultiply) => {
+            unpremultiply(texture.sample(tex_coord, flags))
+        }
+        Filter::PatternFilter(PatternFilter::ColorMatrix(matrix)) => {
+            let color = unpremultiply(texture.sample(tex_coord, flags));
+            matrix.transform(ColorF(color)).0
+        }
+        Filter::PatternFilter(PatternFilter::ComponentTransfer(functions)) => {
+            let color = unpremultiply(texture.sample(tex_coord, flags));
+            F32x4::new(functions[0].apply(color[0]),
+                       functions[1].apply(color[1]),
+                       functions[2].apply(color[2]),
+                       functions[3].apply(color[3]))
+        }
+        Filter::PatternFilter(PatternFilter::Morphology { operator, direction, radius }) => {
+            let texel_size = vec2f(1.0, 1.0) / texture.size.to_f32();
+            let offset = match direction {
+                BlurDirection::X => vec2f(texel_size.x(), 0.0),
+                BlurDirection::Y => vec2f(0.0, texel_size.y()),
+            };
+            let radius = f32::round(radius) as i32;
+            let mut color = texture.sample(tex_coord, flags);
+            for step in -radius..=radius {
+                let sample = texture.sample(tex_coord + offset * step as f32, flags);
+                color = match operator {
+                    MorphologyOperator::Erode => color.min(sample),
+                    MorphologyOperator::Dilate => color.max(sample),
+                };
+            }
+            unpremultiply(color)
+        }
+    }
+}
+
+fn unpremultiply(color: F32x4) -> F32x4 {
+    if color[3] > 0.0 {
+        let mut result = color * F32x4::splat(1.0 / color[3]);
+        result[3] = color[3];
+        result
+    } else {
+        F32x4::default()
+    }
+}
+
+// Compositing
+//
+// These mirror the blend modes that `tile.fs.glsl` implements manually.
+
+fn composite(src_color: F32x4, dest_color: F32x4, blend_mode: BlendMode) -> F32x4 {
+    if !blend_mode.needs_readable_framebuffer() {
+        return src_color;
+    }
+
+    let src = [src_color[0], src_color[1], src_color[2]];
+    let dest = [dest_color[0], dest_color[1], dest_color[2]];
+    let blended = match blend_mode {
+        BlendMode::Hue | BlendMode::Saturation | BlendMode::Color | BlendMode::Luminosity => {
+            let (src_hsl, dest_hsl) = (rgb_to_hsl(src), rgb_to_hsl(dest));
+            hsl_to_rgb(match blend_mode {
+                BlendMode::Hue => [src_hsl[0], dest_hsl[1], dest_hsl[2]],
+                BlendMode::Saturation => [dest_hsl[0], src_hsl[1], dest_hsl[2]],
+                BlendMode::Color => [src_hsl[0], src_hsl[1], dest_hsl[2]],
+                _ => [dest_hsl[0], dest_hsl[1], src_hsl[2]],
+            })
+        }
+        _ => {
+            let per_channel: fn(f32, f32) -> f32 = match blend_mode {
+                BlendMode::Multiply => |dest, src| dest * src,
+                BlendMode::Screen => screen,
+                BlendMode::Overlay => |dest, src| hard_light(src, dest),
+                BlendMode::Darken => f32::min,
+                BlendMode::Lighten => f32::max,
+                BlendMode::ColorDodge => color_dodge,
+                BlendMode::ColorBurn => |dest, src| 1.0 - color_dodge(1.0 - dest, 1.0 - src),
+                BlendMode::HardLight => hard_light,
+                BlendMode::SoftLight => soft_light,
+                BlendMode::Difference => |dest, src| f32::abs(dest - src),
+                _ => |dest, src| dest + src - 2.0 * dest * src,
+            };
+            [per_channel(dest[0], src[0]), per_channel(dest[1], src[1]), per_channel(dest[2], src[2])]
+        }
+    };
+
+    let (src_alpha, dest_alpha) = (src_color[3], dest_color[3]);
+    let mut result = F32x4::splat(1.0);
+    for channel in 0..3 {
+        result[channel] = src_alpha * (1.0 - dest_alpha) * src[channel] +
+            src_alpha * dest_alpha * blended[channel] +
+            (1.0 - src_alpha) * dest[channel];
+    }
+    result
+}
+
+fn screen(dest: f32, src: f32) -> f32 {
+    dest + src - dest * src
+}
+
+fn hard_light(dest: f32, src: f32) -> f32 {
+    if src <= 0.5 { dest * 2.0 * src } else { screen(dest, 2.0 * src - 1.0) }
+}
+
+fn soft_light(dest: f32, src: f32) -> f32 {
+    let darkened_dest = if dest <= 0.25 {
+        ((16.0 * dest - 12.0) * dest + 4.0) * dest
+    } else {
+        f32::sqrt(dest)
+    };
+    let factor = if src <= 0.5 { dest * (1.0 - dest) } else { darkened_dest - dest };
+    dest + (src * 2.0 - 1.0) * factor
+}
+
+fn color_dodge(dest: f32, src: f32) -> f32 {
+    if dest == 0.0 {
+        0.0
+    } else if src == 1.0 {
+        1.0
+    } else {
+        dest / (1.0 - src)
+    }
+}
+
+fn safe_divide(num: f32, denom: f32) -> f32 {
+    if denom != 0.0 { num / denom } else { 0.0 }
+}
+
+// https://en.wikipedia.org/wiki/HSL_and_HSV#HSL_to_RGB_alternative
+fn hsl_to_rgb(hsl: [f32; 3]) -> [f32; 3] {
+    let a = hsl[1] * f32::min(hsl[2], 1.0 - hsl[2]);
+    let mut rgb = [0.0; 3];
+    for (channel, &n) in rgb.iter_mut().zip([0.0, 8.0, 4.0].iter()) {
+        let k = glsl_mod(n + hsl[0] * FRAC_6_PI, 12.0);
+        *channel = hsl[2] - f32::min(k - 3.0, 9.0 - k).clamp(-1.0, 1.0) * a;
+    }
+    rgb
+}
+
+// https://en.wikipedia.org/wiki/HSL_and_HSV#From_RGB
+fn rgb_to_hsl(rgb: [f32; 3]) -> [f32; 3] {
+    let v = f32::max(f32::max(rgb[0], rgb[1]), rgb[2]);
+    let x_min = f32::min(f32::min(rgb[0], rgb[1]), rgb[2]);
+    let (c, l) = (v - x_min, (x_min + v) * 0.5);
+    let terms = if rgb[0] == v {
+        [0.0, rgb[1], rgb[2]]
+    } else if rgb[1] == v {
+        [2.0, rgb[2], rgb[0]]
+    } else {
+        [4.0, rgb[0], rgb[1]]
+    };
+    let h = FRAC_PI_3 * safe_divide(terms[0] * c + terms[1] - terms[2], c);
+    [h, safe_divide(c, v), l]
+}
+
+// Blending
+
+fn blend_factor(factor: BlendFactor, src: F32x4, dest: F32x4) -> F32x4 {
+    match factor {
+        BlendFactor::Zero => F32x4::default(),
+        BlendFactor::One => F32x4::splat(1.0),
+        BlendFactor::SrcAlpha => F32x4::splat(src[3]),
+        BlendFactor::OneMinusSrcAlpha => F32x4::splat(1.0 - src[3]),
+        BlendFactor::DestAlpha => F32x4::splat(dest[3]),
+        BlendFactor::OneMinusDestAlpha => F32x4::splat(1.0 - dest[3]),
+        BlendFactor::DestColor => dest,
+    }
+}
+
+fn blend_op(op: BlendOp, src: F32x4, dest: F32x4) -> F32x4 {
+    match op {
+        BlendOp::Add => src + dest,
+        BlendOp::Subtract => src - dest,
+        BlendOp::ReverseSubtract => dest - src,
+        BlendOp::Min => src.min(dest),
+        BlendOp::Max => src.max(dest),
+    }
+}
+
+// Utilities
+
+fn glsl_mod(x: f32, y: f32) -> f32 {
+    x - y * f32::floor(x / y)
+}
+
+#[inline]
+fn lerp(a: F32x4, b: F32x4, t: f32) -> F32x4 {
+    a + (b - a) * F32x4::splat(t)
+}
+
+fn wrap(coord: i32, size: i32, repeat: bool) -> i32 {
+    if repeat {
+        coord.rem_euclid(size)
+    } else {
+        coord.max(0).min(size - 1)
+    }
+}
+
+// Rounds to RGBA8 precision, as storing into an `RGBA8` texture would.
+fn quantize(color: F32x4) -> F32x4 {
+    let color = color.clamp(F32x4::default(), F32x4::splat(1.0)) * F32x4::splat(255.0);
+    F32x4::new(color[0].round(), color[1].round(), color[2].round(), color[3].round()) *
+        F32x4::splat(1.0 / 255.0)
+}
+
+fn unorm8(value: f32) -> u8 {
+    f32::round(value.clamp(0.0, 1.0) * 255.0) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::gpu::options::RendererOptions;
+    use crate::options::{BuildOptions, RenderTransform};
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, MaskMode, RenderTarget, Scene};
+    use pathfinder_color::{ColorF, ColorU};
+    use pathfinder_content::effects::{BlendMode, ColorMatrix};
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_content::filter_graph::{FilterGraph, FilterInput, FilterPrimitive};
+    use pathfinder_content::gradient::{Gradient, GradientSpread};
+    use pathfinder_content::mesh::{CoonsPatch, MeshGradient};
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_content::pattern::{Image, Pattern};
+    use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{Vector2F, vec2f, vec2i};
+    use pathfinder_simd::default::F32x2;
+    use std::sync::Arc;
+
+    use super::{ReferenceRasterizer, clamped_linear_integral};
+
+    fn rasterize(scene: &mut Scene, size: i32) -> Vec<ColorU> {
+        rasterize_transformed(scene, size, Transform2F::default())
+    }
+
+    fn rasterize_transformed(scene: &mut Scene, size: i32, transform: Transform2F)
+                             -> Vec<ColorU> {
+        let rasterizer = ReferenceRasterizer::new(vec2i(size, size), RendererOptions {
+            background_color: Some(ColorF::white()),
+        });
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(size as f32, size as f32)));
+        let options = BuildOptions {
+            transform: RenderTransform::Transform2D(transform),
+            ..BuildOptions::default()
+        };
+        scene.build(options, Box::new(rasterizer.clone()), &SequentialExecutor);
+        rasterizer.pixels()
+    }
+
+    // The transforms that layers are tested under, which must apply to them exactly once.
+    fn layer_test_transforms() -> [Transform2F; 2] {
+        [Transform2F::from_translation(vec2f(20.0, 20.0)), Transform2F::from_scale(2.0)]
+    }
+
+    fn pixel_at(pixels: &[ColorU], size: i32, point: Vector2F) -> ColorU {
+        pixels[point.y() as usize * size as usize + point.x() as usize]
+    }
+
+    #[test]
+    fn test_clamped_linear_integral() {
+        assert_eq!(clamped_linear_integral(0.5, 0.5), 0.5);
+        assert_eq!(clamped_linear_integral(-1.0, 1.0), 0.25);
+        assert_eq!(clamped_linear_integral(2.0, 3.0), 1.0);
+        assert_eq!(clamped_linear_integral(-1.0, 2.0), 0.5);
+    }
+
+    #[test]
+    fn test_rasterize_rect_and_triangle() {
+        let mut scene = Scene::new();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let rect = RectF::new(vec2f(8.0, 4.0), vec2f(16.0, 8.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+
+        // A triangle whose diagonal edge cuts pixels exactly in half.
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(32.0, 32.0));
+        contour.push_endpoint(vec2f(48.0, 32.0));
+        contour.push_endpoint(vec2f(32.0, 48.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        let mut path = DrawPath::new(outline, red);
+        path.set_fill_rule(FillRule::EvenOdd);
+        scene.push_path(path);
+
+        let size = 64;
+        let pixels = rasterize(&mut scene, size);
+        let pixel = |x: i32, y: i32| pixels[(y * size + x) as usize];
+
+        let (red, white) = (ColorU::new(255, 0, 0, 255), ColorU::white());
+        assert_eq!(pixel(8, 4), red);
+        assert_eq!(pixel(23, 11), red);
+        assert_eq!(pixel(7, 4), white);
+        assert_eq!(pixel(16, 12), white);
+        assert_eq!(pixel(33, 33), red);
+        assert_eq!(pixel(40, 40), white);
+
+        // On the diagonal, half of the pixel is covered.
+        let edge = pixel(39, 40);
+        assert_eq!((edge.r, edge.a), (255, 255));
+        assert!((edge.g as i32 - 128).abs() <= 1, "{:?}", edge);
+    }
+
+    #[cfg(feature = "pf-image")]
+    #[test]
+    fn test_scene_rasterize_unpremultiplies() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 255, 128)));
+        let rect = RectF::new(vec2f(0.0, 0.0), vec2f(8.0, 16.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), paint));
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(16.0, 16.0)));
+
+        let image = scene.rasterize(&BuildOptions::default(),
+                                    vec2i(16, 16),
+                                    ColorF::transparent_black());
+        assert_eq!(image.dimensions(), (16, 16));
+        assert_eq!(image.get_pixel(4, 8).0, [0, 0, 255, 128]);
+        assert_eq!(image.get_pixel(12, 8).0, [0, 0, 0, 0]);
+    }
+
+    // A scene with a gradient and an image, both of which live in texture pages.
+    fn textured_scene() -> Scene {
+        let mut scene = Scene::new();
+        let line = LineSegment2F::new(Vector2F::zero(), vec2f(16.0, 0.0));
+        let mut gradient = Gradient::linear(line);
+        gradient.add_color_stop(ColorU::black(), 0.0);
+        gradient.add_color_stop(ColorU::white(), 1.0);
+        let gradient = scene.push_paint(&Paint::from_gradient(gradient));
+        let rect = RectF::new(Vector2F::zero(), vec2f(16.0, 8.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), gradient));
+
+        let image = Image::new(vec2i(4, 4), Arc::new(vec![ColorU::new(0, 0, 255, 255); 16]));
+        let image = scene.push_paint(&Paint::from_pattern(Pattern::from_image(image)));
+        let rect = RectF::new(vec2f(0.0, 8.0), vec2f(4.0, 4.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), image));
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(16.0, 16.0)));
+        scene
+    }
+
+    #[cfg(feature = "pf-image")]
+    #[test]
+    fn test_scene_rasterize_twice() {
+        let mut scene = textured_scene();
+        let rasterize = |scene: &mut Scene| {
+            scene.rasterize(&BuildOptions::default(), vec2i(16, 16), ColorF::white())
+        };
+        let first_image = rasterize(&mut scene);
+        assert_eq!(first_image.get_pixel(2, 10).0, [0, 0, 255, 255]);
+        assert_eq!(rasterize(&mut scene).into_raw(), first_image.into_raw());
+    }
+
+    #[test]
+    fn test_build_with_two_listeners() {
+        let mut scene = textured_scene();
+        let first_pixels = rasterize(&mut scene, 16);
+        assert_eq!(first_pixels[10 * 16 + 2], ColorU::new(0, 0, 255, 255));
+
+        // The second rasterizer has never seen the scene's texture pages.
+        scene.invalidate_textures();
+        assert_eq!(rasterize(&mut scene, 16), first_pixels);
+    }
+
+    #[test]
+    fn test_soft_masks() {
+        let render_masked_scene = |mode: MaskMode| {
+            let mut scene = Scene::new();
+            let white = scene.push_paint(&Paint::from_color(ColorU::white()));
+            let black = scene.push_paint(&Paint::from_color(ColorU::black()));
+            let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+
+            // The mask is opaque everywhere, white on the left and black on the right.
+            let mask = scene.push_render_target(RenderTarget::new(vec2i(32, 32),
+                                                                  "mask".to_owned()));
+            let left = RectF::new(vec2f(0.0, 0.0), vec2f(16.0, 32.0));
+            let right = RectF::new(vec2f(16.0, 0.0), vec2f(16.0, 32.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(left), white));
+            scene.push_path(DrawPath::new(Outline::from_rect(right), black));
+            scene.pop_render_target();
+
+            scene.push_mask(mask, mode);
+            let rect = RectF::new(vec2f(4.0, 4.0), vec2f(24.0, 24.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+            scene.pop_mask();
+            rasterize(&mut scene, 32)
+        };
+
+        let red = ColorU::new(255, 0, 0, 255);
+        let pixels = render_masked_scene(MaskMode::Alpha);
+        assert_eq!(pixels[16 * 32 + 8], red);
+        assert_eq!(pixels[16 * 32 + 24], red);
+        assert_eq!(pixels[2 * 32 + 8], ColorU::white());
+
+        let pixels = render_masked_scene(MaskMode::Luminance);
+        assert_eq!(pixels[16 * 32 + 8], red);
+        assert_eq!(pixels[16 * 32 + 24], ColorU::white());
+        assert_eq!(pixels[2 * 32 + 8], ColorU::white());
+
+        // Translucent content under an opaque mask comes through unchanged.
+        let mut scene = Scene::new();
+        let white = scene.push_paint(&Paint::from_color(ColorU::white()));
+        let translucent_red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 128)));
+        let mask = scene.push_render_target(RenderTarget::new(vec2i(32, 32), "mask".to_owned()));
+        let rect = RectF::new(Vector2F::zero(), vec2f(32.0, 32.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), white));
+        scene.pop_render_target();
+        scene.push_mask(mask, MaskMode::Alpha);
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), translucent_red));
+        scene.pop_mask();
+        let pixel = rasterize(&mut scene, 32)[16 * 32 + 16];
+        assert_eq!((pixel.r, pixel.a), (255, 255));
+        assert!((pixel.g as i32 - 127).abs() <= 1, "{:?}", pixel);
+        assert_eq!(pixel.g, pixel.b);
+
+        // Under a render transform, a square masked to its left half stays in place.
+        for &transform in &layer_test_transforms() {
+            let mut scene = Scene::new();
+            let white = scene.push_paint(&Paint::from_color(ColorU::white()));
+            let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+            let mask = scene.push_render_target(RenderTarget::new(vec2i(64, 64),
+                                                                  "mask".to_owned()));
+            let left = RectF::new(Vector2F::zero(), vec2f(16.0, 64.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(left), white));
+            scene.pop_render_target();
+            scene.push_mask(mask, MaskMode::Alpha);
+            let rect = RectF::new(vec2f(8.0, 8.0), vec2f(16.0, 16.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+            scene.pop_mask();
+
+            let pixels = rasterize_transformed(&mut scene, 64, transform);
+            let pixel = |point: Vector2F| pixel_at(&pixels, 64, transform * point);
+            assert_eq!(pixel(vec2f(9.0, 9.0)), ColorU::new(255, 0, 0, 255));
+            assert_eq!(pixel(vec2f(15.0, 23.0)), ColorU::new(255, 0, 0, 255));
+            assert_eq!(pixel(vec2f(20.0, 16.0)), ColorU::white());
+            assert_eq!(pixel(vec2f(6.0, 6.0)), ColorU::white());
+        }
+    }
+
+    #[test]
+    fn test_groups_composite_as_one_layer() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let green = scene.push_paint(&Paint::from_color(ColorU::new(0, 255, 0, 255)));
+
+        // Overlapping shapes at half opacity must not be blended with each other.
+        scene.push_group(0.5, BlendMode::SrcOver, None);
+        let left = RectF::new(vec2f(0.0, 0.0), vec2f(12.0, 16.0));
+        let right = RectF::new(vec2f(4.0, 0.0), vec2f(12.0, 16.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(left), red));
+        scene.push_path(DrawPath::new(Outline::from_rect(right), red));
+        scene.pop_group();
+
+        // Blend modes inside a group must not see what is behind the group.
+        let bottom = RectF::new(vec2f(0.0, 16.0), vec2f(32.0, 16.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(bottom), red));
+        scene.push_group(1.0, BlendMode::SrcOver, None);
+        let mut path = DrawPath::new(Outline::from_rect(bottom), green);
+        path.set_blend_mode(BlendMode::Multiply);
+        scene.push_path(path);
+        scene.pop_group();
+
+        // Translucent members must not be premultiplied twice on the way out of the group.
+        let translucent_red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 128)));
+        scene.push_group(1.0, BlendMode::SrcOver, None);
+        let rect = RectF::new(vec2f(20.0, 0.0), vec2f(8.0, 8.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), translucent_red));
+        scene.pop_group();
+
+        let pixels = rasterize(&mut scene, 32);
+        let translucent = pixels[4 * 32 + 24];
+        assert_eq!((translucent.r, translucent.a), (255, 255));
+        assert!((translucent.g as i32 - 127).abs() <= 1, "{:?}", translucent);
+        assert_eq!(translucent.g, translucent.b);
+        let pink = pixels[8 * 32 + 2];
+        assert_eq!((pink.r, pink.a), (255, 255));
+        assert!((pink.g as i32 - 128).abs() <= 1, "{:?}", pink);
+        assert_eq!(pixels[8 * 32 + 8], pink);
+        assert_eq!(pixels[8 * 32 + 24], ColorU::white());
+        assert_eq!(pixels[24 * 32 + 8], ColorU::new(0, 255, 0, 255));
+
+        // Under a render transform, a grouped square lands where an ungrouped one would.
+        for &transform in &layer_test_transforms() {
+            let mut scene = Scene::new();
+            scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(64.0, 64.0)));
+            let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+            scene.push_group(1.0, BlendMode::SrcOver, None);
+            let rect = RectF::new(vec2f(8.0, 8.0), vec2f(16.0, 16.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+            scene.pop_group();
+
+            let pixels = rasterize_transformed(&mut scene, 64, transform);
+            let pixel = |point: Vector2F| pixel_at(&pixels, 64, transform * point);
+            assert_eq!(pixel(vec2f(9.0, 9.0)), ColorU::new(255, 0, 0, 255));
+            assert_eq!(pixel(vec2f(23.0, 23.0)), ColorU::new(255, 0, 0, 255));
+            assert_eq!(pixel(vec2f(6.0, 6.0)), ColorU::white());
+            assert_eq!(pixel(vec2f(25.0, 25.0)), ColorU::white());
+        }
+    }
+
+    #[test]
+    fn test_filter_graphs() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+
+        // Cast an unblurred blue shadow, then turn everything gray.
+        let mut graph = FilterGraph::new();
+        let shadow = graph.push_primitive(FilterPrimitive::DropShadow {
+            input: FilterInput::SourceGraphic,
+            offset: vec2f(12.0, 12.0),
+            sigma: Vector2F::zero(),
+            color: ColorU::new(0, 0, 255, 255),
+        });
+        graph.push_primitive(FilterPrimitive::ColorMatrix {
+            input: FilterInput::Primitive(shadow),
+            matrix: ColorMatrix::saturate(0.0),
+        });
+        scene.push_filter(graph, None);
+        let rect = RectF::new(vec2f(4.0, 4.0), vec2f(8.0, 8.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+        scene.pop_filter();
+
+        let pixels = rasterize(&mut scene, 32);
+        let assert_gray = |x: usize, y: usize, level: u8| {
+            let actual = pixels[y * 32 + x];
+            let close = |c: u8| (c as i32 - level as i32).abs() <= 2;
+            assert!(close(actual.r) && close(actual.g) && close(actual.b) && actual.a == 255,
+                    "at ({}, {}): {:?} != {}",
+                    x,
+                    y,
+                    actual,
+                    level);
+        };
+        assert_gray(8, 8, 54);
+        assert_gray(20, 20, 18);
+        assert_gray(28, 4, 255);
+
+        // Under a render transform, the content and its offset shadow stay in place.
+        for &transform in &layer_test_transforms() {
+            let mut scene = Scene::new();
+            scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(64.0, 64.0)));
+            let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+            let mut graph = FilterGraph::new();
+            graph.push_primitive(FilterPrimitive::DropShadow {
+                input: FilterInput::SourceGraphic,
+                offset: vec2f(12.0, 12.0),
+                sigma: Vector2F::zero(),
+                color: ColorU::new(0, 0, 255, 255),
+            });
+            scene.push_filter(graph, None);
+            let rect = RectF::new(vec2f(4.0, 4.0), vec2f(8.0, 8.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+            scene.pop_filter();
+
+            let pixels = rasterize_transformed(&mut scene, 64, transform);
+            let pixel = |point: Vector2F| pixel_at(&pixels, 64, transform * point);
+            assert_eq!(pixel(vec2f(8.0, 8.0)), ColorU::new(255, 0, 0, 255));
+            assert_eq!(pixel(vec2f(20.0, 20.0)), ColorU::new(0, 0, 255, 255));
+            assert_eq!(pixel(vec2f(2.0, 2.0)), ColorU::white());
+            assert_eq!(pixel(vec2f(14.0, 8.0)), ColorU::white());
+        }
+    }
+
+    #[test]
+    fn test_gradient_spread_modes() {
+        let render_gradient = |mut gradient: Gradient, spread: GradientSpread| {
+            gradient.add_color_stop(ColorU::black(), 0.0);
+            gradient.add_color_stop(ColorU::white(), 1.0);
+            gradient.set_spread(spread);
+            let mut scene = Scene::new();
+            let paint = scene.push_paint(&Paint::from_gradient(gradient.clone()));
+            let rect = RectF::new(Vector2F::zero(), vec2f(64.0, 64.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(rect), paint));
+            (gradient, rasterize(&mut scene, 64))
+        };
+        let assert_close = |actual: ColorU, expected: ColorU| {
+            assert!((actual.r as i32 - expected.r as i32).abs() <= 3,
+                    "{:?} != {:?}",
+                    actual,
+                    expected);
+        };
+
+        // Sample past the end of a linear gradient running from x = 0 to x = 16.
+        let line = LineSegment2F::new(Vector2F::zero(), vec2f(16.0, 0.0));
+        for &spread in &[GradientSpread::Pad, GradientSpread::Repeat, GradientSpread::Reflect] {
+            let (gradient, pixels) = render_gradient(Gradient::linear(line), spread);
+            for &x in &[4, 20, 40] {
+                assert_close(pixels[8 * 64 + x], gradient.sample((x as f32 + 0.5) / 16.0));
+            }
+        }
+        let (_, pixels) = render_gradient(Gradient::linear(line), GradientSpread::Repeat);
+        assert!(pixels[8 * 64 + 17].r < 32);
+        let (_, pixels) = render_gradient(Gradient::linear(line), GradientSpread::Reflect);
+        assert!(pixels[8 * 64 + 17].r > 224);
+
+        // Radial gradients ending at a radius of 8 must reflect outward as rings.
+        let center = LineSegment2F::new(vec2f(32.0, 32.0), vec2f(32.0, 32.0));
+        let radial = Gradient::radial(center, F32x2::new(0.0, 8.0));
+        let (gradient, pixels) = render_gradient(radial, GradientSpread::Reflect);
+        for &x in &[36, 44, 52] {
+            assert_close(pixels[32 * 64 + x], gradient.sample((x as f32 + 0.5 - 32.0) / 8.0));
+        }
+    }
+
+    #[test]
+    fn test_conic_gradients() {
+        let render_conic = |angle: f32| {
+            let mut gradient = Gradient::conic(vec2f(32.5, 32.5), angle);
+            gradient.add_color_stop(ColorU::black(), 0.0);
+            gradient.add_color_stop(ColorU::white(), 1.0);
+            let mut scene = Scene::new();
+            let paint = scene.push_paint(&Paint::from_gradient(gradient.clone()));
+            let rect = RectF::new(Vector2F::zero(), vec2f(64.0, 64.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(rect), paint));
+            (gradient, rasterize(&mut scene, 64))
+        };
+
+        // Pixels on the diagonals through the center, sweeping clockwise on screen from the
+        // lower right.
+        let check_sweep = |angle: f32, offsets: [f32; 4]| {
+            let (gradient, pixels) = render_conic(angle);
+            let points = [(48, 48), (16, 48), (16, 16), (48, 16)];
+            for (&(x, y), &t) in points.iter().zip(offsets.iter()) {
+                let (actual, expected) = (pixels[y * 64 + x], gradient.sample(t));
+                assert!((actual.r as i32 - expected.r as i32).abs() <= 3,
+                        "at ({}, {}): {:?} != {:?}",
+                        x,
+                        y,
+                        actual,
+                        expected);
+            }
+        };
+        check_sweep(0.0, [0.125, 0.375, 0.625, 0.875]);
+        check_sweep(std::f32::consts::FRAC_PI_2, [0.875, 0.125, 0.375, 0.625]);
+    }
+
+    #[test]
+    fn test_mesh_gradients() {
+        // A square patch with straight edges, in mesh space, scaled up to cover the scene.
+        let corners = [vec2f(0.0, 0.0), vec2f(0.0, 32.0), vec2f(32.0, 32.0), vec2f(32.0, 0.0)];
+        let mut points = [Vector2F::zero(); 12];
+        for (index, &corner) in corners.iter().enumerate() {
+            let next = corners[(index + 1) % 4];
+            points[index * 3] = corner;
+            points[index * 3 + 1] = corner.lerp(next, 1.0 / 3.0);
+            points[index * 3 + 2] = corner.lerp(next, 2.0 / 3.0);
+        }
+        let colors = [
+            ColorU::new(255, 0, 0, 255),
+            ColorU::new(0, 255, 0, 255),
+            ColorU::new(0, 0, 255, 255),
+            ColorU::white(),
+        ];
+        let mut mesh = MeshGradient::new();
+        mesh.push_patch(CoonsPatch { points, colors });
+        mesh.apply_transform(Transform2F::from_scale(2.0));
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::from_mesh_gradient(mesh));
+        let rect = RectF::new(Vector2F::zero(), vec2f(64.0, 64.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect), paint));
+        let pixels = rasterize(&mut scene, 64);
+
+        // The corners take the colors of the patch corners, and the center blends all four.
+        let expected = [((0, 0), colors[0]),
+                        ((0, 63), colors[1]),
+                        ((63, 63), colors[2]),
+                        ((63, 0), colors[3]),
+                        ((32, 32), ColorU::new(128, 128, 128, 255))];
+        for &((x, y), expected) in &expected {
+            let actual = pixels[y * 64 + x];
+            let close = |a: u8, b: u8| (a as i32 - b as i32).abs() <= 10;
+            assert!(close(actual.r, expected.r) && close(actual.g, expected.g) &&
+                    close(actual.b, expected.b) && actual.a == 255,
+                    "at ({}, {}): {:?} != {:?}",
+                    x,
+                    y,
+                    actual,
+                    expected);
+        }
+    }
+
+    #[test]
+    fn test_render_targets_in_shared_page() {
+        let mut scene = Scene::new();
+        let size = vec2i(16, 16);
+        let colors = [ColorU::new(255, 0, 0, 255), ColorU::new(0, 0, 255, 255)];
+        for (index, &color) in colors.iter().enumerate() {
+            let paint = scene.push_paint(&Paint::from_color(color));
+            let render_target = scene.push_render_target(RenderTarget::new(size, String::new()));
+            let rect = RectF::new(vec2f(4.0, 4.0), vec2f(8.0, 8.0));
+            scene.push_path(DrawPath::new(Outline::from_rect(rect), paint));
+            scene.pop_render_target();
+
+            let offset = vec2f(16.0 * index as f32, 0.0);
+            let mut pattern = Pattern::from_render_target(render_target, size);
+            pattern.apply_transform(Transform2F::from_translation(offset));
+            let paint = scene.push_paint(&Paint::from_pattern(pattern));
+            let rect = RectF::new(offset, size.to_f32());
+            scene.push_path(DrawPath::new(Outline::from_rect(rect), paint));
+        }
+
+        // Render twice, since clearing one render target must not wipe out the other once both
+        // are resident.
+        let rasterizer = ReferenceRasterizer::new(vec2i(32, 32), RendererOptions {
+            background_color: Some(ColorF::white()),
+        });
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+        for _ in 0..2 {
+            let listener = Box::new(rasterizer.clone());
+            scene.build(BuildOptions::default(), listener, &SequentialExecutor);
+            let pixels = rasterizer.pixels();
+            assert_eq!(pixels[8 * 32 + 8], colors[0]);
+            assert_eq!(pixels[8 * 32 + 24], colors[1]);
+            assert_eq!(pixels[8 * 32 + 2], ColorU::white());
+            assert_eq!(pixels[8 * 32 + 18], ColorU::white());
+        }
+    }
+
+    #[test]
+    fn test_non_scaling_strokes() {
+        let mut scene = Scene::new();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let stroke_style = StrokeStyle {
+            line_width: 2.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Bevel,
+        };
+
+        // Two horizontal lines, of which only the first keeps its width under the zoom.
+        for &(y, non_scaling) in &[(2.0, true), (6.0, false)] {
+            let mut contour = Contour::new();
+            contour.push_endpoint(vec2f(1.0, y));
+            contour.push_endpoint(vec2f(7.0, y));
+            let mut outline = Outline::new();
+            outline.push_contour(contour);
+            let path = if non_scaling {
+                let mut path = DrawPath::new(outline, red);
+                path.set_non_scaling_stroke(Some(stroke_style));
+                path
+            } else {
+                let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
+                stroke_to_fill.offset();
+                DrawPath::new(stroke_to_fill.into_outline(), red)
+            };
+            scene.push_path(path);
+        }
+
+        let rasterizer = ReferenceRasterizer::new(vec2i(32, 32), RendererOptions {
+            background_color: Some(ColorF::white()),
+        });
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+        let options = BuildOptions {
+            transform: RenderTransform::Transform2D(Transform2F::from_scale(4.0)),
+            ..BuildOptions::default()
+        };
+        scene.build(options, Box::new(rasterizer.clone()), &SequentialExecutor);
+        let pixels = rasterizer.pixels();
+
+        let red = ColorU::new(255, 0, 0, 255);
+        assert_eq!(pixels[7 * 32 + 16], red);
+        assert_eq!(pixels[8 * 32 + 16], red);
+        assert_eq!(pixels[6 * 32 + 16], ColorU::white());
+        assert_eq!(pixels[9 * 32 + 16], ColorU::white());
+        assert_eq!(pixels[20 * 32 + 16], red);
+        assert_eq!(pixels[27 * 32 + 16], red);
+        assert_eq!(pixels[19 * 32 + 16], ColorU::white());
+    }
+}
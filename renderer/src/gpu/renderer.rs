@@ -16,15 +16,16 @@ use crate::gpu::shaders::{MAX_FILLS_PER_BATCH, ReprojectionProgram, Reprojection
 use crate::gpu::shaders::{StencilProgram, StencilVertexArray, TileProgram, TileVertexArray};
 use crate::gpu_data::{ClipBatch, ClipBatchKey, ClipBatchKind, Fill, FillBatchEntry, RenderCommand};
 use crate::gpu_data::{TextureLocation, TextureMetadataEntry, TexturePageDescriptor, TexturePageId};
-use crate::gpu_data::{Tile, TileBatchTexture};
-use crate::options::BoundingQuad;
+use crate::gpu_data::{BuildStats, Tile, TileBatchTexture};
+use crate::options::{BoundingQuad, TileSize};
 use crate::paint::PaintCompositeOp;
-use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
 use fxhash::FxHashMap;
 use half::f16;
 use pathfinder_color::{self as color, ColorF, ColorU};
 use pathfinder_content::effects::{BlendMode, BlurDirection, DefringingKernel};
-use pathfinder_content::effects::{Filter, PatternFilter};
+use pathfinder_content::effects::{ColorMatrix, Filter, MorphologyOperator, PatternFilter};
+use pathfinder_content::effects::TransferFunction;
+use pathfinder_content::gradient::GradientSpread;
 use pathfinder_content::render_target::RenderTargetId;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::RectI;
@@ -60,16 +61,19 @@ const TEXTURE_METADATA_ENTRIES_PER_ROW: i32 = 128;
 const TEXTURE_METADATA_TEXTURE_WIDTH:   i32 = TEXTURE_METADATA_ENTRIES_PER_ROW * 4;
 const TEXTURE_METADATA_TEXTURE_HEIGHT:  i32 = 65536 / TEXTURE_METADATA_ENTRIES_PER_ROW;
 
-// FIXME(pcwalton): Shrink this again!
-const MASK_FRAMEBUFFER_WIDTH:  i32 = TILE_WIDTH as i32  * MASK_TILES_ACROSS as i32;
-const MASK_FRAMEBUFFER_HEIGHT: i32 = TILE_HEIGHT as i32 * MASK_TILES_DOWN as i32;
-
 const COMBINER_CTRL_COLOR_COMBINE_SRC_IN: i32 =     0x1;
 const COMBINER_CTRL_COLOR_COMBINE_DEST_IN: i32 =    0x2;
 
 const COMBINER_CTRL_FILTER_RADIAL_GRADIENT: i32 =   0x1;
 const COMBINER_CTRL_FILTER_TEXT: i32 =              0x2;
 const COMBINER_CTRL_FILTER_BLUR: i32 =              0x3;
+const COMBINER_CTRL_FILTER_LUMINANCE_TO_ALPHA: i32 = 0x4;
+const COMBINER_CTRL_FILTER_LINEAR_GRADIENT: i32 =   0x5;
+const COMBINER_CTRL_FILTER_CONIC_GRADIENT: i32 =    0x6;
+const COMBINER_CTRL_FILTER_COLOR_MATRIX: i32 =      0x7;
+const COMBINER_CTRL_FILTER_COMPONENT_TRANSFER: i32 = 0x8;
+const COMBINER_CTRL_FILTER_MORPHOLOGY: i32 =        0x9;
+const COMBINER_CTRL_FILTER_UNPREMULTIPLY: i32 =     0xa;
 
 const COMBINER_CTRL_COMPOSITE_NORMAL: i32 =         0x0;
 const COMBINER_CTRL_COMPOSITE_MULTIPLY: i32 =       0x1;
@@ -89,8 +93,15 @@ const COMBINER_CTRL_COMPOSITE_COLOR: i32 =          0xe;
 const COMBINER_CTRL_COMPOSITE_LUMINOSITY: i32 =     0xf;
 
 const COMBINER_CTRL_COLOR_FILTER_SHIFT: i32 =       4;
-const COMBINER_CTRL_COLOR_COMBINE_SHIFT: i32 =      6;
-const COMBINER_CTRL_COMPOSITE_SHIFT: i32 =          8;
+const COMBINER_CTRL_COLOR_COMBINE_SHIFT: i32 =      8;
+const COMBINER_CTRL_COMPOSITE_SHIFT: i32 =          10;
+
+const TRANSFER_FUNCTION_IDENTITY: i32 =             0;
+const TRANSFER_FUNCTION_TABLE: i32 =                1;
+const TRANSFER_FUNCTION_DISCRETE: i32 =             2;
+const TRANSFER_FUNCTION_LINEAR: i32 =               3;
+const TRANSFER_FUNCTION_GAMMA: i32 =                4;
+const TRANSFER_FUNCTION_COUNT_SHIFT: i32 =          3;
 
 pub struct Renderer<D>
 where
@@ -118,6 +129,7 @@ where
     quads_vertex_indices_length: usize,
     fill_vertex_array: FillVertexArray<D>,
     alpha_tile_pages: FxHashMap<u16, AlphaTilePage<D>>,
+    tile_size: TileSize,
     dest_blend_framebuffer: D::Framebuffer,
     intermediate_dest_framebuffer: D::Framebuffer,
     texture_pages: Vec<Option<TexturePage<D>>>,
@@ -265,6 +277,7 @@ where
             quads_vertex_indices_length: 0,
             fill_vertex_array,
             alpha_tile_pages: FxHashMap::default(),
+            tile_size: TileSize::default(),
             dest_blend_framebuffer,
             intermediate_dest_framebuffer,
             texture_pages: vec![],
@@ -308,12 +321,21 @@ where
     pub fn render_command(&mut self, command: &RenderCommand) {
         debug!("render command: {:?}", command);
         match *command {
-            RenderCommand::Start { bounding_quad, path_count, needs_readable_framebuffer } => {
-                self.start_rendering(bounding_quad, path_count, needs_readable_framebuffer);
+            RenderCommand::Start {
+                bounding_quad,
+                path_count,
+                needs_readable_framebuffer,
+                tile_size,
+            } => {
+                self.start_rendering(bounding_quad,
+                                     path_count,
+                                     needs_readable_framebuffer,
+                                     tile_size);
             }
             RenderCommand::AllocateTexturePage { page_id, ref descriptor } => {
                 self.allocate_texture_page(page_id, descriptor)
             }
+            RenderCommand::FreeTexturePage(page_id) => self.free_texture_page(page_id),
             RenderCommand::UploadTexelData { ref texels, location } => {
                 self.upload_texel_data(texels, location)
             }
@@ -348,7 +370,10 @@ where
                                 batch.blend_mode,
                                 batch.filter)
             }
-            RenderCommand::Finish { cpu_build_time } => self.stats.cpu_build_time = cpu_build_time,
+            RenderCommand::Finish { cpu_build_time, build_stats } => {
+                self.stats.cpu_build_time = cpu_build_time;
+                self.stats.build_stats = build_stats;
+            }
         }
     }
 
@@ -376,11 +401,19 @@ where
     fn start_rendering(&mut self,
                        bounding_quad: BoundingQuad,
                        path_count: usize,
-                       mut needs_readable_framebuffer: bool) {
+                       mut needs_readable_framebuffer: bool,
+                       tile_size: TileSize) {
         if let DestFramebuffer::Other(_) = self.dest_framebuffer {
             needs_readable_framebuffer = false;
         }
 
+        // The mask framebuffers are sized to fit a whole number of tiles, so throw them away if
+        // the tile size changed.
+        if tile_size != self.tile_size {
+            self.alpha_tile_pages.clear();
+            self.tile_size = tile_size;
+        }
+
         if self.flags.contains(RendererFlags::USE_DEPTH) {
             self.draw_stencil(&bounding_quad);
         }
@@ -461,10 +494,7 @@ where
         }
 
         // Clear out any existing texture.
-        if let Some(old_texture_page) = self.texture_pages[page_index].take() {
-            let old_texture = self.device.destroy_framebuffer(old_texture_page.framebuffer);
-            self.texture_cache.release_texture(old_texture);
-        }
+        self.free_texture_page(page_id);
 
         // Allocate texture.
         let texture_size = descriptor.size;
@@ -472,20 +502,23 @@ where
                                                         TextureFormat::RGBA8,
                                                         texture_size);
         let framebuffer = self.device.create_framebuffer(texture);
-        self.texture_pages[page_index] = Some(TexturePage {
-            framebuffer,
-            must_preserve_contents: false,
-        });
+        self.texture_pages[page_index] = Some(TexturePage { framebuffer });
+    }
+
+    fn free_texture_page(&mut self, page_id: TexturePageId) {
+        if let Some(old_texture_page) = self.texture_pages[page_id.0 as usize].take() {
+            let old_texture = self.device.destroy_framebuffer(old_texture_page.framebuffer);
+            self.texture_cache.release_texture(old_texture);
+        }
     }
 
     fn upload_texel_data(&mut self, texels: &[ColorU], location: TextureLocation) {
         let texture_page = self.texture_pages[location.page.0 as usize]
-                               .as_mut()
+                               .as_ref()
                                .expect("Texture page not allocated yet!");
         let texture = self.device.framebuffer_texture(&texture_page.framebuffer);
         let texels = color::color_slice_to_u8_slice(texels);
         self.device.upload_to_texture(texture, location.rect, TextureDataRef::U8(texels));
-        texture_page.must_preserve_contents = true;
     }
 
     fn declare_render_target(&mut self,
@@ -494,6 +527,7 @@ where
         while self.render_targets.len() < render_target_id.render_target as usize + 1 {
             self.render_targets.push(RenderTargetInfo {
                 location: TextureLocation { page: TexturePageId(!0), rect: RectI::default() },
+                must_preserve_contents: false,
             });
         }
         let mut render_target = &mut self.render_targets[render_target_id.render_target as usize];
@@ -581,7 +615,7 @@ where
         for fill_batch_entry in fill_batch {
             let page = fill_batch_entry.page;
             if !self.alpha_tile_pages.contains_key(&page) {
-                self.alpha_tile_pages.insert(page, AlphaTilePage::new(&mut self.device));
+                self.alpha_tile_pages.insert(page, AlphaTilePage::new(&mut self.device, self.tile_size));
             }
             if self.alpha_tile_pages[&page].buffered_fills.len() == MAX_FILLS_PER_BATCH {
                 self.draw_buffered_fills(page);
@@ -626,10 +660,9 @@ where
             textures: &[&self.area_lut_texture],
             uniforms: &[
                 (&self.fill_program.framebuffer_size_uniform,
-                 UniformData::Vec2(F32x2::new(MASK_FRAMEBUFFER_WIDTH as f32,
-                                              MASK_FRAMEBUFFER_HEIGHT as f32))),
+                 UniformData::Vec2(mask_viewport.size().to_f32().0)),
                 (&self.fill_program.tile_size_uniform,
-                 UniformData::Vec2(F32x2::new(TILE_WIDTH as f32, TILE_HEIGHT as f32))),
+                 UniformData::Vec2(F32x2::splat(self.tile_size.get() as f32))),
                 (&self.fill_program.area_lut_uniform, UniformData::TextureUnit(0)),
             ],
             viewport: mask_viewport,
@@ -663,7 +696,7 @@ where
                                     BufferUploadMode::Dynamic);
 
         if !self.alpha_tile_pages.contains_key(&dest_page) {
-            self.alpha_tile_pages.insert(dest_page, AlphaTilePage::new(&mut self.device));
+            self.alpha_tile_pages.insert(dest_page, AlphaTilePage::new(&mut self.device, self.tile_size));
         }
 
         let mut clear_color = None;
@@ -726,21 +759,27 @@ where
         // TODO(pcwalton): Disable blend for solid tiles.
 
         let needs_readable_framebuffer = blend_mode.needs_readable_framebuffer();
-        if needs_readable_framebuffer {
-            self.copy_alpha_tiles_to_dest_blend_texture(tile_count);
-        }
-
         let clear_color = self.clear_color_for_draw_operation();
         let draw_viewport = self.draw_viewport();
 
+        // Fragment coordinates are relative to the whole framebuffer, not the viewport, so when
+        // reading back the destination we need the size of the texture it was copied into.
+        let framebuffer_size = if needs_readable_framebuffer {
+            self.copy_alpha_tiles_to_dest_blend_texture(tile_count);
+            let dest_blend_texture = self.device.framebuffer_texture(&self.dest_blend_framebuffer);
+            self.device.texture_size(dest_blend_texture)
+        } else {
+            draw_viewport.size()
+        };
+
         let mut textures = vec![&self.texture_metadata_texture];
         let mut uniforms = vec![
             (&self.tile_program.transform_uniform,
              UniformData::Mat4(self.tile_transform().to_columns())),
             (&self.tile_program.tile_size_uniform,
-             UniformData::Vec2(F32x2::new(TILE_WIDTH as f32, TILE_HEIGHT as f32))),
+             UniformData::Vec2(F32x2::splat(self.tile_size.get() as f32))),
             (&self.tile_program.framebuffer_size_uniform,
-             UniformData::Vec2(draw_viewport.size().to_f32().0)),
+             UniformData::Vec2(framebuffer_size.to_f32().0)),
             (&self.tile_program.texture_metadata_uniform, UniformData::TextureUnit(0)),
             (&self.tile_program.texture_metadata_size_uniform,
              UniformData::IVec2(I32x2::new(TEXTURE_METADATA_TEXTURE_WIDTH,
@@ -780,9 +819,24 @@ where
 
         match filter {
             Filter::None => {}
-            Filter::RadialGradient { line, radii, uv_origin } => {
+            Filter::LinearGradient { spread } => {
+                ctrl |= COMBINER_CTRL_FILTER_LINEAR_GRADIENT << COMBINER_CTRL_COLOR_FILTER_SHIFT;
+                self.set_uniforms_for_linear_gradient_filter(&mut uniforms, spread)
+            }
+            Filter::RadialGradient { line, radii, uv_origin, spread } => {
                 ctrl |= COMBINER_CTRL_FILTER_RADIAL_GRADIENT << COMBINER_CTRL_COLOR_FILTER_SHIFT;
-                self.set_uniforms_for_radial_gradient_filter(&mut uniforms, line, radii, uv_origin)
+                self.set_uniforms_for_radial_gradient_filter(&mut uniforms,
+                                                             line,
+                                                             radii,
+                                                             uv_origin,
+                                                             spread)
+            }
+            Filter::ConicGradient { center, angle, uv_origin } => {
+                ctrl |= COMBINER_CTRL_FILTER_CONIC_GRADIENT << COMBINER_CTRL_COLOR_FILTER_SHIFT;
+                self.set_uniforms_for_conic_gradient_filter(&mut uniforms,
+                                                            center,
+                                                            angle,
+                                                            uv_origin)
             }
             Filter::PatternFilter(PatternFilter::Text {
                 fg_color,
@@ -802,6 +856,29 @@ where
                 ctrl |= COMBINER_CTRL_FILTER_BLUR << COMBINER_CTRL_COLOR_FILTER_SHIFT;
                 self.set_uniforms_for_blur_filter(&mut uniforms, direction, sigma);
             }
+            Filter::PatternFilter(PatternFilter::LuminanceToAlpha) => {
+                ctrl |= COMBINER_CTRL_FILTER_LUMINANCE_TO_ALPHA <<
+                    COMBINER_CTRL_COLOR_FILTER_SHIFT;
+            }
+            Filter::PatternFilter(PatternFilter::Unpremultiply) => {
+                ctrl |= COMBINER_CTRL_FILTER_UNPREMULTIPLY << COMBINER_CTRL_COLOR_FILTER_SHIFT;
+            }
+            Filter::PatternFilter(PatternFilter::ColorMatrix(matrix)) => {
+                ctrl |= COMBINER_CTRL_FILTER_COLOR_MATRIX << COMBINER_CTRL_COLOR_FILTER_SHIFT;
+                self.set_uniforms_for_color_matrix_filter(&mut uniforms, matrix);
+            }
+            Filter::PatternFilter(PatternFilter::ComponentTransfer(functions)) => {
+                ctrl |= COMBINER_CTRL_FILTER_COMPONENT_TRANSFER <<
+                    COMBINER_CTRL_COLOR_FILTER_SHIFT;
+                self.set_uniforms_for_component_transfer_filter(&mut uniforms, functions);
+            }
+            Filter::PatternFilter(PatternFilter::Morphology { operator, direction, radius }) => {
+                ctrl |= COMBINER_CTRL_FILTER_MORPHOLOGY << COMBINER_CTRL_COLOR_FILTER_SHIFT;
+                self.set_uniforms_for_morphology_filter(&mut uniforms,
+                                                        operator,
+                                                        direction,
+                                                        radius);
+            }
         }
 
         uniforms.push((&self.tile_program.ctrl_uniform, UniformData::Int(ctrl)));
@@ -828,12 +905,25 @@ where
     fn copy_alpha_tiles_to_dest_blend_texture(&mut self, tile_count: u32) {
         let draw_viewport = self.draw_viewport();
 
+        // Render targets may lie anywhere in a texture page, which can be bigger than the window.
+        let draw_framebuffer_size = self.draw_framebuffer_size();
+        let dest_blend_texture = self.device.framebuffer_texture(&self.dest_blend_framebuffer);
+        let dest_blend_size = self.device.texture_size(dest_blend_texture);
+        if draw_framebuffer_size.x() > dest_blend_size.x() ||
+                draw_framebuffer_size.y() > dest_blend_size.y() {
+            let size = dest_blend_size.max(draw_framebuffer_size);
+            let texture = self.device.create_texture(TextureFormat::RGBA8, size);
+            let framebuffer = self.device.create_framebuffer(texture);
+            let old_framebuffer = mem::replace(&mut self.dest_blend_framebuffer, framebuffer);
+            self.device.destroy_framebuffer(old_framebuffer);
+        }
+
         let mut textures = vec![];
         let mut uniforms = vec![
             (&self.tile_copy_program.transform_uniform,
              UniformData::Mat4(self.tile_transform().to_columns())),
             (&self.tile_copy_program.tile_size_uniform,
-             UniformData::Vec2(F32x2::new(TILE_WIDTH as f32, TILE_HEIGHT as f32))),
+             UniformData::Vec2(F32x2::splat(self.tile_size.get() as f32))),
         ];
 
         let draw_framebuffer = match self.draw_render_target() {
@@ -846,7 +936,7 @@ where
                        UniformData::TextureUnit(textures.len() as u32)));
         textures.push(draw_texture);
         uniforms.push((&self.tile_copy_program.framebuffer_size_uniform,
-                       UniformData::Vec2(draw_viewport.size().to_f32().0)));
+                       UniformData::Vec2(draw_framebuffer_size.to_f32().0)));
 
         self.device.draw_elements(tile_count * 6, &RenderState {
             target: &RenderTarget::Framebuffer(&self.dest_blend_framebuffer),
@@ -974,17 +1064,45 @@ where
         self.render_target_stack.pop().expect("Render target stack underflow!");
     }
 
+    fn set_uniforms_for_linear_gradient_filter<'a>(
+            &'a self,
+            uniforms: &mut Vec<(&'a D::Uniform, UniformData)>,
+            spread: GradientSpread) {
+        uniforms.push((&self.tile_program.filter_params_0_uniform,
+                       UniformData::Vec4(F32x4::new(gradient_spread_to_f32(spread),
+                                                    0.0,
+                                                    0.0,
+                                                    0.0))));
+    }
+
     fn set_uniforms_for_radial_gradient_filter<'a>(
             &'a self,
             uniforms: &mut Vec<(&'a D::Uniform, UniformData)>,
             line: LineSegment2F,
             radii: F32x2,
-            uv_origin: Vector2F) {
+            uv_origin: Vector2F,
+            spread: GradientSpread) {
         uniforms.extend_from_slice(&[
             (&self.tile_program.filter_params_0_uniform,
              UniformData::Vec4(line.from().0.concat_xy_xy(line.vector().0))),
             (&self.tile_program.filter_params_1_uniform,
              UniformData::Vec4(radii.concat_xy_xy(uv_origin.0))),
+            (&self.tile_program.filter_params_2_uniform,
+             UniformData::Vec4(F32x4::new(gradient_spread_to_f32(spread), 0.0, 0.0, 0.0))),
+        ]);
+    }
+
+    fn set_uniforms_for_conic_gradient_filter<'a>(
+            &'a self,
+            uniforms: &mut Vec<(&'a D::Uniform, UniformData)>,
+            center: Vector2F,
+            angle: f32,
+            uv_origin: Vector2F) {
+        uniforms.extend_from_slice(&[
+            (&self.tile_program.filter_params_0_uniform,
+             UniformData::Vec4(F32x4::new(center.x(), center.y(), angle, 0.0))),
+            (&self.tile_program.filter_params_1_uniform,
+             UniformData::Vec4(uv_origin.0.concat_xy_xy(F32x2::default()))),
         ]);
     }
 
@@ -1045,6 +1163,65 @@ where
         ]);
     }
 
+    fn set_uniforms_for_color_matrix_filter<'a>(
+            &'a self,
+            uniforms: &mut Vec<(&'a D::Uniform, UniformData)>,
+            matrix: ColorMatrix) {
+        let m = &matrix.0;
+        uniforms.extend_from_slice(&[
+            (&self.tile_program.filter_params_0_uniform,
+             UniformData::Vec4(F32x4::from_slice(&m[0..4]))),
+            (&self.tile_program.filter_params_1_uniform,
+             UniformData::Vec4(F32x4::from_slice(&m[5..9]))),
+            (&self.tile_program.filter_params_2_uniform,
+             UniformData::Vec4(F32x4::from_slice(&m[10..14]))),
+            (&self.tile_program.filter_params_3_uniform,
+             UniformData::Vec4(F32x4::from_slice(&m[15..19]))),
+            (&self.tile_program.filter_params_4_uniform,
+             UniformData::Vec4(F32x4::new(m[4], m[9], m[14], m[19]))),
+        ]);
+    }
+
+    fn set_uniforms_for_component_transfer_filter<'a>(
+            &'a self,
+            uniforms: &mut Vec<(&'a D::Uniform, UniformData)>,
+            functions: [TransferFunction; 4]) {
+        let mut params = [F32x4::default(); 4];
+        let mut kinds = [0.0; 4];
+        for (channel, &function) in functions.iter().enumerate() {
+            let (kind, values) = transfer_function_to_params(function);
+            params[channel] = values;
+            kinds[channel] = kind as f32;
+        }
+        uniforms.extend_from_slice(&[
+            (&self.tile_program.filter_params_0_uniform, UniformData::Vec4(params[0])),
+            (&self.tile_program.filter_params_1_uniform, UniformData::Vec4(params[1])),
+            (&self.tile_program.filter_params_2_uniform, UniformData::Vec4(params[2])),
+            (&self.tile_program.filter_params_3_uniform, UniformData::Vec4(params[3])),
+            (&self.tile_program.filter_params_4_uniform,
+             UniformData::Vec4(F32x4::from_slice(&kinds))),
+        ]);
+    }
+
+    fn set_uniforms_for_morphology_filter<'a>(
+            &'a self,
+            uniforms: &mut Vec<(&'a D::Uniform, UniformData)>,
+            operator: MorphologyOperator,
+            direction: BlurDirection,
+            radius: f32) {
+        let src_offset = match direction {
+            BlurDirection::X => vec2f(1.0, 0.0),
+            BlurDirection::Y => vec2f(0.0, 1.0),
+        };
+        let dilate = match operator {
+            MorphologyOperator::Erode => 0.0,
+            MorphologyOperator::Dilate => 1.0,
+        };
+        uniforms.push((&self.tile_program.filter_params_0_uniform,
+                       UniformData::Vec4(src_offset.0.concat_xy_xy(F32x2::new(f32::round(radius),
+                                                                              dilate)))));
+    }
+
     fn blit_intermediate_dest_framebuffer_if_necessary(&mut self) {
         if !self.flags.contains(RendererFlags::INTERMEDIATE_DEST_FRAMEBUFFER_NEEDED) {
             return;
@@ -1086,37 +1263,41 @@ where
         })
     }
 
-    fn clear_color_for_draw_operation(&self) -> Option<ColorF> {
-        let must_preserve_contents = match self.render_target_stack.last() {
-            Some(&render_target_id) => {
-                let texture_page = self.render_target_location(render_target_id).page;
-                self.texture_pages[texture_page.0 as usize]
-                    .as_ref()
-                    .expect("Draw target texture page not allocated!")
-                    .must_preserve_contents
-            }
+    fn clear_color_for_draw_operation(&mut self) -> Option<ColorF> {
+        let render_target_id = match self.render_target_stack.last() {
+            Some(&render_target_id) => render_target_id,
             None => {
-                self.framebuffer_flags
-                    .contains(FramebufferFlags::MUST_PRESERVE_DEST_FRAMEBUFFER_CONTENTS)
+                if self.framebuffer_flags
+                       .contains(FramebufferFlags::MUST_PRESERVE_DEST_FRAMEBUFFER_CONTENTS) {
+                    return None;
+                }
+                return self.options.background_color;
             }
         };
 
-        if must_preserve_contents {
-            None
-        } else if self.render_target_stack.is_empty() {
-            self.options.background_color
-        } else {
-            Some(ColorF::default())
+        let render_target = &self.render_targets[render_target_id.render_target as usize];
+        if render_target.must_preserve_contents {
+            return None;
+        }
+
+        // Other render targets and gradients may share this page, so unless the render target
+        // covers the whole page we can only clear its own rect. There's no scissor, so do that by
+        // uploading transparent texels.
+        let location = render_target.location;
+        let texture = self.texture_page(location.page);
+        if location.rect == RectI::new(Vector2I::default(), self.device.texture_size(texture)) {
+            return Some(ColorF::default());
         }
+        let area = location.rect.width() as usize * location.rect.height() as usize;
+        let texels = vec![0; area * 4];
+        self.device.upload_to_texture(texture, location.rect, TextureDataRef::U8(&texels));
+        None
     }
 
     fn preserve_draw_framebuffer(&mut self) {
         match self.render_target_stack.last() {
             Some(&render_target_id) => {
-                let texture_page = self.render_target_location(render_target_id).page;
-                self.texture_pages[texture_page.0 as usize]
-                    .as_mut()
-                    .expect("Draw target texture page not allocated!")
+                self.render_targets[render_target_id.render_target as usize]
                     .must_preserve_contents = true;
             }
             None => {
@@ -1126,6 +1307,17 @@ where
         }
     }
 
+    // The size of the whole framebuffer being drawn to, which may be bigger than the viewport if
+    // we're drawing to a render target.
+    fn draw_framebuffer_size(&self) -> Vector2I {
+        match self.draw_render_target() {
+            RenderTarget::Framebuffer(framebuffer) => {
+                self.device.texture_size(self.device.framebuffer_texture(framebuffer))
+            }
+            RenderTarget::Default => self.main_viewport().size(),
+        }
+    }
+
     pub fn draw_viewport(&self) -> RectI {
         match self.render_target_stack.last() {
             Some(&render_target_id) => self.render_target_location(render_target_id).rect,
@@ -1146,7 +1338,7 @@ where
     }
 
     fn mask_viewport(&self) -> RectI {
-        RectI::new(Vector2I::zero(), vec2i(MASK_FRAMEBUFFER_WIDTH, MASK_FRAMEBUFFER_HEIGHT))
+        RectI::new(Vector2I::zero(), mask_framebuffer_size(self.tile_size))
     }
 
     fn render_target_location(&self, render_target_id: RenderTargetId) -> TextureLocation {
@@ -1178,6 +1370,8 @@ pub struct RenderStats {
     pub alpha_tile_count: usize,
     pub solid_tile_count: usize,
     pub cpu_build_time: Duration,
+    /// A breakdown of `cpu_build_time` by stage.
+    pub build_stats: BuildStats,
 }
 
 impl Add<RenderStats> for RenderStats {
@@ -1189,6 +1383,7 @@ impl Add<RenderStats> for RenderStats {
             alpha_tile_count: self.alpha_tile_count + other.alpha_tile_count,
             fill_count: self.fill_count + other.fill_count,
             cpu_build_time: self.cpu_build_time + other.cpu_build_time,
+            build_stats: self.build_stats + other.build_stats,
         }
     }
 }
@@ -1202,6 +1397,7 @@ impl Div<usize> for RenderStats {
             alpha_tile_count: self.alpha_tile_count / divisor,
             fill_count: self.fill_count / divisor,
             cpu_build_time: self.cpu_build_time / divisor as u32,
+            build_stats: self.build_stats / divisor,
         }
     }
 }
@@ -1279,14 +1475,15 @@ impl<D> TextureCache<D> where D: Device {
 
 struct TexturePage<D> where D: Device {
     framebuffer: D::Framebuffer,
-    must_preserve_contents: bool,
 }
 
 struct RenderTargetInfo {
     location: TextureLocation,
+    // Whether the render target has been drawn to this frame.
+    must_preserve_contents: bool,
 }
 
-trait ToBlendState {
+pub(crate) trait ToBlendState {
     fn to_blend_state(self) -> Option<BlendState>;
 }
 
@@ -1460,14 +1657,54 @@ struct AlphaTilePage<D> where D: Device {
 }
 
 impl<D> AlphaTilePage<D> where D: Device {
-    fn new(device: &mut D) -> AlphaTilePage<D> {
-        let framebuffer_size = vec2i(MASK_FRAMEBUFFER_WIDTH, MASK_FRAMEBUFFER_HEIGHT);
+    fn new(device: &mut D, tile_size: TileSize) -> AlphaTilePage<D> {
+        let framebuffer_size = mask_framebuffer_size(tile_size);
         let framebuffer_texture = device.create_texture(TextureFormat::R16F, framebuffer_size);
         let framebuffer = device.create_framebuffer(framebuffer_texture);
         AlphaTilePage { buffered_fills: vec![], framebuffer, must_preserve_framebuffer: false }
     }
 }
 
+// Matches the `GRADIENT_SPREAD_*` values in the tile shader.
+fn gradient_spread_to_f32(spread: GradientSpread) -> f32 {
+    match spread {
+        GradientSpread::Pad => 0.0,
+        GradientSpread::Repeat => 1.0,
+        GradientSpread::Reflect => 2.0,
+    }
+}
+
+// Returns the shader's code for the function, which packs the table length above the kind, and
+// its parameters.
+fn transfer_function_to_params(function: TransferFunction) -> (i32, F32x4) {
+    let (kind, values) = match function {
+        TransferFunction::Identity => return (TRANSFER_FUNCTION_IDENTITY, F32x4::default()),
+        TransferFunction::Linear { slope, intercept } => {
+            return (TRANSFER_FUNCTION_LINEAR, F32x4::new(slope, intercept, 0.0, 0.0));
+        }
+        TransferFunction::Gamma { amplitude, exponent, offset } => {
+            return (TRANSFER_FUNCTION_GAMMA, F32x4::new(amplitude, exponent, offset, 0.0));
+        }
+        TransferFunction::Table(ref table) => (TRANSFER_FUNCTION_TABLE, table.values()),
+        TransferFunction::Discrete(ref table) => {
+            (TRANSFER_FUNCTION_DISCRETE, table.values())
+        }
+    };
+
+    // The shader interpolates between two table entries, so a single value becomes a constant.
+    if values.len() == 1 {
+        return (TRANSFER_FUNCTION_LINEAR, F32x4::new(0.0, values[0], 0.0, 0.0));
+    }
+    let mut params = [0.0; 4];
+    params[0..values.len()].copy_from_slice(values);
+    (kind | ((values.len() as i32) << TRANSFER_FUNCTION_COUNT_SHIFT), F32x4::from_slice(&params))
+}
+
+// FIXME(pcwalton): Shrink this again!
+fn mask_framebuffer_size(tile_size: TileSize) -> Vector2I {
+    vec2i(MASK_TILES_ACROSS as i32, MASK_TILES_DOWN as i32) * tile_size.get() as i32
+}
+
 bitflags! {
     struct RendererFlags: u8 {
         // Whether we need a depth buffer.
@@ -389,6 +389,8 @@ pub struct TileProgram<D> where D: Device {
     pub filter_params_0_uniform: D::Uniform,
     pub filter_params_1_uniform: D::Uniform,
     pub filter_params_2_uniform: D::Uniform,
+    pub filter_params_3_uniform: D::Uniform,
+    pub filter_params_4_uniform: D::Uniform,
     pub framebuffer_size_uniform: D::Uniform,
     pub ctrl_uniform: D::Uniform,
 }
@@ -409,6 +411,8 @@ impl<D> TileProgram<D> where D: Device {
         let filter_params_0_uniform = device.get_uniform(&program, "FilterParams0");
         let filter_params_1_uniform = device.get_uniform(&program, "FilterParams1");
         let filter_params_2_uniform = device.get_uniform(&program, "FilterParams2");
+        let filter_params_3_uniform = device.get_uniform(&program, "FilterParams3");
+        let filter_params_4_uniform = device.get_uniform(&program, "FilterParams4");
         let framebuffer_size_uniform = device.get_uniform(&program, "FramebufferSize");
         let ctrl_uniform = device.get_uniform(&program, "Ctrl");
         TileProgram {
@@ -426,6 +430,8 @@ impl<D> TileProgram<D> where D: Device {
             filter_params_0_uniform,
             filter_params_1_uniform,
             filter_params_2_uniform,
+            filter_params_3_uniform,
+            filter_params_4_uniform,
             framebuffer_size_uniform,
             ctrl_uniform,
         }
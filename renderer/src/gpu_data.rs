@@ -11,7 +11,7 @@
 //! Packed data ready to be sent to the GPU.
 
 use crate::builder::{ALPHA_TILES_PER_LEVEL, ALPHA_TILE_LEVEL_COUNT};
-use crate::options::BoundingQuad;
+use crate::options::{BoundingQuad, TileSize};
 use crate::paint::PaintCompositeOp;
 use pathfinder_color::ColorU;
 use pathfinder_content::effects::{BlendMode, Filter};
@@ -22,6 +22,7 @@ use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::Vector2I;
 use pathfinder_gpu::TextureSamplingFlags;
 use std::fmt::{Debug, Formatter, Result as DebugResult};
+use std::ops::{Add, Div};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
@@ -47,17 +48,24 @@ pub enum RenderCommand {
         /// This is needed if a path that renders directly to the output framebuffer (i.e. not to a
         /// render target) uses one of the more exotic blend modes.
         needs_readable_framebuffer: bool,
+
+        /// The size of the tiles that the scene was built with.
+        tile_size: TileSize,
     },
 
     // Allocates a texture page.
     AllocateTexturePage { page_id: TexturePageId, descriptor: TexturePageDescriptor },
 
+    // Frees a texture page. Its ID may be reused by a later `AllocateTexturePage` command.
+    FreeTexturePage(TexturePageId),
+
     // Uploads data to a texture page.
     UploadTexelData { texels: Arc<Vec<ColorU>>, location: TextureLocation },
 
-    // Associates a render target with a texture page.
+    // Associates a render target with a rect of a texture page.
     //
-    // TODO(pcwalton): Add a rect to this so we can render to subrects of a page.
+    // The page may be shared with other render targets and gradients, so drawing to the render
+    // target must only clear and touch that rect.
     DeclareRenderTarget { id: RenderTargetId, location: TextureLocation },
 
     // Upload texture metadata.
@@ -86,7 +94,46 @@ pub enum RenderCommand {
     DrawTiles(TileBatch),
 
     // Presents a rendered frame.
-    Finish { cpu_build_time: Duration },
+    Finish { cpu_build_time: Duration, build_stats: BuildStats },
+}
+
+/// A breakdown of the time spent in each stage of a scene build, along with counts gathered
+/// along the way.
+///
+/// Tiling is parallelized, so the tiling times are wall-clock times, not CPU times summed across
+/// threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BuildStats {
+    /// Time spent building paint data, including texture allocation and gradient rendering.
+    pub paint_time: Duration,
+    /// Time spent tiling clip paths.
+    pub clip_path_tiling_time: Duration,
+    /// Time spent tiling draw paths.
+    pub draw_path_tiling_time: Duration,
+    /// Time spent sorting clip tiles into batches.
+    pub clip_time: Duration,
+    /// Time spent building the Z-buffers of solid tiles used for occlusion culling.
+    pub solid_tile_time: Duration,
+    /// Time spent culling and batching alpha tiles, not including `solid_tile_time`.
+    pub cull_time: Duration,
+    /// Time spent sending tile batches to the listener.
+    pub pack_time: Duration,
+    /// The number of draw paths whose tiles were reused from a previous build.
+    pub cached_path_count: usize,
+    /// The number of fills that all paths generated together.
+    pub fill_count: usize,
+    /// The most fills that any single path generated. When stats are added together, this is
+    /// the maximum over all of them.
+    pub max_path_fill_count: usize,
+    /// The number of alpha tiles that were culled because solid tiles occlude them.
+    pub occluded_tile_count: usize,
+    /// The number of texture pages newly allocated for paints and render targets.
+    ///
+    /// Texture pages stay resident across builds, so this is usually zero once the scene has
+    /// been built.
+    pub new_texture_page_count: usize,
+    /// The number of texture pages that are resident after the build.
+    pub resident_texture_page_count: usize,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, Default)]
@@ -147,6 +194,9 @@ pub struct FillBatchEntry {
     pub page: u16,
 }
 
+// Fill coordinates are 4.8 fixed point, relative to the tile origin and in units of 1/16 of the
+// tile size.
+//
 // FIXME(pcwalton): Move `subpx` before `px` and remove `repr(packed)`.
 #[derive(Clone, Copy, Debug, Default)]
 #[repr(packed)]
@@ -162,11 +212,13 @@ pub struct ClipBatch {
     pub key: ClipBatchKey,
 }
 
+// Order is significant here: within each destination page, the draw batch must come before the
+// clip batches that intersect with it.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ClipBatchKey {
     pub dest_page: u16,
-    pub src_page: u16,
     pub kind: ClipBatchKind,
+    pub src_page: u16,
 }
 
 // Order is significant here.
@@ -241,6 +293,9 @@ impl Debug for RenderCommand {
             RenderCommand::AllocateTexturePage { page_id, descriptor: _ } => {
                 write!(formatter, "AllocateTexturePage({})", page_id.0)
             }
+            RenderCommand::FreeTexturePage(page_id) => {
+                write!(formatter, "FreeTexturePage({})", page_id.0)
+            }
             RenderCommand::UploadTexelData { ref texels, location } => {
                 write!(formatter, "UploadTexelData(x{:?}, {:?})", texels.len(), location)
             }
@@ -269,9 +324,52 @@ impl Debug for RenderCommand {
                        batch.color_texture,
                        batch.blend_mode)
             }
-            RenderCommand::Finish { cpu_build_time } => {
+            RenderCommand::Finish { cpu_build_time, .. } => {
                 write!(formatter, "Finish({} ms)", cpu_build_time.as_secs_f64() * 1000.0)
             }
         }
     }
 }
+
+impl Add<BuildStats> for BuildStats {
+    type Output = BuildStats;
+    fn add(self, other: BuildStats) -> BuildStats {
+        BuildStats {
+            paint_time: self.paint_time + other.paint_time,
+            clip_path_tiling_time: self.clip_path_tiling_time + other.clip_path_tiling_time,
+            draw_path_tiling_time: self.draw_path_tiling_time + other.draw_path_tiling_time,
+            clip_time: self.clip_time + other.clip_time,
+            solid_tile_time: self.solid_tile_time + other.solid_tile_time,
+            cull_time: self.cull_time + other.cull_time,
+            pack_time: self.pack_time + other.pack_time,
+            cached_path_count: self.cached_path_count + other.cached_path_count,
+            fill_count: self.fill_count + other.fill_count,
+            max_path_fill_count: self.max_path_fill_count.max(other.max_path_fill_count),
+            occluded_tile_count: self.occluded_tile_count + other.occluded_tile_count,
+            new_texture_page_count: self.new_texture_page_count + other.new_texture_page_count,
+            resident_texture_page_count: self.resident_texture_page_count +
+                other.resident_texture_page_count,
+        }
+    }
+}
+
+impl Div<usize> for BuildStats {
+    type Output = BuildStats;
+    fn div(self, divisor: usize) -> BuildStats {
+        BuildStats {
+            paint_time: self.paint_time / divisor as u32,
+            clip_path_tiling_time: self.clip_path_tiling_time / divisor as u32,
+            draw_path_tiling_time: self.draw_path_tiling_time / divisor as u32,
+            clip_time: self.clip_time / divisor as u32,
+            solid_tile_time: self.solid_tile_time / divisor as u32,
+            cull_time: self.cull_time / divisor as u32,
+            pack_time: self.pack_time / divisor as u32,
+            cached_path_count: self.cached_path_count / divisor,
+            fill_count: self.fill_count / divisor,
+            max_path_fill_count: self.max_path_fill_count,
+            occluded_tile_count: self.occluded_tile_count / divisor,
+            new_texture_page_count: self.new_texture_page_count / divisor,
+            resident_texture_page_count: self.resident_texture_page_count / divisor,
+        }
+    }
+}
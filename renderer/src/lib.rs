@@ -15,7 +15,10 @@ extern crate bitflags;
 #[macro_use]
 extern crate log;
 
+pub mod archive;
+pub mod capture;
 pub mod concurrent;
+pub mod cpu;
 pub mod gpu;
 pub mod gpu_data;
 pub mod options;
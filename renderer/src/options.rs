@@ -37,6 +37,7 @@ pub struct BuildOptions {
     pub transform: RenderTransform,
     pub dilation: Vector2F,
     pub subpixel_aa_enabled: bool,
+    pub tile_size: TileSize,
 }
 
 impl BuildOptions {
@@ -45,10 +46,53 @@ impl BuildOptions {
             transform: self.transform.prepare(bounds),
             dilation: self.dilation,
             subpixel_aa_enabled: self.subpixel_aa_enabled,
+            tile_size: self.tile_size,
         }
     }
 }
 
+/// The size of the square tiles that paths are broken up into, in device pixels.
+///
+/// Small tiles waste less mask space and fill rate on small, detailed paths like text. Large tiles
+/// mean fewer tiles to process for big, simple paths. The default is 16.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileSize(u32);
+
+impl TileSize {
+    pub const MIN: u32 = 8;
+    pub const MAX: u32 = 32;
+
+    /// Creates a new tile size.
+    ///
+    /// Panics if `size` isn't a power of two between `TileSize::MIN` and `TileSize::MAX`
+    /// inclusive.
+    #[inline]
+    pub fn new(size: u32) -> TileSize {
+        TileSize::checked_new(size).expect("Tile size must be a power of two between 8 and 32!")
+    }
+
+    #[inline]
+    pub(crate) fn checked_new(size: u32) -> Option<TileSize> {
+        if size.is_power_of_two() && (TileSize::MIN..=TileSize::MAX).contains(&size) {
+            Some(TileSize(size))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn get(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for TileSize {
+    #[inline]
+    fn default() -> TileSize {
+        TileSize(16)
+    }
+}
+
 #[derive(Clone)]
 pub enum RenderTransform {
     Transform2D(Transform2F),
@@ -118,6 +162,7 @@ pub(crate) struct PreparedBuildOptions {
     pub(crate) transform: PreparedRenderTransform,
     pub(crate) dilation: Vector2F,
     pub(crate) subpixel_aa_enabled: bool,
+    pub(crate) tile_size: TileSize,
 }
 
 impl PreparedBuildOptions {
@@ -14,9 +14,10 @@ use crate::gpu_data::{TexturePageId, TileBatchTexture};
 use crate::scene::{RenderTarget, SceneId};
 use hashbrown::HashMap;
 use pathfinder_color::ColorU;
-use pathfinder_content::effects::{Filter, PatternFilter};
-use pathfinder_content::gradient::{Gradient, GradientGeometry};
-use pathfinder_content::pattern::{Pattern, PatternSource};
+use pathfinder_content::effects::{BlurDirection, Filter, PatternFilter};
+use pathfinder_content::gradient::{Gradient, GradientGeometry, GradientSpread};
+use pathfinder_content::mesh::MeshGradient;
+use pathfinder_content::pattern::{Image, Pattern, PatternSource};
 use pathfinder_content::render_target::RenderTargetId;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::{RectF, RectI};
@@ -26,6 +27,7 @@ use pathfinder_gpu::TextureSamplingFlags;
 use pathfinder_simd::default::{F32x2, F32x4};
 use std::f32;
 use std::fmt::{self, Debug, Formatter};
+use std::mem;
 use std::sync::Arc;
 
 // The size of a gradient tile.
@@ -33,25 +35,73 @@ use std::sync::Arc;
 // TODO(pcwalton): Choose this size dynamically!
 const GRADIENT_TILE_LENGTH: u32 = 256;
 
+// The largest size, in texels, that mesh gradients are rasterized at.
+const MAX_MESH_GRADIENT_IMAGE_SIZE: i32 = 2048;
+
+// The default amount of texture memory that images may occupy, in bytes.
+const DEFAULT_IMAGE_CACHE_BUDGET: usize = 64 * 1024 * 1024;
+
 #[derive(Clone)]
 pub struct Palette {
     pub paints: Vec<Paint>,
     render_targets: Vec<RenderTargetData>,
     cache: HashMap<Paint, PaintId>,
     allocator: TextureAllocator,
+    image_cache: ImageCache,
+    gradient_tile_pages: Vec<TexturePageId>,
+    // The image patterns that mesh gradients were rasterized into, kept as long as they're used.
+    mesh_patterns: HashMap<MeshGradient, Pattern>,
     scene_id: SceneId,
 }
 
 #[derive(Clone)]
 struct RenderTargetData {
     render_target: RenderTarget,
-    metadata: RenderTargetMetadata,
+    // Assigned lazily at build time, once we know how the render target is sampled.
+    allocation: Option<RenderTargetAllocation>,
+}
+
+// Where a render target lives in the texture pages.
+//
+// Render targets share atlas pages unless a pattern repeats them. Each one is surrounded by a
+// transparent gutter wide enough that filters sampling past its edges don't pick up neighbors.
+#[derive(Clone, Copy)]
+struct RenderTargetAllocation {
+    // The allocated rect, including the gutter.
+    location: TextureLocation,
+    mode: AllocationMode,
+    gutter: i32,
+    // False if the gutter has to be cleared again because the texture page was reallocated.
+    gutter_is_clear: bool,
+}
+
+// Images that stay resident in texture pages across builds, so that they only need to be
+// uploaded once.
+#[derive(Clone)]
+struct ImageCache {
+    images: HashMap<Image, CachedImage>,
+    // The maximum number of bytes that images not used in the current build may occupy.
+    budget: usize,
+    // Incremented every build and used to find the least recently used images.
+    build_index: u64,
+}
+
+#[derive(Clone, Copy)]
+struct CachedImage {
+    location: TextureLocation,
+    last_used_build_index: u64,
+    // False if the texels have to be uploaded again because the texture page was reallocated.
+    is_uploaded: bool,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Paint {
     base_color: ColorU,
     overlay: Option<PaintOverlay>,
+    // True if this paint and the outlines filled with it are in device space, so the render
+    // transform isn't applied to them. Layers are composited this way, since their contents were
+    // already drawn with the render transform.
+    device_space: bool,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
@@ -64,6 +114,7 @@ pub struct PaintOverlay {
 pub enum PaintContents {
     Gradient(Gradient),
     Pattern(Pattern),
+    Mesh(MeshGradient),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -84,6 +135,7 @@ impl Debug for PaintContents {
         match *self {
             PaintContents::Gradient(ref gradient) => gradient.fmt(formatter),
             PaintContents::Pattern(ref pattern) => pattern.fmt(formatter),
+            PaintContents::Mesh(ref mesh) => mesh.fmt(formatter),
         }
     }
 }
@@ -96,6 +148,9 @@ impl Palette {
             render_targets: vec![],
             cache: HashMap::new(),
             allocator: TextureAllocator::new(),
+            image_cache: ImageCache::new(),
+            gradient_tile_pages: vec![],
+            mesh_patterns: HashMap::new(),
             scene_id,
         }
     }
@@ -104,7 +159,7 @@ impl Palette {
 impl Paint {
     #[inline]
     pub fn from_color(color: ColorU) -> Paint {
-        Paint { base_color: color, overlay: None }
+        Paint { base_color: color, overlay: None, device_space: false }
     }
 
     #[inline]
@@ -115,6 +170,7 @@ impl Paint {
                 composite_op: PaintCompositeOp::SrcIn,
                 contents: PaintContents::Gradient(gradient),
             }),
+            device_space: false,
         }
     }
 
@@ -126,6 +182,19 @@ impl Paint {
                 composite_op: PaintCompositeOp::SrcIn,
                 contents: PaintContents::Pattern(pattern),
             }),
+            device_space: false,
+        }
+    }
+
+    #[inline]
+    pub fn from_mesh_gradient(mesh: MeshGradient) -> Paint {
+        Paint {
+            base_color: ColorU::white(),
+            overlay: Some(PaintOverlay {
+                composite_op: PaintCompositeOp::SrcIn,
+                contents: PaintContents::Mesh(mesh),
+            }),
+            device_space: false,
         }
     }
 
@@ -150,6 +219,8 @@ impl Paint {
                 match overlay.contents {
                     PaintContents::Gradient(ref gradient) => gradient.is_opaque(),
                     PaintContents::Pattern(ref pattern) => pattern.is_opaque(),
+                    // Points outside the patches are transparent.
+                    PaintContents::Mesh(_) => false,
                 }
             }
         }
@@ -166,6 +237,7 @@ impl Paint {
                 match overlay.contents {
                     PaintContents::Gradient(ref gradient) => gradient.is_fully_transparent(),
                     PaintContents::Pattern(_) => false,
+                    PaintContents::Mesh(ref mesh) => mesh.is_fully_transparent(),
                 }
             }
         }
@@ -185,6 +257,7 @@ impl Paint {
             match overlay.contents {
                 PaintContents::Gradient(ref mut gradient) => gradient.apply_transform(*transform),
                 PaintContents::Pattern(ref mut pattern) => pattern.apply_transform(*transform),
+                PaintContents::Mesh(ref mut mesh) => mesh.apply_transform(*transform),
             }
         }
     }
@@ -199,6 +272,16 @@ impl Paint {
         self.base_color = new_base_color;
     }
 
+    #[inline]
+    pub(crate) fn device_space(&self) -> bool {
+        self.device_space
+    }
+
+    #[inline]
+    pub(crate) fn set_device_space(&mut self, new_device_space: bool) {
+        self.device_space = new_device_space;
+    }
+
     #[inline]
     pub fn overlay(&self) -> &Option<PaintOverlay> {
         &self.overlay
@@ -247,6 +330,19 @@ impl Paint {
             }
         }
     }
+
+    #[inline]
+    pub fn mesh_gradient(&self) -> Option<&MeshGradient> {
+        match self.overlay {
+            None => None,
+            Some(ref overlay) => {
+                match overlay.contents {
+                    PaintContents::Mesh(ref mesh) => Some(mesh),
+                    _ => None,
+                }
+            }
+        }
+    }
 }
 
 impl PaintOverlay {
@@ -322,11 +418,23 @@ pub struct RenderTargetMetadata {
 #[derive(Debug)]
 pub enum PaintFilter {
     None,
+    LinearGradient {
+        /// What the gradient does past its ends.
+        spread: GradientSpread,
+    },
     RadialGradient {
         /// The line segment that connects the two circles.
         line: LineSegment2F,
         /// The radii of the two circles.
         radii: F32x2,
+        /// What the gradient does past its ends.
+        spread: GradientSpread,
+    },
+    ConicGradient {
+        /// The point that the gradient sweeps around.
+        center: Vector2F,
+        /// The angle at which the sweep starts.
+        angle: f32,
     },
     PatternFilter(PatternFilter),
 }
@@ -344,26 +452,100 @@ impl Palette {
         paint_id
     }
 
+    /// Replaces the paint with the given ID, leaving the IDs of all other paints unchanged.
+    pub fn set_paint(&mut self, paint_id: PaintId, paint: &Paint) {
+        let old_paint = mem::replace(&mut self.paints[paint_id.0 as usize], (*paint).clone());
+        if self.cache.get(&old_paint) == Some(&paint_id) {
+            self.cache.remove(&old_paint);
+        }
+        self.cache.entry((*paint).clone()).or_insert(paint_id);
+    }
+
+    /// Sets the number of bytes of texture memory that images may occupy before the least
+    /// recently used ones are evicted.
+    ///
+    /// Images used in the current build are never evicted, even if they exceed the budget.
+    #[inline]
+    pub fn set_image_cache_budget(&mut self, budget: usize) {
+        self.image_cache.budget = budget;
+    }
+
+    /// Forgets that any textures have been sent to the renderer, so that the next build allocates
+    /// every resident texture page and uploads its contents again.
+    pub(crate) fn invalidate_textures(&mut self) {
+        self.allocator.mark_all_pages_as_new();
+        for cached_image in self.image_cache.images.values_mut() {
+            cached_image.is_uploaded = false;
+        }
+        for render_target_data in &mut self.render_targets {
+            if let Some(ref mut allocation) = render_target_data.allocation {
+                allocation.gutter_is_clear = false;
+            }
+        }
+    }
+
     pub fn push_render_target(&mut self, render_target: RenderTarget) -> RenderTargetId {
         let id = self.render_targets.len() as u32;
+        self.render_targets.push(RenderTargetData { render_target, allocation: None });
+        RenderTargetId { scene: self.scene_id.0, render_target: id }
+    }
 
-        let metadata = RenderTargetMetadata {
-            location: self.allocator.allocate_image(render_target.size()),
-        };
+    #[inline]
+    pub(crate) fn get_render_target(&self, id: RenderTargetId) -> &RenderTarget {
+        &self.render_targets[id.render_target as usize].render_target
+    }
 
-        self.render_targets.push(RenderTargetData { render_target, metadata });
-        RenderTargetId { scene: self.scene_id.0, render_target: id }
+    #[inline]
+    pub(crate) fn texture_page_count(&self) -> usize {
+        self.allocator.page_ids().count()
+    }
+
+    #[inline]
+    pub(crate) fn render_targets(&self) -> impl Iterator<Item = &RenderTarget> {
+        self.render_targets.iter().map(|render_target_data| &render_target_data.render_target)
     }
 
     pub fn build_paint_info(&mut self, render_transform: Transform2F) -> PaintInfo {
         let mut paint_metadata = vec![];
 
-        // Assign paint locations.
-        let mut gradient_tile_builder = GradientTileBuilder::new();
+        // Move the patterns of device-space paints into device space.
+        let device_transform = render_transform.inverse();
+        let device_patterns: Vec<_> = self.paints.iter().map(|paint| {
+            match paint.pattern() {
+                Some(pattern) if paint.device_space => {
+                    Some(pattern_to_device_space(pattern, device_transform))
+                }
+                _ => None,
+            }
+        }).collect();
+
+        // Assign render target locations.
         let mut image_texel_info = vec![];
-        for paint in &self.paints {
+        let stale_render_target_locations =
+            self.allocate_render_targets(&device_patterns, &mut image_texel_info);
+        let render_target_metadata: Vec<_> = self.render_targets.iter().map(|render_target_data| {
+            render_target_data.metadata()
+        }).collect();
+
+        // Rasterize mesh gradients, reusing the images from the last build where possible.
+        let mut old_mesh_patterns = mem::take(&mut self.mesh_patterns);
+        for mesh in self.paints.iter().filter_map(Paint::mesh_gradient) {
+            if !self.mesh_patterns.contains_key(mesh) {
+                let pattern = old_mesh_patterns.remove(mesh).unwrap_or_else(|| {
+                    mesh.to_pattern(MAX_MESH_GRADIENT_IMAGE_SIZE)
+                });
+                self.mesh_patterns.insert(mesh.clone(), pattern);
+            }
+        }
+
+        // Assign paint locations.
+        let gradient_tile_pages = mem::take(&mut self.gradient_tile_pages);
+        let mut gradient_tile_builder = GradientTileBuilder::new(gradient_tile_pages);
+        for (paint, device_pattern) in self.paints.iter().zip(device_patterns.iter()) {
             let allocator = &mut self.allocator;
-            let render_targets = &self.render_targets;
+            let image_cache = &mut self.image_cache;
+            let mesh_patterns = &self.mesh_patterns;
+            let render_target_metadata = &render_target_metadata;
             let color_texture_metadata = paint.overlay.as_ref().map(|overlay| {
                 match overlay.contents {
                     PaintContents::Gradient(ref gradient) => {
@@ -373,10 +555,19 @@ impl Palette {
                             location,
                             page_scale: allocator.page_scale(location.page),
                             sampling_flags: TextureSamplingFlags::empty(),
-                            filter: match gradient.geometry {
-                                GradientGeometry::Linear(_) => PaintFilter::None,
-                                GradientGeometry::Radial { line, radii, .. } => {
-                                    PaintFilter::RadialGradient { line, radii }
+                            filter: match (&gradient.geometry, gradient.spread()) {
+                                // Padding falls out of clamping the texture coordinates.
+                                (&GradientGeometry::Linear(_), GradientSpread::Pad) => {
+                                    PaintFilter::None
+                                }
+                                (&GradientGeometry::Linear(_), spread) => {
+                                    PaintFilter::LinearGradient { spread }
+                                }
+                                (&GradientGeometry::Radial { line, radii, .. }, spread) => {
+                                    PaintFilter::RadialGradient { line, radii, spread }
+                                }
+                                (&GradientGeometry::Conic { center, angle, .. }, _) => {
+                                    PaintFilter::ConicGradient { center, angle }
                                 }
                             },
                             transform: Transform2F::default(),
@@ -384,49 +575,21 @@ impl Palette {
                         }
                     }
                     PaintContents::Pattern(ref pattern) => {
-                        let location;
-                        match *pattern.source() {
-                            PatternSource::RenderTarget { id: render_target_id, .. } => {
-                                let index = render_target_id.render_target as usize;
-                                location = render_targets[index].metadata.location;
-                            }
-                            PatternSource::Image(ref image) => {
-                                // TODO(pcwalton): We should be able to use tile cleverness to
-                                // repeat inside the atlas in some cases.
-                                let allocation_mode = AllocationMode::OwnPage;
-                                location = allocator.allocate(image.size(), allocation_mode);
-                                image_texel_info.push(ImageTexelInfo {
-                                    location,
-                                    texels: (*image.pixels()).clone(),
-                                });
-                            }
-                        }
-
-                        let mut sampling_flags = TextureSamplingFlags::empty();
-                        if pattern.repeat_x() {
-                            sampling_flags.insert(TextureSamplingFlags::REPEAT_U);
-                        }
-                        if pattern.repeat_y() {
-                            sampling_flags.insert(TextureSamplingFlags::REPEAT_V);
-                        }
-                        if !pattern.smoothing_enabled() {
-                            sampling_flags.insert(TextureSamplingFlags::NEAREST_MIN |
-                                                  TextureSamplingFlags::NEAREST_MAG);
-                        }
-
-                        let filter = match pattern.filter() {
-                            None => PaintFilter::None,
-                            Some(pattern_filter) => PaintFilter::PatternFilter(pattern_filter),
-                        };
-
-                        PaintColorTextureMetadata {
-                            location,
-                            page_scale: allocator.page_scale(location.page),
-                            sampling_flags,
-                            filter,
-                            transform: Transform2F::default(),
-                            composite_op: overlay.composite_op(),
-                        }
+                        pattern_color_texture_metadata(device_pattern.as_ref().unwrap_or(pattern),
+                                                       overlay.composite_op(),
+                                                       allocator,
+                                                       image_cache,
+                                                       &mut image_texel_info,
+                                                       render_target_metadata)
+                    }
+                    // Mesh gradients are drawn as the image patterns they were rasterized into.
+                    PaintContents::Mesh(ref mesh) => {
+                        pattern_color_texture_metadata(&mesh_patterns[mesh],
+                                                       overlay.composite_op(),
+                                                       allocator,
+                                                       image_cache,
+                                                       &mut image_texel_info,
+                                                       render_target_metadata)
                     }
                 }
             });
@@ -439,7 +602,10 @@ impl Palette {
         }
 
         // Calculate texture transforms.
-        for (paint, metadata) in self.paints.iter().zip(paint_metadata.iter_mut()) {
+        for ((paint, device_pattern), metadata) in self.paints
+                                                       .iter()
+                                                       .zip(device_patterns.iter())
+                                                       .zip(paint_metadata.iter_mut()) {
             let mut color_texture_metadata = match metadata.color_texture_metadata {
                 None => continue,
                 Some(ref mut color_texture_metadata) => color_texture_metadata,
@@ -466,27 +632,25 @@ impl Palette {
                 PaintContents::Gradient(Gradient {
                     geometry: GradientGeometry::Radial { ref transform, .. },
                     ..
+                }) |
+                PaintContents::Gradient(Gradient {
+                    geometry: GradientGeometry::Conic { ref transform, .. },
+                    ..
                 }) => transform.inverse(),
                 PaintContents::Pattern(ref pattern) => {
-                    match pattern.source() {
-                        PatternSource::Image(_) => {
-                            let texture_origin_uv =
-                                rect_to_uv(texture_rect, texture_scale).origin();
-                            Transform2F::from_scale(texture_scale).translate(texture_origin_uv) *
-                                pattern.transform().inverse()
-                        }
-                        PatternSource::RenderTarget { .. } => {
-                            // FIXME(pcwalton): Only do this in GL, not Metal!
-                            let texture_origin_uv =
-                                rect_to_uv(texture_rect, texture_scale).lower_left();
-                            Transform2F::from_translation(texture_origin_uv) *
-                                Transform2F::from_scale(texture_scale * vec2f(1.0, -1.0)) *
-                                pattern.transform().inverse()
-                        }
-                    }
+                    pattern_texture_transform(device_pattern.as_ref().unwrap_or(pattern),
+                                              texture_rect,
+                                              texture_scale)
+                }
+                PaintContents::Mesh(ref mesh) => {
+                    pattern_texture_transform(&self.mesh_patterns[mesh],
+                                              texture_rect,
+                                              texture_scale)
                 }
             };
-            color_texture_metadata.transform *= render_transform;
+            if !paint.device_space {
+                color_texture_metadata.transform *= render_transform;
+            }
         }
 
         // Create texture metadata.
@@ -501,25 +665,46 @@ impl Palette {
         }).collect();
         let mut render_commands = vec![RenderCommand::UploadTextureMetadata(texture_metadata)];
 
-        // Allocate textures.
-        let mut texture_page_descriptors = vec![];
-        for page_index in 0..self.allocator.page_count() {
-            let page_id = TexturePageId(page_index);
-            let page_size = self.allocator.page_size(page_id);
-            let descriptor = TexturePageDescriptor { size: page_size };
-            texture_page_descriptors.push(descriptor);
+        // Free textures that are no longer needed. Nothing allocated during this build can be
+        // freed, so none of these pages are reused until the next build. Pages that the renderer
+        // hasn't been told about since the textures were last invalidated are freed silently.
+        let unsent_pages: Vec<_> = self.allocator
+                                       .page_ids()
+                                       .filter(|&page_id| self.allocator.page_is_new(page_id))
+                                       .collect();
+        let mut freed_pages = vec![];
+        for page_id in gradient_tile_builder.take_unused_pages() {
+            let location = TextureLocation {
+                page: page_id,
+                rect: RectI::new(Vector2I::zero(), Vector2I::splat(GRADIENT_TILE_LENGTH as i32)),
+            };
+            if self.allocator.free(location) {
+                freed_pages.push(page_id);
+            }
+        }
+        for location in stale_render_target_locations {
+            if self.allocator.free(location) {
+                freed_pages.push(location.page);
+            }
+        }
+        self.image_cache.evict(&mut self.allocator, &mut freed_pages);
+        for page_id in freed_pages {
+            if unsent_pages.contains(&page_id) {
+                continue;
+            }
+            render_commands.push(RenderCommand::FreeTexturePage(page_id));
+        }
 
+        // Allocate textures.
+        let page_ids: Vec<_> = self.allocator.page_ids().collect();
+        for page_id in page_ids {
             if self.allocator.page_is_new(page_id) {
+                let descriptor = TexturePageDescriptor { size: self.allocator.page_size(page_id) };
                 render_commands.push(RenderCommand::AllocateTexturePage { page_id, descriptor });
                 self.allocator.mark_page_as_allocated(page_id);
             }
         }
 
-        // Gather up render target metadata.
-        let render_target_metadata: Vec<_> = self.render_targets.iter().map(|render_target_data| {
-            render_target_data.metadata
-        }).collect();
-
         // Create render commands.
         for (index, metadata) in render_target_metadata.iter().enumerate() {
             let id = RenderTargetId { scene: self.scene_id.0, render_target: index as u32 };
@@ -528,7 +713,8 @@ impl Palette {
                 location: metadata.location,
             });
         }
-        gradient_tile_builder.create_render_commands(&mut render_commands);
+        self.gradient_tile_pages =
+            gradient_tile_builder.create_render_commands(&mut render_commands);
         for image_texel_info in image_texel_info {
             render_commands.push(RenderCommand::UploadTexelData {
                 texels: image_texel_info.texels,
@@ -539,6 +725,67 @@ impl Palette {
         PaintInfo { render_commands, paint_metadata, render_target_metadata }
     }
 
+    // Allocates render targets that don't have a suitable location yet, queueing up uploads that
+    // clear their gutters. Returns the old locations of any render targets that moved; these must
+    // not be freed until after this build's allocations are made.
+    fn allocate_render_targets(&mut self,
+                               device_patterns: &[Option<Pattern>],
+                               image_texel_info: &mut Vec<ImageTexelInfo>)
+                               -> Vec<TextureLocation> {
+        // Find out how each render target is sampled.
+        let mut requirements = vec![(AllocationMode::Atlas, 1); self.render_targets.len()];
+        for (paint, device_pattern) in self.paints.iter().zip(device_patterns) {
+            let pattern = match paint.overlay {
+                Some(PaintOverlay { contents: PaintContents::Pattern(ref pattern), .. }) => {
+                    device_pattern.as_ref().unwrap_or(pattern)
+                }
+                _ => continue,
+            };
+            let render_target_id = match *pattern.source() {
+                PatternSource::RenderTarget { id, .. } => id,
+                PatternSource::Image(_) => continue,
+            };
+            let (ref mut mode, ref mut gutter) =
+                requirements[render_target_id.render_target as usize];
+            // TODO(pcwalton): We should be able to repeat inside the atlas in some cases.
+            if pattern.repeat_x() || pattern.repeat_y() {
+                *mode = AllocationMode::OwnPage;
+            }
+            *gutter = (*gutter).max(pattern_filter_reach(pattern.filter()) + 1);
+        }
+
+        let mut stale_locations = vec![];
+        for (render_target_data, (mode, gutter)) in self.render_targets
+                                                        .iter_mut()
+                                                        .zip(requirements) {
+            // A render target on its own page needs no gutter, since sampling clamps to the edge
+            // of the page.
+            let gutter = if mode == AllocationMode::OwnPage { 0 } else { gutter };
+            if let Some(ref mut allocation) = render_target_data.allocation {
+                if allocation.mode == mode && allocation.gutter >= gutter {
+                    if !allocation.gutter_is_clear {
+                        clear_gutter(allocation.location, allocation.gutter, image_texel_info);
+                        allocation.gutter_is_clear = true;
+                    }
+                    continue;
+                }
+                stale_locations.push(allocation.location);
+            }
+
+            let size = render_target_data.render_target.size() + vec2i(gutter, gutter) * 2;
+            let location = self.allocator.allocate(size, mode);
+            clear_gutter(location, gutter, image_texel_info);
+            render_target_data.allocation = Some(RenderTargetAllocation {
+                location,
+                mode,
+                gutter,
+                gutter_is_clear: true,
+            });
+        }
+
+        stale_locations
+    }
+
     pub(crate) fn append_palette(&mut self, palette: Palette) -> MergedPaletteInfo {
         // Merge render targets.
         let mut render_target_mapping = HashMap::new();
@@ -571,7 +818,10 @@ impl Palette {
                                     new_pattern.set_repeat_x(pattern.repeat_x());
                                     new_pattern.set_repeat_y(pattern.repeat_y());
                                     new_pattern.set_smoothing_enabled(pattern.smoothing_enabled());
-                                    self.push_paint(&Paint::from_pattern(new_pattern))
+                                    let mut new_paint = Paint::from_pattern(new_pattern);
+                                    new_paint.set_base_color(old_paint.base_color());
+                                    new_paint.set_device_space(old_paint.device_space());
+                                    self.push_paint(&new_paint)
                                 }
                                 _ => self.push_paint(old_paint),
                             }
@@ -599,11 +849,14 @@ impl PaintMetadata {
             Some(ref color_metadata) => {
                 match color_metadata.filter {
                     PaintFilter::None => Filter::None,
-                    PaintFilter::RadialGradient { line, radii } => {
-                        let uv_rect = rect_to_uv(color_metadata.location.rect,
-                                                 color_metadata.page_scale).contract(
-                            vec2f(0.0, color_metadata.page_scale.y() * 0.5));
-                        Filter::RadialGradient { line, radii, uv_origin: uv_rect.origin() }
+                    PaintFilter::LinearGradient { spread } => Filter::LinearGradient { spread },
+                    PaintFilter::RadialGradient { line, radii, spread } => {
+                        let uv_origin = color_metadata.gradient_uv_origin();
+                        Filter::RadialGradient { line, radii, uv_origin, spread }
+                    }
+                    PaintFilter::ConicGradient { center, angle } => {
+                        let uv_origin = color_metadata.gradient_uv_origin();
+                        Filter::ConicGradient { center, angle, uv_origin }
                     }
                     PaintFilter::PatternFilter(pattern_filter) => {
                         Filter::PatternFilter(pattern_filter)
@@ -618,6 +871,95 @@ impl PaintMetadata {
     }
 }
 
+fn pattern_color_texture_metadata(pattern: &Pattern,
+                                  composite_op: PaintCompositeOp,
+                                  allocator: &mut TextureAllocator,
+                                  image_cache: &mut ImageCache,
+                                  image_texel_info: &mut Vec<ImageTexelInfo>,
+                                  render_target_metadata: &[RenderTargetMetadata])
+                                  -> PaintColorTextureMetadata {
+    let location = match *pattern.source() {
+        PatternSource::RenderTarget { id: render_target_id, .. } => {
+            let index = render_target_id.render_target as usize;
+            render_target_metadata[index].location
+        }
+        PatternSource::Image(ref image) => image_cache.allocate(allocator, image, image_texel_info),
+    };
+
+    let mut sampling_flags = TextureSamplingFlags::empty();
+    if pattern.repeat_x() {
+        sampling_flags.insert(TextureSamplingFlags::REPEAT_U);
+    }
+    if pattern.repeat_y() {
+        sampling_flags.insert(TextureSamplingFlags::REPEAT_V);
+    }
+    if !pattern.smoothing_enabled() {
+        sampling_flags.insert(TextureSamplingFlags::NEAREST_MIN |
+                              TextureSamplingFlags::NEAREST_MAG);
+    }
+
+    let filter = match pattern.filter() {
+        None => PaintFilter::None,
+        Some(pattern_filter) => PaintFilter::PatternFilter(pattern_filter),
+    };
+
+    PaintColorTextureMetadata {
+        location,
+        page_scale: allocator.page_scale(location.page),
+        sampling_flags,
+        filter,
+        transform: Transform2F::default(),
+        composite_op,
+    }
+}
+
+fn pattern_texture_transform(pattern: &Pattern, texture_rect: RectI, texture_scale: Vector2F)
+                             -> Transform2F {
+    match pattern.source() {
+        PatternSource::Image(_) => {
+            let texture_origin_uv = rect_to_uv(texture_rect, texture_scale).origin();
+            Transform2F::from_scale(texture_scale).translate(texture_origin_uv) *
+                pattern.transform().inverse()
+        }
+        PatternSource::RenderTarget { .. } => {
+            // FIXME(pcwalton): Only do this in GL, not Metal!
+            let texture_origin_uv = rect_to_uv(texture_rect, texture_scale).lower_left();
+            Transform2F::from_translation(texture_origin_uv) *
+                Transform2F::from_scale(texture_scale * vec2f(1.0, -1.0)) *
+                pattern.transform().inverse()
+        }
+    }
+}
+
+// Moves the scene-space offset and filter radii of a device-space pattern into device space.
+fn pattern_to_device_space(pattern: &Pattern, device_transform: Transform2F) -> Pattern {
+    let mut device_pattern = pattern.clone();
+    let transform = device_transform * pattern.transform() * device_transform.inverse();
+    device_pattern.apply_transform(transform * pattern.transform().inverse());
+
+    let axis_scale = |direction: BlurDirection| {
+        let axis = match direction {
+            BlurDirection::X => vec2f(1.0, 0.0),
+            BlurDirection::Y => vec2f(0.0, 1.0),
+        };
+        (device_transform.matrix * axis).length()
+    };
+    device_pattern.set_filter(match pattern.filter() {
+        Some(PatternFilter::Blur { direction, sigma }) => {
+            Some(PatternFilter::Blur { direction, sigma: sigma * axis_scale(direction) })
+        }
+        Some(PatternFilter::Morphology { operator, direction, radius }) => {
+            Some(PatternFilter::Morphology {
+                operator,
+                direction,
+                radius: radius * axis_scale(direction),
+            })
+        }
+        filter => filter,
+    });
+    device_pattern
+}
+
 fn rect_to_uv(rect: RectI, texture_scale: Vector2F) -> RectF {
     rect.to_f32() * texture_scale
 }
@@ -626,6 +968,8 @@ fn rect_to_uv(rect: RectI, texture_scale: Vector2F) -> RectF {
 
 struct GradientTileBuilder {
     tiles: Vec<GradientTile>,
+    // Pages from the previous build that haven't been reused yet, in reverse order.
+    unused_pages: Vec<TexturePageId>,
 }
 
 struct GradientTile {
@@ -635,8 +979,9 @@ struct GradientTile {
 }
 
 impl GradientTileBuilder {
-    fn new() -> GradientTileBuilder {
-        GradientTileBuilder { tiles: vec![] }
+    fn new(mut old_pages: Vec<TexturePageId>) -> GradientTileBuilder {
+        old_pages.reverse();
+        GradientTileBuilder { tiles: vec![], unused_pages: old_pages }
     }
 
     fn allocate(&mut self, allocator: &mut TextureAllocator, gradient: &Gradient)
@@ -645,9 +990,13 @@ impl GradientTileBuilder {
                 self.tiles.last().unwrap().next_index == GRADIENT_TILE_LENGTH {
             let size = Vector2I::splat(GRADIENT_TILE_LENGTH as i32);
             let area = size.x() as usize * size.y() as usize;
+            let page = match self.unused_pages.pop() {
+                Some(page) => page,
+                None => allocator.allocate(size, AllocationMode::OwnPage).page,
+            };
             self.tiles.push(GradientTile {
                 texels: vec![ColorU::black(); area],
-                page: allocator.allocate(size, AllocationMode::OwnPage).page,
+                page,
                 next_index: 0,
             })
         }
@@ -673,7 +1022,14 @@ impl GradientTileBuilder {
         location
     }
 
-    fn create_render_commands(self, render_commands: &mut Vec<RenderCommand>) {
+    fn take_unused_pages(&mut self) -> Vec<TexturePageId> {
+        mem::take(&mut self.unused_pages)
+    }
+
+    // Returns the pages that the gradient tiles were allocated in.
+    fn create_render_commands(self, render_commands: &mut Vec<RenderCommand>)
+                              -> Vec<TexturePageId> {
+        let mut pages = vec![];
         for tile in self.tiles {
             render_commands.push(RenderCommand::UploadTexelData {
                 texels: Arc::new(tile.texels),
@@ -682,7 +1038,46 @@ impl GradientTileBuilder {
                     page: tile.page,
                 },
             });
+            pages.push(tile.page);
         }
+        pages
+    }
+}
+
+impl RenderTargetData {
+    fn metadata(&self) -> RenderTargetMetadata {
+        let allocation = self.allocation.expect("Render target wasn't allocated!");
+        let origin = allocation.location.rect.origin() + vec2i(allocation.gutter, allocation.gutter);
+        let rect = RectI::new(origin, self.render_target.size());
+        RenderTargetMetadata { location: TextureLocation { page: allocation.location.page, rect } }
+    }
+}
+
+// Queues up an upload that clears the given render target location, if it has a gutter.
+fn clear_gutter(location: TextureLocation,
+                gutter: i32,
+                image_texel_info: &mut Vec<ImageTexelInfo>) {
+    if gutter > 0 {
+        let area = location.rect.width() as usize * location.rect.height() as usize;
+        image_texel_info.push(ImageTexelInfo {
+            location,
+            texels: Arc::new(vec![ColorU::transparent_black(); area]),
+        });
+    }
+}
+
+// How many texels past the edge of a pattern the given filter may sample.
+fn pattern_filter_reach(filter: Option<PatternFilter>) -> i32 {
+    match filter {
+        None |
+        Some(PatternFilter::LuminanceToAlpha) |
+        Some(PatternFilter::Unpremultiply) |
+        Some(PatternFilter::ColorMatrix(_)) |
+        Some(PatternFilter::ComponentTransfer(_)) => 0,
+        Some(PatternFilter::Text { .. }) => 4,
+        // This must match the support computed in `Renderer::set_uniforms_for_blur_filter()`.
+        Some(PatternFilter::Blur { sigma, .. }) => (f32::ceil(1.5 * sigma) * 2.0) as i32,
+        Some(PatternFilter::Morphology { radius, .. }) => f32::round(radius) as i32,
     }
 }
 
@@ -691,6 +1086,76 @@ struct ImageTexelInfo {
     texels: Arc<Vec<ColorU>>,
 }
 
+impl ImageCache {
+    fn new() -> ImageCache {
+        ImageCache { images: HashMap::new(), budget: DEFAULT_IMAGE_CACHE_BUDGET, build_index: 0 }
+    }
+
+    // Returns the location of the given image, uploading it if it isn't already resident.
+    fn allocate(&mut self,
+                allocator: &mut TextureAllocator,
+                image: &Image,
+                image_texel_info: &mut Vec<ImageTexelInfo>)
+                -> TextureLocation {
+        if let Some(cached_image) = self.images.get_mut(image) {
+            cached_image.last_used_build_index = self.build_index;
+            if !cached_image.is_uploaded {
+                image_texel_info.push(ImageTexelInfo {
+                    location: cached_image.location,
+                    texels: (*image.pixels()).clone(),
+                });
+                cached_image.is_uploaded = true;
+            }
+            return cached_image.location;
+        }
+
+        // TODO(pcwalton): We should be able to use tile cleverness to repeat inside the atlas in
+        // some cases.
+        let location = allocator.allocate(image.size(), AllocationMode::OwnPage);
+        image_texel_info.push(ImageTexelInfo { location, texels: (*image.pixels()).clone() });
+        self.images.insert((*image).clone(), CachedImage {
+            location,
+            last_used_build_index: self.build_index,
+            is_uploaded: true,
+        });
+        location
+    }
+
+    // Evicts least recently used images until the images not used in this build fit within the
+    // budget, then starts a new build.
+    fn evict(&mut self, allocator: &mut TextureAllocator, freed_pages: &mut Vec<TexturePageId>) {
+        let build_index = self.build_index;
+        let mut size = self.images
+                           .iter()
+                           .filter(|(_, cached)| cached.last_used_build_index < build_index)
+                           .map(|(image, _)| image_byte_size(image))
+                           .sum::<usize>();
+        while size > self.budget {
+            let lru_image = self.images
+                                .iter()
+                                .filter(|(_, cached)| cached.last_used_build_index < build_index)
+                                .min_by_key(|(_, cached)| cached.last_used_build_index)
+                                .map(|(image, _)| (*image).clone());
+            let lru_image = match lru_image {
+                None => break,
+                Some(lru_image) => lru_image,
+            };
+
+            let cached_image = self.images.remove(&lru_image).unwrap();
+            if allocator.free(cached_image.location) {
+                freed_pages.push(cached_image.location.page);
+            }
+            size -= image_byte_size(&lru_image);
+        }
+
+        self.build_index += 1;
+    }
+}
+
+fn image_byte_size(image: &Image) -> usize {
+    image.size().x() as usize * image.size().y() as usize * 4
+}
+
 impl PaintColorTextureMetadata {
     pub(crate) fn as_tile_batch_texture(&self) -> TileBatchTexture {
         TileBatchTexture {
@@ -699,4 +1164,135 @@ impl PaintColorTextureMetadata {
             composite_op: self.composite_op,
         }
     }
+
+    // The texture coordinates of the start of the gradient's row, in its vertical center.
+    fn gradient_uv_origin(&self) -> Vector2F {
+        let uv_rect = rect_to_uv(self.location.rect, self.page_scale);
+        uv_rect.contract(vec2f(0.0, self.page_scale.y() * 0.5)).origin()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gpu_data::{RenderCommand, TexturePageId};
+    use crate::scene::{RenderTarget, SceneId};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::gradient::Gradient;
+    use pathfinder_content::pattern::{Image, Pattern};
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{vec2f, vec2i};
+    use std::sync::Arc;
+
+    use super::{Paint, Palette};
+
+    fn image_paint(color: ColorU) -> Paint {
+        let image = Image::new(vec2i(4, 4), Arc::new(vec![color; 16]));
+        Paint::from_pattern(Pattern::from_image(image))
+    }
+
+    // Returns the pages that were allocated, freed, and uploaded to, in that order.
+    fn build(palette: &mut Palette)
+             -> (Vec<TexturePageId>, Vec<TexturePageId>, Vec<TexturePageId>) {
+        let (mut allocated, mut freed, mut uploaded) = (vec![], vec![], vec![]);
+        for command in palette.build_paint_info(Transform2F::default()).render_commands {
+            match command {
+                RenderCommand::AllocateTexturePage { page_id, .. } => allocated.push(page_id),
+                RenderCommand::FreeTexturePage(page_id) => freed.push(page_id),
+                RenderCommand::UploadTexelData { location, .. } => uploaded.push(location.page),
+                _ => {}
+            }
+        }
+        (allocated, freed, uploaded)
+    }
+
+    #[test]
+    fn test_textures_are_reused_across_builds() {
+        let mut palette = Palette::new(SceneId(0));
+        let mut gradient = Gradient::linear_from_points(vec2f(0.0, 0.0), vec2f(1.0, 0.0));
+        gradient.add_color_stop(ColorU::black(), 0.0);
+        gradient.add_color_stop(ColorU::white(), 1.0);
+        palette.push_paint(&Paint::from_gradient(gradient));
+        let red_id = palette.push_paint(&image_paint(ColorU::new(255, 0, 0, 255)));
+
+        let (allocated, freed, uploaded) = build(&mut palette);
+        assert_eq!(allocated.len(), 2);
+        assert!(freed.is_empty());
+        assert_eq!(uploaded.len(), 2);
+
+        // Only the gradient tile is uploaded again.
+        let (gradient_page, red_page) = (uploaded[0], uploaded[1]);
+        assert_eq!(build(&mut palette), (vec![], vec![], vec![gradient_page]));
+
+        // With no budget, the old image is evicted as soon as it's no longer used.
+        palette.set_image_cache_budget(0);
+        palette.set_paint(red_id, &image_paint(ColorU::new(0, 0, 255, 255)));
+        let (allocated, freed, uploaded) = build(&mut palette);
+        assert_eq!(freed, vec![red_page]);
+        assert_eq!(allocated.len(), 1);
+        assert_eq!(uploaded.len(), 2);
+        assert_ne!(allocated[0], red_page);
+
+        // The freed page is reused by the next new image.
+        palette.set_paint(red_id, &image_paint(ColorU::new(0, 255, 0, 255)));
+        let (allocated, _, _) = build(&mut palette);
+        assert_eq!(allocated, vec![red_page]);
+    }
+
+    #[test]
+    fn test_in_use_images_do_not_count_against_budget() {
+        let mut palette = Palette::new(SceneId(0));
+        let big_image = Image::new(vec2i(16, 16), Arc::new(vec![ColorU::white(); 256]));
+        palette.push_paint(&Paint::from_pattern(Pattern::from_image(big_image)));
+        let small_id = palette.push_paint(&image_paint(ColorU::new(255, 0, 0, 255)));
+        build(&mut palette);
+
+        // The images in use exceed the budget, but the one idle image fits within it.
+        palette.set_image_cache_budget(64);
+        palette.set_paint(small_id, &image_paint(ColorU::new(0, 0, 255, 255)));
+        let (_, freed, _) = build(&mut palette);
+        assert!(freed.is_empty());
+        let (_, freed, _) = build(&mut palette);
+        assert!(freed.is_empty());
+
+        // A second idle image pushes the idle images over budget, evicting the older one.
+        palette.set_paint(small_id, &image_paint(ColorU::new(0, 255, 0, 255)));
+        let (_, freed, _) = build(&mut palette);
+        assert_eq!(freed.len(), 1);
+    }
+
+    #[test]
+    fn test_render_targets_share_pages() {
+        let mut palette = Palette::new(SceneId(0));
+        let size = vec2i(16, 16);
+        let ids: Vec<_> = (0..3).map(|index| {
+            palette.push_render_target(RenderTarget::new(size, format!("{}", index)))
+        }).collect();
+        for (index, &id) in ids.iter().enumerate() {
+            let mut pattern = Pattern::from_render_target(id, size);
+            pattern.set_repeat_x(index == 2);
+            palette.push_paint(&Paint::from_pattern(pattern));
+        }
+
+        let paint_info = palette.build_paint_info(Transform2F::default());
+        let locations: Vec<_> = paint_info.render_target_metadata
+                                          .iter()
+                                          .map(|metadata| metadata.location)
+                                          .collect();
+        assert_eq!(locations[0].page, locations[1].page);
+        assert!(locations[0].rect.intersection(locations[1].rect).is_none());
+        assert!(locations.iter().all(|location| location.rect.size() == size));
+
+        // Repeating patterns can't sample a subrect of a page.
+        assert_ne!(locations[2].page, locations[0].page);
+        assert_eq!(locations[2].rect.origin(), vec2i(0, 0));
+
+        // Locations are stable across builds.
+        let (allocated, freed, _) = build(&mut palette);
+        assert!(allocated.is_empty() && freed.is_empty());
+        let paint_info = palette.build_paint_info(Transform2F::default());
+        assert!(paint_info.render_target_metadata
+                          .iter()
+                          .zip(locations.iter())
+                          .all(|(metadata, location)| metadata.location == *location));
+    }
 }
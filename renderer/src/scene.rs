@@ -10,18 +10,38 @@
 
 //! A set of paths to be rendered.
 
-use crate::builder::SceneBuilder;
+use crate::builder::{BuildCache, SceneBuilder};
 use crate::concurrent::executor::Executor;
+#[cfg(feature = "pf-image")]
+use crate::concurrent::executor::SequentialExecutor;
+#[cfg(feature = "pf-image")]
+use crate::cpu::rasterizer::ReferenceRasterizer;
+#[cfg(feature = "pf-image")]
+use crate::gpu::options::RendererOptions;
 use crate::options::{BuildOptions, PreparedBuildOptions};
-use crate::options::{PreparedRenderTransform, RenderCommandListener};
+use crate::options::{PreparedRenderTransform, RenderCommandListener, RenderTransform};
 use crate::paint::{MergedPaletteInfo, Paint, PaintId, PaintInfo, Palette};
-use pathfinder_content::effects::BlendMode;
+#[cfg(feature = "pf-image")]
+use image::RgbaImage;
+#[cfg(feature = "pf-image")]
+use pathfinder_color::ColorF;
+use pathfinder_color::ColorU;
+use pathfinder_content::effects::{BlendMode, BlurDirection, ColorMatrix, PatternFilter};
 use pathfinder_content::fill::FillRule;
+use pathfinder_content::filter_graph::{FilterGraph, FilterInput, FilterPrimitive};
 use pathfinder_content::outline::Outline;
+use pathfinder_content::pattern::Pattern;
 use pathfinder_content::render_target::RenderTargetId;
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
 use pathfinder_geometry::rect::RectF;
 use pathfinder_geometry::transform2d::Transform2F;
-use pathfinder_geometry::vector::{Vector2I, vec2f};
+use pathfinder_geometry::transform3d::Perspective;
+use pathfinder_geometry::util;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, Vector4F, vec2f};
+use std::borrow::Cow;
+use std::f32::consts::SQRT_2;
+use std::iter;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 static NEXT_SCENE_ID: AtomicUsize = AtomicUsize::new(0);
@@ -31,10 +51,14 @@ pub struct Scene {
     pub(crate) display_list: Vec<DisplayItem>,
     pub(crate) paths: Vec<DrawPath>,
     pub(crate) clip_paths: Vec<ClipPath>,
-    palette: Palette,
+    pub(crate) nodes: Vec<SceneNode>,
+    pub(crate) palette: Palette,
     bounds: RectF,
     view_box: RectF,
-    id: SceneId,
+    pub(crate) id: SceneId,
+    pub(crate) build_cache: BuildCache,
+    // Masks, groups, and filters share one stack, since they share the render target stack.
+    layer_stack: Vec<PendingLayer>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -48,21 +72,41 @@ impl Scene {
             display_list: vec![],
             paths: vec![],
             clip_paths: vec![],
+            nodes: vec![],
             palette: Palette::new(scene_id),
             bounds: RectF::default(),
             view_box: RectF::default(),
             id: scene_id,
+            build_cache: BuildCache::new(),
+            layer_stack: vec![],
         }
     }
 
-    pub fn push_path(&mut self, path: DrawPath) {
+    pub fn push_path(&mut self, path: DrawPath) -> DrawPathId {
         let path_index = self.paths.len() as u32;
         self.paths.push(path);
         self.push_path_with_index(path_index);
+        DrawPathId(path_index)
+    }
+
+    #[inline]
+    pub fn get_path(&self, path_id: DrawPathId) -> &DrawPath {
+        &self.paths[path_id.0 as usize]
+    }
+
+    /// Replaces the path with the given ID.
+    ///
+    /// As long as the build options stay the same, only the paths that were replaced, and those
+    /// whose paints changed, are retiled on the next build.
+    pub fn set_path(&mut self, path_id: DrawPathId, path: DrawPath) {
+        self.bounds = self.bounds.union_rect(self.draw_path_bounds(&path));
+        self.paths[path_id.0 as usize] = path;
+        self.build_cache.invalidate_path(path_id);
     }
 
     fn push_path_with_index(&mut self, path_index: u32) {
-        self.bounds = self.bounds.union_rect(self.paths[path_index as usize].outline.bounds());
+        let path = &self.paths[path_index as usize];
+        self.bounds = self.bounds.union_rect(self.draw_path_bounds(path));
 
         if let Some(DisplayItem::DrawPaths {
             start_index: _,
@@ -77,13 +121,117 @@ impl Scene {
         }
     }
 
+    /// Adds a clip path to the scene.
+    ///
+    /// If the clip path is itself clipped, its parent must already have been pushed.
     pub fn push_clip_path(&mut self, clip_path: ClipPath) -> ClipPathId {
-        self.bounds = self.bounds.union_rect(clip_path.outline.bounds());
+        if let Some(parent_clip_path_id) = clip_path.clip_path {
+            assert!((parent_clip_path_id.0 as usize) < self.clip_paths.len(),
+                    "Parent clip path must be pushed before its children!");
+        }
+        self.bounds = self.bounds.union_rect(self.node_transform(clip_path.node) *
+                                             clip_path.outline.bounds());
         let clip_path_id = ClipPathId(self.clip_paths.len() as u32);
         self.clip_paths.push(clip_path);
         clip_path_id
     }
 
+    /// Adds a node to the scene graph.
+    ///
+    /// Paths and clip paths attached to the node with `set_node()` are drawn with its transform,
+    /// preceded by the transforms of its ancestors, which must already have been pushed.
+    pub fn push_node(&mut self, node: SceneNode) -> SceneNodeId {
+        if let Some(parent_id) = node.parent {
+            assert!((parent_id.0 as usize) < self.nodes.len(),
+                    "Parent nodes must be pushed before their children!");
+        }
+        let node_id = SceneNodeId(self.nodes.len() as u32);
+        self.nodes.push(node);
+        node_id
+    }
+
+    #[inline]
+    pub fn get_node(&self, node_id: SceneNodeId) -> &SceneNode {
+        &self.nodes[node_id.0 as usize]
+    }
+
+    /// Changes the transform of a node, moving everything beneath it.
+    ///
+    /// The outlines themselves are untouched, so only the affected draw paths are retiled on the
+    /// next build.
+    pub fn set_node_transform(&mut self, node_id: SceneNodeId, new_transform: Transform2F) {
+        self.nodes[node_id.0 as usize].transform = new_transform;
+        for path_index in 0..self.paths.len() {
+            let path = &self.paths[path_index];
+            if self.node_chain(path.node).any(|ancestor_id| ancestor_id == node_id) {
+                self.bounds = self.bounds.union_rect(self.draw_path_bounds(path));
+                self.build_cache.invalidate_path(DrawPathId(path_index as u32));
+            }
+        }
+        for clip_path in &self.clip_paths {
+            if self.node_chain(clip_path.node).any(|ancestor_id| ancestor_id == node_id) {
+                self.bounds = self.bounds.union_rect(self.node_transform(clip_path.node) *
+                                                     clip_path.outline.bounds());
+            }
+        }
+    }
+
+    // Returns the given node followed by all of its ancestors.
+    fn node_chain(&self, node_id: Option<SceneNodeId>)
+                  -> impl Iterator<Item = SceneNodeId> + '_ {
+        iter::successors(node_id, move |node_id| self.nodes[node_id.0 as usize].parent)
+    }
+
+    /// Returns the transform from the space of the given node to the space of the scene.
+    pub fn node_transform(&self, node_id: Option<SceneNodeId>) -> Transform2F {
+        self.node_chain(node_id).fold(Transform2F::default(), |transform, node_id| {
+            self.nodes[node_id.0 as usize].transform * transform
+        })
+    }
+
+    // Returns the transform from the space of a draw path's outline to the space of the scene.
+    fn draw_path_transform(&self, path: &DrawPath) -> Transform2F {
+        self.node_transform(path.node) * path.transform
+    }
+
+    // Returns the bounds of a draw path in the space of the scene.
+    //
+    // Non-scaling strokes are widened by how far they reach past their centerline. Their width
+    // is in device pixels, which are the same as scene units unless a 2D render transform
+    // scales the scene, so it isn't scaled by the path's transform.
+    fn draw_path_bounds(&self, path: &DrawPath) -> RectF {
+        let bounds = self.draw_path_transform(path) * path.outline.bounds();
+        match path.non_scaling_stroke {
+            None => bounds,
+            Some(stroke_style) => bounds.dilate(stroke_reach(stroke_style)),
+        }
+    }
+
+    // Computes the transform of every node at once. Parents precede their children, so each
+    // node only has to look at its parent.
+    pub(crate) fn node_transforms(&self) -> Vec<Transform2F> {
+        let mut node_transforms: Vec<Transform2F> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let transform = match node.parent {
+                None => node.transform,
+                Some(parent_id) => node_transforms[parent_id.0 as usize] * node.transform,
+            };
+            node_transforms.push(transform);
+        }
+        node_transforms
+    }
+
+    // Returns every clip path that a draw path is clipped by: its own clip path chain, followed
+    // by the clip path chains of the nodes above it.
+    pub(crate) fn draw_path_clip_paths(&self, path: &DrawPath) -> Vec<ClipPathId> {
+        let mut clip_path_ids: Vec<_> = self.clip_path_chain(path.clip_path).collect();
+        for node_id in self.node_chain(path.node) {
+            let node_clip_path = self.nodes[node_id.0 as usize].clip_path;
+            clip_path_ids.extend(self.clip_path_chain(node_clip_path));
+        }
+        clip_path_ids
+    }
+
     pub fn push_render_target(&mut self, render_target: RenderTarget) -> RenderTargetId {
         let render_target_id = self.palette.push_render_target(render_target);
         self.display_list.push(DisplayItem::PushRenderTarget(render_target_id));
@@ -94,17 +242,354 @@ impl Scene {
         self.display_list.push(DisplayItem::PopRenderTarget);
     }
 
+    /// Starts drawing content that is modulated by a soft mask.
+    ///
+    /// The mask is a render target that has already been drawn. Everything drawn until the
+    /// matching `pop_mask()` goes to an intermediate render target of the same size, which is
+    /// then multiplied by the alpha or luminance of the mask and composited onto the current
+    /// render target. Both render targets are drawn with the render transform like everything
+    /// else, so they are placed at the origin of device space.
+    pub fn push_mask(&mut self, mask: RenderTargetId, mode: MaskMode) {
+        let size = self.palette.get_render_target(mask).size();
+        let content = self.push_render_target(RenderTarget::new(size, String::new()));
+        self.layer_stack.push(PendingLayer::Mask(PendingMask { mask, mode, content, size }));
+    }
+
+    pub fn pop_mask(&mut self) {
+        let PendingMask { mask, mode, content, size } = match self.layer_stack.pop() {
+            Some(PendingLayer::Mask(pending_mask)) => pending_mask,
+            _ => panic!("`pop_mask()` doesn't match a `push_mask()`!"),
+        };
+        let outline = Outline::from_rect(RectF::new(Vector2F::zero(), size.to_f32()));
+
+        // Multiply the content by the mask.
+        let mut mask_pattern = Pattern::from_render_target(mask, size);
+        if mode == MaskMode::Luminance {
+            mask_pattern.set_filter(Some(PatternFilter::LuminanceToAlpha));
+        }
+        let mask_paint_id = self.push_device_paint(Paint::from_pattern(mask_pattern));
+        let mut mask_path = DrawPath::new(outline.clone(), mask_paint_id);
+        mask_path.set_blend_mode(BlendMode::DestIn);
+        self.push_path(mask_path);
+        self.pop_render_target();
+
+        // Composite the masked content. Render targets are premultiplied, so unpremultiply it
+        // first.
+        let mut content_pattern = Pattern::from_render_target(content, size);
+        content_pattern.set_filter(Some(PatternFilter::Unpremultiply));
+        let content_paint_id = self.push_device_paint(Paint::from_pattern(content_pattern));
+        let mut path = DrawPath::new(outline, content_paint_id);
+        path.set_composite_source(Some(content));
+        self.push_path(path);
+    }
+
+    /// Starts drawing an isolated group.
+    ///
+    /// Everything drawn until the matching `pop_group()` goes to an intermediate render target
+    /// that starts out transparent, so blend modes inside the group only see other members of
+    /// the group. The result is then composited onto the current render target as a single
+    /// layer, multiplied by `opacity`, blended with `blend_mode`, and optionally clipped.
+    ///
+    /// The intermediate render target covers the view box, so the view box must be set before
+    /// the group is pushed.
+    pub fn push_group(&mut self, opacity: f32, blend_mode: BlendMode, clip: Option<ClipPathId>) {
+        let size = self.view_box.lower_right().ceil().to_i32().max(Vector2I::splat(1));
+        let content = self.push_render_target(RenderTarget::new(size, String::new()));
+        let pending_group = PendingGroup { content, size, opacity, blend_mode, clip };
+        self.layer_stack.push(PendingLayer::Group(pending_group));
+    }
+
+    pub fn pop_group(&mut self) {
+        let PendingGroup { content, size, opacity, blend_mode, clip } =
+            match self.layer_stack.pop() {
+                Some(PendingLayer::Group(pending_group)) => pending_group,
+                _ => panic!("`pop_group()` doesn't match a `push_group()`!"),
+            };
+        self.pop_render_target();
+
+        // Render targets are premultiplied, so unpremultiply the group contents first.
+        let mut pattern = Pattern::from_render_target(content, size);
+        pattern.set_filter(Some(PatternFilter::Unpremultiply));
+
+        // The base color scales the alpha of the group contents by the opacity.
+        let alpha = (util::clamp(opacity, 0.0, 1.0) * 255.0).round() as u8;
+        let mut paint = Paint::from_pattern(pattern);
+        paint.set_base_color(ColorU::new(255, 255, 255, alpha));
+        let paint_id = self.push_device_paint(paint);
+
+        let outline = Outline::from_rect(RectF::new(Vector2F::zero(), size.to_f32()));
+        let mut path = DrawPath::new(outline, paint_id);
+        path.set_blend_mode(blend_mode);
+        path.set_clip_path(clip);
+        path.set_composite_source(Some(content));
+        self.push_path(path);
+    }
+
+    /// Starts drawing content that a filter graph is applied to.
+    ///
+    /// Everything drawn until the matching `pop_filter()` goes to an intermediate render target,
+    /// which the graph reads as its source graphic. Each primitive of the graph renders into a
+    /// render target of its own, and the result of the last one is composited onto the current
+    /// render target, optionally clipped to the filter region.
+    ///
+    /// As with groups, the intermediate render targets cover the view box, so the view box must
+    /// be set before the filter is pushed. All primitives operate in sRGB.
+    pub fn push_filter(&mut self, graph: FilterGraph, clip: Option<ClipPathId>) {
+        let size = self.view_box.lower_right().ceil().to_i32().max(Vector2I::splat(1));
+        let content = self.push_render_target(RenderTarget::new(size, String::new()));
+        self.layer_stack.push(PendingLayer::Filter(PendingFilter { graph, content, size, clip }));
+    }
+
+    pub fn pop_filter(&mut self) {
+        let PendingFilter { graph, content, size, clip } = match self.layer_stack.pop() {
+            Some(PendingLayer::Filter(pending_filter)) => pending_filter,
+            _ => panic!("`pop_filter()` doesn't match a `push_filter()`!"),
+        };
+        self.pop_render_target();
+
+        let mut results: Vec<RenderTargetId> = Vec::with_capacity(graph.primitives().len());
+        let mut source_alpha = None;
+        for primitive in graph.primitives() {
+            let mut inputs = Vec::with_capacity(primitive.inputs().len());
+            for &input in primitive.inputs() {
+                inputs.push(match input {
+                    FilterInput::SourceGraphic => content,
+                    FilterInput::SourceAlpha => match source_alpha {
+                        Some(source_alpha) => source_alpha,
+                        None => {
+                            let black = ColorMatrix::colorize(ColorU::black().to_f32());
+                            let alpha = self.push_filter_pass(size, |scene| {
+                                scene.draw_filter_layer(content,
+                                                        size,
+                                                        PatternFilter::ColorMatrix(black),
+                                                        Vector2F::zero(),
+                                                        BlendMode::SrcOver);
+                            });
+                            *source_alpha.get_or_insert(alpha)
+                        }
+                    },
+                    FilterInput::Primitive(primitive_id) => results[primitive_id.0 as usize],
+                });
+            }
+            let result = self.push_filter_primitive(primitive, &inputs, size);
+            results.push(result);
+        }
+
+        // An empty graph makes the content disappear.
+        let result = match results.last() {
+            None => return,
+            Some(&result) => result,
+        };
+        let mut pattern = Pattern::from_render_target(result, size);
+        pattern.set_filter(Some(PatternFilter::Unpremultiply));
+        let paint_id = self.push_device_paint(Paint::from_pattern(pattern));
+        let outline = Outline::from_rect(RectF::new(Vector2F::zero(), size.to_f32()));
+        let mut path = DrawPath::new(outline, paint_id);
+        path.set_clip_path(clip);
+        path.set_composite_source(Some(content));
+        self.push_path(path);
+    }
+
+    // Renders one primitive of a filter graph into a new render target, returning it.
+    fn push_filter_primitive(&mut self,
+                             primitive: &FilterPrimitive,
+                             inputs: &[RenderTargetId],
+                             size: Vector2I)
+                             -> RenderTargetId {
+        let unpremultiply = PatternFilter::Unpremultiply;
+        match *primitive {
+            FilterPrimitive::ColorMatrix { matrix, .. } => {
+                self.push_filter_pass(size, |scene| {
+                    scene.draw_filter_layer(inputs[0],
+                                            size,
+                                            PatternFilter::ColorMatrix(matrix),
+                                            Vector2F::zero(),
+                                            BlendMode::SrcOver);
+                })
+            }
+            FilterPrimitive::ComponentTransfer { functions, .. } => {
+                self.push_filter_pass(size, |scene| {
+                    scene.draw_filter_layer(inputs[0],
+                                            size,
+                                            PatternFilter::ComponentTransfer(functions),
+                                            Vector2F::zero(),
+                                            BlendMode::SrcOver);
+                })
+            }
+            FilterPrimitive::Offset { offset, .. } => {
+                self.push_filter_pass(size, |scene| {
+                    scene.draw_filter_layer(inputs[0],
+                                            size,
+                                            unpremultiply,
+                                            offset,
+                                            BlendMode::SrcOver);
+                })
+            }
+            FilterPrimitive::Flood { color } => {
+                self.push_filter_pass(size, |scene| {
+                    let paint_id = scene.push_device_paint(Paint::from_color(color));
+                    let outline = Outline::from_rect(RectF::new(Vector2F::zero(), size.to_f32()));
+                    scene.push_path(DrawPath::new(outline, paint_id));
+                })
+            }
+            FilterPrimitive::Blur { sigma, .. } => self.push_filter_blur(inputs[0], sigma, size),
+            FilterPrimitive::Composite { operator, .. } => {
+                self.push_filter_pass(size, |scene| {
+                    let zero = Vector2F::zero();
+                    scene.draw_filter_layer(inputs[1],
+                                            size,
+                                            unpremultiply,
+                                            zero,
+                                            BlendMode::SrcOver);
+                    scene.draw_filter_layer(inputs[0],
+                                            size,
+                                            unpremultiply,
+                                            zero,
+                                            operator.to_blend_mode());
+                })
+            }
+            FilterPrimitive::Blend { mode, .. } => {
+                self.push_filter_pass(size, |scene| {
+                    let zero = Vector2F::zero();
+                    scene.draw_filter_layer(inputs[1],
+                                            size,
+                                            unpremultiply,
+                                            zero,
+                                            BlendMode::SrcOver);
+                    scene.draw_filter_layer(inputs[0], size, unpremultiply, zero, mode);
+                })
+            }
+            FilterPrimitive::Merge { .. } => {
+                self.push_filter_pass(size, |scene| {
+                    for &input in inputs {
+                        scene.draw_filter_layer(input,
+                                                size,
+                                                unpremultiply,
+                                                Vector2F::zero(),
+                                                BlendMode::SrcOver);
+                    }
+                })
+            }
+            FilterPrimitive::Morphology { operator, radius, .. } => {
+                // A radius of zero along either axis disables the primitive.
+                if radius.x() <= 0.0 || radius.y() <= 0.0 {
+                    return inputs[0];
+                }
+                let mut result = inputs[0];
+                for &(direction, radius) in &[(BlurDirection::X, radius.x()),
+                                              (BlurDirection::Y, radius.y())] {
+                    let filter = PatternFilter::Morphology { operator, direction, radius };
+                    result = self.push_filter_pass(size, |scene| {
+                        scene.draw_filter_layer(result,
+                                                size,
+                                                filter,
+                                                Vector2F::zero(),
+                                                BlendMode::SrcOver);
+                    });
+                }
+                result
+            }
+            FilterPrimitive::DropShadow { offset, sigma, color, .. } => {
+                // Blurring is exact for the alpha channel, which is all the shadow needs.
+                let blurred = self.push_filter_blur(inputs[0], sigma, size);
+                let shadow = PatternFilter::ColorMatrix(ColorMatrix::colorize(color.to_f32()));
+                self.push_filter_pass(size, |scene| {
+                    scene.draw_filter_layer(blurred, size, shadow, offset, BlendMode::SrcOver);
+                    scene.draw_filter_layer(inputs[0],
+                                            size,
+                                            unpremultiply,
+                                            Vector2F::zero(),
+                                            BlendMode::SrcOver);
+                })
+            }
+        }
+    }
+
+    // Blurs along each axis whose standard deviation is positive.
+    fn push_filter_blur(&mut self, input: RenderTargetId, sigma: Vector2F, size: Vector2I)
+                        -> RenderTargetId {
+        let mut result = input;
+        for &(direction, sigma) in &[(BlurDirection::X, sigma.x()), (BlurDirection::Y, sigma.y())] {
+            if sigma <= 0.0 {
+                continue;
+            }
+            let filter = PatternFilter::Blur { direction, sigma };
+            result = self.push_filter_pass(size, |scene| {
+                scene.draw_filter_layer(result, size, filter, Vector2F::zero(), BlendMode::SrcOver);
+            });
+        }
+        result
+    }
+
+    fn push_filter_pass<F>(&mut self, size: Vector2I, draw: F) -> RenderTargetId
+                           where F: FnOnce(&mut Scene) {
+        let render_target = self.push_render_target(RenderTarget::new(size, String::new()));
+        draw(self);
+        self.pop_render_target();
+        render_target
+    }
+
+    // Draws a render target of the filter graph, moved by `offset` in scene space.
+    fn draw_filter_layer(&mut self,
+                         source: RenderTargetId,
+                         size: Vector2I,
+                         filter: PatternFilter,
+                         offset: Vector2F,
+                         blend_mode: BlendMode) {
+        let offset = Transform2F::from_translation(offset);
+        let mut pattern = Pattern::from_render_target(source, size);
+        pattern.set_filter(Some(filter));
+        pattern.apply_transform(offset);
+        let paint_id = self.push_device_paint(Paint::from_pattern(pattern));
+        let outline = Outline::from_rect(RectF::new(Vector2F::zero(), size.to_f32()));
+        let mut path = DrawPath::new(outline, paint_id);
+        path.set_transform(offset);
+        path.set_blend_mode(blend_mode);
+        self.push_path(path);
+    }
+
+    // Pushes a paint for compositing a layer. Layers are drawn with the render transform already
+    // applied, so the paint and the outlines filled with it are in device space.
+    fn push_device_paint(&mut self, mut paint: Paint) -> PaintId {
+        paint.set_device_space(true);
+        self.push_paint(&paint)
+    }
+
     pub fn append_scene(&mut self, scene: Scene) {
+        // Paint IDs may be shuffled around below, so start afresh.
+        self.build_cache.clear();
+
         let MergedPaletteInfo {
             render_target_mapping,
             paint_mapping,
         } = self.palette.append_palette(scene.palette);
 
+        // Merge nodes. Nodes and clip paths may refer to each other, so both are appended
+        // wholesale and their IDs offset.
+        let node_offset = self.nodes.len() as u32;
+        let clip_path_offset = self.clip_paths.len() as u32;
+        let map_node = |node_id: SceneNodeId| SceneNodeId(node_id.0 + node_offset);
+        let map_clip_path = |clip_path_id: ClipPathId| {
+            ClipPathId(clip_path_id.0 + clip_path_offset)
+        };
+        for node in scene.nodes {
+            self.nodes.push(SceneNode {
+                transform: node.transform,
+                parent: node.parent.map(map_node),
+                clip_path: node.clip_path.map(map_clip_path),
+                name: node.name,
+            });
+        }
+
         // Merge clip paths.
-        let mut clip_path_mapping = Vec::with_capacity(scene.clip_paths.len());
         for clip_path in scene.clip_paths {
-            clip_path_mapping.push(self.clip_paths.len());
-            self.clip_paths.push(clip_path);
+            self.clip_paths.push(ClipPath {
+                outline: clip_path.outline,
+                clip_path: clip_path.clip_path.map(map_clip_path),
+                node: clip_path.node.map(map_node),
+                fill_rule: clip_path.fill_rule,
+                name: clip_path.name,
+            });
         }
 
         // Merge draw paths.
@@ -113,12 +598,16 @@ impl Scene {
             draw_path_mapping.push(self.paths.len() as u32);
             self.paths.push(DrawPath {
                 outline: draw_path.outline,
+                transform: draw_path.transform,
                 paint: paint_mapping[&draw_path.paint],
-                clip_path: draw_path.clip_path.map(|clip_path_id| {
-                    ClipPathId(clip_path_mapping[clip_path_id.0 as usize] as u32)
-                }),
+                clip_path: draw_path.clip_path.map(map_clip_path),
+                node: draw_path.node.map(map_node),
                 fill_rule: draw_path.fill_rule,
                 blend_mode: draw_path.blend_mode,
+                non_scaling_stroke: draw_path.non_scaling_stroke,
+                composite_source: draw_path.composite_source.map(|render_target_id| {
+                    render_target_mapping[&render_target_id]
+                }),
                 name: draw_path.name,
             });
         }
@@ -145,6 +634,20 @@ impl Scene {
         }
     }
 
+    #[inline]
+    pub fn get_clip_path(&self, clip_path_id: ClipPathId) -> &ClipPath {
+        &self.clip_paths[clip_path_id.0 as usize]
+    }
+
+    // Returns the given clip path followed by all of its ancestors. A path drawn with this clip
+    // is visible only where every clip path in the chain covers it.
+    pub(crate) fn clip_path_chain(&self, clip_path_id: Option<ClipPathId>)
+                                  -> impl Iterator<Item = ClipPathId> + '_ {
+        iter::successors(clip_path_id, move |clip_path_id| {
+            self.clip_paths[clip_path_id.0 as usize].clip_path
+        })
+    }
+
     #[inline]
     pub fn build_paint_info(&mut self, render_transform: Transform2F) -> PaintInfo {
         self.palette.build_paint_info(render_transform)
@@ -155,6 +658,35 @@ impl Scene {
         self.palette.push_paint(paint)
     }
 
+    /// Replaces the paint with the given ID, marking all paths that use it as changed.
+    pub fn set_paint(&mut self, paint_id: PaintId, paint: &Paint) {
+        self.palette.set_paint(paint_id, paint);
+        for (path_index, path) in self.paths.iter().enumerate() {
+            if path.paint == paint_id {
+                self.build_cache.invalidate_path(DrawPathId(path_index as u32));
+            }
+        }
+    }
+
+    /// Sets the number of bytes of texture memory that image patterns may occupy between builds.
+    ///
+    /// Images stay resident on the GPU across builds and are only uploaded once. When the budget
+    /// is exceeded, the least recently used images are evicted.
+    #[inline]
+    pub fn set_image_cache_budget(&mut self, budget: usize) {
+        self.palette.set_image_cache_budget(budget);
+    }
+
+    /// Makes the next build allocate and upload every texture that the scene keeps resident.
+    ///
+    /// Texture pages stay resident in the renderer across builds, so each build only sends the
+    /// pages and texels that changed. Call this before building the scene with a listener that
+    /// hasn't received all of its previous builds, such as a new renderer or a capture recorder.
+    #[inline]
+    pub fn invalidate_textures(&mut self) {
+        self.palette.invalidate_textures();
+    }
+
     #[inline]
     pub fn path_count(&self) -> usize {
         self.paths.len()
@@ -183,10 +715,46 @@ impl Scene {
     pub(crate) fn apply_render_options(
         &self,
         original_outline: &Outline,
+        path_transform: Transform2F,
+        non_scaling_stroke: Option<StrokeStyle>,
+        device_space: bool,
         options: &PreparedBuildOptions,
     ) -> Outline {
         let effective_view_box = self.effective_view_box(options);
 
+        // Outlines in device space skip the render transform. Only the scene-space offset in
+        // their path transform is moved into device space.
+        if device_space {
+            let render_transform = match options.transform {
+                PreparedRenderTransform::Transform2D(transform) => transform,
+                _ => Transform2F::default(),
+            };
+            let mut device_transform = render_transform;
+            if options.subpixel_aa_enabled {
+                device_transform *= Transform2F::from_scale(vec2f(3.0, 1.0))
+            }
+            let mut outline = (*original_outline).clone();
+            outline.transform(&(device_transform * path_transform * render_transform.inverse()));
+            outline.close_all_contours();
+            outline.clip_against_rect(effective_view_box);
+            if !options.dilation.is_zero() {
+                outline.dilate(options.dilation);
+            }
+            outline.prepare_for_tiling(effective_view_box);
+            return outline;
+        }
+
+        // Move the outline into scene space first.
+        let scene_outline;
+        let original_outline = if path_transform.is_identity() {
+            original_outline
+        } else {
+            let mut outline = original_outline.clone();
+            outline.transform(&path_transform);
+            scene_outline = outline;
+            &scene_outline
+        };
+
         let mut outline;
         match options.transform {
             PreparedRenderTransform::Perspective {
@@ -194,31 +762,76 @@ impl Scene {
                 ref clip_polygon,
                 ..
             } => {
-                if original_outline.is_outside_polygon(clip_polygon) {
-                    outline = Outline::new();
-                } else {
-                    outline = (*original_outline).clone();
-                    outline.close_all_contours();
-                    outline.clip_against_polygon(clip_polygon);
-                    outline.apply_perspective(perspective);
-
-                    // TODO(pcwalton): Support subpixel AA in 3D.
+                match non_scaling_stroke {
+                    Some(stroke_style) if is_in_front_of_camera(original_outline, perspective) => {
+                        // Project the centerline, then stroke it in device space.
+                        let mut centerline = (*original_outline).clone();
+                        centerline.apply_perspective(perspective);
+                        outline = stroke_in_space(&centerline,
+                                                  &Transform2F::default(),
+                                                  stroke_style);
+                        outline.close_all_contours();
+                        outline.clip_against_rect(effective_view_box);
+                    }
+                    _ => {
+                        // Clipping away the part of a centerline behind the camera would close
+                        // it, so such a stroke is made in scene space instead.
+                        let stroked_outline;
+                        let original_outline = match non_scaling_stroke {
+                            None => original_outline,
+                            Some(stroke_style) => {
+                                warn!("Non-scaling stroke crosses the camera plane; stroking it \
+                                       in scene space instead.");
+                                stroked_outline = stroke_in_space(original_outline,
+                                                                  &Transform2F::default(),
+                                                                  stroke_style);
+                                &stroked_outline
+                            }
+                        };
+                        if original_outline.is_outside_polygon(clip_polygon) {
+                            outline = Outline::new();
+                        } else {
+                            outline = (*original_outline).clone();
+                            outline.close_all_contours();
+                            outline.clip_against_polygon(clip_polygon);
+                            outline.apply_perspective(perspective);
+
+                            // TODO(pcwalton): Support subpixel AA in 3D.
+                        }
+                    }
                 }
             }
             _ => {
-                // TODO(pcwalton): Short circuit.
-                outline = (*original_outline).clone();
-                outline.close_all_contours();
-                if options.transform.is_2d() || options.subpixel_aa_enabled {
-                    let mut transform = match options.transform {
-                        PreparedRenderTransform::Transform2D(transform) => transform,
-                        PreparedRenderTransform::None => Transform2F::default(),
-                        PreparedRenderTransform::Perspective { .. } => unreachable!(),
-                    };
-                    if options.subpixel_aa_enabled {
-                        transform *= Transform2F::from_scale(vec2f(3.0, 1.0))
+                let render_transform = match options.transform {
+                    PreparedRenderTransform::Transform2D(transform) => transform,
+                    PreparedRenderTransform::None => Transform2F::default(),
+                    PreparedRenderTransform::Perspective { .. } => unreachable!(),
+                };
+                let mut transform = render_transform;
+                if options.subpixel_aa_enabled {
+                    transform *= Transform2F::from_scale(vec2f(3.0, 1.0))
+                }
+
+                match non_scaling_stroke {
+                    None => {
+                        // TODO(pcwalton): Short circuit.
+                        outline = (*original_outline).clone();
+                        outline.close_all_contours();
+                        if options.transform.is_2d() || options.subpixel_aa_enabled {
+                            outline.transform(&transform);
+                        }
+                    }
+                    Some(stroke_style) => {
+                        // Stroke in device space, then let subpixel AA stretch the stroke the
+                        // same way it stretches everything else.
+                        outline = stroke_in_space(original_outline,
+                                                  &render_transform,
+                                                  stroke_style);
+                        outline.close_all_contours();
+                        if options.subpixel_aa_enabled {
+                            outline.transform(&(transform * render_transform.inverse()));
+                        }
                     }
-                    outline.transform(&transform);
                 }
                 outline.clip_against_rect(effective_view_box);
             }
@@ -253,12 +866,182 @@ impl Scene {
         SceneBuilder::new(self, &prepared_options, listener).build(executor)
     }
 
-    pub fn paths<'a>(&'a self) -> PathIter {
+    /// Builds the scene and renders it off-screen into an image of the given size.
+    ///
+    /// The view box maps to the image the same way it maps to the framebuffer when rendering
+    /// normally, so callers will usually want to set it to cover `size` first. Rendering happens
+    /// on the CPU via the `ReferenceRasterizer`, so no GPU device is needed. The returned image
+    /// doesn't have premultiplied alpha.
+    #[cfg(feature = "pf-image")]
+    pub fn rasterize(&mut self, options: &BuildOptions, size: Vector2I, background_color: ColorF)
+                     -> RgbaImage {
+        let renderer_options = RendererOptions { background_color: Some(background_color) };
+        let rasterizer = ReferenceRasterizer::new(size, renderer_options);
+        self.invalidate_textures();
+        self.build((*options).clone(), Box::new(rasterizer.clone()), &SequentialExecutor);
+
+        let mut pixels = Vec::with_capacity(size.x() as usize * size.y() as usize * 4);
+        for pixel in rasterizer.pixels() {
+            let alpha = pixel.a as u32;
+            let unpremultiply = |value: u8| {
+                match (value as u32 * 255 + alpha / 2).checked_div(alpha) {
+                    Some(value) => value.min(255) as u8,
+                    None => 0,
+                }
+            };
+            pixels.extend_from_slice(&[
+                unpremultiply(pixel.r),
+                unpremultiply(pixel.g),
+                unpremultiply(pixel.b),
+                pixel.a,
+            ]);
+        }
+        RgbaImage::from_raw(size.x() as u32, size.y() as u32, pixels).unwrap()
+    }
+
+    /// Returns the IDs of the draw paths under the given point, topmost first.
+    ///
+    /// The point is in the same space as the view box, so if the scene is built with a render
+    /// transform, pass that transform too and the point will be mapped back through it. Points
+    /// outside the view box hit nothing. Each path's fill rule, clip paths, and node transforms
+    /// are respected.
+    ///
+    /// Paths drawn into groups, masks, and filters are hit wherever the composited layer covers
+    /// the point and lets them through its clip; the layer itself is never returned. Other paths
+    /// drawn into render targets reach the screen only through the patterns that sample them,
+    /// so they are never hit.
+    pub fn hit_test(&self, point: Vector2F, transform: Option<&RenderTransform>)
+                    -> Vec<DrawPathId> {
+        if !self.view_box.contains_point(point) {
+            return vec![];
+        }
+        let device_point = point;
+        let point = match transform {
+            None => point,
+            Some(RenderTransform::Transform2D(transform)) => transform.inverse() * point,
+            Some(RenderTransform::Perspective(perspective)) => {
+                match unproject(perspective, point) {
+                    None => return vec![],
+                    Some(point) => point,
+                }
+            }
+        };
+        // Non-scaling strokes are hit in the space that they are stroked in.
+        let (stroke_point, stroke_transform) = match transform {
+            Some(RenderTransform::Transform2D(transform)) => (device_point, *transform),
+            _ => (point, Transform2F::default()),
+        };
+        let hit_point = HitTestPoint { point, stroke_point, stroke_transform };
+
+        // Find the render target that each path is drawn into.
+        let mut path_render_targets = vec![None; self.paths.len()];
+        let mut render_target_stack = vec![];
+        for display_item in &self.display_list {
+            match *display_item {
+                DisplayItem::PushRenderTarget(render_target_id) => {
+                    render_target_stack.push(render_target_id)
+                }
+                DisplayItem::PopRenderTarget => {
+                    render_target_stack.pop();
+                }
+                DisplayItem::DrawPaths { start_index, end_index } => {
+                    for path_index in start_index..end_index {
+                        path_render_targets[path_index as usize] =
+                            render_target_stack.last().cloned();
+                    }
+                }
+            }
+        }
+
+        let mut path_ids = vec![];
+        self.hit_test_render_target(None, &hit_point, &path_render_targets, &mut path_ids);
+        path_ids.reverse();
+        path_ids
+    }
+
+    // Appends the paths drawn into the given render target, or the framebuffer if `None`, that
+    // are under the point, bottommost first. Composited layers are replaced by their contents.
+    fn hit_test_render_target(&self,
+                              render_target: Option<RenderTargetId>,
+                              hit_point: &HitTestPoint,
+                              path_render_targets: &[Option<RenderTargetId>],
+                              path_ids: &mut Vec<DrawPathId>) {
+        let point = hit_point.point;
+        for (path_index, path) in self.paths.iter().enumerate() {
+            if path_render_targets[path_index] != render_target {
+                continue;
+            }
+            let path_transform = self.draw_path_transform(path);
+            let device_space = self.palette.paints[path.paint.0 as usize].device_space();
+            let hit = match path.non_scaling_stroke {
+                // Layers cover the render targets they composite, in device space.
+                None if device_space => {
+                    path.outline.contains_point(hit_point.stroke_point, path.fill_rule)
+                }
+                None => {
+                    let path_point = path_transform.inverse() * point;
+                    path.outline.contains_point(path_point, path.fill_rule)
+                }
+                Some(stroke_style) => {
+                    let outline = stroke_in_space(&path.outline,
+                                                  &(hit_point.stroke_transform * path_transform),
+                                                  stroke_style);
+                    outline.contains_point(hit_point.stroke_point, path.fill_rule)
+                }
+            } &&
+                self.draw_path_clip_paths(path).into_iter().all(|clip_path_id| {
+                    let clip_path = &self.clip_paths[clip_path_id.0 as usize];
+                    let clip_point = self.node_transform(clip_path.node).inverse() * point;
+                    clip_path.outline.contains_point(clip_point, clip_path.fill_rule)
+                });
+            if !hit {
+                continue;
+            }
+            match path.composite_source {
+                None => path_ids.push(DrawPathId(path_index as u32)),
+                Some(source) => {
+                    self.hit_test_render_target(Some(source),
+                                                hit_point,
+                                                path_render_targets,
+                                                path_ids);
+                }
+            }
+        }
+    }
+
+    /// Returns the paint, outline, and name of each draw path.
+    ///
+    /// The outlines are in the space of their paths, before instance and node transforms. Use
+    /// `transformed_paths()` to get them in scene space.
+    pub fn paths(&self) -> PathIter<'_> {
         PathIter {
             scene: self,
             pos: 0
         }
     }
+
+    /// Returns the paint, outline, and name of each draw path, with the outlines moved into
+    /// scene space.
+    pub fn transformed_paths(&self) -> TransformedPathIter<'_> {
+        TransformedPathIter {
+            scene: self,
+            pos: 0
+        }
+    }
+}
+
+// Maps a point in window coordinates back onto the z = 0 plane that the scene lies in, or
+// returns `None` if that plane isn't visible there.
+fn unproject(perspective: &Perspective, point: Vector2F) -> Option<Vector2F> {
+    let ndc = (point / perspective.window_size.to_f32() * 2.0 - 1.0) * vec2f(1.0, -1.0);
+    let inverse_transform = perspective.transform.inverse();
+    let near = (inverse_transform * Vector4F::new(ndc.x(), ndc.y(), -1.0, 1.0)).to_3d();
+    let far = (inverse_transform * Vector4F::new(ndc.x(), ndc.y(), 1.0, 1.0)).to_3d();
+    let t = near.z() / (near.z() - far.z());
+    if !(0.0..=1.0).contains(&t) {
+        return None;
+    }
+    Some(near.to_2d().lerp(far.to_2d(), t))
 }
 
 pub struct PathIter<'a> {
@@ -272,7 +1055,39 @@ impl<'a> Iterator for PathIter<'a> {
         let item = self.scene.paths.get(self.pos).map(|path_object| {
             (
                 self.scene.palette.paints.get(path_object.paint.0 as usize).unwrap(),
-                &path_object.outline,
+                &*path_object.outline,
+                &*path_object.name
+            )
+        });
+        self.pos += 1;
+        item
+    }
+}
+
+pub struct TransformedPathIter<'a> {
+    scene: &'a Scene,
+    pos: usize
+}
+
+// Outlines are yielded with instance and node transforms applied. Non-scaling strokes are
+// stroked in scene space.
+impl<'a> Iterator for TransformedPathIter<'a> {
+    type Item = (&'a Paint, Cow<'a, Outline>, &'a str);
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.scene.paths.get(self.pos).map(|path_object| {
+            let transform = self.scene.draw_path_transform(path_object);
+            let outline = if let Some(stroke_style) = path_object.non_scaling_stroke {
+                Cow::Owned(stroke_in_space(&path_object.outline, &transform, stroke_style))
+            } else if transform.is_identity() {
+                Cow::Borrowed(&*path_object.outline)
+            } else {
+                let mut outline = (*path_object.outline).clone();
+                outline.transform(&transform);
+                Cow::Owned(outline)
+            };
+            (
+                self.scene.palette.paints.get(path_object.paint.0 as usize).unwrap(),
+                outline,
                 &*path_object.name
             )
         });
@@ -283,24 +1098,94 @@ impl<'a> Iterator for PathIter<'a> {
 
 #[derive(Clone, Debug)]
 pub struct DrawPath {
-    outline: Outline,
+    outline: Arc<Outline>,
+    transform: Transform2F,
     paint: PaintId,
     clip_path: Option<ClipPathId>,
+    node: Option<SceneNodeId>,
     fill_rule: FillRule,
     blend_mode: BlendMode,
+    non_scaling_stroke: Option<StrokeStyle>,
+    composite_source: Option<RenderTargetId>,
     name: String,
 }
 
 #[derive(Clone, Debug)]
 pub struct ClipPath {
     outline: Outline,
+    clip_path: Option<ClipPathId>,
+    node: Option<SceneNodeId>,
     fill_rule: FillRule,
     name: String,
 }
 
+/// A node in the scene graph, which positions everything attached to it and to its descendants.
+#[derive(Clone, Debug)]
+pub struct SceneNode {
+    transform: Transform2F,
+    parent: Option<SceneNodeId>,
+    clip_path: Option<ClipPathId>,
+    name: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawPathId(pub u32);
+
 #[derive(Clone, Copy, Debug)]
 pub struct ClipPathId(pub u32);
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneNodeId(pub u32);
+
+/// How a soft mask modulates the content drawn under it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaskMode {
+    /// The content is multiplied by the alpha of the mask.
+    Alpha,
+    /// The content is multiplied by the luminance of the mask, multiplied by its alpha.
+    Luminance,
+}
+
+// A point being hit tested, in scene space and in the space that non-scaling strokes are made in.
+struct HitTestPoint {
+    point: Vector2F,
+    stroke_point: Vector2F,
+    stroke_transform: Transform2F,
+}
+
+// A mask, group, or filter whose contents are still being drawn.
+#[derive(Clone, Debug)]
+enum PendingLayer {
+    Mask(PendingMask),
+    Group(PendingGroup),
+    Filter(PendingFilter),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PendingMask {
+    mask: RenderTargetId,
+    mode: MaskMode,
+    content: RenderTargetId,
+    size: Vector2I,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PendingGroup {
+    content: RenderTargetId,
+    size: Vector2I,
+    opacity: f32,
+    blend_mode: BlendMode,
+    clip: Option<ClipPathId>,
+}
+
+#[derive(Clone, Debug)]
+struct PendingFilter {
+    graph: FilterGraph,
+    content: RenderTargetId,
+    size: Vector2I,
+    clip: Option<ClipPathId>,
+}
+
 #[derive(Clone, Debug)]
 pub struct RenderTarget {
     size: Vector2I,
@@ -323,12 +1208,25 @@ pub enum DisplayItem {
 impl DrawPath {
     #[inline]
     pub fn new(outline: Outline, paint: PaintId) -> DrawPath {
+        DrawPath::from_shared_outline(Arc::new(outline), paint)
+    }
+
+    /// Creates a path that draws an outline shared with other paths.
+    ///
+    /// Only the reference is cloned, so drawing the same shape many times costs one outline in
+    /// memory. Each instance is positioned with `set_transform()`.
+    #[inline]
+    pub fn from_shared_outline(outline: Arc<Outline>, paint: PaintId) -> DrawPath {
         DrawPath {
             outline,
+            transform: Transform2F::default(),
             paint,
             clip_path: None,
+            node: None,
             fill_rule: FillRule::Winding,
             blend_mode: BlendMode::SrcOver,
+            non_scaling_stroke: None,
+            composite_source: None,
             name: String::new(),
         }
     }
@@ -338,6 +1236,23 @@ impl DrawPath {
         &self.outline
     }
 
+    #[inline]
+    pub fn shared_outline(&self) -> &Arc<Outline> {
+        &self.outline
+    }
+
+    #[inline]
+    pub fn transform(&self) -> Transform2F {
+        self.transform
+    }
+
+    /// Sets the transform applied to the outline when the scene is built, before the transform
+    /// of the path's node.
+    #[inline]
+    pub fn set_transform(&mut self, new_transform: Transform2F) {
+        self.transform = new_transform
+    }
+
     #[inline]
     pub(crate) fn clip_path(&self) -> Option<ClipPathId> {
         self.clip_path
@@ -348,6 +1263,18 @@ impl DrawPath {
         self.clip_path = new_clip_path
     }
 
+    #[inline]
+    pub(crate) fn node(&self) -> Option<SceneNodeId> {
+        self.node
+    }
+
+    /// Attaches this path to a node, so that it is drawn with the node's transform and clipped
+    /// by the node's clip path.
+    #[inline]
+    pub fn set_node(&mut self, new_node: Option<SceneNodeId>) {
+        self.node = new_node
+    }
+
     #[inline]
     pub(crate) fn paint(&self) -> PaintId {
         self.paint
@@ -373,16 +1300,89 @@ impl DrawPath {
         self.blend_mode = new_blend_mode
     }
 
+    #[inline]
+    pub(crate) fn non_scaling_stroke(&self) -> Option<StrokeStyle> {
+        self.non_scaling_stroke
+    }
+
+    /// Strokes the outline with the given style after it has been moved into device space, as
+    /// SVG's `vector-effect: non-scaling-stroke` does, so that the stroke keeps the same width in
+    /// pixels however the path, its node, or the render transform scale it.
+    ///
+    /// The outline is then the centerline of the stroke. Scene bounds include the stroke,
+    /// taking its width to be in scene units. Under a perspective render transform, the
+    /// centerline is projected before it is stroked, unless part of it is behind the camera, in
+    /// which case the stroke is made in scene space and a warning is logged.
+    #[inline]
+    pub fn set_non_scaling_stroke(&mut self, new_non_scaling_stroke: Option<StrokeStyle>) {
+        self.non_scaling_stroke = new_non_scaling_stroke
+    }
+
+    /// The render target of the group, mask, or filter whose contents this path composites, if
+    /// the scene pushed it to do so.
+    #[inline]
+    pub(crate) fn composite_source(&self) -> Option<RenderTargetId> {
+        self.composite_source
+    }
+
+    #[inline]
+    pub(crate) fn set_composite_source(&mut self, new_composite_source: Option<RenderTargetId>) {
+        self.composite_source = new_composite_source
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     #[inline]
     pub fn set_name(&mut self, new_name: String) {
         self.name = new_name
     }
 }
 
+// Strokes an outline after moving it into the space that the stroke width is measured in.
+// How far a stroke with the given style can reach past its centerline.
+fn stroke_reach(stroke_style: StrokeStyle) -> f32 {
+    let mut reach = stroke_style.line_width * 0.5;
+    if let LineJoin::Miter(miter_limit) = stroke_style.line_join {
+        reach *= miter_limit.max(1.0);
+    }
+    if stroke_style.line_cap == LineCap::Square {
+        reach = reach.max(stroke_style.line_width * 0.5 * SQRT_2);
+    }
+    reach
+}
+
+fn stroke_in_space(outline: &Outline, transform: &Transform2F, stroke_style: StrokeStyle)
+                   -> Outline {
+    let mut outline = outline.clone();
+    outline.transform(transform);
+    let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
+    stroke_to_fill.offset();
+    stroke_to_fill.into_outline()
+}
+
+// Returns true if every point of the outline, control points included, is in front of the
+// camera, so that projecting the points projects the whole outline.
+fn is_in_front_of_camera(outline: &Outline, perspective: &Perspective) -> bool {
+    outline.contours().iter().all(|contour| {
+        (0..contour.len()).all(|point_index| {
+            (perspective.transform * contour.position_of(point_index).to_4d()).w() > 0.0
+        })
+    })
+}
+
 impl ClipPath {
     #[inline]
     pub fn new(outline: Outline) -> ClipPath {
-        ClipPath { outline, fill_rule: FillRule::Winding, name: String::new() }
+        ClipPath {
+            outline,
+            clip_path: None,
+            node: None,
+            fill_rule: FillRule::Winding,
+            name: String::new(),
+        }
     }
 
     #[inline]
@@ -390,6 +1390,30 @@ impl ClipPath {
         &self.outline
     }
 
+    #[inline]
+    pub fn clip_path(&self) -> Option<ClipPathId> {
+        self.clip_path
+    }
+
+    /// Clips this clip path by another one, so that it covers only the intersection of the two.
+    #[inline]
+    pub fn set_clip_path(&mut self, new_clip_path: Option<ClipPathId>) {
+        self.clip_path = new_clip_path
+    }
+
+    #[inline]
+    pub fn node(&self) -> Option<SceneNodeId> {
+        self.node
+    }
+
+    /// Attaches this clip path to a node, so that it is transformed along with the node.
+    ///
+    /// Unlike draw paths, clip paths aren't clipped by the clip paths of their nodes.
+    #[inline]
+    pub fn set_node(&mut self, new_node: Option<SceneNodeId>) {
+        self.node = new_node
+    }
+
     #[inline]
     pub(crate) fn fill_rule(&self) -> FillRule {
         self.fill_rule
@@ -400,6 +1424,57 @@ impl ClipPath {
         self.fill_rule = new_fill_rule
     }
 
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn set_name(&mut self, new_name: String) {
+        self.name = new_name
+    }
+}
+
+impl SceneNode {
+    #[inline]
+    pub fn new(transform: Transform2F) -> SceneNode {
+        SceneNode { transform, parent: None, clip_path: None, name: String::new() }
+    }
+
+    #[inline]
+    pub fn transform(&self) -> Transform2F {
+        self.transform
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<SceneNodeId> {
+        self.parent
+    }
+
+    /// Nests this node inside another one, so that the parent's transform applies on top of this
+    /// one's.
+    #[inline]
+    pub fn set_parent(&mut self, new_parent: Option<SceneNodeId>) {
+        self.parent = new_parent
+    }
+
+    #[inline]
+    pub fn clip_path(&self) -> Option<ClipPathId> {
+        self.clip_path
+    }
+
+    /// Clips every draw path beneath this node. The clip path is positioned by its own node, not
+    /// this one.
+    #[inline]
+    pub fn set_clip_path(&mut self, new_clip_path: Option<ClipPathId>) {
+        self.clip_path = new_clip_path
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     #[inline]
     pub fn set_name(&mut self, new_name: String) {
         self.name = new_name
@@ -416,4 +1491,158 @@ impl RenderTarget {
     pub fn size(&self) -> Vector2I {
         self.size
     }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::options::{BuildOptions, RenderTransform};
+    use crate::paint::Paint;
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::BlendMode;
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_content::stroke::{LineCap, LineJoin, StrokeStyle};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::transform3d::{Perspective, Transform4F};
+    use pathfinder_geometry::vector::{Vector2F, Vector4F, vec2f, vec2i};
+
+    use super::{ClipPath, DrawPath, DrawPathId, MaskMode, RenderTarget, Scene, SceneNode};
+
+    #[test]
+    fn test_hit_test() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(100.0, 100.0)));
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+
+        // A square with a square hole in it under the even-odd rule.
+        let mut outline = Outline::from_rect(RectF::new(vec2f(10.0, 10.0), vec2f(60.0, 60.0)));
+        outline.push_contour(Contour::from_rect(RectF::new(vec2f(30.0, 30.0), vec2f(20.0, 20.0))));
+        let mut ring = DrawPath::new(outline, red);
+        ring.set_fill_rule(FillRule::EvenOdd);
+        let ring = scene.push_path(ring);
+
+        // A disc-like curve clipped to its left half, on top of the ring.
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(40.0, 20.0));
+        contour.push_quadratic(vec2f(60.0, 20.0), vec2f(60.0, 40.0));
+        contour.push_quadratic(vec2f(60.0, 60.0), vec2f(40.0, 60.0));
+        contour.push_quadratic(vec2f(20.0, 60.0), vec2f(20.0, 40.0));
+        contour.push_quadratic(vec2f(20.0, 20.0), vec2f(40.0, 20.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        let clip_rect = RectF::new(vec2f(0.0, 0.0), vec2f(40.0, 100.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(Outline::from_rect(clip_rect)));
+        let mut disc = DrawPath::new(outline, red);
+        disc.set_clip_path(Some(clip_path));
+        let disc = scene.push_path(disc);
+
+        assert_eq!(scene.hit_test(vec2f(35.0, 40.0), None), vec![disc]);
+        assert_eq!(scene.hit_test(vec2f(25.0, 40.0), None), vec![disc, ring]);
+        assert_eq!(scene.hit_test(vec2f(45.0, 40.0), None), Vec::<DrawPathId>::new());
+        assert_eq!(scene.hit_test(vec2f(55.0, 25.0), None), vec![ring]);
+        // Inside the quadratic's control polygon but outside the curve itself.
+        assert_eq!(scene.hit_test(vec2f(22.0, 22.0), None), vec![ring]);
+        assert!(scene.hit_test(vec2f(5.0, 5.0), None).is_empty());
+
+        // Points are mapped back through the render transform, and the view box is respected.
+        let transform = RenderTransform::Transform2D(Transform2F::from_scale(2.0));
+        assert_eq!(scene.hit_test(vec2f(50.0, 80.0), Some(&transform)), vec![disc, ring]);
+        assert!(scene.hit_test(vec2f(110.0, 80.0), Some(&transform)).is_empty());
+    }
+
+    #[test]
+    fn test_hit_test_groups() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+
+        // Members of a group are hit through it, clipped by the group's clip, and the layer that
+        // composites the group is never hit itself.
+        let clip_rect = RectF::new(vec2f(0.0, 0.0), vec2f(32.0, 8.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(Outline::from_rect(clip_rect)));
+        scene.push_group(0.5, BlendMode::SrcOver, Some(clip_path));
+        let rect = RectF::new(vec2f(0.0, 0.0), vec2f(4.0, 16.0));
+        let member = scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+        scene.pop_group();
+        let rect = RectF::new(vec2f(0.0, 0.0), vec2f(8.0, 8.0));
+        let top = scene.push_path(DrawPath::new(Outline::from_rect(rect), red));
+
+        assert_eq!(scene.hit_test(vec2f(2.0, 2.0), None), vec![top, member]);
+        assert_eq!(scene.hit_test(vec2f(6.0, 2.0), None), vec![top]);
+        assert!(scene.hit_test(vec2f(2.0, 12.0), None).is_empty());
+        assert!(scene.hit_test(vec2f(12.0, 12.0), None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "`pop_group()` doesn't match a `push_group()`!")]
+    fn test_mismatched_layers() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+        let mask = scene.push_render_target(RenderTarget::new(vec2i(32, 32), String::new()));
+        scene.pop_render_target();
+        scene.push_group(1.0, BlendMode::SrcOver, None);
+        scene.push_mask(mask, MaskMode::Alpha);
+        scene.pop_group();
+    }
+
+    #[test]
+    fn test_non_scaling_stroke_bounds() {
+        let mut scene = Scene::new();
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let style = StrokeStyle {
+            line_width: 8.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Bevel,
+        };
+
+        // The node scales the centerline, but not the width of the stroke.
+        let node = scene.push_node(SceneNode::new(Transform2F::from_scale(2.0)));
+        let rect = RectF::new(vec2f(10.0, 10.0), vec2f(20.0, 10.0));
+        let mut path = DrawPath::new(Outline::from_rect(rect), red);
+        path.set_node(Some(node));
+        path.set_non_scaling_stroke(Some(style));
+        scene.push_path(path);
+        assert_eq!(scene.bounds().lower_right(), vec2f(64.0, 44.0));
+    }
+
+    #[test]
+    fn test_non_scaling_stroke_after_perspective() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2f(64.0, 64.0)));
+        let style = StrokeStyle {
+            line_width: 4.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Bevel,
+        };
+        let mut centerline = Outline::new();
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(4.0, 8.0));
+        contour.push_endpoint(vec2f(24.0, 8.0));
+        centerline.push_contour(contour);
+
+        // A projection that doubles the size of the scene keeps the stroke 4 pixels wide.
+        let transform = Transform4F::from_translation(Vector4F::new(-1.0, 1.0, 0.0, 1.0)) *
+            Transform4F::from_scale(Vector4F::new(1.0 / 16.0, -1.0 / 16.0, 1.0, 1.0));
+        let options = BuildOptions {
+            transform: RenderTransform::Perspective(Perspective::new(&transform, vec2i(64, 64))),
+            ..BuildOptions::default()
+        }.prepare(RectF::new(Vector2F::zero(), vec2f(32.0, 32.0)));
+        let outline = scene.apply_render_options(&centerline,
+                                                 Transform2F::default(),
+                                                 Some(style),
+                                                 false,
+                                                 &options);
+        let bounds = outline.bounds();
+        assert!((bounds.min_y() - 14.0).abs() < 0.01, "{:?}", bounds);
+        assert!((bounds.max_y() - 18.0).abs() < 0.01, "{:?}", bounds);
+        assert!((bounds.min_x() - 8.0).abs() < 0.01, "{:?}", bounds);
+        assert!((bounds.max_x() - 48.0).abs() < 0.01, "{:?}", bounds);
+    }
 }
@@ -10,6 +10,7 @@
 
 use crate::builder::{BuiltPath, ObjectBuilder, Occluder, SceneBuilder, SolidTiles};
 use crate::gpu_data::{AlphaTileId, TileObjectPrimitive};
+use crate::options::TileSize;
 use crate::paint::{PaintId, PaintMetadata};
 use pathfinder_content::effects::BlendMode;
 use pathfinder_content::fill::FillRule;
@@ -18,16 +19,13 @@ use pathfinder_content::segment::Segment;
 use pathfinder_content::sorted_vector::SortedVector;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::{RectF, RectI};
-use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2i};
 use std::cmp::Ordering;
 use std::mem;
 
 // TODO(pcwalton): Make this configurable.
 const FLATTENING_TOLERANCE: f32 = 0.1;
 
-pub const TILE_WIDTH: u32 = 16;
-pub const TILE_HEIGHT: u32 = 16;
-
 pub(crate) struct Tiler<'a, 'b> {
     scene_builder: &'a SceneBuilder<'b, 'a>,
     pub(crate) object_builder: ObjectBuilder,
@@ -50,7 +48,8 @@ pub(crate) struct DrawTilingPathInfo<'a> {
     pub(crate) paint_id: PaintId,
     pub(crate) paint_metadata: &'a PaintMetadata,
     pub(crate) blend_mode: BlendMode,
-    pub(crate) built_clip_path: Option<&'a BuiltPath>,
+    // The clip path of this path, followed by its ancestors.
+    pub(crate) built_clip_paths: &'a [&'a BuiltPath],
     pub(crate) fill_rule: FillRule,
 }
 
@@ -67,7 +66,11 @@ impl<'a, 'b> Tiler<'a, 'b> {
             .bounds()
             .intersection(view_box)
             .unwrap_or(RectF::default());
-        let object_builder = ObjectBuilder::new(bounds, view_box, fill_rule, &path_info);
+        let object_builder = ObjectBuilder::new(bounds,
+                                                view_box,
+                                                fill_rule,
+                                                scene_builder.tile_size(),
+                                                &path_info);
 
         Tiler {
             scene_builder,
@@ -107,7 +110,8 @@ impl<'a, 'b> Tiler<'a, 'b> {
         self.process_old_active_edges(strip_origin_y);
 
         // Add new active edges.
-        let strip_max_y = ((i32::from(strip_origin_y) + 1) * TILE_HEIGHT as i32) as f32;
+        let tile_size = self.object_builder.tile_size.get() as i32;
+        let strip_max_y = ((i32::from(strip_origin_y) + 1) * tile_size) as f32;
         while let Some(queued_endpoint) = self.point_queue.peek() {
             // We're done when we see an endpoint that belongs to the next tile strip.
             //
@@ -185,7 +189,8 @@ impl<'a, 'b> Tiler<'a, 'b> {
         // FIXME(pcwalton): Yuck.
         let mut last_segment_x = -9999.0;
 
-        let tile_top = (i32::from(tile_y) * TILE_HEIGHT as i32) as f32;
+        let tile_size = self.object_builder.tile_size.get() as i32;
+        let tile_top = (i32::from(tile_y) * tile_size) as f32;
 
         debug!("---------- tile y {}({}) ----------", tile_y, tile_top);
         debug!("old active edges: {:#?}", self.old_active_edges);
@@ -221,11 +226,11 @@ impl<'a, 'b> Tiler<'a, 'b> {
             last_segment_x = segment_x;
 
             // Do initial subtile fill, if necessary.
-            let segment_tile_x = f32::floor(segment_x) as i32 / TILE_WIDTH as i32;
+            let segment_tile_x = f32::floor(segment_x) as i32 / tile_size;
             if current_tile_x < segment_tile_x && current_subtile_x > 0.0 {
                 let current_x =
-                    (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32 + current_subtile_x;
-                let tile_right_x = ((i32::from(current_tile_x) + 1) * TILE_WIDTH as i32) as f32;
+                    (i32::from(current_tile_x) * tile_size) as f32 + current_subtile_x;
+                let tile_right_x = ((i32::from(current_tile_x) + 1) * tile_size) as f32;
                 let current_tile_coords = vec2i(current_tile_x, tile_y);
                 self.object_builder.add_active_fill(
                     self.scene_builder,
@@ -259,10 +264,10 @@ impl<'a, 'b> Tiler<'a, 'b> {
             // Do final subtile fill, if necessary.
             debug_assert_eq!(current_tile_x, segment_tile_x);
             let segment_subtile_x =
-                segment_x - (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32;
+                segment_x - (i32::from(current_tile_x) * tile_size) as f32;
             if segment_subtile_x > current_subtile_x {
                 let current_x =
-                    (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32 + current_subtile_x;
+                    (i32::from(current_tile_x) * tile_size) as f32 + current_subtile_x;
                 let current_tile_coords = vec2i(current_tile_x, tile_y);
                 self.object_builder.add_active_fill(
                     self.scene_builder,
@@ -399,7 +404,8 @@ pub(crate) struct PackedTile<'a> {
     pub(crate) tile_type: TileType,
     pub(crate) tile_coords: Vector2I,
     pub(crate) draw_tile: &'a TileObjectPrimitive,
-    pub(crate) clip_tile: Option<&'a TileObjectPrimitive>,
+    // Masks that the draw tile is intersected with. Empty if the tile isn't clipped.
+    pub(crate) clip_tiles: Vec<&'a TileObjectPrimitive>,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -425,7 +431,7 @@ impl<'a> PackedTile<'a> {
                         tile_type: TileType::Empty,
                         tile_coords,
                         draw_tile,
-                        clip_tile: None,
+                        clip_tiles: vec![],
                     };
                 }
                 (FillRule::Winding, _) => {}
@@ -434,90 +440,70 @@ impl<'a> PackedTile<'a> {
                         tile_type: TileType::Empty,
                         tile_coords,
                         draw_tile,
-                        clip_tile: None,
+                        clip_tiles: vec![],
                     };
                 }
                 (FillRule::EvenOdd, _) => {}
             }
         }
 
-        // Figure out what clip tile we need, if any.
-        let clip_tile = match draw_tiling_path_info.built_clip_path {
-            None => None,
-            Some(built_clip_path) => {
-                match built_clip_path.tiles.get(tile_coords) {
-                    None => {
-                        // This tile is outside of the bounds of the clip path entirely. We can
-                        // cull it.
+        // Figure out what clip tiles we need, if any. Every clip path in the chain must cover
+        // this tile for it to be visible.
+        let mut clip_tiles = vec![];
+        for built_clip_path in draw_tiling_path_info.built_clip_paths {
+            match built_clip_path.tiles.get(tile_coords) {
+                None => {
+                    // This tile is outside of the bounds of the clip path entirely. We can
+                    // cull it.
+                    return PackedTile {
+                        tile_type: TileType::Empty,
+                        tile_coords,
+                        draw_tile,
+                        clip_tiles: vec![],
+                    };
+                }
+                Some(clip_tile) if clip_tile.is_solid() => {
+                    if clip_tile.backdrop == 0 {
+                        // This tile is completely clipped out. Cull it.
                         return PackedTile {
                             tile_type: TileType::Empty,
                             tile_coords,
                             draw_tile,
-                            clip_tile: None,
+                            clip_tiles: vec![],
                         };
                     }
-                    Some(clip_tile) if clip_tile.is_solid() => {
-                        if clip_tile.backdrop != 0 {
-                            // The clip tile is fully opaque, so this tile isn't clipped at
-                            // all.
-                            None
-                        } else {
-                            // This tile is completely clipped out. Cull it.
-                            return PackedTile {
-                                tile_type: TileType::Empty,
-                                tile_coords,
-                                draw_tile,
-                                clip_tile: None,
-                            };
-                        }
-                    }
-                    Some(clip_tile) => Some(clip_tile),
+                    // Otherwise, the clip tile is fully opaque, so this clip path doesn't clip
+                    // this tile at all.
                 }
+                Some(clip_tile) => clip_tiles.push(clip_tile),
             }
-        };
+        }
 
         // Choose a tile type.
-        match clip_tile {
-            None if draw_tile.is_solid() => {
+        if clip_tiles.is_empty() {
+            if draw_tile.is_solid() {
                 // This is a solid tile that completely occludes the background.
-                PackedTile { tile_type: TileType::Solid, tile_coords, draw_tile, clip_tile }
-            }
-            None => {
+                PackedTile { tile_type: TileType::Solid, tile_coords, draw_tile, clip_tiles }
+            } else {
                 // We have a draw tile and no clip tile.
-                PackedTile {
-                    tile_type: TileType::SingleMask,
-                    tile_coords,
-                    draw_tile,
-                    clip_tile: None,
-                }
-            }
-            Some(clip_tile) if draw_tile.is_solid() => {
-                // We have a solid draw tile and a clip tile. This is effectively the same as
-                // having a draw tile and no clip tile.
-                //
-                // FIXME(pcwalton): This doesn't preserve the fill rule of the clip path!
-                PackedTile {
-                    tile_type: TileType::SingleMask,
-                    tile_coords,
-                    draw_tile: clip_tile,
-                    clip_tile: None,
-                }
-            }
-            Some(clip_tile) => {
-                // We have both a draw and clip mask. Composite them together.
-                PackedTile {
-                    tile_type: TileType::SingleMask,
-                    tile_coords,
-                    draw_tile,
-                    clip_tile: Some(clip_tile),
-                }
+                PackedTile { tile_type: TileType::SingleMask, tile_coords, draw_tile, clip_tiles }
             }
+        } else if draw_tile.is_solid() {
+            // We have a solid draw tile and clip tiles. This is effectively the same as drawing
+            // the first clip tile, clipped by the rest.
+            //
+            // FIXME(pcwalton): This doesn't preserve the fill rule of the clip path!
+            let draw_tile = clip_tiles.remove(0);
+            PackedTile { tile_type: TileType::SingleMask, tile_coords, draw_tile, clip_tiles }
+        } else {
+            // We have both a draw and clip masks. Composite them together.
+            PackedTile { tile_type: TileType::SingleMask, tile_coords, draw_tile, clip_tiles }
         }
     }
 }
 
-pub fn round_rect_out_to_tile_bounds(rect: RectF) -> RectI {
-    (rect * vec2f(1.0 / TILE_WIDTH as f32, 1.0 / TILE_HEIGHT as f32)).round_out().to_i32()
+pub fn round_rect_out_to_tile_bounds(rect: RectF, tile_size: TileSize) -> RectI {
+    (rect * Vector2F::splat(1.0 / tile_size.get() as f32)).round_out().to_i32()
 }
 
 fn process_active_segment(
@@ -581,7 +567,8 @@ impl ActiveEdge {
                builder: &SceneBuilder,
                object_builder: &mut ObjectBuilder,
                tile_y: i32) {
-        let tile_bottom = ((i32::from(tile_y) + 1) * TILE_HEIGHT as i32) as f32;
+        let tile_size = object_builder.tile_size.get() as i32;
+        let tile_bottom = ((i32::from(tile_y) + 1) * tile_size) as f32;
         debug!(
             "process_active_edge({:#?}, tile_y={}({}))",
             self, tile_y, tile_bottom
@@ -664,7 +651,8 @@ impl ActiveEdge {
         object_builder: &mut ObjectBuilder,
         tile_y: i32,
     ) -> Option<LineSegment2F> {
-        let tile_bottom = ((i32::from(tile_y) + 1) * TILE_HEIGHT as i32) as f32;
+        let tile_size = object_builder.tile_size.get() as i32;
+        let tile_bottom = ((i32::from(tile_y) + 1) * tile_size) as f32;
         debug!(
             "process_line_segment({:?}, tile_y={}) tile_bottom={}",
             line_segment, tile_y, tile_bottom
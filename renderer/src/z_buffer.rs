@@ -12,6 +12,7 @@
 
 use crate::builder::Occluder;
 use crate::gpu_data::{Tile, TileBatch};
+use crate::options::TileSize;
 use crate::paint::{PaintId, PaintMetadata};
 use crate::tile_map::DenseTileMap;
 use crate::tiles;
@@ -34,8 +35,8 @@ pub(crate) struct DepthMetadata {
     pub(crate) paint_id: PaintId,
 }
 impl ZBuffer {
-    pub(crate) fn new(view_box: RectF) -> ZBuffer {
-        let tile_rect = tiles::round_rect_out_to_tile_bounds(view_box);
+    pub(crate) fn new(view_box: RectF, tile_size: TileSize) -> ZBuffer {
+        let tile_rect = tiles::round_rect_out_to_tile_bounds(view_box, tile_size);
         ZBuffer {
             buffer: DenseTileMap::from_builder(|_| 0, tile_rect),
             depth_metadata: VecMap::new(),
@@ -37,8 +37,9 @@ vec2 computeTileOffset(uint tileIndex, float stencilTextureWidth){
 void main(){
     vec2 tileOrigin = computeTileOffset(aTileIndex, uFramebufferSize . x);
 
-    vec2 from = vec2(aFromPx & 15u, aFromPx >> 4u)+ aFromSubpx;
-    vec2 to = vec2(aToPx & 15u, aToPx >> 4u)+ aToSubpx;
+    vec2 fillScale = uTileSize / 16.0;
+    vec2 from =(vec2(aFromPx & 15u, aFromPx >> 4u)+ aFromSubpx)* fillScale;
+    vec2 to =(vec2(aToPx & 15u, aToPx >> 4u)+ aToSubpx)* fillScale;
 
     vec2 position;
     if(aTessCoord . x == 0u)
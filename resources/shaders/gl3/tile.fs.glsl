@@ -87,6 +87,8 @@ uniform sampler2D uGammaLUT;
 uniform vec4 uFilterParams0;
 uniform vec4 uFilterParams1;
 uniform vec4 uFilterParams2;
+uniform vec4 uFilterParams3;
+uniform vec4 uFilterParams4;
 uniform vec2 uFramebufferSize;
 uniform vec2 uColorTexture0Size;
 uniform int uCtrl;
@@ -298,6 +300,26 @@ vec4 filterText(vec2 colorTexCoord,
 
 
 
+
+float applyGradientSpread(float t, int spread){
+    switch(spread){
+    case 1 :
+        return fract(t);
+    case 2 :
+        return 1.0 - abs(mod(t, 2.0)- 1.0);
+    }
+    return clamp(t, 0.0, 1.0);
+}
+
+
+
+
+
+
+vec4 filterLinearGradient(vec2 colorTexCoord, sampler2D colorTexture, vec4 filterParams0){
+    float t = applyGradientSpread(colorTexCoord . x, int(filterParams0 . x));
+    return texture(colorTexture, vec2(t, colorTexCoord . y));
+}
 
 vec4 filterRadialGradient(vec2 colorTexCoord,
                           sampler2D colorTexture,
@@ -305,7 +327,8 @@ vec4 filterRadialGradient(vec2 colorTexCoord,
                           vec2 fragCoord,
                           vec2 framebufferSize,
                           vec4 filterParams0,
-                          vec4 filterParams1){
+                          vec4 filterParams1,
+                          vec4 filterParams2){
     vec2 lineFrom = filterParams0 . xy, lineVector = filterParams0 . zw;
     vec2 radii = filterParams1 . xy, uvOrigin = filterParams1 . zw;
 
@@ -323,12 +346,25 @@ vec4 filterRadialGradient(vec2 colorTexCoord,
         if(ts . x > ts . y)
             ts = ts . yx;
         float t = ts . x >= 0.0 ? ts . x : ts . y;
-        color = texture(colorTexture, uvOrigin + vec2(clamp(t, 0.0, 1.0), 0.0));
+        t = applyGradientSpread(t, int(filterParams2 . x));
+        color = texture(colorTexture, uvOrigin + vec2(t, 0.0));
     }
 
     return color;
 }
 
+vec4 filterConicGradient(vec2 colorTexCoord,
+                         sampler2D colorTexture,
+                         vec4 filterParams0,
+                         vec4 filterParams1){
+    vec2 center = filterParams0 . xy, uvOrigin = filterParams1 . xy;
+    float angle = filterParams0 . z;
+
+    vec2 dP = colorTexCoord - center;
+    float t = fract((atan(dP . y, dP . x)- angle)/ 6.283185307179586);
+    return texture(colorTexture, uvOrigin + vec2(t, 0.0));
+}
+
 
 
 
@@ -374,6 +410,118 @@ vec4 filterBlur(vec2 colorTexCoord,
     return color / gaussSum;
 }
 
+vec4 filterLuminanceToAlpha(vec2 colorTexCoord, sampler2D colorTexture){
+    vec4 color = texture(colorTexture, colorTexCoord);
+    return vec4(vec3(0.0), dot(color . rgb, vec3(0.2125, 0.7154, 0.0721)));
+}
+
+
+
+
+vec4 filterUnpremultiply(vec4 color){
+    return color . a > 0.0 ? vec4(color . rgb / color . a, color . a): vec4(0.0);
+}
+
+
+
+
+
+
+
+
+
+
+vec4 filterColorMatrix(vec2 colorTexCoord,
+                       sampler2D colorTexture,
+                       vec4 filterParams0,
+                       vec4 filterParams1,
+                       vec4 filterParams2,
+                       vec4 filterParams3,
+                       vec4 filterParams4){
+    vec4 color = filterUnpremultiply(texture(colorTexture, colorTexCoord));
+    vec4 result = vec4(dot(filterParams0, color),
+                       dot(filterParams1, color),
+                       dot(filterParams2, color),
+                       dot(filterParams3, color))+ filterParams4;
+    return clamp(result, 0.0, 1.0);
+}
+
+
+
+
+
+
+
+
+
+float filterTransferChannel(float value, vec4 params, int function){
+    int count = function >> 3;
+    switch(function & 0x7){
+    case 1 :
+        {
+            float position = value * float(count - 1);
+            int index = min(int(position), count - 2);
+            return mix(params[index], params[index + 1], position - float(index));
+        }
+    case 2 :
+        return params[min(int(value * float(count)), count - 1)];
+    case 3 :
+        return params . x * value + params . y;
+    case 4 :
+        return params . x * pow(value, params . y)+ params . z;
+    }
+    return value;
+}
+
+
+
+
+
+
+
+
+vec4 filterComponentTransfer(vec2 colorTexCoord,
+                             sampler2D colorTexture,
+                             vec4 filterParams0,
+                             vec4 filterParams1,
+                             vec4 filterParams2,
+                             vec4 filterParams3,
+                             vec4 filterParams4){
+    vec4 color = filterUnpremultiply(texture(colorTexture, colorTexCoord));
+    ivec4 functions = ivec4(filterParams4);
+    vec4 result = vec4(filterTransferChannel(color . r, filterParams0, functions . x),
+                       filterTransferChannel(color . g, filterParams1, functions . y),
+                       filterTransferChannel(color . b, filterParams2, functions . z),
+                       filterTransferChannel(color . a, filterParams3, functions . w));
+    return clamp(result, 0.0, 1.0);
+}
+
+
+
+
+
+
+vec4 filterMorphology(vec2 colorTexCoord,
+                      sampler2D colorTexture,
+                      vec2 colorTextureSize,
+                      vec4 filterParams0){
+    vec2 srcOffsetScale = filterParams0 . xy / colorTextureSize;
+    int radius = int(filterParams0 . z);
+    bool dilate = filterParams0 . w != 0.0;
+
+    vec4 color = texture(colorTexture, colorTexCoord);
+    for(int i = 1;i <= radius;i ++){
+        vec2 srcOffset = srcOffsetScale * float(i);
+        vec4 colorA = texture(colorTexture, colorTexCoord - srcOffset);
+        vec4 colorB = texture(colorTexture, colorTexCoord + srcOffset);
+        if(dilate)
+            color = max(color, max(colorA, colorB));
+        else
+            color = min(color, min(colorA, colorB));
+    }
+    return filterUnpremultiply(color);
+}
+
 vec4 filterNone(vec2 colorTexCoord, sampler2D colorTexture){
     return sampleColor(colorTexture, colorTexCoord);
 }
@@ -387,6 +535,8 @@ vec4 filterColor(vec2 colorTexCoord,
                  vec4 filterParams0,
                  vec4 filterParams1,
                  vec4 filterParams2,
+                 vec4 filterParams3,
+                 vec4 filterParams4,
                  int colorFilter){
     switch(colorFilter){
     case 0x1 :
@@ -396,7 +546,12 @@ vec4 filterColor(vec2 colorTexCoord,
                                     fragCoord,
                                     framebufferSize,
                                     filterParams0,
-                                    filterParams1);
+                                    filterParams1,
+                                    filterParams2);
+    case 0x5 :
+        return filterLinearGradient(colorTexCoord, colorTexture, filterParams0);
+    case 0x6 :
+        return filterConicGradient(colorTexCoord, colorTexture, filterParams0, filterParams1);
     case 0x3 :
         return filterBlur(colorTexCoord,
                           colorTexture,
@@ -411,6 +566,28 @@ vec4 filterColor(vec2 colorTexCoord,
                           filterParams0,
                           filterParams1,
                           filterParams2);
+    case 0x4 :
+        return filterLuminanceToAlpha(colorTexCoord, colorTexture);
+    case 0x7 :
+        return filterColorMatrix(colorTexCoord,
+                                 colorTexture,
+                                 filterParams0,
+                                 filterParams1,
+                                 filterParams2,
+                                 filterParams3,
+                                 filterParams4);
+    case 0x8 :
+        return filterComponentTransfer(colorTexCoord,
+                                       colorTexture,
+                                       filterParams0,
+                                       filterParams1,
+                                       filterParams2,
+                                       filterParams3,
+                                       filterParams4);
+    case 0x9 :
+        return filterMorphology(colorTexCoord, colorTexture, colorTextureSize, filterParams0);
+    case 0xa :
+        return filterUnpremultiply(texture(colorTexture, colorTexCoord));
     }
     return filterNone(colorTexCoord, colorTexture);
 }
@@ -566,10 +743,10 @@ void calculateColor(int tileCtrl, int ctrl){
 
 
     vec4 color = vBaseColor;
-    int color0Combine =(ctrl >> 6)&
+    int color0Combine =(ctrl >> 8)&
                                        0x3;
     if(color0Combine != 0){
-        int color0Filter =(ctrl >> 4)& 0x3;
+        int color0Filter =(ctrl >> 4)& 0xf;
         vec4 color0 = filterColor(vColorTexCoord0,
                                   uColorTexture0,
                                   uGammaLUT,
@@ -579,6 +756,8 @@ void calculateColor(int tileCtrl, int ctrl){
                                   uFilterParams0,
                                   uFilterParams1,
                                   uFilterParams2,
+                                  uFilterParams3,
+                                  uFilterParams4,
                                   color0Filter);
         color = combineColor0(color, color0, color0Combine);
     }
@@ -587,7 +766,7 @@ void calculateColor(int tileCtrl, int ctrl){
     color . a *= maskAlpha;
 
 
-    int compositeOp =(ctrl >> 8)& 0xf;
+    int compositeOp =(ctrl >> 10)& 0xf;
     color = composite(color, uDestTexture, uFramebufferSize, gl_FragCoord . xy, compositeOp);
 
 
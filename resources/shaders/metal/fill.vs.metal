@@ -42,8 +42,9 @@ vertex main0_out main0(main0_in in [[stage_in]], constant spvDescriptorSetBuffer
     uint param = in.aTileIndex;
     float param_1 = (*spvDescriptorSet0.uFramebufferSize).x;
     float2 tileOrigin = computeTileOffset(param, param_1, (*spvDescriptorSet0.uTileSize));
-    float2 from = float2(float(in.aFromPx & 15u), float(in.aFromPx >> 4u)) + in.aFromSubpx;
-    float2 to = float2(float(in.aToPx & 15u), float(in.aToPx >> 4u)) + in.aToSubpx;
+    float2 fillScale = (*spvDescriptorSet0.uTileSize) / float2(16.0);
+    float2 from = (float2(float(in.aFromPx & 15u), float(in.aFromPx >> 4u)) + in.aFromSubpx) * fillScale;
+    float2 to = (float2(float(in.aToPx & 15u), float(in.aToPx >> 4u)) + in.aToSubpx) * fillScale;
     float2 position;
     if (in.aTessCoord.x == 0u)
     {
@@ -19,9 +19,11 @@ struct spvDescriptorSetBuffer0
     constant float4* uFilterParams0 [[id(8)]];
     constant float4* uFilterParams1 [[id(9)]];
     constant float4* uFilterParams2 [[id(10)]];
-    texture2d<float> uDestTexture [[id(11)]];
-    sampler uDestTextureSmplr [[id(12)]];
-    constant int* uCtrl [[id(13)]];
+    constant float4* uFilterParams3 [[id(11)]];
+    constant float4* uFilterParams4 [[id(12)]];
+    texture2d<float> uDestTexture [[id(13)]];
+    sampler uDestTextureSmplr [[id(14)]];
+    constant int* uCtrl [[id(15)]];
 };
 
 constant float3 _1041 = {};
@@ -64,7 +66,31 @@ float sampleMask(thread const float& maskAlpha, thread const texture2d<float> ma
     return fast::min(maskAlpha, coverage);
 }
 
-float4 filterRadialGradient(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const float2& colorTextureSize, thread const float2& fragCoord, thread const float2& framebufferSize, thread const float4& filterParams0, thread const float4& filterParams1)
+float applyGradientSpread(thread const float& t, thread const int& spread)
+{
+    switch (spread)
+    {
+        case 1:
+        {
+            return fract(t);
+        }
+        case 2:
+        {
+            return 1.0 - abs(mod(t, 2.0) - 1.0);
+        }
+    }
+    return fast::clamp(t, 0.0, 1.0);
+}
+
+float4 filterLinearGradient(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const float4& filterParams0)
+{
+    float param = colorTexCoord.x;
+    int param_1 = int(filterParams0.x);
+    float t = applyGradientSpread(param, param_1);
+    return colorTexture.sample(colorTextureSmplr, float2(t, colorTexCoord.y));
+}
+
+float4 filterRadialGradient(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const float2& colorTextureSize, thread const float2& fragCoord, thread const float2& framebufferSize, thread const float4& filterParams0, thread const float4& filterParams1, thread const float4& filterParams2)
 {
     float2 lineFrom = filterParams0.xy;
     float2 lineVector = filterParams0.zw;
@@ -95,11 +121,24 @@ float4 filterRadialGradient(thread const float2& colorTexCoord, thread const tex
             _554 = ts.y;
         }
         float t = _554;
-        color = colorTexture.sample(colorTextureSmplr, (uvOrigin + float2(fast::clamp(t, 0.0, 1.0), 0.0)));
+        float param = t;
+        int param_1 = int(filterParams2.x);
+        t = applyGradientSpread(param, param_1);
+        color = colorTexture.sample(colorTextureSmplr, (uvOrigin + float2(t, 0.0)));
     }
     return color;
 }
 
+float4 filterConicGradient(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const float4& filterParams0, thread const float4& filterParams1)
+{
+    float2 center = filterParams0.xy;
+    float2 uvOrigin = filterParams1.xy;
+    float angle = filterParams0.z;
+    float2 dP = colorTexCoord - center;
+    float t = fract((precise::atan2(dP.y, dP.x) - angle) / 6.283185307179586);
+    return colorTexture.sample(colorTextureSmplr, (uvOrigin + float2(t, 0.0)));
+}
+
 float4 filterBlur(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const float2& colorTextureSize, thread const float4& filterParams0, thread const float4& filterParams1)
 {
     float2 srcOffsetScale = filterParams0.xy / colorTextureSize;
@@ -245,13 +284,113 @@ float4 sampleColor(thread const texture2d<float> colorTexture, thread const samp
     return colorTexture.sample(colorTextureSmplr, colorTexCoord);
 }
 
+float4 filterLuminanceToAlpha(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr)
+{
+    float4 color = colorTexture.sample(colorTextureSmplr, colorTexCoord);
+    return float4(float3(0.0), dot(color.xyz, float3(0.2125, 0.7154, 0.0721)));
+}
+
+float4 filterUnpremultiply(thread const float4& color)
+{
+    float4 _1412;
+    if (color.w > 0.0)
+    {
+        _1412 = float4(color.xyz / float3(color.w), color.w);
+    }
+    else
+    {
+        _1412 = float4(0.0);
+    }
+    return _1412;
+}
+
+float4 filterColorMatrix(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const float4& filterParams0, thread const float4& filterParams1, thread const float4& filterParams2, thread const float4& filterParams3, thread const float4& filterParams4)
+{
+    float4 param = colorTexture.sample(colorTextureSmplr, colorTexCoord);
+    float4 color = filterUnpremultiply(param);
+    float4 result = float4(dot(filterParams0, color), dot(filterParams1, color), dot(filterParams2, color), dot(filterParams3, color)) + filterParams4;
+    return fast::clamp(result, float4(0.0), float4(1.0));
+}
+
+float filterTransferChannel(thread const float& value, thread const float4& params, thread const int& function)
+{
+    int count = function >> 3;
+    switch (function & 7)
+    {
+        case 1:
+        {
+            float position = value * float(count - 1);
+            int index = min(int(position), (count - 2));
+            return mix(params[index], params[index + 1], position - float(index));
+        }
+        case 2:
+        {
+            return params[min(int(value * float(count)), (count - 1))];
+        }
+        case 3:
+        {
+            return (params.x * value) + params.y;
+        }
+        case 4:
+        {
+            return (params.x * pow(value, params.y)) + params.z;
+        }
+    }
+    return value;
+}
+
+float4 filterComponentTransfer(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const float4& filterParams0, thread const float4& filterParams1, thread const float4& filterParams2, thread const float4& filterParams3, thread const float4& filterParams4)
+{
+    float4 param = colorTexture.sample(colorTextureSmplr, colorTexCoord);
+    float4 color = filterUnpremultiply(param);
+    int4 functions = int4(filterParams4);
+    float param_1 = color.x;
+    float4 param_2 = filterParams0;
+    int param_3 = functions.x;
+    float param_4 = color.y;
+    float4 param_5 = filterParams1;
+    int param_6 = functions.y;
+    float param_7 = color.z;
+    float4 param_8 = filterParams2;
+    int param_9 = functions.z;
+    float param_10 = color.w;
+    float4 param_11 = filterParams3;
+    int param_12 = functions.w;
+    float4 result = float4(filterTransferChannel(param_1, param_2, param_3), filterTransferChannel(param_4, param_5, param_6), filterTransferChannel(param_7, param_8, param_9), filterTransferChannel(param_10, param_11, param_12));
+    return fast::clamp(result, float4(0.0), float4(1.0));
+}
+
+float4 filterMorphology(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const float2& colorTextureSize, thread const float4& filterParams0)
+{
+    float2 srcOffsetScale = filterParams0.xy / colorTextureSize;
+    int radius = int(filterParams0.z);
+    bool dilate = filterParams0.w != 0.0;
+    float4 color = colorTexture.sample(colorTextureSmplr, colorTexCoord);
+    for (int i = 1; i <= radius; i++)
+    {
+        float2 srcOffset = srcOffsetScale * float(i);
+        float4 colorA = colorTexture.sample(colorTextureSmplr, (colorTexCoord - srcOffset));
+        float4 colorB = colorTexture.sample(colorTextureSmplr, (colorTexCoord + srcOffset));
+        if (dilate)
+        {
+            color = fast::max(color, fast::max(colorA, colorB));
+        }
+        else
+        {
+            color = fast::min(color, fast::min(colorA, colorB));
+        }
+    }
+    float4 param = color;
+    return filterUnpremultiply(param);
+}
+
 float4 filterNone(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr)
 {
     float2 param = colorTexCoord;
     return sampleColor(colorTexture, colorTextureSmplr, param);
 }
 
-float4 filterColor(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const texture2d<float> gammaLUT, thread const sampler gammaLUTSmplr, thread const float2& colorTextureSize, thread const float2& fragCoord, thread const float2& framebufferSize, thread const float4& filterParams0, thread const float4& filterParams1, thread const float4& filterParams2, thread const int& colorFilter)
+float4 filterColor(thread const float2& colorTexCoord, thread const texture2d<float> colorTexture, thread const sampler colorTextureSmplr, thread const texture2d<float> gammaLUT, thread const sampler gammaLUTSmplr, thread const float2& colorTextureSize, thread const float2& fragCoord, thread const float2& framebufferSize, thread const float4& filterParams0, thread const float4& filterParams1, thread const float4& filterParams2, thread const float4& filterParams3, thread const float4& filterParams4, thread const int& colorFilter)
 {
     switch (colorFilter)
     {
@@ -263,7 +402,21 @@ float4 filterColor(thread const float2& colorTexCoord, thread const texture2d<fl
             float2 param_3 = framebufferSize;
             float4 param_4 = filterParams0;
             float4 param_5 = filterParams1;
-            return filterRadialGradient(param, colorTexture, colorTextureSmplr, param_1, param_2, param_3, param_4, param_5);
+            float4 param_17 = filterParams2;
+            return filterRadialGradient(param, colorTexture, colorTextureSmplr, param_1, param_2, param_3, param_4, param_5, param_17);
+        }
+        case 5:
+        {
+            float2 param_18 = colorTexCoord;
+            float4 param_19 = filterParams0;
+            return filterLinearGradient(param_18, colorTexture, colorTextureSmplr, param_19);
+        }
+        case 6:
+        {
+            float2 param_20 = colorTexCoord;
+            float4 param_21 = filterParams0;
+            float4 param_22 = filterParams1;
+            return filterConicGradient(param_20, colorTexture, colorTextureSmplr, param_21, param_22);
         }
         case 3:
         {
@@ -282,6 +435,43 @@ float4 filterColor(thread const float2& colorTexCoord, thread const texture2d<fl
             float4 param_14 = filterParams2;
             return filterText(param_10, colorTexture, colorTextureSmplr, gammaLUT, gammaLUTSmplr, param_11, param_12, param_13, param_14);
         }
+        case 4:
+        {
+            float2 param_16 = colorTexCoord;
+            return filterLuminanceToAlpha(param_16, colorTexture, colorTextureSmplr);
+        }
+        case 7:
+        {
+            float2 param_23 = colorTexCoord;
+            float4 param_24 = filterParams0;
+            float4 param_25 = filterParams1;
+            float4 param_26 = filterParams2;
+            float4 param_27 = filterParams3;
+            float4 param_28 = filterParams4;
+            return filterColorMatrix(param_23, colorTexture, colorTextureSmplr, param_24, param_25, param_26, param_27, param_28);
+        }
+        case 8:
+        {
+            float2 param_29 = colorTexCoord;
+            float4 param_30 = filterParams0;
+            float4 param_31 = filterParams1;
+            float4 param_32 = filterParams2;
+            float4 param_33 = filterParams3;
+            float4 param_34 = filterParams4;
+            return filterComponentTransfer(param_29, colorTexture, colorTextureSmplr, param_30, param_31, param_32, param_33, param_34);
+        }
+        case 9:
+        {
+            float2 param_35 = colorTexCoord;
+            float2 param_36 = colorTextureSize;
+            float4 param_37 = filterParams0;
+            return filterMorphology(param_35, colorTexture, colorTextureSmplr, param_36, param_37);
+        }
+        case 10:
+        {
+            float4 param_38 = colorTexture.sample(colorTextureSmplr, colorTexCoord);
+            return filterUnpremultiply(param_38);
+        }
     }
     float2 param_15 = colorTexCoord;
     return filterNone(param_15, colorTexture, colorTextureSmplr);
@@ -546,7 +736,7 @@ float4 composite(thread const float4& srcColor, thread const texture2d<float> de
     return float4(((srcColor.xyz * (srcColor.w * (1.0 - destColor.w))) + (blendedRGB * (srcColor.w * destColor.w))) + (destColor.xyz * (1.0 - srcColor.w)), 1.0);
 }
 
-void calculateColor(thread const int& tileCtrl, thread const int& ctrl, thread texture2d<float> uMaskTexture0, thread const sampler uMaskTexture0Smplr, thread float3& vMaskTexCoord0, thread float4& vBaseColor, thread float2& vColorTexCoord0, thread texture2d<float> uColorTexture0, thread const sampler uColorTexture0Smplr, thread texture2d<float> uGammaLUT, thread const sampler uGammaLUTSmplr, thread float2 uColorTexture0Size, thread float4& gl_FragCoord, thread float2 uFramebufferSize, thread float4 uFilterParams0, thread float4 uFilterParams1, thread float4 uFilterParams2, thread texture2d<float> uDestTexture, thread const sampler uDestTextureSmplr, thread float4& oFragColor)
+void calculateColor(thread const int& tileCtrl, thread const int& ctrl, thread texture2d<float> uMaskTexture0, thread const sampler uMaskTexture0Smplr, thread float3& vMaskTexCoord0, thread float4& vBaseColor, thread float2& vColorTexCoord0, thread texture2d<float> uColorTexture0, thread const sampler uColorTexture0Smplr, thread texture2d<float> uGammaLUT, thread const sampler uGammaLUTSmplr, thread float2 uColorTexture0Size, thread float4& gl_FragCoord, thread float2 uFramebufferSize, thread float4 uFilterParams0, thread float4 uFilterParams1, thread float4 uFilterParams2, thread float4 uFilterParams3, thread float4 uFilterParams4, thread texture2d<float> uDestTexture, thread const sampler uDestTextureSmplr, thread float4& oFragColor)
 {
     int maskCtrl0 = (tileCtrl >> 0) & 3;
     float maskAlpha = 1.0;
@@ -555,10 +745,10 @@ void calculateColor(thread const int& tileCtrl, thread const int& ctrl, thread t
     int param_2 = maskCtrl0;
     maskAlpha = sampleMask(param, uMaskTexture0, uMaskTexture0Smplr, param_1, param_2);
     float4 color = vBaseColor;
-    int color0Combine = (ctrl >> 6) & 3;
+    int color0Combine = (ctrl >> 8) & 3;
     if (color0Combine != 0)
     {
-        int color0Filter = (ctrl >> 4) & 3;
+        int color0Filter = (ctrl >> 4) & 15;
         float2 param_3 = vColorTexCoord0;
         float2 param_4 = uColorTexture0Size;
         float2 param_5 = gl_FragCoord.xy;
@@ -566,15 +756,17 @@ void calculateColor(thread const int& tileCtrl, thread const int& ctrl, thread t
         float4 param_7 = uFilterParams0;
         float4 param_8 = uFilterParams1;
         float4 param_9 = uFilterParams2;
+        float4 param_18 = uFilterParams3;
+        float4 param_19 = uFilterParams4;
         int param_10 = color0Filter;
-        float4 color0 = filterColor(param_3, uColorTexture0, uColorTexture0Smplr, uGammaLUT, uGammaLUTSmplr, param_4, param_5, param_6, param_7, param_8, param_9, param_10);
+        float4 color0 = filterColor(param_3, uColorTexture0, uColorTexture0Smplr, uGammaLUT, uGammaLUTSmplr, param_4, param_5, param_6, param_7, param_8, param_9, param_18, param_19, param_10);
         float4 param_11 = color;
         float4 param_12 = color0;
         int param_13 = color0Combine;
         color = combineColor0(param_11, param_12, param_13);
     }
     color.w *= maskAlpha;
-    int compositeOp = (ctrl >> 8) & 15;
+    int compositeOp = (ctrl >> 10) & 15;
     float4 param_14 = color;
     float2 param_15 = uFramebufferSize;
     float2 param_16 = gl_FragCoord.xy;
@@ -590,7 +782,7 @@ fragment main0_out main0(main0_in in [[stage_in]], constant spvDescriptorSetBuff
     main0_out out = {};
     int param = int(in.vTileCtrl);
     int param_1 = (*spvDescriptorSet0.uCtrl);
-    calculateColor(param, param_1, spvDescriptorSet0.uMaskTexture0, spvDescriptorSet0.uMaskTexture0Smplr, in.vMaskTexCoord0, in.vBaseColor, in.vColorTexCoord0, spvDescriptorSet0.uColorTexture0, spvDescriptorSet0.uColorTexture0Smplr, spvDescriptorSet0.uGammaLUT, spvDescriptorSet0.uGammaLUTSmplr, (*spvDescriptorSet0.uColorTexture0Size), gl_FragCoord, (*spvDescriptorSet0.uFramebufferSize), (*spvDescriptorSet0.uFilterParams0), (*spvDescriptorSet0.uFilterParams1), (*spvDescriptorSet0.uFilterParams2), spvDescriptorSet0.uDestTexture, spvDescriptorSet0.uDestTextureSmplr, out.oFragColor);
+    calculateColor(param, param_1, spvDescriptorSet0.uMaskTexture0, spvDescriptorSet0.uMaskTexture0Smplr, in.vMaskTexCoord0, in.vBaseColor, in.vColorTexCoord0, spvDescriptorSet0.uColorTexture0, spvDescriptorSet0.uColorTexture0Smplr, spvDescriptorSet0.uGammaLUT, spvDescriptorSet0.uGammaLUTSmplr, (*spvDescriptorSet0.uColorTexture0Size), gl_FragCoord, (*spvDescriptorSet0.uFramebufferSize), (*spvDescriptorSet0.uFilterParams0), (*spvDescriptorSet0.uFilterParams1), (*spvDescriptorSet0.uFilterParams2), (*spvDescriptorSet0.uFilterParams3), (*spvDescriptorSet0.uFilterParams4), spvDescriptorSet0.uDestTexture, spvDescriptorSet0.uDestTextureSmplr, out.oFragColor);
     return out;
 }
 
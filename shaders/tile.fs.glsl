@@ -40,6 +40,7 @@ precision highp sampler2D;
 
 #define FRAC_6_PI   1.9098593171027443
 #define FRAC_PI_3   1.0471975511965976
+#define TAU         6.283185307179586
 
 #define TILE_CTRL_MASK_MASK                     0x3
 #define TILE_CTRL_MASK_WINDING                  0x1
@@ -51,10 +52,17 @@ precision highp sampler2D;
 #define COMBINER_CTRL_COLOR_COMBINE_SRC_IN      0x1
 #define COMBINER_CTRL_COLOR_COMBINE_DEST_IN     0x2
 
-#define COMBINER_CTRL_FILTER_MASK               0x3
+#define COMBINER_CTRL_FILTER_MASK               0xf
 #define COMBINER_CTRL_FILTER_RADIAL_GRADIENT    0x1
 #define COMBINER_CTRL_FILTER_TEXT               0x2
 #define COMBINER_CTRL_FILTER_BLUR               0x3
+#define COMBINER_CTRL_FILTER_LUMINANCE_TO_ALPHA 0x4
+#define COMBINER_CTRL_FILTER_LINEAR_GRADIENT    0x5
+#define COMBINER_CTRL_FILTER_CONIC_GRADIENT     0x6
+#define COMBINER_CTRL_FILTER_COLOR_MATRIX       0x7
+#define COMBINER_CTRL_FILTER_COMPONENT_TRANSFER 0x8
+#define COMBINER_CTRL_FILTER_MORPHOLOGY         0x9
+#define COMBINER_CTRL_FILTER_UNPREMULTIPLY      0xa
 
 #define COMBINER_CTRL_COMPOSITE_MASK            0xf
 #define COMBINER_CTRL_COMPOSITE_NORMAL          0x0
@@ -75,8 +83,8 @@ precision highp sampler2D;
 #define COMBINER_CTRL_COMPOSITE_LUMINOSITY      0xf
 
 #define COMBINER_CTRL_COLOR_FILTER_SHIFT        4
-#define COMBINER_CTRL_COLOR_COMBINE_SHIFT       6
-#define COMBINER_CTRL_COMPOSITE_SHIFT           8
+#define COMBINER_CTRL_COLOR_COMBINE_SHIFT       8
+#define COMBINER_CTRL_COMPOSITE_SHIFT           10
 
 uniform sampler2D uColorTexture0;
 uniform sampler2D uMaskTexture0;
@@ -85,6 +93,8 @@ uniform sampler2D uGammaLUT;
 uniform vec4 uFilterParams0;
 uniform vec4 uFilterParams1;
 uniform vec4 uFilterParams2;
+uniform vec4 uFilterParams3;
+uniform vec4 uFilterParams4;
 uniform vec2 uFramebufferSize;
 uniform vec2 uColorTexture0Size;
 uniform int uCtrl;
@@ -209,6 +219,31 @@ vec4 filterText(vec2 colorTexCoord,
 
 // Other filters
 
+#define GRADIENT_SPREAD_PAD     0
+#define GRADIENT_SPREAD_REPEAT  1
+#define GRADIENT_SPREAD_REFLECT 2
+
+// Maps a gradient parameter onto the range [0, 1] covered by the color stops.
+float applyGradientSpread(float t, int spread) {
+    switch (spread) {
+    case GRADIENT_SPREAD_REPEAT:
+        return fract(t);
+    case GRADIENT_SPREAD_REFLECT:
+        return 1.0 - abs(mod(t, 2.0) - 1.0);
+    }
+    return clamp(t, 0.0, 1.0);
+}
+
+//                | x           y           z               w
+//  --------------+-----------------------------------------------------
+//  filterParams0 | spread      -           -               -
+//  filterParams1 | -           -           -               -
+//  filterParams2 | -           -           -               -
+vec4 filterLinearGradient(vec2 colorTexCoord, sampler2D colorTexture, vec4 filterParams0) {
+    float t = applyGradientSpread(colorTexCoord.x, int(filterParams0.x));
+    return texture(colorTexture, vec2(t, colorTexCoord.y));
+}
+
 // This is based on Pixman (MIT license). Copy and pasting the excellent comment
 // from there:
 
@@ -296,14 +331,15 @@ vec4 filterText(vec2 colorTexCoord,
 //  --------------+-----------------------------------------------------
 //  filterParams0 | lineFrom.x  lineFrom.y  lineVector.x    lineVector.y
 //  filterParams1 | radii.x     radii.y     uvOrigin.x      uvOrigin.y
-//  filterParams2 | -           -           -               -
+//  filterParams2 | spread      -           -               -
 vec4 filterRadialGradient(vec2 colorTexCoord,
                           sampler2D colorTexture,
                           vec2 colorTextureSize,
                           vec2 fragCoord,
                           vec2 framebufferSize,
                           vec4 filterParams0,
-                          vec4 filterParams1) {
+                          vec4 filterParams1,
+                          vec4 filterParams2) {
     vec2 lineFrom = filterParams0.xy, lineVector = filterParams0.zw;
     vec2 radii = filterParams1.xy, uvOrigin = filterParams1.zw;
 
@@ -321,12 +357,32 @@ vec4 filterRadialGradient(vec2 colorTexCoord,
         if (ts.x > ts.y)
             ts = ts.yx;
         float t = ts.x >= 0.0 ? ts.x : ts.y;
-        color = texture(colorTexture, uvOrigin + vec2(clamp(t, 0.0, 1.0), 0.0));
+        t = applyGradientSpread(t, int(filterParams2.x));
+        color = texture(colorTexture, uvOrigin + vec2(t, 0.0));
     }
 
     return color;
 }
 
+// The angle is measured clockwise from the positive X axis, as screen space points down.
+//
+//                | x           y           z               w
+//  --------------+-----------------------------------------------------
+//  filterParams0 | center.x    center.y    angle           -
+//  filterParams1 | uvOrigin.x  uvOrigin.y  -               -
+//  filterParams2 | -           -           -               -
+vec4 filterConicGradient(vec2 colorTexCoord,
+                         sampler2D colorTexture,
+                         vec4 filterParams0,
+                         vec4 filterParams1) {
+    vec2 center = filterParams0.xy, uvOrigin = filterParams1.xy;
+    float angle = filterParams0.z;
+
+    vec2 dP = colorTexCoord - center;
+    float t = fract((atan(dP.y, dP.x) - angle) / TAU);
+    return texture(colorTexture, uvOrigin + vec2(t, 0.0));
+}
+
 //                | x             y             z             w
 //  --------------+----------------------------------------------------
 //  filterParams0 | srcOffset.x   srcOffset.y   support       -
@@ -372,6 +428,120 @@ vec4 filterBlur(vec2 colorTexCoord,
     return color / gaussSum;
 }
 
+// Uses the Rec. 709 coefficients, as SVG's `luminanceToAlpha` does. Since the texture is
+// premultiplied, this also multiplies the luminance by the alpha.
+vec4 filterLuminanceToAlpha(vec2 colorTexCoord, sampler2D colorTexture) {
+    vec4 color = texture(colorTexture, colorTexCoord);
+    return vec4(vec3(0.0), dot(color.rgb, vec3(0.2125, 0.7154, 0.0721)));
+}
+
+// The filters below expect premultiplied colors, as render targets have, and return
+// unpremultiplied ones.
+
+vec4 filterUnpremultiply(vec4 color) {
+    return color.a > 0.0 ? vec4(color.rgb / color.a, color.a) : vec4(0.0);
+}
+
+// The first four parameters hold the rows of the matrix, and the last its constant column.
+//
+//                | x          y          z          w
+//  --------------+--------------------------------------------
+//  filterParams0 | m[0][0]    m[0][1]    m[0][2]    m[0][3]
+//  filterParams1 | m[1][0]    m[1][1]    m[1][2]    m[1][3]
+//  filterParams2 | m[2][0]    m[2][1]    m[2][2]    m[2][3]
+//  filterParams3 | m[3][0]    m[3][1]    m[3][2]    m[3][3]
+//  filterParams4 | m[0][4]    m[1][4]    m[2][4]    m[3][4]
+vec4 filterColorMatrix(vec2 colorTexCoord,
+                       sampler2D colorTexture,
+                       vec4 filterParams0,
+                       vec4 filterParams1,
+                       vec4 filterParams2,
+                       vec4 filterParams3,
+                       vec4 filterParams4) {
+    vec4 color = filterUnpremultiply(texture(colorTexture, colorTexCoord));
+    vec4 result = vec4(dot(filterParams0, color),
+                       dot(filterParams1, color),
+                       dot(filterParams2, color),
+                       dot(filterParams3, color)) + filterParams4;
+    return clamp(result, 0.0, 1.0);
+}
+
+#define TRANSFER_FUNCTION_IDENTITY  0
+#define TRANSFER_FUNCTION_TABLE     1
+#define TRANSFER_FUNCTION_DISCRETE  2
+#define TRANSFER_FUNCTION_LINEAR    3
+#define TRANSFER_FUNCTION_GAMMA     4
+
+// The low 3 bits of `function` hold its kind, and the bits above them hold the number of values
+// in its table, which is at least 2.
+float filterTransferChannel(float value, vec4 params, int function) {
+    int count = function >> 3;
+    switch (function & 0x7) {
+    case TRANSFER_FUNCTION_TABLE:
+        {
+            float position = value * float(count - 1);
+            int index = min(int(position), count - 2);
+            return mix(params[index], params[index + 1], position - float(index));
+        }
+    case TRANSFER_FUNCTION_DISCRETE:
+        return params[min(int(value * float(count)), count - 1)];
+    case TRANSFER_FUNCTION_LINEAR:
+        return params.x * value + params.y;
+    case TRANSFER_FUNCTION_GAMMA:
+        return params.x * pow(value, params.y) + params.z;
+    }
+    return value;
+}
+
+//                | x          y          z          w
+//  --------------+--------------------------------------------
+//  filterParams0 | red function parameters
+//  filterParams1 | green function parameters
+//  filterParams2 | blue function parameters
+//  filterParams3 | alpha function parameters
+//  filterParams4 | redFunc    greenFunc  blueFunc   alphaFunc
+vec4 filterComponentTransfer(vec2 colorTexCoord,
+                             sampler2D colorTexture,
+                             vec4 filterParams0,
+                             vec4 filterParams1,
+                             vec4 filterParams2,
+                             vec4 filterParams3,
+                             vec4 filterParams4) {
+    vec4 color = filterUnpremultiply(texture(colorTexture, colorTexCoord));
+    ivec4 functions = ivec4(filterParams4);
+    vec4 result = vec4(filterTransferChannel(color.r, filterParams0, functions.x),
+                       filterTransferChannel(color.g, filterParams1, functions.y),
+                       filterTransferChannel(color.b, filterParams2, functions.z),
+                       filterTransferChannel(color.a, filterParams3, functions.w));
+    return clamp(result, 0.0, 1.0);
+}
+
+//                | x            y            z            w
+//  --------------+------------------------------------------------
+//  filterParams0 | srcOffset.x  srcOffset.y  radius       dilate
+//  filterParams1 | -            -            -            -
+//  filterParams2 | -            -            -            -
+vec4 filterMorphology(vec2 colorTexCoord,
+                      sampler2D colorTexture,
+                      vec2 colorTextureSize,
+                      vec4 filterParams0) {
+    vec2 srcOffsetScale = filterParams0.xy / colorTextureSize;
+    int radius = int(filterParams0.z);
+    bool dilate = filterParams0.w != 0.0;
+
+    vec4 color = texture(colorTexture, colorTexCoord);
+    for (int i = 1; i <= radius; i++) {
+        vec2 srcOffset = srcOffsetScale * float(i);
+        vec4 colorA = texture(colorTexture, colorTexCoord - srcOffset);
+        vec4 colorB = texture(colorTexture, colorTexCoord + srcOffset);
+        if (dilate)
+            color = max(color, max(colorA, colorB));
+        else
+            color = min(color, min(colorA, colorB));
+    }
+    return filterUnpremultiply(color);
+}
+
 vec4 filterNone(vec2 colorTexCoord, sampler2D colorTexture) {
     return sampleColor(colorTexture, colorTexCoord);
 }
@@ -385,6 +555,8 @@ vec4 filterColor(vec2 colorTexCoord,
                  vec4 filterParams0,
                  vec4 filterParams1,
                  vec4 filterParams2,
+                 vec4 filterParams3,
+                 vec4 filterParams4,
                  int colorFilter) {
     switch (colorFilter) {
     case COMBINER_CTRL_FILTER_RADIAL_GRADIENT:
@@ -394,7 +566,12 @@ vec4 filterColor(vec2 colorTexCoord,
                                     fragCoord,
                                     framebufferSize,
                                     filterParams0,
-                                    filterParams1);
+                                    filterParams1,
+                                    filterParams2);
+    case COMBINER_CTRL_FILTER_LINEAR_GRADIENT:
+        return filterLinearGradient(colorTexCoord, colorTexture, filterParams0);
+    case COMBINER_CTRL_FILTER_CONIC_GRADIENT:
+        return filterConicGradient(colorTexCoord, colorTexture, filterParams0, filterParams1);
     case COMBINER_CTRL_FILTER_BLUR:
         return filterBlur(colorTexCoord,
                           colorTexture,
@@ -409,6 +586,28 @@ vec4 filterColor(vec2 colorTexCoord,
                           filterParams0,
                           filterParams1,
                           filterParams2);
+    case COMBINER_CTRL_FILTER_LUMINANCE_TO_ALPHA:
+        return filterLuminanceToAlpha(colorTexCoord, colorTexture);
+    case COMBINER_CTRL_FILTER_COLOR_MATRIX:
+        return filterColorMatrix(colorTexCoord,
+                                 colorTexture,
+                                 filterParams0,
+                                 filterParams1,
+                                 filterParams2,
+                                 filterParams3,
+                                 filterParams4);
+    case COMBINER_CTRL_FILTER_COMPONENT_TRANSFER:
+        return filterComponentTransfer(colorTexCoord,
+                                       colorTexture,
+                                       filterParams0,
+                                       filterParams1,
+                                       filterParams2,
+                                       filterParams3,
+                                       filterParams4);
+    case COMBINER_CTRL_FILTER_MORPHOLOGY:
+        return filterMorphology(colorTexCoord, colorTexture, colorTextureSize, filterParams0);
+    case COMBINER_CTRL_FILTER_UNPREMULTIPLY:
+        return filterUnpremultiply(texture(colorTexture, colorTexCoord));
     }
     return filterNone(colorTexCoord, colorTexture);
 }
@@ -577,6 +776,8 @@ void calculateColor(int tileCtrl, int ctrl) {
                                   uFilterParams0,
                                   uFilterParams1,
                                   uFilterParams2,
+                                  uFilterParams3,
+                                  uFilterParams4,
                                   color0Filter);
         color = combineColor0(color, color0, color0Combine);
     }
@@ -0,0 +1,894 @@
+// pathfinder/software/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A CPU software implementation of the device abstraction.
+//!
+//! This is slow, but it needs no GPU, windowing system, or graphics driver, which makes it
+//! useful for headless rendering and for running the renderer in tests and on CI. It emulates
+//! the OpenGL backend's conventions: the origin is at the lower left, and pixels read back with
+//! `read_pixels()` are ordered top to bottom.
+
+use crate::shaders::{MAX_VARYINGS, ProgramKind, ShaderEnv, Varyings};
+use half::f16;
+use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
+use pathfinder_gpu::{BlendFactor, BlendOp, BlendState, BufferData, BufferTarget, BufferUploadMode};
+use pathfinder_gpu::{DepthFunc, Device, Primitive, RenderOptions, RenderState, RenderTarget};
+use pathfinder_gpu::{ShaderKind, StencilFunc, TextureData, TextureDataRef, TextureFormat};
+use pathfinder_gpu::{TextureSamplingFlags, VertexAttrClass, VertexAttrDescriptor, VertexAttrType};
+use pathfinder_resources::ResourceLoader;
+use pathfinder_simd::default::F32x4;
+use std::cell::{Cell, RefCell, RefMut};
+use std::mem;
+use std::rc::Rc;
+use std::slice;
+use std::time::{Duration, Instant};
+
+mod shaders;
+
+// The number of fractional bits in the fixed-point window coordinates used for rasterization.
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_SCALE: f32 = (1 << SUBPIXEL_BITS) as f32;
+
+pub struct SoftwareDevice {
+    default_framebuffer: SoftwareFramebuffer,
+}
+
+impl SoftwareDevice {
+    /// Creates a new software device whose default framebuffer is an RGBA8 image of the given
+    /// size with depth and stencil attachments.
+    pub fn new(default_framebuffer_size: Vector2I) -> SoftwareDevice {
+        let texture = SoftwareTexture::new(TextureFormat::RGBA8, default_framebuffer_size);
+        let area = default_framebuffer_size.x() as usize * default_framebuffer_size.y() as usize;
+        SoftwareDevice {
+            default_framebuffer: SoftwareFramebuffer {
+                texture,
+                depth: Some(RefCell::new(vec![1.0; area])),
+                stencil: Some(RefCell::new(vec![0; area])),
+            },
+        }
+    }
+
+    fn render_target_framebuffer<'a>(&'a self, render_target: &RenderTarget<'a, SoftwareDevice>)
+                                     -> &'a SoftwareFramebuffer {
+        match *render_target {
+            RenderTarget::Default => &self.default_framebuffer,
+            RenderTarget::Framebuffer(framebuffer) => framebuffer,
+        }
+    }
+
+    fn draw(&self,
+            vertex_count: u32,
+            instance_count: u32,
+            indexed: bool,
+            render_state: &RenderState<SoftwareDevice>) {
+        let framebuffer = self.render_target_framebuffer(render_state.target);
+        framebuffer.clear(&render_state.options);
+
+        let program = render_state.program.kind;
+        let mut uniforms = vec![None; program.uniforms().len()];
+        for &(uniform, data) in render_state.uniforms {
+            if let Some(index) = uniform.index {
+                uniforms[index] = Some(data);
+            }
+        }
+        let env = ShaderEnv { uniforms: &uniforms, textures: render_state.textures };
+
+        let framebuffer_rect = RectI::new(Vector2I::default(), framebuffer.texture.size);
+        let bounds = match render_state.viewport.intersection(framebuffer_rect) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        let mut context = DrawContext {
+            program,
+            env,
+            framebuffer,
+            depth: framebuffer.depth.as_ref().map(|depth| depth.borrow_mut()),
+            stencil: framebuffer.stencil.as_ref().map(|stencil| stencil.borrow_mut()),
+            options: &render_state.options,
+            viewport: render_state.viewport,
+            bounds,
+        };
+
+        let vertex_array = render_state.vertex_array.state.borrow();
+        let index_buffer = vertex_array.index_buffer.as_ref().map(|buffer| buffer.borrow());
+        let attribute_count = program.attributes().len();
+        let vertices_per_primitive = match render_state.primitive {
+            Primitive::Triangles => 3,
+            Primitive::Lines => 2,
+        };
+
+        for instance_index in 0..instance_count {
+            let mut primitive = vec![];
+            for vertex_index in 0..vertex_count {
+                let vertex_index = match (indexed, &index_buffer) {
+                    (false, _) => vertex_index,
+                    (true, Some(index_buffer)) => {
+                        read_u32(index_buffer, vertex_index as usize * mem::size_of::<u32>())
+                    }
+                    (true, None) => panic!("Indexed draw without an index buffer bound!"),
+                };
+
+                let mut inputs = vec![[0.0, 0.0, 0.0, 1.0]; attribute_count];
+                for &(attr_index, ref descriptor, ref buffer) in &vertex_array.attrs {
+                    let element_index = match instance_index.checked_div(descriptor.divisor) {
+                        Some(element_index) => element_index,
+                        None => vertex_index,
+                    };
+                    inputs[attr_index] = fetch_attr(&buffer.borrow(), descriptor, element_index);
+                }
+
+                primitive.push(program.run_vertex_shader(&inputs, &context.env));
+                if primitive.len() == vertices_per_primitive {
+                    let vertices: Vec<_> =
+                        primitive.drain(..).filter_map(|vertex| context.to_window(vertex))
+                                           .collect();
+                    match vertices.len() {
+                        3 => context.rasterize_triangle(&vertices[0], &vertices[1], &vertices[2]),
+                        2 if vertices_per_primitive == 2 => {
+                            context.rasterize_line(&vertices[0], &vertices[1])
+                        }
+                        // FIXME(pcwalton): Clip primitives that cross the `w = 0` plane instead
+                        // of dropping them. The renderer never generates any.
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Device for SoftwareDevice {
+    type Buffer = SoftwareBuffer;
+    type Framebuffer = SoftwareFramebuffer;
+    type Program = SoftwareProgram;
+    type Shader = SoftwareShader;
+    type Texture = SoftwareTexture;
+    type TextureDataReceiver = SoftwareTextureDataReceiver;
+    type TimerQuery = SoftwareTimerQuery;
+    type Uniform = SoftwareUniform;
+    type VertexArray = SoftwareVertexArray;
+    type VertexAttr = SoftwareVertexAttr;
+
+    fn create_texture(&self, format: TextureFormat, size: Vector2I) -> SoftwareTexture {
+        SoftwareTexture::new(format, size)
+    }
+
+    fn create_texture_from_data(&self, format: TextureFormat, size: Vector2I, data: TextureDataRef)
+                                -> SoftwareTexture {
+        let texture = SoftwareTexture::new(format, size);
+        self.upload_to_texture(&texture, RectI::new(Vector2I::default(), size), data);
+        texture
+    }
+
+    fn create_shader(&self, _: &dyn ResourceLoader, name: &str, kind: ShaderKind)
+                     -> SoftwareShader {
+        SoftwareShader { name: name.to_owned(), kind }
+    }
+
+    fn create_shader_from_source(&self, name: &str, _: &[u8], kind: ShaderKind)
+                                 -> SoftwareShader {
+        // We can't compile shaders, so ignore the source and use our built-in implementation.
+        SoftwareShader { name: name.to_owned(), kind }
+    }
+
+    fn create_vertex_array(&self) -> SoftwareVertexArray {
+        SoftwareVertexArray { state: RefCell::new(VertexArrayState::default()) }
+    }
+
+    fn create_program_from_shaders(&self,
+                                   _: &dyn ResourceLoader,
+                                   name: &str,
+                                   vertex_shader: SoftwareShader,
+                                   fragment_shader: SoftwareShader)
+                                   -> SoftwareProgram {
+        debug_assert_eq!(vertex_shader.kind, ShaderKind::Vertex);
+        debug_assert_eq!(fragment_shader.kind, ShaderKind::Fragment);
+        debug_assert_eq!(vertex_shader.name, fragment_shader.name);
+        match ProgramKind::from_name(name) {
+            Some(kind) => SoftwareProgram { kind },
+            None => panic!("The software device has no implementation of the `{}` program!", name),
+        }
+    }
+
+    fn get_vertex_attr(&self, program: &SoftwareProgram, name: &str)
+                       -> Option<SoftwareVertexAttr> {
+        program.kind
+               .attributes()
+               .iter()
+               .position(|attr_name| *attr_name == name)
+               .map(|index| SoftwareVertexAttr { index })
+    }
+
+    fn get_uniform(&self, program: &SoftwareProgram, name: &str) -> SoftwareUniform {
+        let index = program.kind.uniforms().iter().position(|uniform_name| *uniform_name == name);
+        SoftwareUniform { index }
+    }
+
+    fn bind_buffer(&self,
+                   vertex_array: &SoftwareVertexArray,
+                   buffer: &SoftwareBuffer,
+                   target: BufferTarget) {
+        let mut state = vertex_array.state.borrow_mut();
+        match target {
+            BufferTarget::Vertex => state.vertex_buffer = Some(buffer.data.clone()),
+            BufferTarget::Index => state.index_buffer = Some(buffer.data.clone()),
+        }
+    }
+
+    fn configure_vertex_attr(&self,
+                             vertex_array: &SoftwareVertexArray,
+                             attr: &SoftwareVertexAttr,
+                             descriptor: &VertexAttrDescriptor) {
+        // Like OpenGL, this captures the vertex buffer that is bound at this point.
+        let mut state = vertex_array.state.borrow_mut();
+        let buffer = state.vertex_buffer
+                          .clone()
+                          .expect("No vertex buffer bound when configuring a vertex attribute!");
+        state.attrs.retain(|&(index, _, _)| index != attr.index);
+        state.attrs.push((attr.index, *descriptor, buffer));
+    }
+
+    fn create_framebuffer(&self, texture: SoftwareTexture) -> SoftwareFramebuffer {
+        SoftwareFramebuffer { texture, depth: None, stencil: None }
+    }
+
+    fn create_buffer(&self) -> SoftwareBuffer {
+        SoftwareBuffer { data: Rc::new(RefCell::new(vec![])) }
+    }
+
+    fn allocate_buffer<T>(&self,
+                          buffer: &SoftwareBuffer,
+                          data: BufferData<T>,
+                          _: BufferTarget,
+                          _: BufferUploadMode) {
+        *buffer.data.borrow_mut() = match data {
+            BufferData::Uninitialized(len) => vec![0; len * mem::size_of::<T>()],
+            BufferData::Memory(buffer) => unsafe {
+                slice::from_raw_parts(buffer.as_ptr() as *const u8, mem::size_of_val(buffer))
+                    .to_vec()
+            },
+        };
+    }
+
+    #[inline]
+    fn framebuffer_texture<'f>(&self, framebuffer: &'f SoftwareFramebuffer)
+                               -> &'f SoftwareTexture {
+        &framebuffer.texture
+    }
+
+    #[inline]
+    fn destroy_framebuffer(&self, framebuffer: SoftwareFramebuffer) -> SoftwareTexture {
+        framebuffer.texture
+    }
+
+    #[inline]
+    fn texture_format(&self, texture: &SoftwareTexture) -> TextureFormat {
+        texture.format
+    }
+
+    #[inline]
+    fn texture_size(&self, texture: &SoftwareTexture) -> Vector2I {
+        texture.size
+    }
+
+    fn set_texture_sampling_mode(&self, texture: &SoftwareTexture, flags: TextureSamplingFlags) {
+        texture.sampling_flags.set(flags);
+    }
+
+    fn upload_to_texture(&self, texture: &SoftwareTexture, rect: RectI, data: TextureDataRef) {
+        assert!(rect.size().x() >= 0);
+        assert!(rect.size().y() >= 0);
+        assert!(rect.min_x() >= 0 && rect.min_y() >= 0);
+        assert!(rect.max_x() <= texture.size.x());
+        assert!(rect.max_y() <= texture.size.y());
+
+        let channels = texture.format.channels();
+        let row_length = rect.width() as usize * channels;
+        let value_count = row_length * rect.height() as usize;
+        let values: Vec<f32> = match data {
+            TextureDataRef::U8(data) => {
+                assert!(data.len() >= value_count);
+                data[0..value_count].iter().map(|&value| value as f32 / 255.0).collect()
+            }
+            TextureDataRef::F16(data) => {
+                assert!(data.len() >= value_count);
+                data[0..value_count].iter().map(|value| value.to_f32()).collect()
+            }
+            TextureDataRef::F32(data) => {
+                assert!(data.len() >= value_count);
+                data[0..value_count].to_vec()
+            }
+        };
+
+        let mut texels = texture.texels.borrow_mut();
+        for (row_index, row) in values.chunks(row_length.max(1)).enumerate() {
+            let start = texture.texel_index(vec2i(rect.min_x(), rect.min_y() + row_index as i32));
+            for (texel, &value) in texels[start..(start + row.len())].iter_mut().zip(row) {
+                *texel = quantize(texture.format, value);
+            }
+        }
+        drop(texels);
+
+        self.set_texture_sampling_mode(texture, TextureSamplingFlags::empty());
+    }
+
+    fn read_pixels(&self, render_target: &RenderTarget<SoftwareDevice>, viewport: RectI)
+                   -> SoftwareTextureDataReceiver {
+        let texture = &self.render_target_framebuffer(render_target).texture;
+        let texels = texture.texels.borrow();
+        let channels = texture.format.channels();
+
+        // Like OpenGL, return rows from top to bottom.
+        let mut values = Vec::with_capacity(viewport.width().max(0) as usize *
+                                            viewport.height().max(0) as usize *
+                                            channels);
+        for y in (viewport.min_y()..viewport.max_y()).rev() {
+            let start = texture.texel_index(vec2i(viewport.min_x(), y));
+            values.extend_from_slice(&texels[start..(start + viewport.width() as usize *
+                                                               channels)]);
+        }
+
+        let data = match texture.format {
+            TextureFormat::R8 | TextureFormat::RGBA8 => {
+                TextureData::U8(values.iter().map(|&value| unorm8(value)).collect())
+            }
+            TextureFormat::R16F | TextureFormat::RGBA16F => {
+                TextureData::F16(values.iter().map(|&value| f16::from_f32(value)).collect())
+            }
+            TextureFormat::RGBA32F => TextureData::F32(values),
+        };
+        SoftwareTextureDataReceiver { data }
+    }
+
+    fn begin_commands(&self) {}
+
+    fn end_commands(&self) {}
+
+    fn draw_arrays(&self, index_count: u32, render_state: &RenderState<SoftwareDevice>) {
+        self.draw(index_count, 1, false, render_state);
+    }
+
+    fn draw_elements(&self, index_count: u32, render_state: &RenderState<SoftwareDevice>) {
+        self.draw(index_count, 1, true, render_state);
+    }
+
+    fn draw_elements_instanced(&self,
+                               index_count: u32,
+                               instance_count: u32,
+                               render_state: &RenderState<SoftwareDevice>) {
+        self.draw(index_count, instance_count, true, render_state);
+    }
+
+    #[inline]
+    fn create_timer_query(&self) -> SoftwareTimerQuery {
+        SoftwareTimerQuery { start: Cell::new(None), elapsed: Cell::new(None) }
+    }
+
+    #[inline]
+    fn begin_timer_query(&self, query: &SoftwareTimerQuery) {
+        query.start.set(Some(Instant::now()));
+        query.elapsed.set(None);
+    }
+
+    #[inline]
+    fn end_timer_query(&self, query: &SoftwareTimerQuery) {
+        let start = query.start.get().expect("Timer query was never begun!");
+        query.elapsed.set(Some(Instant::now() - start));
+    }
+
+    #[inline]
+    fn try_recv_timer_query(&self, query: &SoftwareTimerQuery) -> Option<Duration> {
+        query.elapsed.get()
+    }
+
+    #[inline]
+    fn recv_timer_query(&self, query: &SoftwareTimerQuery) -> Duration {
+        query.elapsed.get().expect("Timer query was never ended!")
+    }
+
+    #[inline]
+    fn try_recv_texture_data(&self, receiver: &SoftwareTextureDataReceiver)
+                             -> Option<TextureData> {
+        Some(receiver.data.clone())
+    }
+
+    #[inline]
+    fn recv_texture_data(&self, receiver: &SoftwareTextureDataReceiver) -> TextureData {
+        receiver.data.clone()
+    }
+}
+
+pub struct SoftwareBuffer {
+    data: BufferStorage,
+}
+
+// Shared so that vertex arrays can keep the buffers that were bound when they were configured.
+type BufferStorage = Rc<RefCell<Vec<u8>>>;
+
+pub struct SoftwareFramebuffer {
+    texture: SoftwareTexture,
+    depth: Option<RefCell<Vec<f32>>>,
+    stencil: Option<RefCell<Vec<u8>>>,
+}
+
+impl SoftwareFramebuffer {
+    // Like `glClear()`, this ignores the viewport.
+    fn clear(&self, options: &RenderOptions) {
+        if let Some(color) = options.clear_ops.color {
+            let color = F32x4::new(color.r(), color.g(), color.b(), color.a());
+            let format = self.texture.format;
+            let channels = format.channels();
+            for texel in self.texture.texels.borrow_mut().chunks_mut(channels) {
+                for (channel, value) in texel.iter_mut().enumerate() {
+                    *value = quantize(format, color[channel]);
+                }
+            }
+        }
+        if let (Some(depth), Some(value)) = (&self.depth, options.clear_ops.depth) {
+            for texel in depth.borrow_mut().iter_mut() {
+                *texel = value;
+            }
+        }
+        if let (Some(stencil), Some(value)) = (&self.stencil, options.clear_ops.stencil) {
+            for texel in stencil.borrow_mut().iter_mut() {
+                *texel = value;
+            }
+        }
+    }
+}
+
+pub struct SoftwareProgram {
+    kind: ProgramKind,
+}
+
+pub struct SoftwareShader {
+    name: String,
+    kind: ShaderKind,
+}
+
+pub struct SoftwareTexture {
+    size: Vector2I,
+    format: TextureFormat,
+    texels: RefCell<Vec<f32>>,
+    sampling_flags: Cell<TextureSamplingFlags>,
+}
+
+impl SoftwareTexture {
+    fn new(format: TextureFormat, size: Vector2I) -> SoftwareTexture {
+        let len = size.x().max(0) as usize * size.y().max(0) as usize * format.channels();
+        SoftwareTexture {
+            size,
+            format,
+            texels: RefCell::new(vec![0.0; len]),
+            sampling_flags: Cell::new(TextureSamplingFlags::empty()),
+        }
+    }
+
+    #[inline]
+    fn texel_index(&self, position: Vector2I) -> usize {
+        (position.y() as usize * self.size.x() as usize + position.x() as usize) *
+            self.format.channels()
+    }
+
+    fn fetch(&self, texels: &[f32], x: i32, y: i32) -> F32x4 {
+        let flags = self.sampling_flags.get();
+        let x = wrap(x, self.size.x(), flags.contains(TextureSamplingFlags::REPEAT_U));
+        let y = wrap(y, self.size.y(), flags.contains(TextureSamplingFlags::REPEAT_V));
+        let index = self.texel_index(vec2i(x, y));
+        match self.format.channels() {
+            1 => F32x4::new(texels[index], 0.0, 0.0, 1.0),
+            _ => F32x4::from_slice(&texels[index..(index + 4)]),
+        }
+    }
+
+    fn sample(&self, tex_coord: Vector2F) -> F32x4 {
+        if self.size.x() <= 0 || self.size.y() <= 0 {
+            return F32x4::new(0.0, 0.0, 0.0, 1.0);
+        }
+
+        let texels = self.texels.borrow();
+        let position = tex_coord * self.size.to_f32();
+
+        // We don't have mipmaps, so only the magnification filter matters.
+        if self.sampling_flags.get().contains(TextureSamplingFlags::NEAREST_MAG) {
+            let position = position.floor();
+            return self.fetch(&texels, position.x() as i32, position.y() as i32);
+        }
+
+        let position = position - Vector2F::splat(0.5);
+        let origin = position.floor();
+        let fraction = position - origin;
+        let (x, y) = (origin.x() as i32, origin.y() as i32);
+        let (fraction_x, fraction_y) = (F32x4::splat(fraction.x()), F32x4::splat(fraction.y()));
+        let lower = lerp(self.fetch(&texels, x, y), self.fetch(&texels, x + 1, y), fraction_x);
+        let upper = lerp(self.fetch(&texels, x, y + 1),
+                         self.fetch(&texels, x + 1, y + 1),
+                         fraction_x);
+        lerp(lower, upper, fraction_y)
+    }
+}
+
+pub struct SoftwareTextureDataReceiver {
+    data: TextureData,
+}
+
+pub struct SoftwareTimerQuery {
+    start: Cell<Option<Instant>>,
+    elapsed: Cell<Option<Duration>>,
+}
+
+pub struct SoftwareUniform {
+    index: Option<usize>,
+}
+
+pub struct SoftwareVertexArray {
+    state: RefCell<VertexArrayState>,
+}
+
+#[derive(Default)]
+struct VertexArrayState {
+    vertex_buffer: Option<BufferStorage>,
+    index_buffer: Option<BufferStorage>,
+    attrs: Vec<(usize, VertexAttrDescriptor, BufferStorage)>,
+}
+
+pub struct SoftwareVertexAttr {
+    index: usize,
+}
+
+// Rasterization
+
+struct DrawContext<'a> {
+    program: ProgramKind,
+    env: ShaderEnv<'a>,
+    framebuffer: &'a SoftwareFramebuffer,
+    depth: Option<RefMut<'a, Vec<f32>>>,
+    stencil: Option<RefMut<'a, Vec<u8>>>,
+    options: &'a RenderOptions,
+    viewport: RectI,
+    bounds: RectI,
+}
+
+// A vertex after the viewport transform, with window coordinates in fixed point.
+struct WindowVertex {
+    x: i64,
+    y: i64,
+    depth: f32,
+    inv_w: f32,
+    varyings: Varyings,
+}
+
+impl<'a> DrawContext<'a> {
+    fn to_window(&self, vertex: shaders::VertexOutput) -> Option<WindowVertex> {
+        let position = vertex.position;
+        if position.w() <= 0.0 || position.w().is_nan() {
+            return None;
+        }
+        let inv_w = 1.0 / position.w();
+        let ndc = vec2f(position.x(), position.y()) * inv_w;
+        let window = self.viewport.origin().to_f32() +
+            (ndc + Vector2F::splat(1.0)) * 0.5 * self.viewport.size().to_f32();
+        Some(WindowVertex {
+            x: f32::round(window.x() * SUBPIXEL_SCALE) as i64,
+            y: f32::round(window.y() * SUBPIXEL_SCALE) as i64,
+            depth: (position.z() * inv_w + 1.0) * 0.5,
+            inv_w,
+            varyings: vertex.varyings,
+        })
+    }
+
+    fn rasterize_triangle<'v>(&mut self,
+                              mut v0: &'v WindowVertex,
+                              mut v1: &'v WindowVertex,
+                              v2: &'v WindowVertex) {
+        // Make the winding counterclockwise. We never cull faces.
+        let mut area = edge_function(v0, v1, v2.x, v2.y);
+        if area == 0 {
+            return;
+        }
+        if area < 0 {
+            mem::swap(&mut v0, &mut v1);
+            area = -area;
+        }
+
+        let min_x = v0.x.min(v1.x).min(v2.x) >> SUBPIXEL_BITS;
+        let min_y = v0.y.min(v1.y).min(v2.y) >> SUBPIXEL_BITS;
+        let max_x = (v0.x.max(v1.x).max(v2.x) >> SUBPIXEL_BITS) + 1;
+        let max_y = (v0.y.max(v1.y).max(v2.y) >> SUBPIXEL_BITS) + 1;
+        let min_x = min_x.max(self.bounds.min_x() as i64);
+        let min_y = min_y.max(self.bounds.min_y() as i64);
+        let max_x = max_x.min(self.bounds.max_x() as i64);
+        let max_y = max_y.min(self.bounds.max_y() as i64);
+
+        // Each edge function gives the weight of the vertex opposite that edge.
+        let edges = [(v1, v2), (v2, v0), (v0, v1)];
+        let vertices = [v0, v1, v2];
+        let inv_area = 1.0 / area as f32;
+        let half_pixel = 1 << (SUBPIXEL_BITS - 1);
+
+        for y in min_y..max_y {
+            let sample_y = (y << SUBPIXEL_BITS) + half_pixel;
+            for x in min_x..max_x {
+                let sample_x = (x << SUBPIXEL_BITS) + half_pixel;
+                let mut weights = [0.0; 3];
+                let mut inside = true;
+                for (weight, &(from, to)) in weights.iter_mut().zip(edges.iter()) {
+                    let value = edge_function(from, to, sample_x, sample_y);
+                    if value < 0 || (value == 0 && !is_top_left_edge(from, to)) {
+                        inside = false;
+                        break;
+                    }
+                    *weight = value as f32 * inv_area;
+                }
+                if !inside {
+                    continue;
+                }
+
+                let depth = weights.iter()
+                                   .zip(vertices.iter())
+                                   .map(|(weight, vertex)| weight * vertex.depth)
+                                   .sum();
+
+                // Perspective-correct interpolation.
+                let mut perspective_weights = [0.0; 3];
+                for (index, vertex) in vertices.iter().enumerate() {
+                    perspective_weights[index] = weights[index] * vertex.inv_w;
+                }
+                let weight_sum: f32 = perspective_weights.iter().sum();
+                let mut varyings = [0.0; MAX_VARYINGS];
+                for (index, vertex) in vertices.iter().enumerate() {
+                    let weight = perspective_weights[index] / weight_sum;
+                    for (varying, &value) in varyings.iter_mut().zip(vertex.varyings.iter()) {
+                        *varying += weight * value;
+                    }
+                }
+
+                self.process_fragment(x as i32, y as i32, depth, &varyings);
+            }
+        }
+    }
+
+    fn rasterize_line(&mut self, from: &WindowVertex, to: &WindowVertex) {
+        let scale = 1.0 / SUBPIXEL_SCALE;
+        let from_position = vec2f(from.x as f32, from.y as f32) * scale;
+        let to_position = vec2f(to.x as f32, to.y as f32) * scale;
+        let vector = to_position - from_position;
+        let step_count = f32::max(f32::abs(vector.x()), f32::abs(vector.y())).ceil() as i32;
+
+        // Like OpenGL, include the first endpoint but not the last.
+        for step in 0..step_count {
+            let t = (step as f32 + 0.5) / step_count as f32;
+            let position = (from_position + vector * t).floor();
+            let (x, y) = (position.x() as i32, position.y() as i32);
+            if x < self.bounds.min_x() || x >= self.bounds.max_x() ||
+                    y < self.bounds.min_y() || y >= self.bounds.max_y() {
+                continue;
+            }
+
+            let depth = from.depth + (to.depth - from.depth) * t;
+            let mut varyings = [0.0; MAX_VARYINGS];
+            for (index, varying) in varyings.iter_mut().enumerate() {
+                *varying = from.varyings[index] +
+                    (to.varyings[index] - from.varyings[index]) * t;
+            }
+            self.process_fragment(x, y, depth, &varyings);
+        }
+    }
+
+    fn process_fragment(&mut self, x: i32, y: i32, depth: f32, varyings: &Varyings) {
+        let pixel_index = y as usize * self.framebuffer.texture.size.x() as usize + x as usize;
+
+        // Stencil test.
+        if let (Some(stencil_state), Some(stencil)) = (self.options.stencil, &mut self.stencil) {
+            let (reference, mask) = (stencil_state.reference as u8, stencil_state.mask as u8);
+            let value = stencil[pixel_index];
+            let passed = match stencil_state.func {
+                StencilFunc::Always => true,
+                StencilFunc::Equal => (reference & mask) == (value & mask),
+            };
+            if !passed {
+                return;
+            }
+        }
+
+        // Depth test.
+        if let (Some(depth_state), Some(depth_buffer)) = (self.options.depth, &mut self.depth) {
+            let passed = match depth_state.func {
+                DepthFunc::Less => depth < depth_buffer[pixel_index],
+                DepthFunc::Always => true,
+            };
+            if !passed {
+                return;
+            }
+            if depth_state.write {
+                depth_buffer[pixel_index] = depth;
+            }
+        }
+
+        // The stencil operation is always `KEEP`, `KEEP`, `REPLACE`, with the write mask equal
+        // to the test mask.
+        if let (Some(stencil_state), Some(stencil)) = (self.options.stencil, &mut self.stencil) {
+            if stencil_state.write {
+                let (reference, mask) = (stencil_state.reference as u8, stencil_state.mask as u8);
+                let value = &mut stencil[pixel_index];
+                *value = (*value & !mask) | (reference & mask);
+            }
+        }
+
+        if !self.options.color_mask {
+            return;
+        }
+
+        // Run the shader before borrowing the render target, since the shader might sample it.
+        let frag_coord = vec2f(x as f32 + 0.5, y as f32 + 0.5);
+        let mut color = self.program.run_fragment_shader(varyings, frag_coord, &self.env);
+
+        let texture = &self.framebuffer.texture;
+        let format = texture.format;
+        if is_normalized(format) {
+            color = color.clamp(F32x4::default(), F32x4::splat(1.0));
+        }
+
+        let channels = format.channels();
+        let texel_index = pixel_index * channels;
+        let mut texels = texture.texels.borrow_mut();
+        let texel = &mut texels[texel_index..(texel_index + channels)];
+
+        if let Some(ref blend_state) = self.options.blend {
+            let dest = match channels {
+                1 => F32x4::new(texel[0], 0.0, 0.0, 1.0),
+                _ => F32x4::from_slice(texel),
+            };
+            color = blend(blend_state, color, dest);
+        }
+
+        for (channel, value) in texel.iter_mut().enumerate() {
+            *value = quantize(format, color[channel]);
+        }
+    }
+}
+
+fn edge_function(from: &WindowVertex, to: &WindowVertex, x: i64, y: i64) -> i64 {
+    (to.x - from.x) * (y - from.y) - (to.y - from.y) * (x - from.x)
+}
+
+// The tie-breaking rule for samples exactly on an edge. Edges shared between two triangles are
+// traversed in opposite directions, so exactly one of them owns the sample. This matters for the
+// fill program, which accumulates coverage additively.
+fn is_top_left_edge(from: &WindowVertex, to: &WindowVertex) -> bool {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    dy > 0 || (dy == 0 && dx < 0)
+}
+
+// Blending
+
+fn blend(state: &BlendState, src: F32x4, dest: F32x4) -> F32x4 {
+    let src_rgb = src * blend_factor(state.src_rgb_factor, src, dest);
+    let dest_rgb = dest * blend_factor(state.dest_rgb_factor, src, dest);
+    let src_alpha = src.w() * blend_factor(state.src_alpha_factor, src, dest).w();
+    let dest_alpha = dest.w() * blend_factor(state.dest_alpha_factor, src, dest).w();
+
+    // Like OpenGL, the min and max operations ignore the blend factors.
+    let mut result = match state.op {
+        BlendOp::Add => src_rgb + dest_rgb,
+        BlendOp::Subtract => src_rgb - dest_rgb,
+        BlendOp::ReverseSubtract => dest_rgb - src_rgb,
+        BlendOp::Min => src.min(dest),
+        BlendOp::Max => src.max(dest),
+    };
+    result[3] = match state.op {
+        BlendOp::Add => src_alpha + dest_alpha,
+        BlendOp::Subtract => src_alpha - dest_alpha,
+        BlendOp::ReverseSubtract => dest_alpha - src_alpha,
+        BlendOp::Min => f32::min(src.w(), dest.w()),
+        BlendOp::Max => f32::max(src.w(), dest.w()),
+    };
+    result
+}
+
+fn blend_factor(factor: BlendFactor, src: F32x4, dest: F32x4) -> F32x4 {
+    match factor {
+        BlendFactor::Zero => F32x4::default(),
+        BlendFactor::One => F32x4::splat(1.0),
+        BlendFactor::SrcAlpha => F32x4::splat(src.w()),
+        BlendFactor::OneMinusSrcAlpha => F32x4::splat(1.0 - src.w()),
+        BlendFactor::DestAlpha => F32x4::splat(dest.w()),
+        BlendFactor::OneMinusDestAlpha => F32x4::splat(1.0 - dest.w()),
+        BlendFactor::DestColor => dest,
+    }
+}
+
+// Vertex fetching
+
+fn fetch_attr(data: &[u8], descriptor: &VertexAttrDescriptor, element_index: u32) -> [f32; 4] {
+    let mut result = [0.0, 0.0, 0.0, 1.0];
+    let component_size = match descriptor.attr_type {
+        VertexAttrType::F32 => 4,
+        VertexAttrType::I16 | VertexAttrType::U16 => 2,
+        VertexAttrType::I8 | VertexAttrType::U8 => 1,
+    };
+    let base = descriptor.offset + descriptor.stride * element_index as usize;
+    for (component_index, component) in result.iter_mut().take(descriptor.size).enumerate() {
+        let offset = base + component_index * component_size;
+
+        // Like robust buffer access in OpenGL, out-of-bounds reads return zero.
+        if offset + component_size > data.len() {
+            *component = 0.0;
+            continue;
+        }
+
+        let bytes = &data[offset..(offset + component_size)];
+        let normalized = descriptor.class == VertexAttrClass::FloatNorm;
+        *component = match descriptor.attr_type {
+            VertexAttrType::F32 => f32::from_bits(read_u32(bytes, 0)),
+            VertexAttrType::I16 => {
+                let value = i16::from_le_bytes([bytes[0], bytes[1]]) as f32;
+                if normalized { f32::max(value / 32767.0, -1.0) } else { value }
+            }
+            VertexAttrType::U16 => {
+                let value = u16::from_le_bytes([bytes[0], bytes[1]]) as f32;
+                if normalized { value / 65535.0 } else { value }
+            }
+            VertexAttrType::I8 => {
+                let value = bytes[0] as i8 as f32;
+                if normalized { f32::max(value / 127.0, -1.0) } else { value }
+            }
+            VertexAttrType::U8 => {
+                let value = bytes[0] as f32;
+                if normalized { value / 255.0 } else { value }
+            }
+        };
+    }
+    result
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+// Utilities
+
+#[inline]
+fn lerp(a: F32x4, b: F32x4, t: F32x4) -> F32x4 {
+    a + (b - a) * t
+}
+
+fn wrap(coord: i32, size: i32, repeat: bool) -> i32 {
+    if repeat {
+        coord.rem_euclid(size)
+    } else {
+        coord.max(0).min(size - 1)
+    }
+}
+
+fn is_normalized(format: TextureFormat) -> bool {
+    match format {
+        TextureFormat::R8 | TextureFormat::RGBA8 => true,
+        TextureFormat::R16F | TextureFormat::RGBA16F | TextureFormat::RGBA32F => false,
+    }
+}
+
+// Rounds a value to the precision of the given texture format.
+fn quantize(format: TextureFormat, value: f32) -> f32 {
+    match format {
+        TextureFormat::R8 | TextureFormat::RGBA8 => unorm8(value) as f32 / 255.0,
+        TextureFormat::R16F | TextureFormat::RGBA16F => f16::from_f32(value).to_f32(),
+        TextureFormat::RGBA32F => value,
+    }
+}
+
+fn unorm8(value: f32) -> u8 {
+    f32::round(value.clamp(0.0, 1.0) * 255.0) as u8
+}
+
+#[cfg(test)]
+mod test;
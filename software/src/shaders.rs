@@ -0,0 +1,1161 @@
+// pathfinder/software/src/shaders.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rust ports of the shader programs in `pathfinder/shaders/`.
+//!
+//! The software device can't compile GLSL, so each program is matched by name and executed by
+//! the corresponding hand-written function here. These must be kept in sync with the GLSL
+//! sources.
+
+use crate::SoftwareTexture;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use pathfinder_gpu::UniformData;
+use pathfinder_simd::default::F32x4;
+use std::f32;
+use std::f32::consts::{FRAC_PI_3, PI};
+
+pub(crate) const MAX_VARYINGS: usize = 12;
+
+pub(crate) type Varyings = [f32; MAX_VARYINGS];
+
+const EPSILON: f32 = 0.00001;
+
+const FRAC_6_PI: f32 = 1.909_859_3;
+
+const TILE_CTRL_MASK_MASK: i32 = 0x3;
+const TILE_CTRL_MASK_WINDING: i32 = 0x1;
+
+const TILE_CTRL_MASK_0_SHIFT: i32 = 0;
+
+const COMBINER_CTRL_COLOR_COMBINE_MASK: i32 = 0x3;
+const COMBINER_CTRL_COLOR_COMBINE_SRC_IN: i32 = 0x1;
+const COMBINER_CTRL_COLOR_COMBINE_DEST_IN: i32 = 0x2;
+
+const COMBINER_CTRL_FILTER_MASK: i32 = 0xf;
+const COMBINER_CTRL_FILTER_RADIAL_GRADIENT: i32 = 0x1;
+const COMBINER_CTRL_FILTER_TEXT: i32 = 0x2;
+const COMBINER_CTRL_FILTER_BLUR: i32 = 0x3;
+const COMBINER_CTRL_FILTER_LUMINANCE_TO_ALPHA: i32 = 0x4;
+const COMBINER_CTRL_FILTER_LINEAR_GRADIENT: i32 = 0x5;
+const COMBINER_CTRL_FILTER_CONIC_GRADIENT: i32 = 0x6;
+const COMBINER_CTRL_FILTER_COLOR_MATRIX: i32 = 0x7;
+const COMBINER_CTRL_FILTER_COMPONENT_TRANSFER: i32 = 0x8;
+const COMBINER_CTRL_FILTER_MORPHOLOGY: i32 = 0x9;
+const COMBINER_CTRL_FILTER_UNPREMULTIPLY: i32 = 0xa;
+
+const GRADIENT_SPREAD_REPEAT: i32 = 1;
+const GRADIENT_SPREAD_REFLECT: i32 = 2;
+
+const TRANSFER_FUNCTION_TABLE: i32 = 1;
+const TRANSFER_FUNCTION_DISCRETE: i32 = 2;
+const TRANSFER_FUNCTION_LINEAR: i32 = 3;
+const TRANSFER_FUNCTION_GAMMA: i32 = 4;
+
+const COMBINER_CTRL_COMPOSITE_MASK: i32 = 0xf;
+const COMBINER_CTRL_COMPOSITE_NORMAL: i32 = 0x0;
+const COMBINER_CTRL_COMPOSITE_MULTIPLY: i32 = 0x1;
+const COMBINER_CTRL_COMPOSITE_SCREEN: i32 = 0x2;
+const COMBINER_CTRL_COMPOSITE_OVERLAY: i32 = 0x3;
+const COMBINER_CTRL_COMPOSITE_DARKEN: i32 = 0x4;
+const COMBINER_CTRL_COMPOSITE_LIGHTEN: i32 = 0x5;
+const COMBINER_CTRL_COMPOSITE_COLOR_DODGE: i32 = 0x6;
+const COMBINER_CTRL_COMPOSITE_COLOR_BURN: i32 = 0x7;
+const COMBINER_CTRL_COMPOSITE_HARD_LIGHT: i32 = 0x8;
+const COMBINER_CTRL_COMPOSITE_SOFT_LIGHT: i32 = 0x9;
+const COMBINER_CTRL_COMPOSITE_DIFFERENCE: i32 = 0xa;
+const COMBINER_CTRL_COMPOSITE_EXCLUSION: i32 = 0xb;
+const COMBINER_CTRL_COMPOSITE_HUE: i32 = 0xc;
+const COMBINER_CTRL_COMPOSITE_SATURATION: i32 = 0xd;
+const COMBINER_CTRL_COMPOSITE_COLOR: i32 = 0xe;
+const COMBINER_CTRL_COMPOSITE_LUMINOSITY: i32 = 0xf;
+
+const COMBINER_CTRL_COLOR_FILTER_SHIFT: i32 = 4;
+const COMBINER_CTRL_COLOR_COMBINE_SHIFT: i32 = 8;
+const COMBINER_CTRL_COMPOSITE_SHIFT: i32 = 10;
+
+/// The shader programs that the software device knows how to run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ProgramKind {
+    Blit,
+    DebugSolid,
+    DebugTexture,
+    Fill,
+    Reproject,
+    Stencil,
+    Tile,
+    TileClip,
+    TileCopy,
+}
+
+pub(crate) struct VertexOutput {
+    pub(crate) position: F32x4,
+    pub(crate) varyings: Varyings,
+}
+
+/// Uniform values and bound textures for a single draw call.
+pub(crate) struct ShaderEnv<'a> {
+    pub(crate) uniforms: &'a [Option<UniformData>],
+    pub(crate) textures: &'a [&'a SoftwareTexture],
+}
+
+impl ProgramKind {
+    pub(crate) fn from_name(name: &str) -> Option<ProgramKind> {
+        match name {
+            "blit" => Some(ProgramKind::Blit),
+            "debug_solid" => Some(ProgramKind::DebugSolid),
+            "debug_texture" => Some(ProgramKind::DebugTexture),
+            "fill" => Some(ProgramKind::Fill),
+            "reproject" => Some(ProgramKind::Reproject),
+            "stencil" => Some(ProgramKind::Stencil),
+            "tile" => Some(ProgramKind::Tile),
+            "tile_clip" => Some(ProgramKind::TileClip),
+            "tile_copy" => Some(ProgramKind::TileCopy),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn attributes(self) -> &'static [&'static str] {
+        match self {
+            ProgramKind::Blit => blit::ATTRIBUTES,
+            ProgramKind::DebugSolid => debug_solid::ATTRIBUTES,
+            ProgramKind::DebugTexture => debug_texture::ATTRIBUTES,
+            ProgramKind::Fill => fill::ATTRIBUTES,
+            ProgramKind::Reproject => reproject::ATTRIBUTES,
+            ProgramKind::Stencil => stencil::ATTRIBUTES,
+            ProgramKind::Tile => tile::ATTRIBUTES,
+            ProgramKind::TileClip => tile_clip::ATTRIBUTES,
+            ProgramKind::TileCopy => tile_copy::ATTRIBUTES,
+        }
+    }
+
+    pub(crate) fn uniforms(self) -> &'static [&'static str] {
+        match self {
+            ProgramKind::Blit => blit::UNIFORMS,
+            ProgramKind::DebugSolid => debug_solid::UNIFORMS,
+            ProgramKind::DebugTexture => debug_texture::UNIFORMS,
+            ProgramKind::Fill => fill::UNIFORMS,
+            ProgramKind::Reproject => reproject::UNIFORMS,
+            ProgramKind::Stencil => stencil::UNIFORMS,
+            ProgramKind::Tile => tile::UNIFORMS,
+            ProgramKind::TileClip => tile_clip::UNIFORMS,
+            ProgramKind::TileCopy => tile_copy::UNIFORMS,
+        }
+    }
+
+    pub(crate) fn run_vertex_shader(self, inputs: &[[f32; 4]], env: &ShaderEnv) -> VertexOutput {
+        match self {
+            ProgramKind::Blit => blit::vertex(inputs),
+            ProgramKind::DebugSolid => debug_solid::vertex(inputs, env),
+            ProgramKind::DebugTexture => debug_texture::vertex(inputs, env),
+            ProgramKind::Fill => fill::vertex(inputs, env),
+            ProgramKind::Reproject => reproject::vertex(inputs, env),
+            ProgramKind::Stencil => stencil::vertex(inputs),
+            ProgramKind::Tile => tile::vertex(inputs, env),
+            ProgramKind::TileClip => tile_clip::vertex(inputs),
+            ProgramKind::TileCopy => tile_copy::vertex(inputs, env),
+        }
+    }
+
+    pub(crate) fn run_fragment_shader(self,
+                                      varyings: &Varyings,
+                                      frag_coord: Vector2F,
+                                      env: &ShaderEnv)
+                                      -> F32x4 {
+        match self {
+            ProgramKind::Blit => blit::fragment(varyings, env),
+            ProgramKind::DebugSolid => debug_solid::fragment(env),
+            ProgramKind::DebugTexture => debug_texture::fragment(varyings, env),
+            ProgramKind::Fill => fill::fragment(varyings, env),
+            ProgramKind::Reproject => reproject::fragment(varyings, env),
+            ProgramKind::Stencil => stencil::fragment(),
+            ProgramKind::Tile => tile::fragment(varyings, frag_coord, env),
+            ProgramKind::TileClip => tile_clip::fragment(varyings, env),
+            ProgramKind::TileCopy => tile_copy::fragment(frag_coord, env),
+        }
+    }
+}
+
+impl<'a> ShaderEnv<'a> {
+    fn uniform(&self, index: usize) -> Option<UniformData> {
+        self.uniforms.get(index).cloned().unwrap_or(None)
+    }
+
+    fn int(&self, index: usize) -> i32 {
+        match self.uniform(index) {
+            Some(UniformData::Int(value)) => value,
+            Some(UniformData::TextureUnit(unit)) => unit as i32,
+            _ => 0,
+        }
+    }
+
+    fn vec2(&self, index: usize) -> Vector2F {
+        match self.uniform(index) {
+            Some(UniformData::Vec2(value)) => Vector2F(value),
+            Some(UniformData::IVec2(value)) => vec2f(value[0] as f32, value[1] as f32),
+            _ => Vector2F::zero(),
+        }
+    }
+
+    fn vec4(&self, index: usize) -> F32x4 {
+        match self.uniform(index) {
+            Some(UniformData::Vec4(value)) => value,
+            _ => F32x4::default(),
+        }
+    }
+
+    fn mat4(&self, index: usize) -> [F32x4; 4] {
+        match self.uniform(index) {
+            Some(UniformData::Mat4(value)) => value,
+            _ => [F32x4::new(1.0, 0.0, 0.0, 0.0),
+                  F32x4::new(0.0, 1.0, 0.0, 0.0),
+                  F32x4::new(0.0, 0.0, 1.0, 0.0),
+                  F32x4::new(0.0, 0.0, 0.0, 1.0)],
+        }
+    }
+
+    // Like GLSL, a sampler uniform that was never set refers to texture unit 0.
+    fn sample(&self, index: usize, tex_coord: Vector2F) -> F32x4 {
+        match self.textures.get(self.int(index) as usize) {
+            Some(texture) => texture.sample(tex_coord),
+            None => F32x4::new(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+}
+
+// Utilities
+
+fn vec2_input(input: [f32; 4]) -> Vector2F {
+    vec2f(input[0], input[1])
+}
+
+fn mat4_mul(matrix: &[F32x4; 4], vector: F32x4) -> F32x4 {
+    matrix[0] * F32x4::splat(vector.x()) +
+        matrix[1] * F32x4::splat(vector.y()) +
+        matrix[2] * F32x4::splat(vector.z()) +
+        matrix[3] * F32x4::splat(vector.w())
+}
+
+fn clip_position(position: Vector2F) -> F32x4 {
+    F32x4::new(position.x(), position.y(), 0.0, 1.0)
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn clamp(x: f32, min: f32, max: f32) -> f32 {
+    f32::min(f32::max(x, min), max)
+}
+
+// GLSL `mod`, which takes the sign of the divisor.
+fn glsl_mod(x: f32, y: f32) -> f32 {
+    x - y * f32::floor(x / y)
+}
+
+// Varyings that are constant across a primitive are interpolated back to within a rounding error
+// of their value, so round instead of truncating as GLSL `int()` would.
+fn varying_to_int(value: f32) -> i32 {
+    f32::round(value) as i32
+}
+
+mod blit {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] = &["Position"];
+    pub(super) const UNIFORMS: &[&str] = &["Src"];
+
+    const SRC: usize = 0;
+
+    pub(super) fn vertex(inputs: &[[f32; 4]]) -> VertexOutput {
+        let position = vec2_input(inputs[0]);
+        let mut varyings = Varyings::default();
+        varyings[0] = position.x();
+        varyings[1] = position.y();
+        VertexOutput {
+            position: clip_position(position * 2.0 - Vector2F::splat(1.0)),
+            varyings,
+        }
+    }
+
+    pub(super) fn fragment(varyings: &Varyings, env: &ShaderEnv) -> F32x4 {
+        let color = env.sample(SRC, vec2f(varyings[0], varyings[1]));
+        color * F32x4::new(color.w(), color.w(), color.w(), 1.0)
+    }
+}
+
+mod debug_solid {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] = &["Position"];
+    pub(super) const UNIFORMS: &[&str] = &["FramebufferSize", "Color"];
+
+    const FRAMEBUFFER_SIZE: usize = 0;
+    const COLOR: usize = 1;
+
+    pub(super) fn vertex(inputs: &[[f32; 4]], env: &ShaderEnv) -> VertexOutput {
+        let position =
+            vec2_input(inputs[0]) / env.vec2(FRAMEBUFFER_SIZE) * 2.0 - Vector2F::splat(1.0);
+        VertexOutput {
+            position: clip_position(vec2f(position.x(), -position.y())),
+            varyings: Varyings::default(),
+        }
+    }
+
+    pub(super) fn fragment(env: &ShaderEnv) -> F32x4 {
+        let color = env.vec4(COLOR);
+        F32x4::new(color.x(), color.y(), color.z(), 1.0) * F32x4::splat(color.w())
+    }
+}
+
+mod debug_texture {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] = &["Position", "TexCoord"];
+    pub(super) const UNIFORMS: &[&str] = &["FramebufferSize", "TextureSize", "Texture", "Color"];
+
+    const FRAMEBUFFER_SIZE: usize = 0;
+    const TEXTURE_SIZE: usize = 1;
+    const TEXTURE: usize = 2;
+    const COLOR: usize = 3;
+
+    pub(super) fn vertex(inputs: &[[f32; 4]], env: &ShaderEnv) -> VertexOutput {
+        let tex_coord = vec2_input(inputs[1]) / env.vec2(TEXTURE_SIZE);
+        let position =
+            vec2_input(inputs[0]) / env.vec2(FRAMEBUFFER_SIZE) * 2.0 - Vector2F::splat(1.0);
+        let mut varyings = Varyings::default();
+        varyings[0] = tex_coord.x();
+        varyings[1] = tex_coord.y();
+        VertexOutput { position: clip_position(vec2f(position.x(), -position.y())), varyings }
+    }
+
+    pub(super) fn fragment(varyings: &Varyings, env: &ShaderEnv) -> F32x4 {
+        let color = env.vec4(COLOR);
+        let alpha = env.sample(TEXTURE, vec2f(varyings[0], varyings[1])).x() * color.w();
+        F32x4::new(color.x(), color.y(), color.z(), 1.0) * F32x4::splat(alpha)
+    }
+}
+
+mod fill {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] =
+        &["TessCoord", "FromPx", "ToPx", "FromSubpx", "ToSubpx", "TileIndex"];
+    pub(super) const UNIFORMS: &[&str] = &["FramebufferSize", "TileSize", "AreaLUT"];
+
+    const TESS_COORD: usize = 0;
+    const FROM_PX: usize = 1;
+    const TO_PX: usize = 2;
+    const FROM_SUBPX: usize = 3;
+    const TO_SUBPX: usize = 4;
+    const TILE_INDEX: usize = 5;
+
+    const FRAMEBUFFER_SIZE: usize = 0;
+    const TILE_SIZE: usize = 1;
+    const AREA_LUT: usize = 2;
+
+    fn compute_tile_offset(tile_index: u32, stencil_texture_width: f32, tile_size: Vector2F)
+                           -> Vector2F {
+        let tiles_per_row = (stencil_texture_width / tile_size.x()) as u32;
+        let tile_offset = vec2f((tile_index % tiles_per_row) as f32,
+                                (tile_index / tiles_per_row) as f32);
+        tile_offset * tile_size
+    }
+
+    pub(super) fn vertex(inputs: &[[f32; 4]], env: &ShaderEnv) -> VertexOutput {
+        let framebuffer_size = env.vec2(FRAMEBUFFER_SIZE);
+        let tile_size = env.vec2(TILE_SIZE);
+        let tile_origin = compute_tile_offset(inputs[TILE_INDEX][0] as u32,
+                                              framebuffer_size.x(),
+                                              tile_size);
+
+        let fill_scale = tile_size * (1.0 / 16.0);
+        let (from_px, to_px) = (inputs[FROM_PX][0] as u32, inputs[TO_PX][0] as u32);
+        let from = (vec2f((from_px & 15) as f32, (from_px >> 4) as f32) +
+                    vec2_input(inputs[FROM_SUBPX])) * fill_scale;
+        let to = (vec2f((to_px & 15) as f32, (to_px >> 4) as f32) +
+                  vec2_input(inputs[TO_SUBPX])) * fill_scale;
+
+        let tess_coord = inputs[TESS_COORD];
+        let position_x = if tess_coord[0] == 0.0 {
+            f32::floor(f32::min(from.x(), to.x()))
+        } else {
+            f32::ceil(f32::max(from.x(), to.x()))
+        };
+        let position_y = if tess_coord[1] == 0.0 {
+            f32::floor(f32::min(from.y(), to.y()))
+        } else {
+            tile_size.y()
+        };
+        let position = vec2f(position_x, position_y);
+
+        let (from, to) = (from - position, to - position);
+        let mut varyings = Varyings::default();
+        varyings[0] = from.x();
+        varyings[1] = from.y();
+        varyings[2] = to.x();
+        varyings[3] = to.y();
+
+        let global_position =
+            (tile_origin + position) / framebuffer_size * 2.0 - Vector2F::splat(1.0);
+        VertexOutput { position: clip_position(global_position), varyings }
+    }
+
+    pub(super) fn fragment(varyings: &Varyings, env: &ShaderEnv) -> F32x4 {
+        // Unpack.
+        let (from, to) = (vec2f(varyings[0], varyings[1]), vec2f(varyings[2], varyings[3]));
+
+        // Determine winding, and sort into a consistent order so we only need to find one root
+        // below.
+        let (left, right) = if from.x() < to.x() { (from, to) } else { (to, from) };
+
+        // Shoot a vertical ray toward the curve.
+        let window = vec2f(clamp(from.x(), -0.5, 0.5), clamp(to.x(), -0.5, 0.5));
+        let offset = mix(window.x(), window.y(), 0.5) - left.x();
+        let t = offset / (right.x() - left.x());
+
+        // Compute position and derivative to form a line approximation.
+        let y = mix(left.y(), right.y(), t);
+        let d = (right.y() - left.y()) / (right.x() - left.x());
+
+        // Look up area under that line, and scale horizontally to the window size.
+        let dx = window.x() - window.y();
+        let area = env.sample(AREA_LUT, vec2f(y + 8.0, f32::abs(d * dx)) / 16.0).x() * dx;
+        F32x4::splat(area)
+    }
+}
+
+mod reproject {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] = &["Position"];
+    pub(super) const UNIFORMS: &[&str] = &["OldTransform", "NewTransform", "Texture"];
+
+    const OLD_TRANSFORM: usize = 0;
+    const NEW_TRANSFORM: usize = 1;
+    const TEXTURE: usize = 2;
+
+    pub(super) fn vertex(inputs: &[[f32; 4]], env: &ShaderEnv) -> VertexOutput {
+        let position = vec2_input(inputs[0]);
+        let mut varyings = Varyings::default();
+        varyings[0] = position.x();
+        varyings[1] = position.y();
+        VertexOutput {
+            position: mat4_mul(&env.mat4(NEW_TRANSFORM), clip_position(position)),
+            varyings,
+        }
+    }
+
+    pub(super) fn fragment(varyings: &Varyings, env: &ShaderEnv) -> F32x4 {
+        let tex_coord = clip_position(vec2f(varyings[0], varyings[1]));
+        let norm_tex_coord = mat4_mul(&env.mat4(OLD_TRANSFORM), tex_coord);
+        let tex_coord = (vec2f(norm_tex_coord.x(), norm_tex_coord.y()) / norm_tex_coord.w() +
+                         Vector2F::splat(1.0)) * 0.5;
+        env.sample(TEXTURE, tex_coord)
+    }
+}
+
+mod stencil {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] = &["Position"];
+    pub(super) const UNIFORMS: &[&str] = &[];
+
+    pub(super) fn vertex(inputs: &[[f32; 4]]) -> VertexOutput {
+        let position = inputs[0];
+        VertexOutput {
+            position: F32x4::new(position[0], position[1], position[2], 1.0),
+            varyings: Varyings::default(),
+        }
+    }
+
+    pub(super) fn fragment() -> F32x4 {
+        // This should be color masked out.
+        F32x4::new(1.0, 0.0, 0.0, 1.0)
+    }
+}
+
+mod tile_clip {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] =
+        &["TileOffset", "DestTileOrigin", "SrcTileOrigin", "SrcBackdrop"];
+    pub(super) const UNIFORMS: &[&str] = &["Src"];
+
+    const TILE_OFFSET: usize = 0;
+    const DEST_TILE_ORIGIN: usize = 1;
+    const SRC_TILE_ORIGIN: usize = 2;
+    const SRC_BACKDROP: usize = 3;
+
+    const SRC: usize = 0;
+
+    pub(super) fn vertex(inputs: &[[f32; 4]]) -> VertexOutput {
+        let tile_offset = vec2_input(inputs[TILE_OFFSET]);
+        let dest_position = (vec2_input(inputs[DEST_TILE_ORIGIN]) + tile_offset) / 256.0;
+        let src_position = (vec2_input(inputs[SRC_TILE_ORIGIN]) + tile_offset) / 256.0;
+        let mut varyings = Varyings::default();
+        varyings[0] = src_position.x();
+        varyings[1] = src_position.y();
+        varyings[2] = inputs[SRC_BACKDROP][0];
+        VertexOutput {
+            position: clip_position(dest_position * 2.0 - Vector2F::splat(1.0)),
+            varyings,
+        }
+    }
+
+    pub(super) fn fragment(varyings: &Varyings, env: &ShaderEnv) -> F32x4 {
+        let coverage = env.sample(SRC, vec2f(varyings[0], varyings[1])).x() + varyings[2];
+        let alpha = clamp(f32::abs(coverage), 0.0, 1.0);
+        F32x4::new(alpha, 0.0, 0.0, 1.0)
+    }
+}
+
+mod tile_copy {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] = &["TilePosition"];
+    pub(super) const UNIFORMS: &[&str] = &["Transform", "TileSize", "FramebufferSize", "Src"];
+
+    const TRANSFORM: usize = 0;
+    const TILE_SIZE: usize = 1;
+    const FRAMEBUFFER_SIZE: usize = 2;
+    const SRC: usize = 3;
+
+    pub(super) fn vertex(inputs: &[[f32; 4]], env: &ShaderEnv) -> VertexOutput {
+        let position = vec2_input(inputs[0]) * env.vec2(TILE_SIZE);
+        VertexOutput {
+            position: mat4_mul(&env.mat4(TRANSFORM), clip_position(position)),
+            varyings: Varyings::default(),
+        }
+    }
+
+    pub(super) fn fragment(frag_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        env.sample(SRC, frag_coord / env.vec2(FRAMEBUFFER_SIZE))
+    }
+}
+
+mod tile {
+    use super::*;
+
+    pub(super) const ATTRIBUTES: &[&str] =
+        &["TileOffset", "TileOrigin", "MaskTexCoord0", "MaskBackdrop", "Color", "TileCtrl"];
+    pub(super) const UNIFORMS: &[&str] = &[
+        "Transform",
+        "TileSize",
+        "TextureMetadata",
+        "TextureMetadataSize",
+        "DestTexture",
+        "ColorTexture0",
+        "ColorTexture1",
+        "MaskTexture0",
+        "GammaLUT",
+        "ColorTexture0Size",
+        "FilterParams0",
+        "FilterParams1",
+        "FilterParams2",
+        "FilterParams3",
+        "FilterParams4",
+        "FramebufferSize",
+        "Ctrl",
+    ];
+
+    const TILE_OFFSET: usize = 0;
+    const TILE_ORIGIN: usize = 1;
+    const MASK_TEX_COORD_0: usize = 2;
+    const MASK_BACKDROP: usize = 3;
+    const COLOR: usize = 4;
+    const TILE_CTRL: usize = 5;
+
+    const TRANSFORM: usize = 0;
+    const TILE_SIZE: usize = 1;
+    const TEXTURE_METADATA: usize = 2;
+    const TEXTURE_METADATA_SIZE: usize = 3;
+    const DEST_TEXTURE: usize = 4;
+    const COLOR_TEXTURE_0: usize = 5;
+    const MASK_TEXTURE_0: usize = 7;
+    const GAMMA_LUT: usize = 8;
+    const COLOR_TEXTURE_0_SIZE: usize = 9;
+    const FILTER_PARAMS_0: usize = 10;
+    const FILTER_PARAMS_1: usize = 11;
+    const FILTER_PARAMS_2: usize = 12;
+    const FILTER_PARAMS_3: usize = 13;
+    const FILTER_PARAMS_4: usize = 14;
+    const FRAMEBUFFER_SIZE: usize = 15;
+    const CTRL: usize = 16;
+
+    // Varying layout.
+    const V_MASK_TEX_COORD_0: usize = 0;
+    const V_COLOR_TEX_COORD_0: usize = 3;
+    const V_BASE_COLOR: usize = 5;
+    const V_TILE_CTRL: usize = 9;
+
+    pub(super) fn vertex(inputs: &[[f32; 4]], env: &ShaderEnv) -> VertexOutput {
+        let tile_origin = vec2_input(inputs[TILE_ORIGIN]);
+        let tile_offset = vec2_input(inputs[TILE_OFFSET]);
+        let position = (tile_origin + tile_offset) * env.vec2(TILE_SIZE);
+
+        let mask_tex_coord_0 = (vec2_input(inputs[MASK_TEX_COORD_0]) + tile_offset) / 256.0;
+
+        let color = inputs[COLOR][0] as i32;
+        let texture_metadata_scale =
+            Vector2F::splat(1.0) / env.vec2(TEXTURE_METADATA_SIZE);
+        let metadata_entry_coord = vec2f((color % 128 * 4) as f32, (color / 128) as f32);
+        let color_tex_matrix_0_coord =
+            (metadata_entry_coord + vec2f(0.5, 0.5)) * texture_metadata_scale;
+        let color_tex_offsets_coord =
+            (metadata_entry_coord + vec2f(1.5, 0.5)) * texture_metadata_scale;
+        let base_color_coord = (metadata_entry_coord + vec2f(2.5, 0.5)) * texture_metadata_scale;
+        let color_tex_matrix_0 = env.sample(TEXTURE_METADATA, color_tex_matrix_0_coord);
+        let color_tex_offsets = env.sample(TEXTURE_METADATA, color_tex_offsets_coord);
+        let base_color = env.sample(TEXTURE_METADATA, base_color_coord);
+
+        // `mat2(colorTexMatrix0) * position`, where the matrix is stored column-major.
+        let color_tex_coord_0 = vec2f(
+            color_tex_matrix_0.x() * position.x() + color_tex_matrix_0.z() * position.y(),
+            color_tex_matrix_0.y() * position.x() + color_tex_matrix_0.w() * position.y(),
+        ) + vec2f(color_tex_offsets.x(), color_tex_offsets.y());
+
+        let mut varyings = Varyings::default();
+        varyings[V_MASK_TEX_COORD_0] = mask_tex_coord_0.x();
+        varyings[V_MASK_TEX_COORD_0 + 1] = mask_tex_coord_0.y();
+        varyings[V_MASK_TEX_COORD_0 + 2] = inputs[MASK_BACKDROP][0];
+        varyings[V_COLOR_TEX_COORD_0] = color_tex_coord_0.x();
+        varyings[V_COLOR_TEX_COORD_0 + 1] = color_tex_coord_0.y();
+        for channel in 0..4 {
+            varyings[V_BASE_COLOR + channel] = base_color[channel];
+        }
+        varyings[V_TILE_CTRL] = inputs[TILE_CTRL][0];
+
+        VertexOutput {
+            position: mat4_mul(&env.mat4(TRANSFORM), clip_position(position)),
+            varyings,
+        }
+    }
+
+    pub(super) fn fragment(varyings: &Varyings, frag_coord: Vector2F, env: &ShaderEnv)
+                           -> F32x4 {
+        let tile_ctrl = varying_to_int(varyings[V_TILE_CTRL]);
+        let ctrl = env.int(CTRL);
+
+        // Sample mask.
+        let mask_ctrl_0 = (tile_ctrl >> TILE_CTRL_MASK_0_SHIFT) & TILE_CTRL_MASK_MASK;
+        let mask_tex_coord_0 = vec2f(varyings[V_MASK_TEX_COORD_0],
+                                     varyings[V_MASK_TEX_COORD_0 + 1]);
+        let mask_backdrop_0 = varyings[V_MASK_TEX_COORD_0 + 2];
+        let mask_alpha = sample_mask(1.0, env, mask_tex_coord_0, mask_backdrop_0, mask_ctrl_0);
+
+        // Sample color.
+        let mut color = F32x4::new(varyings[V_BASE_COLOR],
+                                   varyings[V_BASE_COLOR + 1],
+                                   varyings[V_BASE_COLOR + 2],
+                                   varyings[V_BASE_COLOR + 3]);
+        let color_0_combine = (ctrl >> COMBINER_CTRL_COLOR_COMBINE_SHIFT) &
+            COMBINER_CTRL_COLOR_COMBINE_MASK;
+        if color_0_combine != 0 {
+            let color_0_filter = (ctrl >> COMBINER_CTRL_COLOR_FILTER_SHIFT) &
+                COMBINER_CTRL_FILTER_MASK;
+            let color_tex_coord_0 = vec2f(varyings[V_COLOR_TEX_COORD_0],
+                                          varyings[V_COLOR_TEX_COORD_0 + 1]);
+            let color_0 = filter_color(color_tex_coord_0, env, color_0_filter);
+            color = combine_color_0(color, color_0, color_0_combine);
+        }
+
+        // Apply mask.
+        color[3] *= mask_alpha;
+
+        // Apply composite.
+        let composite_op = (ctrl >> COMBINER_CTRL_COMPOSITE_SHIFT) & COMBINER_CTRL_COMPOSITE_MASK;
+        color = composite(color, env, frag_coord, composite_op);
+
+        // Premultiply alpha.
+        color * F32x4::new(color.w(), color.w(), color.w(), 1.0)
+    }
+
+    // Color combining
+
+    fn combine_color_0(dest_color: F32x4, src_color: F32x4, op: i32) -> F32x4 {
+        match op {
+            COMBINER_CTRL_COLOR_COMBINE_SRC_IN => {
+                F32x4::new(src_color.x(),
+                           src_color.y(),
+                           src_color.z(),
+                           src_color.w() * dest_color.w())
+            }
+            COMBINER_CTRL_COLOR_COMBINE_DEST_IN => {
+                F32x4::new(dest_color.x(),
+                           dest_color.y(),
+                           dest_color.z(),
+                           src_color.w() * dest_color.w())
+            }
+            _ => dest_color,
+        }
+    }
+
+    // Filters
+
+    fn filter_color(color_tex_coord: Vector2F, env: &ShaderEnv, color_filter: i32) -> F32x4 {
+        match color_filter {
+            COMBINER_CTRL_FILTER_RADIAL_GRADIENT => {
+                filter_radial_gradient(color_tex_coord, env)
+            }
+            COMBINER_CTRL_FILTER_BLUR => filter_blur(color_tex_coord, env),
+            COMBINER_CTRL_FILTER_TEXT => filter_text(color_tex_coord, env),
+            COMBINER_CTRL_FILTER_LUMINANCE_TO_ALPHA => {
+                filter_luminance_to_alpha(color_tex_coord, env)
+            }
+            COMBINER_CTRL_FILTER_LINEAR_GRADIENT => {
+                filter_linear_gradient(color_tex_coord, env)
+            }
+            COMBINER_CTRL_FILTER_CONIC_GRADIENT => {
+                filter_conic_gradient(color_tex_coord, env)
+            }
+            COMBINER_CTRL_FILTER_COLOR_MATRIX => filter_color_matrix(color_tex_coord, env),
+            COMBINER_CTRL_FILTER_COMPONENT_TRANSFER => {
+                filter_component_transfer(color_tex_coord, env)
+            }
+            COMBINER_CTRL_FILTER_MORPHOLOGY => filter_morphology(color_tex_coord, env),
+            COMBINER_CTRL_FILTER_UNPREMULTIPLY => {
+                filter_unpremultiply(env.sample(COLOR_TEXTURE_0, color_tex_coord))
+            }
+            _ => env.sample(COLOR_TEXTURE_0, color_tex_coord),
+        }
+    }
+
+    fn filter_luminance_to_alpha(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        let color = env.sample(COLOR_TEXTURE_0, color_tex_coord);
+        let luminance = color.x() * 0.2125 + color.y() * 0.7154 + color.z() * 0.0721;
+        F32x4::new(0.0, 0.0, 0.0, luminance)
+    }
+
+    fn filter_text_sample_1_tap(offset: f32, env: &ShaderEnv, color_tex_coord: Vector2F)
+                                -> f32 {
+        env.sample(COLOR_TEXTURE_0, color_tex_coord + vec2f(offset, 0.0)).x()
+    }
+
+    fn filter_text_gamma_correct_channel(bg_color: f32, fg_color: f32, env: &ShaderEnv) -> f32 {
+        env.sample(GAMMA_LUT, vec2f(fg_color, 1.0 - bg_color)).x()
+    }
+
+    //                | x          y          z          w
+    //  --------------+--------------------------------------------------------
+    //  filterParams0 | kernel[0]  kernel[1]  kernel[2]  kernel[3]
+    //  filterParams1 | bgColor.r  bgColor.g  bgColor.b  -
+    //  filterParams2 | fgColor.r  fgColor.g  fgColor.b  gammaCorrectionEnabled
+    fn filter_text(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        // Unpack.
+        let kernel = env.vec4(FILTER_PARAMS_0);
+        let bg_color = env.vec4(FILTER_PARAMS_1);
+        let fg_color = env.vec4(FILTER_PARAMS_2);
+        let gamma_correction_enabled = fg_color.w() != 0.0;
+
+        // Apply defringing if necessary.
+        let mut alpha = [0.0; 3];
+        if kernel.w() == 0.0 {
+            let center = filter_text_sample_1_tap(0.0, env, color_tex_coord);
+            alpha = [center; 3];
+        } else {
+            // Sample 9 taps around the current pixel.
+            let one_pixel = 1.0 / env.vec2(COLOR_TEXTURE_0_SIZE).x();
+            let wide = kernel.x() > 0.0;
+            let mut taps = [0.0; 9];
+            for (tap_index, tap) in taps.iter_mut().enumerate() {
+                let offset = tap_index as i32 - 4;
+                if offset.abs() < 4 || wide {
+                    *tap = filter_text_sample_1_tap(offset as f32 * one_pixel,
+                                                    env,
+                                                    color_tex_coord);
+                }
+            }
+
+            // Convolve with 7 taps for each subpixel.
+            let kernel = [kernel.x(), kernel.y(), kernel.z(), kernel.w(),
+                          kernel.z(), kernel.y(), kernel.x()];
+            for (channel, alpha) in alpha.iter_mut().enumerate() {
+                *alpha = kernel.iter()
+                               .zip(taps[channel..].iter())
+                               .map(|(weight, tap)| weight * tap)
+                               .sum();
+            }
+        }
+
+        // Apply gamma correction if necessary.
+        if gamma_correction_enabled {
+            for channel in 0..3 {
+                alpha[channel] =
+                    filter_text_gamma_correct_channel(bg_color[channel], alpha[channel], env);
+            }
+        }
+
+        // Finish.
+        F32x4::new(mix(bg_color.x(), fg_color.x(), alpha[0]),
+                   mix(bg_color.y(), fg_color.y(), alpha[1]),
+                   mix(bg_color.z(), fg_color.z(), alpha[2]),
+                   1.0)
+    }
+
+    fn apply_gradient_spread(t: f32, spread: i32) -> f32 {
+        match spread {
+            GRADIENT_SPREAD_REPEAT => t - f32::floor(t),
+            GRADIENT_SPREAD_REFLECT => 1.0 - f32::abs(t - 2.0 * f32::floor(t * 0.5) - 1.0),
+            _ => clamp(t, 0.0, 1.0),
+        }
+    }
+
+    //                | x           y           z               w
+    //  --------------+-----------------------------------------------------
+    //  filterParams0 | spread      -           -               -
+    //  filterParams1 | -           -           -               -
+    //  filterParams2 | -           -           -               -
+    fn filter_linear_gradient(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        let spread = env.vec4(FILTER_PARAMS_0).x() as i32;
+        let t = apply_gradient_spread(color_tex_coord.x(), spread);
+        env.sample(COLOR_TEXTURE_0, vec2f(t, color_tex_coord.y()))
+    }
+
+    // See the comment in `tile.fs.glsl` for the derivation of this formula.
+    //
+    //                | x           y           z               w
+    //  --------------+-----------------------------------------------------
+    //  filterParams0 | lineFrom.x  lineFrom.y  lineVector.x    lineVector.y
+    //  filterParams1 | radii.x     radii.y     uvOrigin.x      uvOrigin.y
+    //  filterParams2 | spread      -           -               -
+    fn filter_radial_gradient(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        let (filter_params_0, filter_params_1) =
+            (env.vec4(FILTER_PARAMS_0), env.vec4(FILTER_PARAMS_1));
+        let line_from = vec2f(filter_params_0.x(), filter_params_0.y());
+        let line_vector = vec2f(filter_params_0.z(), filter_params_0.w());
+        let radii = vec2f(filter_params_1.x(), filter_params_1.y());
+        let uv_origin = vec2f(filter_params_1.z(), filter_params_1.w());
+
+        let (d_p, d_c) = (color_tex_coord - line_from, line_vector);
+        let d_r = radii.y() - radii.x();
+
+        let a = d_c.dot(d_c) - d_r * d_r;
+        let b = d_p.dot(d_c) + radii.x() * d_r;
+        let c = d_p.dot(d_p) - radii.x() * radii.x();
+        let discrim = b * b - a * c;
+
+        if f32::abs(discrim) < EPSILON {
+            return F32x4::default();
+        }
+
+        let mut ts = ((f32::sqrt(discrim) + b) / a, (-f32::sqrt(discrim) + b) / a);
+        if ts.0 > ts.1 {
+            ts = (ts.1, ts.0);
+        }
+        let t = if ts.0 >= 0.0 { ts.0 } else { ts.1 };
+        let t = apply_gradient_spread(t, env.vec4(FILTER_PARAMS_2).x() as i32);
+        env.sample(COLOR_TEXTURE_0, uv_origin + vec2f(t, 0.0))
+    }
+
+    //                | x           y           z               w
+    //  --------------+-----------------------------------------------------
+    //  filterParams0 | center.x    center.y    angle           -
+    //  filterParams1 | uvOrigin.x  uvOrigin.y  -               -
+    //  filterParams2 | -           -           -               -
+    fn filter_conic_gradient(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        let (filter_params_0, filter_params_1) =
+            (env.vec4(FILTER_PARAMS_0), env.vec4(FILTER_PARAMS_1));
+        let center = vec2f(filter_params_0.x(), filter_params_0.y());
+        let angle = filter_params_0.z();
+        let uv_origin = vec2f(filter_params_1.x(), filter_params_1.y());
+
+        let d_p = color_tex_coord - center;
+        let t = (f32::atan2(d_p.y(), d_p.x()) - angle) / (2.0 * PI);
+        env.sample(COLOR_TEXTURE_0, uv_origin + vec2f(t - f32::floor(t), 0.0))
+    }
+
+    //                | x             y             z             w
+    //  --------------+----------------------------------------------------
+    //  filterParams0 | srcOffset.x   srcOffset.y   support       -
+    //  filterParams1 | gaussCoeff.x  gaussCoeff.y  gaussCoeff.z  -
+    //  filterParams2 | -             -                 -             -
+    fn filter_blur(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        // Unpack.
+        let (filter_params_0, filter_params_1) =
+            (env.vec4(FILTER_PARAMS_0), env.vec4(FILTER_PARAMS_1));
+        let src_offset_scale = vec2f(filter_params_0.x(), filter_params_0.y()) /
+            env.vec2(COLOR_TEXTURE_0_SIZE);
+        let support = filter_params_0.z() as i32;
+        let mut gauss_coeff = [filter_params_1.x(), filter_params_1.y(), filter_params_1.z()];
+
+        // Set up our incremental calculation.
+        let mut gauss_sum = gauss_coeff[0];
+        let mut color = env.sample(COLOR_TEXTURE_0, color_tex_coord) *
+            F32x4::splat(gauss_coeff[0]);
+        gauss_coeff[0] *= gauss_coeff[1];
+        gauss_coeff[1] *= gauss_coeff[2];
+
+        // See `tile.fs.glsl` for an explanation of this two-texels-at-a-time trick.
+        let mut i = 1;
+        while i <= support {
+            let mut gauss_partial_sum = gauss_coeff[0];
+            gauss_coeff[0] *= gauss_coeff[1];
+            gauss_coeff[1] *= gauss_coeff[2];
+            gauss_partial_sum += gauss_coeff[0];
+
+            let src_offset =
+                src_offset_scale * (i as f32 + gauss_coeff[0] / gauss_partial_sum);
+            let samples = env.sample(COLOR_TEXTURE_0, color_tex_coord - src_offset) +
+                env.sample(COLOR_TEXTURE_0, color_tex_coord + src_offset);
+            color += samples * F32x4::splat(gauss_partial_sum);
+
+            gauss_sum += 2.0 * gauss_partial_sum;
+            gauss_coeff[0] *= gauss_coeff[1];
+            gauss_coeff[1] *= gauss_coeff[2];
+            i += 2;
+        }
+
+        // Finish.
+        color * F32x4::splat(1.0 / gauss_sum)
+    }
+
+    fn filter_unpremultiply(color: F32x4) -> F32x4 {
+        if color.w() > 0.0 {
+            F32x4::new(color.x() / color.w(), color.y() / color.w(), color.z() / color.w(),
+                       color.w())
+        } else {
+            F32x4::default()
+        }
+    }
+
+    // The first four parameters hold the rows of the matrix, and the last its constant column.
+    fn filter_color_matrix(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        let color = filter_unpremultiply(env.sample(COLOR_TEXTURE_0, color_tex_coord));
+        let rows = [FILTER_PARAMS_0, FILTER_PARAMS_1, FILTER_PARAMS_2, FILTER_PARAMS_3];
+        let constants = env.vec4(FILTER_PARAMS_4);
+        let mut result = F32x4::default();
+        for (channel, &row) in rows.iter().enumerate() {
+            let row = env.vec4(row);
+            let value = row.x() * color.x() + row.y() * color.y() + row.z() * color.z() +
+                row.w() * color.w() + constants[channel];
+            result[channel] = clamp(value, 0.0, 1.0);
+        }
+        result
+    }
+
+    // The low three bits of `function` hold its kind, and the rest the length of its table.
+    fn filter_transfer_channel(value: f32, params: F32x4, function: i32) -> f32 {
+        let count = function >> 3;
+        match function & 0x7 {
+            TRANSFER_FUNCTION_TABLE => {
+                let position = value * (count - 1) as f32;
+                let index = i32::min(position as i32, count - 2);
+                mix(params[index as usize],
+                    params[index as usize + 1],
+                    position - index as f32)
+            }
+            TRANSFER_FUNCTION_DISCRETE => {
+                params[i32::min((value * count as f32) as i32, count - 1) as usize]
+            }
+            TRANSFER_FUNCTION_LINEAR => params.x() * value + params.y(),
+            TRANSFER_FUNCTION_GAMMA => params.x() * value.powf(params.y()) + params.z(),
+            _ => value,
+        }
+    }
+
+    //                | x          y          z          w
+    //  --------------+--------------------------------------------
+    //  filterParams0 | red function parameters
+    //  filterParams1 | green function parameters
+    //  filterParams2 | blue function parameters
+    //  filterParams3 | alpha function parameters
+    //  filterParams4 | redFunc    greenFunc  blueFunc   alphaFunc
+    fn filter_component_transfer(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        let color = filter_unpremultiply(env.sample(COLOR_TEXTURE_0, color_tex_coord));
+        let params = [FILTER_PARAMS_0, FILTER_PARAMS_1, FILTER_PARAMS_2, FILTER_PARAMS_3];
+        let functions = env.vec4(FILTER_PARAMS_4);
+        let mut result = F32x4::default();
+        for (channel, &params) in params.iter().enumerate() {
+            let value = filter_transfer_channel(color[channel],
+                                                env.vec4(params),
+                                                functions[channel] as i32);
+            result[channel] = clamp(value, 0.0, 1.0);
+        }
+        result
+    }
+
+    //                | x            y            z            w
+    //  --------------+------------------------------------------------
+    //  filterParams0 | srcOffset.x  srcOffset.y  radius       dilate
+    //  filterParams1 | -            -            -            -
+    //  filterParams2 | -            -            -            -
+    fn filter_morphology(color_tex_coord: Vector2F, env: &ShaderEnv) -> F32x4 {
+        let filter_params_0 = env.vec4(FILTER_PARAMS_0);
+        let src_offset_scale = vec2f(filter_params_0.x(), filter_params_0.y()) /
+            env.vec2(COLOR_TEXTURE_0_SIZE);
+        let radius = filter_params_0.z() as i32;
+        let dilate = filter_params_0.w() != 0.0;
+
+        let mut color = env.sample(COLOR_TEXTURE_0, color_tex_coord);
+        for i in 1..=radius {
+            let src_offset = src_offset_scale * i as f32;
+            let color_a = env.sample(COLOR_TEXTURE_0, color_tex_coord - src_offset);
+            let color_b = env.sample(COLOR_TEXTURE_0, color_tex_coord + src_offset);
+            color = if dilate {
+                color.max(color_a.max(color_b))
+            } else {
+                color.min(color_a.min(color_b))
+            };
+        }
+        filter_unpremultiply(color)
+    }
+
+    // Compositing
+
+    fn composite_divide(num: f32, denom: f32) -> f32 {
+        if denom != 0.0 { num / denom } else { 0.0 }
+    }
+
+    fn composite_color_dodge(dest_color: f32, src_color: f32) -> f32 {
+        if dest_color == 0.0 {
+            0.0
+        } else if src_color == 1.0 {
+            1.0
+        } else {
+            dest_color / (1.0 - src_color)
+        }
+    }
+
+    // https://en.wikipedia.org/wiki/HSL_and_HSV#HSL_to_RGB_alternative
+    fn composite_hsl_to_rgb(hsl: [f32; 3]) -> [f32; 3] {
+        let a = hsl[1] * f32::min(hsl[2], 1.0 - hsl[2]);
+        let mut rgb = [0.0; 3];
+        for (channel, &n) in rgb.iter_mut().zip([0.0, 8.0, 4.0].iter()) {
+            let k = glsl_mod(n + hsl[0] * FRAC_6_PI, 12.0);
+            *channel = hsl[2] - clamp(f32::min(k - 3.0, 9.0 - k), -1.0, 1.0) * a;
+        }
+        rgb
+    }
+
+    // https://en.wikipedia.org/wiki/HSL_and_HSV#From_RGB
+    fn composite_rgb_to_hsl(rgb: [f32; 3]) -> [f32; 3] {
+        let v = f32::max(f32::max(rgb[0], rgb[1]), rgb[2]);
+        let x_min = f32::min(f32::min(rgb[0], rgb[1]), rgb[2]);
+        let (c, l) = (v - x_min, mix(x_min, v, 0.5));
+        let terms = if rgb[0] == v {
+            [0.0, rgb[1], rgb[2]]
+        } else if rgb[1] == v {
+            [2.0, rgb[2], rgb[0]]
+        } else {
+            [4.0, rgb[0], rgb[1]]
+        };
+        let h = FRAC_PI_3 * composite_divide(terms[0] * c + terms[1] - terms[2], c);
+        let s = composite_divide(c, v);
+        [h, s, l]
+    }
+
+    fn composite_screen(dest_color: f32, src_color: f32) -> f32 {
+        dest_color + src_color - dest_color * src_color
+    }
+
+    fn composite_hard_light(dest_color: f32, src_color: f32) -> f32 {
+        if src_color <= 0.5 {
+            dest_color * 2.0 * src_color
+        } else {
+            composite_screen(dest_color, 2.0 * src_color - 1.0)
+        }
+    }
+
+    fn composite_soft_light(dest_color: f32, src_color: f32) -> f32 {
+        let darkened_dest_color = if dest_color <= 0.25 {
+            ((16.0 * dest_color - 12.0) * dest_color + 4.0) * dest_color
+        } else {
+            f32::sqrt(dest_color)
+        };
+        let factor = if src_color <= 0.5 {
+            dest_color * (1.0 - dest_color)
+        } else {
+            darkened_dest_color - dest_color
+        };
+        dest_color + (src_color * 2.0 - 1.0) * factor
+    }
+
+    fn composite_hsl(dest_color: [f32; 3], src_color: [f32; 3], op: i32) -> [f32; 3] {
+        match op {
+            COMBINER_CTRL_COMPOSITE_HUE => [src_color[0], dest_color[1], dest_color[2]],
+            COMBINER_CTRL_COMPOSITE_SATURATION => [dest_color[0], src_color[1], dest_color[2]],
+            COMBINER_CTRL_COMPOSITE_COLOR => [src_color[0], src_color[1], dest_color[2]],
+            _ => [dest_color[0], dest_color[1], src_color[2]],
+        }
+    }
+
+    fn composite_rgb(dest_color: [f32; 3], src_color: [f32; 3], op: i32) -> [f32; 3] {
+        let per_channel: fn(f32, f32) -> f32 = match op {
+            COMBINER_CTRL_COMPOSITE_MULTIPLY => |dest, src| dest * src,
+            COMBINER_CTRL_COMPOSITE_SCREEN => composite_screen,
+            COMBINER_CTRL_COMPOSITE_OVERLAY => |dest, src| composite_hard_light(src, dest),
+            COMBINER_CTRL_COMPOSITE_DARKEN => f32::min,
+            COMBINER_CTRL_COMPOSITE_LIGHTEN => f32::max,
+            COMBINER_CTRL_COMPOSITE_COLOR_DODGE => composite_color_dodge,
+            COMBINER_CTRL_COMPOSITE_COLOR_BURN => {
+                |dest, src| 1.0 - composite_color_dodge(1.0 - dest, 1.0 - src)
+            }
+            COMBINER_CTRL_COMPOSITE_HARD_LIGHT => composite_hard_light,
+            COMBINER_CTRL_COMPOSITE_SOFT_LIGHT => composite_soft_light,
+            COMBINER_CTRL_COMPOSITE_DIFFERENCE => |dest, src| f32::abs(dest - src),
+            COMBINER_CTRL_COMPOSITE_EXCLUSION => |dest, src| dest + src - 2.0 * dest * src,
+            COMBINER_CTRL_COMPOSITE_HUE |
+            COMBINER_CTRL_COMPOSITE_SATURATION |
+            COMBINER_CTRL_COMPOSITE_COLOR |
+            COMBINER_CTRL_COMPOSITE_LUMINOSITY => {
+                return composite_hsl_to_rgb(composite_hsl(composite_rgb_to_hsl(dest_color),
+                                                          composite_rgb_to_hsl(src_color),
+                                                          op));
+            }
+            _ => return src_color,
+        };
+        [
+            per_channel(dest_color[0], src_color[0]),
+            per_channel(dest_color[1], src_color[1]),
+            per_channel(dest_color[2], src_color[2]),
+        ]
+    }
+
+    fn composite(src_color: F32x4, env: &ShaderEnv, frag_coord: Vector2F, op: i32) -> F32x4 {
+        if op == COMBINER_CTRL_COMPOSITE_NORMAL {
+            return src_color;
+        }
+
+        // FIXME(pcwalton): What should the output alpha be here?
+        let dest_tex_coord = frag_coord / env.vec2(FRAMEBUFFER_SIZE);
+        let dest_color = env.sample(DEST_TEXTURE, dest_tex_coord);
+        let blended_rgb = composite_rgb([dest_color.x(), dest_color.y(), dest_color.z()],
+                                        [src_color.x(), src_color.y(), src_color.z()],
+                                        op);
+        let (src_alpha, dest_alpha) = (src_color.w(), dest_color.w());
+        let mut result = F32x4::splat(1.0);
+        for channel in 0..3 {
+            result[channel] = src_alpha * (1.0 - dest_alpha) * src_color[channel] +
+                src_alpha * dest_alpha * blended_rgb[channel] +
+                (1.0 - src_alpha) * dest_color[channel];
+        }
+        result
+    }
+
+    // Masks
+
+    fn sample_mask(mask_alpha: f32,
+                   env: &ShaderEnv,
+                   mask_tex_coord: Vector2F,
+                   mask_backdrop: f32,
+                   mask_ctrl: i32)
+                   -> f32 {
+        if mask_ctrl == 0 {
+            return mask_alpha;
+        }
+        let mut coverage = env.sample(MASK_TEXTURE_0, mask_tex_coord).x() + mask_backdrop;
+        if (mask_ctrl & TILE_CTRL_MASK_WINDING) != 0 {
+            coverage = f32::abs(coverage);
+        } else {
+            coverage = 1.0 - f32::abs(1.0 - glsl_mod(coverage, 2.0));
+        }
+        f32::min(mask_alpha, coverage)
+    }
+}
@@ -0,0 +1,205 @@
+// pathfinder/software/src/test.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::SoftwareDevice;
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::outline::Outline;
+use pathfinder_geometry::rect::{RectF, RectI};
+use pathfinder_geometry::vector::{Vector2I, vec2f, vec2i};
+use pathfinder_gpu::{BlendState, BufferData, BufferTarget, BufferUploadMode, ClearOps, Device};
+use pathfinder_gpu::{Primitive, RenderOptions, RenderState, RenderTarget, TextureData};
+use pathfinder_gpu::{VertexAttrClass, VertexAttrDescriptor, VertexAttrType};
+use pathfinder_renderer::concurrent::executor::SequentialExecutor;
+use pathfinder_renderer::concurrent::scene_proxy::SceneProxy;
+use pathfinder_renderer::gpu::options::{DestFramebuffer, RendererOptions};
+use pathfinder_renderer::gpu::renderer::Renderer;
+use pathfinder_renderer::options::BuildOptions;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{DrawPath, MaskMode, RenderTarget as SceneRenderTarget, Scene};
+use pathfinder_resources::embedded::EmbeddedResourceLoader;
+
+fn read_rgba8(device: &SoftwareDevice, size: Vector2I) -> Vec<u8> {
+    let receiver = device.read_pixels(&RenderTarget::Default, RectI::new(Vector2I::default(), size));
+    match device.recv_texture_data(&receiver) {
+        TextureData::U8(pixels) => pixels,
+        _ => panic!("Expected RGBA8 pixels!"),
+    }
+}
+
+fn pixel(pixels: &[u8], size: Vector2I, position: Vector2I) -> [u8; 4] {
+    let index = (position.y() * size.x() + position.x()) as usize * 4;
+    [pixels[index], pixels[index + 1], pixels[index + 2], pixels[index + 3]]
+}
+
+// Draws a triangle in normalized device coordinates with the `stencil` program, which outputs
+// opaque red.
+fn draw_triangle(device: &SoftwareDevice,
+                 size: Vector2I,
+                 positions: &[f32],
+                 clear_color: Option<ColorF>,
+                 blend: Option<BlendState>) {
+    let resources = EmbeddedResourceLoader::new();
+    let program = device.create_program(&resources, "stencil");
+    let position_attr = device.get_vertex_attr(&program, "Position").unwrap();
+    let vertex_array = device.create_vertex_array();
+    let buffer = device.create_buffer();
+    device.allocate_buffer(&buffer,
+                           BufferData::Memory(positions),
+                           BufferTarget::Vertex,
+                           BufferUploadMode::Static);
+    device.bind_buffer(&vertex_array, &buffer, BufferTarget::Vertex);
+    device.configure_vertex_attr(&vertex_array, &position_attr, &VertexAttrDescriptor {
+        size: 3,
+        class: VertexAttrClass::Float,
+        attr_type: VertexAttrType::F32,
+        stride: 12,
+        offset: 0,
+        divisor: 0,
+        buffer_index: 0,
+    });
+
+    device.draw_arrays(3, &RenderState {
+        target: &RenderTarget::Default,
+        program: &program,
+        vertex_array: &vertex_array,
+        primitive: Primitive::Triangles,
+        uniforms: &[],
+        textures: &[],
+        viewport: RectI::new(Vector2I::default(), size),
+        options: RenderOptions {
+            blend,
+            clear_ops: ClearOps { color: clear_color, ..ClearOps::default() },
+            ..RenderOptions::default()
+        },
+    });
+}
+
+#[test]
+fn test_clear_and_draw_triangle() {
+    let size = vec2i(4, 4);
+    let device = SoftwareDevice::new(size);
+
+    // Cover the lower left half of the framebuffer.
+    draw_triangle(&device,
+                  size,
+                  &[-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, -1.0, 1.0, 0.0],
+                  Some(ColorF::new(0.0, 0.0, 1.0, 1.0)),
+                  None);
+
+    // Rows come back top to bottom, like OpenGL after flipping.
+    let pixels = read_rgba8(&device, size);
+    assert_eq!(pixel(&pixels, size, vec2i(0, 3)), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(3, 0)), [0, 0, 255, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(0, 0)), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(3, 3)), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(3, 1)), [0, 0, 255, 255]);
+}
+
+#[test]
+fn test_shared_edges_are_not_blended_twice() {
+    let size = vec2i(8, 8);
+    let device = SoftwareDevice::new(size);
+    let additive = BlendState {
+        src_rgb_factor: pathfinder_gpu::BlendFactor::One,
+        dest_rgb_factor: pathfinder_gpu::BlendFactor::One,
+        src_alpha_factor: pathfinder_gpu::BlendFactor::One,
+        dest_alpha_factor: pathfinder_gpu::BlendFactor::One,
+        ..BlendState::default()
+    };
+
+    // Two triangles forming a quad that covers the whole framebuffer.
+    draw_triangle(&device,
+                  size,
+                  &[-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0, 1.0, 0.0],
+                  Some(ColorF::transparent_black()),
+                  Some(additive));
+    draw_triangle(&device,
+                  size,
+                  &[-1.0, -1.0, 0.0, 1.0, 1.0, 0.0, -1.0, 1.0, 0.0],
+                  None,
+                  Some(additive));
+
+    let pixels = read_rgba8(&device, size);
+    for y in 0..size.y() {
+        for x in 0..size.x() {
+            assert_eq!(pixel(&pixels, size, vec2i(x, y)), [255, 0, 0, 255]);
+        }
+    }
+}
+
+#[test]
+fn test_render_scene() {
+    let size = vec2i(32, 32);
+    let device = SoftwareDevice::new(size);
+    let mut renderer = Renderer::new(device,
+                                     &EmbeddedResourceLoader::new(),
+                                     DestFramebuffer::full_window(size),
+                                     RendererOptions { background_color: Some(ColorF::white()) });
+
+    let mut scene = Scene::new();
+    let paint = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+    let outline = Outline::from_rect(RectF::new(vec2f(8.0, 4.0), vec2f(16.0, 8.0)));
+    scene.push_path(DrawPath::new(outline, paint));
+    scene.set_view_box(RectF::new(Vector2I::default().to_f32(), size.to_f32()));
+
+    let scene_proxy = SceneProxy::from_scene(scene, SequentialExecutor);
+    scene_proxy.build_and_render(&mut renderer, BuildOptions::default());
+
+    let pixels = read_rgba8(&renderer.device, size);
+    assert_eq!(pixel(&pixels, size, vec2i(16, 8)), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(8, 4)), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(23, 11)), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(7, 8)), [255, 255, 255, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(16, 12)), [255, 255, 255, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(16, 24)), [255, 255, 255, 255]);
+}
+
+#[test]
+fn test_luminance_mask() {
+    let size = vec2i(32, 32);
+    let device = SoftwareDevice::new(size);
+    let mut renderer = Renderer::new(device,
+                                     &EmbeddedResourceLoader::new(),
+                                     DestFramebuffer::full_window(size),
+                                     RendererOptions { background_color: Some(ColorF::white()) });
+
+    let mut scene = Scene::new();
+    let white = scene.push_paint(&Paint::from_color(ColorU::white()));
+    let black = scene.push_paint(&Paint::from_color(ColorU::black()));
+    let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+    let mask = scene.push_render_target(SceneRenderTarget::new(size, "mask".to_owned()));
+    let left = RectF::new(vec2f(0.0, 0.0), vec2f(16.0, 32.0));
+    let right = RectF::new(vec2f(16.0, 0.0), vec2f(16.0, 32.0));
+    scene.push_path(DrawPath::new(Outline::from_rect(left), white));
+    scene.push_path(DrawPath::new(Outline::from_rect(right), black));
+    scene.pop_render_target();
+    scene.push_mask(mask, MaskMode::Luminance);
+    let outline = Outline::from_rect(RectF::new(vec2f(4.0, 4.0), vec2f(24.0, 24.0)));
+    scene.push_path(DrawPath::new(outline, red));
+    let translucent_blue = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 255, 128)));
+    let outline = Outline::from_rect(RectF::new(vec2f(0.0, 4.0), vec2f(4.0, 24.0)));
+    scene.push_path(DrawPath::new(outline, translucent_blue));
+    scene.pop_mask();
+    scene.set_view_box(RectF::new(Vector2I::default().to_f32(), size.to_f32()));
+
+    let scene_proxy = SceneProxy::from_scene(scene, SequentialExecutor);
+    scene_proxy.build_and_render(&mut renderer, BuildOptions::default());
+
+    // Only the part of the content under the white half of the mask shows through.
+    let pixels = read_rgba8(&renderer.device, size);
+    assert_eq!(pixel(&pixels, size, vec2i(8, 16)), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(24, 16)), [255, 255, 255, 255]);
+    assert_eq!(pixel(&pixels, size, vec2i(8, 2)), [255, 255, 255, 255]);
+
+    // Translucent content isn't darkened by being premultiplied twice.
+    let [r, g, b, a] = pixel(&pixels, size, vec2i(2, 16));
+    assert_eq!((b, a), (255, 255));
+    assert!((r as i32 - 127).abs() <= 1 && r == g, "{:?}", (r, g));
+}
@@ -16,8 +16,12 @@ extern crate bitflags;
 use hashbrown::HashMap;
 use pathfinder_color::ColorU;
 use pathfinder_content::dash::OutlineDash;
+use pathfinder_content::effects::{BlendMode, ColorMatrix, MorphologyOperator, TransferFunction};
+use pathfinder_content::effects::TransferTable;
 use pathfinder_content::fill::FillRule;
-use pathfinder_content::gradient::{ColorStop, Gradient};
+use pathfinder_content::filter_graph::{CompositeOperator, FilterGraph, FilterInput};
+use pathfinder_content::filter_graph::{FilterPrimitive, FilterPrimitiveId};
+use pathfinder_content::gradient::{ColorStop, Gradient, GradientSpread};
 use pathfinder_content::outline::Outline;
 use pathfinder_content::segment::{Segment, SegmentFlags};
 use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
@@ -30,10 +34,13 @@ use pathfinder_renderer::paint::Paint;
 use pathfinder_renderer::scene::{ClipPath, ClipPathId, DrawPath, Scene};
 use pathfinder_simd::default::F32x2;
 use std::fmt::{Display, Formatter, Result as FormatResult};
-use usvg::{BaseGradient, Color as SvgColor, FillRule as UsvgFillRule, LineCap as UsvgLineCap};
-use usvg::{LineJoin as UsvgLineJoin, Node, NodeExt, NodeKind, Opacity, Paint as UsvgPaint};
-use usvg::{PathSegment as UsvgPathSegment, Rect as UsvgRect, SpreadMethod, Stop};
-use usvg::{Transform as UsvgTransform, Tree, Visibility};
+use usvg::{BaseGradient, Color as SvgColor, FeBlendMode, FeColorMatrixKind, FeCompositeOperator};
+use usvg::{FeMorphologyOperator, FillRule as UsvgFillRule, Filter as UsvgFilter};
+use usvg::{FilterInput as UsvgFilterInput, FilterKind, LineCap as UsvgLineCap};
+use usvg::{LineJoin as UsvgLineJoin, Node, NodeExt};
+use usvg::{NodeKind, Opacity, Paint as UsvgPaint, PathSegment as UsvgPathSegment};
+use usvg::{Rect as UsvgRect, SpreadMethod, Stop, Transform as UsvgTransform};
+use usvg::{TransferFunction as UsvgTransferFunction, Tree, Units, Visibility};
 
 const HAIRLINE_STROKE_WIDTH: f32 = 0.0333;
 
@@ -42,6 +49,7 @@ pub struct BuiltSVG {
     pub result_flags: BuildResultFlags,
     pub clip_paths: HashMap<String, ClipPathId>,
     gradients: HashMap<String, GradientInfo>,
+    filters: HashMap<String, UsvgFilter>,
 }
 
 bitflags! {
@@ -77,6 +85,7 @@ impl BuiltSVG {
             result_flags: BuildResultFlags::empty(),
             clip_paths: HashMap::new(),
             gradients: HashMap::new(),
+            filters: HashMap::new(),
         };
 
         let root = &tree.root();
@@ -103,26 +112,66 @@ impl BuiltSVG {
 
         match *node.borrow() {
             NodeKind::Group(ref group) => {
-                if group.filter.is_some() {
-                    self.result_flags.insert(BuildResultFlags::UNSUPPORTED_FILTER_ATTR);
-                }
                 if group.mask.is_some() {
                     self.result_flags.insert(BuildResultFlags::UNSUPPORTED_MASK_ATTR);
                 }
 
                 if let Some(ref clip_path_name) = group.clip_path {
-                    if let Some(clip_path_id) = self.clip_paths.get(clip_path_name) {
-                        // TODO(pcwalton): Combine multiple clip paths if there's already one.
-                        if state.clip_path.is_some() {
-                            self.result_flags
-                                .insert(BuildResultFlags::UNSUPPORTED_MULTIPLE_CLIP_PATHS);
-                        }
-                        state.clip_path = Some(*clip_path_id);
+                    if let Some(&clip_path_id) = self.clip_paths.get(clip_path_name) {
+                        // If we're already clipped, intersect with the enclosing clip path.
+                        let clip_path_id = match state.clip_path {
+                            None => clip_path_id,
+                            Some(parent_clip_path_id) => {
+                                let mut clip_path =
+                                    self.scene.get_clip_path(clip_path_id).clone();
+                                clip_path.set_clip_path(Some(parent_clip_path_id));
+                                self.scene.push_clip_path(clip_path)
+                            }
+                        };
+                        state.clip_path = Some(clip_path_id);
                     }
                 }
 
-                for kid in node.children() {
-                    self.process_node(&kid, &state, clip_outline)
+                let filter = match group.filter {
+                    Some(ref filter_name) if state.path_destination == PathDestination::Draw => {
+                        let filter = self.filters.get(filter_name).and_then(|filter| {
+                            let graph = FilterGraph::from_usvg_filter(filter, &state.transform)?;
+                            Some((graph, filter_region(filter, node, &state)))
+                        });
+                        if filter.is_none() {
+                            self.result_flags.insert(BuildResultFlags::UNSUPPORTED_FILTER_ATTR);
+                        }
+                        filter
+                    }
+                    _ => None,
+                };
+
+                match filter {
+                    None => {
+                        for kid in node.children() {
+                            self.process_node(&kid, &state, clip_outline)
+                        }
+                    }
+                    Some((graph, region)) => {
+                        // The filter sees its content unclipped. Clip paths apply to the result,
+                        // along with the filter region.
+                        let clip_path_id = match region {
+                            None => state.clip_path,
+                            Some(region) => {
+                                let mut clip_path = ClipPath::new(region);
+                                clip_path.set_clip_path(state.clip_path);
+                                clip_path.set_name(format!("FilterRegion({})", node.id()));
+                                Some(self.scene.push_clip_path(clip_path))
+                            }
+                        };
+                        self.scene.push_filter(graph, clip_path_id);
+                        let mut kid_state = state.clone();
+                        kid_state.clip_path = None;
+                        for kid in node.children() {
+                            self.process_node(&kid, &kid_state, clip_outline)
+                        }
+                        self.scene.pop_filter();
+                    }
                 }
             }
             NodeKind::Path(ref path) if state.path_destination == PathDestination::Clip => {
@@ -220,9 +269,15 @@ impl BuiltSVG {
                                   svg_radial_gradient.id.clone(),
                                   &svg_radial_gradient.base)
             }
-            NodeKind::Filter(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_FILTER_NODE);
+            NodeKind::Filter(ref filter) => {
+                // Check that the primitives are supported now; they are scaled once we know the
+                // transform of the group that uses the filter.
+                if FilterGraph::from_usvg_filter(filter, &Transform2F::default()).is_some() {
+                    self.filters.insert(filter.id.clone(), filter.clone());
+                } else {
+                    self.result_flags
+                        .insert(BuildResultFlags::UNSUPPORTED_FILTER_NODE);
+                }
             }
             NodeKind::Image(..) => {
                 self.result_flags
@@ -251,9 +306,11 @@ impl BuiltSVG {
             gradient.add(ColorStop::from_usvg_stop(stop));
         }
 
-        if usvg_base_gradient.spread_method != SpreadMethod::Pad {
-            self.result_flags.insert(BuildResultFlags::UNSUPPORTED_GRADIENT_SPREAD_METHOD);
-        }
+        gradient.set_spread(match usvg_base_gradient.spread_method {
+            SpreadMethod::Pad => GradientSpread::Pad,
+            SpreadMethod::Reflect => GradientSpread::Reflect,
+            SpreadMethod::Repeat => GradientSpread::Repeat,
+        });
 
         let transform = usvg_transform_to_transform_2d(&usvg_base_gradient.transform);
 
@@ -364,6 +421,189 @@ impl PaintExt for Paint {
     }
 }
 
+trait FilterGraphExt: Sized {
+    fn from_usvg_filter(usvg_filter: &UsvgFilter, transform: &Transform2F) -> Option<Self>;
+}
+
+impl FilterGraphExt for FilterGraph {
+    // Returns `None` if the filter uses anything that filter graphs don't support.
+    //
+    // FIXME(pcwalton): Primitives always operate in sRGB, regardless of
+    // `color-interpolation-filters`.
+    fn from_usvg_filter(usvg_filter: &UsvgFilter, transform: &Transform2F)
+                        -> Option<FilterGraph> {
+        if usvg_filter.primitive_units != Units::UserSpaceOnUse {
+            return None;
+        }
+
+        let mut graph = FilterGraph::new();
+        let mut results: HashMap<&str, FilterPrimitiveId> = HashMap::new();
+        for usvg_primitive in &usvg_filter.children {
+            // TODO(pcwalton): Primitive subregions.
+            if usvg_primitive.x.is_some() || usvg_primitive.y.is_some() ||
+                    usvg_primitive.width.is_some() || usvg_primitive.height.is_some() {
+                return None;
+            }
+
+            let input = |usvg_input: &UsvgFilterInput| {
+                match *usvg_input {
+                    UsvgFilterInput::SourceGraphic => Some(FilterInput::SourceGraphic),
+                    UsvgFilterInput::SourceAlpha => Some(FilterInput::SourceAlpha),
+                    UsvgFilterInput::Reference(ref name) => {
+                        results.get(name.as_str()).map(|&id| FilterInput::Primitive(id))
+                    }
+                    _ => None,
+                }
+            };
+
+            let primitive = match usvg_primitive.kind {
+                FilterKind::FeColorMatrix(ref fe) => {
+                    let matrix = match fe.kind {
+                        FeColorMatrixKind::Matrix(ref values) if values.len() == 20 => {
+                            let mut matrix = [0.0; 20];
+                            for (dest, &src) in matrix.iter_mut().zip(values.iter()) {
+                                *dest = src as f32;
+                            }
+                            ColorMatrix(matrix)
+                        }
+                        FeColorMatrixKind::Matrix(_) => return None,
+                        FeColorMatrixKind::Saturate(amount) => {
+                            ColorMatrix::saturate(amount.value() as f32)
+                        }
+                        FeColorMatrixKind::HueRotate(degrees) => {
+                            ColorMatrix::hue_rotate((degrees as f32).to_radians())
+                        }
+                        FeColorMatrixKind::LuminanceToAlpha => ColorMatrix::luminance_to_alpha(),
+                    };
+                    FilterPrimitive::ColorMatrix { input: input(&fe.input)?, matrix }
+                }
+                FilterKind::FeComponentTransfer(ref fe) => {
+                    let mut functions = [TransferFunction::Identity; 4];
+                    let usvg_functions = [&fe.func_r, &fe.func_g, &fe.func_b, &fe.func_a];
+                    for (function, usvg_function) in functions.iter_mut().zip(&usvg_functions) {
+                        *function = TransferFunction::from_usvg_transfer_function(usvg_function)?;
+                    }
+                    FilterPrimitive::ComponentTransfer { input: input(&fe.input)?, functions }
+                }
+                FilterKind::FeOffset(ref fe) => {
+                    let offset = transform.matrix * vec2f(fe.dx as f32, fe.dy as f32);
+                    FilterPrimitive::Offset { input: input(&fe.input)?, offset }
+                }
+                FilterKind::FeFlood(ref fe) => {
+                    let mut color = ColorU::from_svg_color(fe.color);
+                    color.a = (fe.opacity.value() * 255.0).round() as u8;
+                    FilterPrimitive::Flood { color }
+                }
+                FilterKind::FeGaussianBlur(ref fe) => {
+                    let sigma = vec2f(fe.std_dev_x.value() as f32, fe.std_dev_y.value() as f32) *
+                        transform.extract_scale();
+                    FilterPrimitive::Blur { input: input(&fe.input)?, sigma }
+                }
+                FilterKind::FeComposite(ref fe) => {
+                    let operator = match fe.operator {
+                        FeCompositeOperator::Over => CompositeOperator::Over,
+                        FeCompositeOperator::In => CompositeOperator::In,
+                        FeCompositeOperator::Out => CompositeOperator::Out,
+                        FeCompositeOperator::Atop => CompositeOperator::Atop,
+                        FeCompositeOperator::Xor => CompositeOperator::Xor,
+                        // TODO(pcwalton)
+                        FeCompositeOperator::Arithmetic { .. } => return None,
+                    };
+                    let inputs = [input(&fe.input1)?, input(&fe.input2)?];
+                    FilterPrimitive::Composite { inputs, operator }
+                }
+                FilterKind::FeBlend(ref fe) => {
+                    let mode = match fe.mode {
+                        FeBlendMode::Normal => BlendMode::SrcOver,
+                        FeBlendMode::Multiply => BlendMode::Multiply,
+                        FeBlendMode::Screen => BlendMode::Screen,
+                        FeBlendMode::Darken => BlendMode::Darken,
+                        FeBlendMode::Lighten => BlendMode::Lighten,
+                    };
+                    let inputs = [input(&fe.input1)?, input(&fe.input2)?];
+                    FilterPrimitive::Blend { inputs, mode }
+                }
+                FilterKind::FeMerge(ref fe) => {
+                    let inputs = fe.inputs.iter().map(&input).collect::<Option<Vec<_>>>()?;
+                    FilterPrimitive::Merge { inputs }
+                }
+                FilterKind::FeMorphology(ref fe) => {
+                    let operator = match fe.operator {
+                        FeMorphologyOperator::Erode => MorphologyOperator::Erode,
+                        FeMorphologyOperator::Dilate => MorphologyOperator::Dilate,
+                    };
+                    let radius = vec2f(fe.radius_x.value() as f32, fe.radius_y.value() as f32) *
+                        transform.extract_scale();
+                    FilterPrimitive::Morphology { input: input(&fe.input)?, operator, radius }
+                }
+                _ => return None,
+            };
+
+            let primitive_id = graph.push_primitive(primitive);
+            results.insert(&usvg_primitive.result, primitive_id);
+        }
+        Some(graph)
+    }
+}
+
+trait TransferFunctionExt: Sized {
+    fn from_usvg_transfer_function(usvg_function: &UsvgTransferFunction) -> Option<Self>;
+}
+
+impl TransferFunctionExt for TransferFunction {
+    fn from_usvg_transfer_function(usvg_function: &UsvgTransferFunction)
+                                   -> Option<TransferFunction> {
+        let table = |values: &[f64]| {
+            let values: Vec<f32> = values.iter().map(|&value| value as f32).collect();
+            TransferTable::new(&values)
+        };
+        match *usvg_function {
+            UsvgTransferFunction::Identity => Some(TransferFunction::Identity),
+            // Empty tables leave the channel alone.
+            UsvgTransferFunction::Table(ref values) |
+            UsvgTransferFunction::Discrete(ref values) if values.is_empty() => {
+                Some(TransferFunction::Identity)
+            }
+            UsvgTransferFunction::Table(ref values) => {
+                Some(TransferFunction::Table(table(values)?))
+            }
+            UsvgTransferFunction::Discrete(ref values) => {
+                Some(TransferFunction::Discrete(table(values)?))
+            }
+            UsvgTransferFunction::Linear { slope, intercept } => {
+                Some(TransferFunction::Linear { slope: slope as f32, intercept: intercept as f32 })
+            }
+            UsvgTransferFunction::Gamma { amplitude, exponent, offset } => {
+                Some(TransferFunction::Gamma {
+                    amplitude: amplitude as f32,
+                    exponent: exponent as f32,
+                    offset: offset as f32,
+                })
+            }
+        }
+    }
+}
+
+// Returns the filter region of the given group in scene space, or `None` if the region is
+// relative to the bounding box of a group that has none.
+fn filter_region(filter: &UsvgFilter, node: &Node, state: &State) -> Option<Outline> {
+    let rect = usvg_rect_to_euclid_rect(&filter.rect);
+    match filter.units {
+        Units::UserSpaceOnUse => {
+            let mut outline = Outline::from_rect(rect);
+            outline.transform(&state.transform);
+            Some(outline)
+        }
+        Units::ObjectBoundingBox => {
+            // FIXME(pcwalton): The bounding box is in scene space, so this is only right for
+            // transforms without rotation or skew.
+            let bbox = usvg_rect_to_euclid_rect(&node.calculate_bbox()?);
+            Some(Outline::from_rect(RectF::new(bbox.origin() + rect.origin() * bbox.size(),
+                                               rect.size() * bbox.size())))
+        }
+    }
+}
+
 fn usvg_rect_to_euclid_rect(rect: &UsvgRect) -> RectF {
     RectF::new(vec2f(rect.x() as f32, rect.y() as f32),
                vec2f(rect.width() as f32, rect.height() as f32))
@@ -555,3 +795,45 @@ struct GradientInfo {
     gradient: Gradient,
     transform: Transform2F,
 }
+
+#[cfg(test)]
+mod test {
+    use pathfinder_content::effects::MorphologyOperator;
+    use pathfinder_content::filter_graph::{FilterGraph, FilterInput, FilterPrimitive};
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+    use usvg::{NodeKind, Options, Tree};
+
+    use super::{BuildResultFlags, BuiltSVG, FilterGraphExt};
+
+    #[test]
+    fn test_morphology_filter() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+            <filter id="thicken">
+                <feMorphology operator="dilate" radius="1 2"/>
+            </filter>
+            <g filter="url(#thicken)" transform="scale(2 3)">
+                <rect x="4" y="4" width="8" height="8" fill="red"/>
+            </g>
+        </svg>"#;
+        let tree = Tree::from_str(svg, &Options::default()).unwrap();
+        let built_svg = BuiltSVG::from_tree(&tree);
+        assert!(!built_svg.result_flags.intersects(BuildResultFlags::UNSUPPORTED_FILTER_NODE |
+                                                    BuildResultFlags::UNSUPPORTED_FILTER_ATTR));
+
+        // The radius is scaled into device space, like blur deviations are.
+        let filter = tree.root().descendants().find_map(|node| {
+            match *node.borrow() {
+                NodeKind::Filter(ref filter) => Some(filter.clone()),
+                _ => None,
+            }
+        }).unwrap();
+        let transform = Transform2F::from_scale(vec2f(2.0, 3.0));
+        let graph = FilterGraph::from_usvg_filter(&filter, &transform).unwrap();
+        assert_eq!(graph.primitives(), &[FilterPrimitive::Morphology {
+            input: FilterInput::SourceGraphic,
+            operator: MorphologyOperator::Dilate,
+            radius: vec2f(2.0, 6.0),
+        }]);
+    }
+}
@@ -182,18 +182,25 @@ pub fn draw_paths_into_scene(library: &SymbolLibrary, scene: &mut Scene) {
                     path.push_contour(contour);
                 }
 
+                let mut non_scaling_stroke = None;
                 if let PaintOrLine::Line(line) = style_layer.kind() {
-                    let mut stroke_to_fill = OutlineStrokeToFill::new(&path, StrokeStyle {
+                    let stroke_style = StrokeStyle {
                         line_width: line.width.as_f32(),
                         line_cap: line.cap,
                         line_join: line.join,
-                    });
-                    stroke_to_fill.offset();
-                    path = stroke_to_fill.into_outline();
+                    };
+                    if line.non_scaling {
+                        non_scaling_stroke = Some(stroke_style);
+                    } else {
+                        let mut stroke_to_fill = OutlineStrokeToFill::new(&path, stroke_style);
+                        stroke_to_fill.offset();
+                        path = stroke_to_fill.into_outline();
+                    }
                 }
 
                 let mut path = DrawPath::new(path, paint_id);
                 path.set_fill_rule(FillRule::EvenOdd);
+                path.set_non_scaling_stroke(non_scaling_stroke);
                 scene.push_path(path);
             }
         }
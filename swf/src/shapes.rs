@@ -123,6 +123,7 @@ pub(crate) struct SwfLineStyle {
     pub(crate) width: Twips,
     pub(crate) join: LineJoin,
     pub(crate) cap: LineCap,
+    pub(crate) non_scaling: bool,
 }
 
 pub(crate) enum PaintOrLine {
@@ -265,10 +266,10 @@ fn get_new_styles<'a>(
             join,
             start_cap,
             end_cap: _,
+            no_h_scale,
+            no_v_scale,
             /*
             TODO(jon): Handle these cases?
-            pub no_h_scale: bool,
-            pub no_v_scale: bool,
             pub no_close: bool,
             pub pixel_hinting: bool,
             */
@@ -301,6 +302,9 @@ fn get_new_styles<'a>(
                         CapStyle::Square => LineCap::Square,
                         CapStyle::Round => LineCap::Round,
                     },
+                    // NOTE: Strokes can't keep their width along only one axis, so either flag
+                    // makes the whole stroke non-scaling.
+                    non_scaling: *no_h_scale || *no_v_scale,
                 }))
             } else {
                 unimplemented!("unimplemented line fill style");